@@ -0,0 +1,222 @@
+//! Sockets handed over by a service manager: systemd socket activation (`LISTEN_FDS`) and
+//! launchd's `launch_activate_socket`.
+
+use crate::{FileOrStdin, FileOrStdio};
+use std::{
+    env,
+    fs::File,
+    io,
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The first file descriptor systemd passes, after stdin, stdout and stderr.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Whether the inherited sockets have been handed out, since they can only be owned once.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// A socket the process was started with by its service manager.
+///
+/// So a simple service can take "stdin, a file, or the socket systemd handed me" alike: a
+/// listening socket accepts connections, and a socket that already is a connection (systemd's
+/// `Accept=yes`) is read from directly.
+///
+/// ```no_run
+/// # use polymorphio::{activated_sockets, FileOrStdin};
+/// let input = match activated_sockets()?.pop() {
+///     Some(socket) => socket.input()?,
+///     None => FileOrStdin::from_path("-")?,
+/// };
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ActivatedSocket {
+    socket: File,
+    name: Option<String>,
+}
+
+/// The sockets passed by systemd socket activation, in order, or none if the process wasn't
+/// socket activated.
+///
+/// The sockets are only handed out by the first call; later calls return none.
+pub fn activated_sockets() -> io::Result<Vec<ActivatedSocket>> {
+    let count = match listen_fds(
+        env::var("LISTEN_PID").ok().as_deref(),
+        env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    ) {
+        Some(count) if !TAKEN.swap(true, Ordering::SeqCst) => count,
+        _ => return Ok(Vec::new()),
+    };
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+    let mut names = names.split(':').map(|name| Some(name.to_owned()));
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            set_cloexec(fd)?;
+            Ok(ActivatedSocket {
+                // SAFETY: systemd passed these descriptors for this process to own, and `TAKEN`
+                // makes sure they're only wrapped once.
+                socket: unsafe { File::from_raw_fd(fd) },
+                name: names.next().flatten().filter(|name| !name.is_empty()),
+            })
+        })
+        .collect()
+}
+
+/// The sockets launchd opened for the `Sockets` entry `name` in the job's property list.
+#[cfg(target_os = "macos")]
+pub fn launchd_sockets(name: &str) -> io::Result<Vec<ActivatedSocket>> {
+    extern "C" {
+        fn launch_activate_socket(
+            name: *const libc::c_char,
+            fds: *mut *mut libc::c_int,
+            cnt: *mut libc::size_t,
+        ) -> libc::c_int;
+    }
+
+    let c_name = std::ffi::CString::new(name)?;
+    let mut fds = std::ptr::null_mut();
+    let mut count = 0;
+    // SAFETY: the out pointers are valid, and launchd allocates `fds` for us to free.
+    let ret = unsafe { launch_activate_socket(c_name.as_ptr(), &mut fds, &mut count) };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(ret));
+    }
+    // SAFETY: launchd returned `count` descriptors at `fds`, owned by this process from now on.
+    let sockets = unsafe { std::slice::from_raw_parts(fds, count) }
+        .iter()
+        .map(|&fd| ActivatedSocket {
+            // SAFETY: as above, each descriptor is wrapped once.
+            socket: unsafe { File::from_raw_fd(fd) },
+            name: Some(name.to_owned()),
+        })
+        .collect();
+    // SAFETY: `fds` was allocated with malloc by launchd.
+    unsafe { libc::free(fds.cast()) };
+    Ok(sockets)
+}
+
+impl ActivatedSocket {
+    /// The socket's name from `FileDescriptorName=` (systemd) or the `Sockets` key (launchd).
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Whether this is a listening socket to [`accept`](ActivatedSocket::accept) connections on,
+    /// rather than a connection.
+    pub fn is_listening(&self) -> io::Result<bool> {
+        let mut value: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // SAFETY: `value` and `len` describe a writable c_int.
+        let ret = unsafe {
+            libc::getsockopt(
+                self.socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_ACCEPTCONN,
+                (&mut value as *mut libc::c_int).cast(),
+                &mut len,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(value != 0)
+    }
+
+    /// Wait for the next connection on a listening socket.
+    pub fn accept(&self) -> io::Result<FileOrStdio> {
+        loop {
+            // SAFETY: accept doesn't need the peer address, so null pointers are fine.
+            let fd = unsafe {
+                libc::accept(
+                    self.socket.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+            };
+            if fd < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            // SAFETY: the fd was just returned by accept and nothing else owns it.
+            let connection = unsafe { File::from_raw_fd(fd) };
+            set_cloexec(fd)?;
+            return Ok(connection.into());
+        }
+    }
+
+    /// Read from the socket: one accepted connection for a listening socket, or the socket
+    /// itself for a connection.
+    pub fn input(self) -> io::Result<FileOrStdin> {
+        if self.is_listening()? {
+            let (input, _) = self.accept()?.split()?;
+            Ok(input)
+        } else {
+            Ok(self.socket.into())
+        }
+    }
+
+    pub fn into_file(self) -> File {
+        self.socket
+    }
+}
+
+/// The number of sockets passed, if `LISTEN_PID` says they are meant for the process `pid`.
+fn listen_fds(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<RawFd> {
+    if listen_pid?.parse::<u32>().ok()? != pid {
+        return None;
+    }
+    listen_fds?.parse().ok().filter(|&count| count > 0)
+}
+
+fn set_cloexec(fd: RawFd) -> io::Result<()> {
+    // SAFETY: F_SETFD only changes the descriptor's flags.
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        os::unix::{io::IntoRawFd, net::UnixListener, net::UnixStream},
+        thread,
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn activated_listener() -> Result<(), io::Error> {
+        assert_eq!(listen_fds(Some("42"), Some("2"), 42), Some(2));
+        assert_eq!(listen_fds(Some("41"), Some("2"), 42), None);
+        assert_eq!(listen_fds(None, Some("2"), 42), None);
+        assert_eq!(listen_fds(Some("42"), Some("0"), 42), None);
+
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("socket");
+        let listener = UnixListener::bind(&path)?;
+        let socket = ActivatedSocket {
+            // SAFETY: the listener's descriptor is given up to the socket.
+            socket: unsafe { File::from_raw_fd(listener.into_raw_fd()) },
+            name: Some("control".into()),
+        };
+        assert!(socket.is_listening()?);
+
+        let client = thread::spawn(move || -> io::Result<()> {
+            UnixStream::connect(&path)?.write_all(b"request")
+        });
+        let mut content = String::new();
+        let mut input = socket.input()?;
+        input.lock().read_to_string(&mut content)?;
+        assert_eq!(content, "request");
+        client.join().expect("client doesn't panic")?;
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{
+    digest::{to_hex, Sha256},
+    temp, template,
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_TEMPLATE: &str = "{hash}";
+
+/// Output whose file name is derived from the SHA-256 digest of its content.
+///
+/// Content is hashed while being written to a temporary file in the store directory, then
+/// [`finish`](ContentAddressed::finish) moves it to a name made from a template in which `{hash}`
+/// is the hex digest and `{hash:N}` its first `N` characters, e.g. `{hash:2}/{hash}.json` to
+/// shard the store into subdirectories. If a file with that name already exists it is assumed to
+/// hold the same content and is left alone. Dropping the output without finishing discards it.
+pub struct ContentAddressed {
+    dir: PathBuf,
+    template: String,
+    temp_path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    sha: Sha256,
+}
+
+impl ContentAddressed {
+    /// Start writing a new object into the store directory `dir`.
+    pub fn new<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        let (file, temp_path) = temp::create_unique(&dir, "object")?;
+        Ok(Self {
+            dir,
+            template: DEFAULT_TEMPLATE.to_owned(),
+            temp_path,
+            writer: Some(BufWriter::new(file)),
+            sha: Sha256::new(),
+        })
+    }
+
+    /// Set the file name template, relative to the store directory. Defaults to `{hash}`.
+    pub fn template<S: Into<String>>(mut self, template: S) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// Finish writing and move the content to its final name, returning the path.
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        let file = self
+            .writer
+            .take()
+            .expect("output is only finished once")
+            .into_inner()
+            .map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+
+        let hash = to_hex(&self.sha.clone().finish());
+        let path = self.dir.join(render_template(&self.template, &hash));
+        if path.exists() {
+            fs::remove_file(&self.temp_path)?;
+            return Ok(path);
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Err(e) = fs::rename(&self.temp_path, &path) {
+            let _ = fs::remove_file(&self.temp_path);
+            return Err(e);
+        }
+        Ok(path)
+    }
+}
+
+impl Write for ContentAddressed {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amt = self
+            .writer
+            .as_mut()
+            .expect("output not finished yet")
+            .write(buf)?;
+        self.sha.update(&buf[..amt]);
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer
+            .as_mut()
+            .expect("output not finished yet")
+            .flush()
+    }
+}
+
+impl Drop for ContentAddressed {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+fn render_template(template: &str, hash: &str) -> String {
+    template::expand(template, |field, out| {
+        let len = match field {
+            "hash" => hash.len(),
+            _ => match field.strip_prefix("hash:").and_then(|n| n.parse().ok()) {
+                Some(len) => hash.len().min(len),
+                None => return false,
+            },
+        };
+        out.push_str(&hash[..len]);
+        true
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn named_by_content() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let hash = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        let mut output = ContentAddressed::new(tmp_dir.path())?.template("{hash:2}/{hash}.txt");
+        output.write_all(b"abc")?;
+        let path = output.finish()?;
+        assert_eq!(
+            path,
+            tmp_dir.path().join("ba").join(format!("{}.txt", hash))
+        );
+        assert_eq!(fs::read_to_string(&path)?, "abc");
+
+        let mut again = ContentAddressed::new(tmp_dir.path())?.template("{hash:2}/{hash}.txt");
+        again.write_all(b"abc")?;
+        assert_eq!(again.finish()?, path);
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 1);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{FileOrStdout, FileOrStdoutLock};
+use std::io::{self, Read, Write};
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Incremental filter removing ANSI escape sequences (CSI, OSC and two-byte escapes).
+///
+/// State is kept between calls, so sequences split across reads or writes are still removed.
+#[derive(Clone, Copy, Default)]
+struct Stripper {
+    state: State,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+impl Stripper {
+    /// Strip escapes from `buf` in place, returning the length of the remaining data.
+    fn strip_in_place(&mut self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        for i in 0..buf.len() {
+            let b = buf[i];
+            if self.keep(b) {
+                buf[len] = b;
+                len += 1;
+            }
+        }
+        len
+    }
+
+    fn keep(&mut self, b: u8) -> bool {
+        self.state = match (self.state, b) {
+            (State::Ground, ESC) => State::Escape,
+            (State::Ground, _) => return true,
+            (State::Escape, b'[') => State::Csi,
+            (State::Escape, b']') => State::Osc,
+            // Intermediate bytes, e.g. the `(` in a charset selection like `ESC ( B`.
+            (State::Escape, 0x20..=0x2f) => State::Escape,
+            (State::Escape, _) => State::Ground,
+            (State::Csi, 0x40..=0x7e) => State::Ground,
+            (State::Csi, _) => State::Csi,
+            (State::Osc, BEL) => State::Ground,
+            (State::Osc, ESC) => State::OscEscape,
+            (State::Osc, _) => State::Osc,
+            (State::OscEscape, b'\\') => State::Ground,
+            (State::OscEscape, _) => State::Osc,
+        };
+        false
+    }
+}
+
+/// Reader adapter that removes ANSI escape sequences from the data read.
+pub struct StripAnsiReader<R> {
+    inner: R,
+    stripper: Stripper,
+}
+
+impl<R: Read> StripAnsiReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            stripper: Stripper::default(),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for StripAnsiReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let amt = self.inner.read(buf)?;
+            if amt == 0 {
+                return Ok(0);
+            }
+            let kept = self.stripper.strip_in_place(&mut buf[..amt]);
+            if kept > 0 {
+                return Ok(kept);
+            }
+        }
+    }
+}
+
+/// Writer adapter that removes ANSI escape sequences before passing data on.
+///
+/// Stripping can be turned off, which makes it easy to strip only for non-terminal destinations
+/// (see [`FileOrStdout::lock_ansi_aware`]).
+pub struct StripAnsiWriter<W> {
+    inner: W,
+    stripper: Stripper,
+    enabled: bool,
+    scratch: Vec<u8>,
+}
+
+impl<W: Write> StripAnsiWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            stripper: Stripper::default(),
+            enabled: true,
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for StripAnsiWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.enabled {
+            return self.inner.write(buf);
+        }
+        self.scratch.clear();
+        self.scratch.extend_from_slice(buf);
+        let kept = self.stripper.strip_in_place(&mut self.scratch);
+        self.inner.write_all(&self.scratch[..kept])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl FileOrStdout {
+    /// Lock for writing, stripping ANSI escape sequences unless the destination is a terminal.
+    pub fn lock_ansi_aware(&mut self) -> StripAnsiWriter<FileOrStdoutLock<'_>> {
+        let strip = !self.is_terminal();
+        StripAnsiWriter::new(self.lock()).enabled(strip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLORED: &[u8] = b"\x1b[1;31merror\x1b[0m: \x1b]0;title\x07done\x1b(B\n";
+
+    #[test]
+    fn strip_reader() -> Result<(), io::Error> {
+        // Tiny reads split escape sequences across calls.
+        let mut reader = StripAnsiReader::new(io::BufReader::with_capacity(3, COLORED));
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        assert_eq!(content, "error: done\n");
+        Ok(())
+    }
+
+    #[test]
+    fn strip_writer() -> Result<(), io::Error> {
+        let mut writer = StripAnsiWriter::new(Vec::new());
+        for chunk in COLORED.chunks(2) {
+            writer.write_all(chunk)?;
+        }
+        assert_eq!(writer.into_inner(), b"error: done\n");
+
+        let mut passthrough = StripAnsiWriter::new(Vec::new()).enabled(false);
+        passthrough.write_all(COLORED)?;
+        assert_eq!(passthrough.into_inner(), COLORED);
+        Ok(())
+    }
+}
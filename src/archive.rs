@@ -0,0 +1,435 @@
+#[cfg(feature = "tar")]
+use crate::tar;
+use crate::{deterministic, temp, STDIO_FILENAME};
+#[cfg(feature = "zip")]
+use crate::{digest::Crc32, zip};
+use std::{
+    collections::HashSet,
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Container format of an [`ArchiveOutput`]. Zip entries are stored uncompressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    #[cfg(feature = "tar")]
+    Tar,
+    #[cfg(feature = "zip")]
+    Zip,
+}
+
+/// Output collecting named streams into a single archive, written to a file or stdout.
+///
+/// Entries are written one at a time. When the archive is a file, entries stream straight into
+/// it and their headers are filled in once they are complete. On stdout, where headers can't be
+/// revisited, each entry is spooled to a temporary file until its size is known. Either way
+/// nothing is held in memory. The archive is only complete once
+/// [`finish`](ArchiveOutput::finish) is called.
+pub struct ArchiveOutput {
+    format: ArchiveFormat,
+    sink: Sink,
+    /// Bytes written to the archive so far.
+    pos: u64,
+    names: HashSet<String>,
+    spool_dir: PathBuf,
+    deterministic: bool,
+    #[cfg(feature = "zip")]
+    directory: Vec<u8>,
+    /// Number of central headers in `directory`.
+    #[cfg(feature = "zip")]
+    directory_entries: usize,
+}
+
+/// An entry being written to an [`ArchiveOutput`].
+///
+/// Dropping the entry completes it like [`finish`](ArchiveEntry::finish), ignoring errors.
+pub struct ArchiveEntry<'a> {
+    archive: &'a mut ArchiveOutput,
+    name: String,
+    /// Where the entry's header was written to, when streaming directly into a file.
+    header_offset: Option<u64>,
+    spool: Option<(BufWriter<File>, PathBuf)>,
+    size: u64,
+    modified: SystemTime,
+    #[cfg(feature = "zip")]
+    crc: Crc32,
+    done: bool,
+}
+
+enum Sink {
+    File(BufWriter<File>),
+    Stdout(BufWriter<io::Stdout>),
+}
+
+impl ArchiveFormat {
+    /// The format matching a `.tar` or `.zip` extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            #[cfg(feature = "tar")]
+            "tar" => Some(Self::Tar),
+            #[cfg(feature = "zip")]
+            "zip" => Some(Self::Zip),
+            _ => None,
+        }
+    }
+}
+
+impl ArchiveOutput {
+    /// Create an archive at `path`, or write it to stdout for `-`.
+    pub fn from_path<P: AsRef<Path>>(path: P, format: ArchiveFormat) -> io::Result<Self> {
+        let path = path.as_ref();
+        let sink = if path.to_string_lossy() == STDIO_FILENAME {
+            Sink::Stdout(BufWriter::new(io::stdout()))
+        } else {
+            Sink::File(BufWriter::new(File::create(path)?))
+        };
+        Ok(Self {
+            format,
+            sink,
+            pos: 0,
+            names: HashSet::new(),
+            spool_dir: env::temp_dir(),
+            deterministic: false,
+            #[cfg(feature = "zip")]
+            directory: Vec::new(),
+            #[cfg(feature = "zip")]
+            directory_entries: 0,
+        })
+    }
+
+    /// Set the directory entries are spooled in when writing to stdout. Defaults to
+    /// `std::env::temp_dir()`.
+    pub fn spool_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.spool_dir = dir.as_ref().to_owned();
+        self
+    }
+
+    /// Make the archive the same on every run, for reproducible builds, by dating entries
+    /// `SOURCE_DATE_EPOCH` if it's set, or else the epoch (1980 for zip), rather than the time
+    /// they were written.
+    pub fn deterministic(mut self, yes: bool) -> Self {
+        self.deterministic = yes;
+        self
+    }
+
+    /// Start a new entry called `name`, a `/`-separated path inside the archive.
+    pub fn entry(&mut self, name: &str) -> io::Result<ArchiveEntry<'_>> {
+        if name.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "archive entry names can't be empty",
+            ));
+        }
+        if self.names.contains(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("archive already has an entry `{}`", name),
+            ));
+        }
+        let modified = deterministic::timestamp(self.deterministic);
+
+        let (header_offset, spool) = match self.sink {
+            Sink::File(_) => {
+                let offset = self.pos;
+                // A placeholder of the right length, rewritten when the entry is finished.
+                let header = self.headers(name, 0, 0, modified, offset)?;
+                self.write_all(&header)?;
+                (Some(offset), None)
+            }
+            Sink::Stdout(_) => {
+                let (file, path) = temp::create_unique(&self.spool_dir, "archive-entry")?;
+                (None, Some((BufWriter::new(file), path)))
+            }
+        };
+        self.names.insert(name.to_owned());
+
+        Ok(ArchiveEntry {
+            archive: self,
+            name: name.to_owned(),
+            header_offset,
+            spool,
+            size: 0,
+            modified,
+            #[cfg(feature = "zip")]
+            crc: Crc32::new(),
+            done: false,
+        })
+    }
+
+    /// Add an entry called `name` with everything read from `reader`, returning its size.
+    pub fn add<R: Read + ?Sized>(&mut self, name: &str, reader: &mut R) -> io::Result<u64> {
+        let mut entry = self.entry(name)?;
+        let size = io::copy(reader, &mut entry)?;
+        entry.finish()?;
+        Ok(size)
+    }
+
+    /// Write the end of the archive and flush it.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.format {
+            #[cfg(feature = "tar")]
+            ArchiveFormat::Tar => self.write_all(&tar::END_OF_ARCHIVE)?,
+            #[cfg(feature = "zip")]
+            ArchiveFormat::Zip => {
+                let offset = self.pos;
+                let directory = std::mem::take(&mut self.directory);
+                let end =
+                    zip::end_of_directory(directory.len(), self.directory_entries, offset, &[]);
+                self.write_all(&directory)?;
+                self.write_all(&end)?;
+            }
+        }
+        self.sink.flush()
+    }
+
+    #[cfg_attr(not(feature = "zip"), allow(unused_variables))]
+    fn headers(
+        &self,
+        name: &str,
+        size: u64,
+        crc: u32,
+        modified: SystemTime,
+        offset: u64,
+    ) -> io::Result<Vec<u8>> {
+        match self.format {
+            #[cfg(feature = "tar")]
+            ArchiveFormat::Tar => {
+                let mtime = modified
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                Ok(tar::file_headers(name, size, mtime))
+            }
+            #[cfg(feature = "zip")]
+            ArchiveFormat::Zip => Ok(zip_entry(name, size, crc, modified, offset)?.local_header()),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.sink.write_all(buf)?;
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+}
+
+impl ArchiveEntry<'_> {
+    /// Complete the entry, writing its header and, for spooled entries, its data.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.complete()
+    }
+
+    fn complete(&mut self) -> io::Result<()> {
+        self.done = true;
+        #[cfg(feature = "zip")]
+        let crc = self.crc.finish();
+        #[cfg(not(feature = "zip"))]
+        let crc = 0;
+
+        #[cfg_attr(not(feature = "zip"), allow(unused_variables))]
+        let offset = match (self.header_offset, self.spool.take()) {
+            (Some(offset), _) => {
+                let header =
+                    self.archive
+                        .headers(&self.name, self.size, crc, self.modified, offset)?;
+                let file = match &mut self.archive.sink {
+                    Sink::File(file) => file,
+                    Sink::Stdout(_) => unreachable!("stdout entries are spooled"),
+                };
+                file.flush()?;
+                let file = file.get_mut();
+                file.seek(SeekFrom::Start(offset))?;
+                file.write_all(&header)?;
+                file.seek(SeekFrom::Start(self.archive.pos))?;
+                offset
+            }
+            (None, Some((spool, path))) => {
+                let result = self.copy_spooled(spool, crc);
+                let _ = fs::remove_file(path);
+                result?
+            }
+            (None, None) => unreachable!("entries are either direct or spooled"),
+        };
+
+        match self.archive.format {
+            #[cfg(feature = "tar")]
+            ArchiveFormat::Tar => {
+                let padding = tar::padding(self.size) as usize;
+                self.archive.write_all(&vec![0; padding])
+            }
+            #[cfg(feature = "zip")]
+            ArchiveFormat::Zip => {
+                zip_entry(&self.name, self.size, crc, self.modified, offset)?
+                    .put_central_header(&mut self.archive.directory);
+                self.archive.directory_entries += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Write the header and spooled data of the entry, returning the header's offset.
+    fn copy_spooled(&mut self, spool: BufWriter<File>, crc: u32) -> io::Result<u64> {
+        let mut spool = spool.into_inner().map_err(|e| e.into_error())?;
+        spool.seek(SeekFrom::Start(0))?;
+        let offset = self.archive.pos;
+        let header = self
+            .archive
+            .headers(&self.name, self.size, crc, self.modified, offset)?;
+        self.archive.write_all(&header)?;
+        let copied = io::copy(&mut spool, &mut self.archive.sink)?;
+        self.archive.pos += copied;
+        if copied != self.size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(offset)
+    }
+}
+
+impl Write for ArchiveEntry<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amt = match &mut self.spool {
+            Some((spool, _)) => spool.write(buf)?,
+            None => {
+                let amt = self.archive.sink.write(buf)?;
+                self.archive.pos += amt as u64;
+                amt
+            }
+        };
+        self.size += amt as u64;
+        #[cfg(feature = "zip")]
+        self.crc.update(&buf[..amt]);
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.spool {
+            Some((spool, _)) => spool.flush(),
+            None => self.archive.sink.flush(),
+        }
+    }
+}
+
+impl Drop for ArchiveEntry<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.complete();
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::File(file) => file.write(buf),
+            Sink::Stdout(stdout) => stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::File(file) => file.flush(),
+            Sink::Stdout(stdout) => stdout.flush(),
+        }
+    }
+}
+
+#[cfg(feature = "zip")]
+fn zip_entry(
+    name: &str,
+    size: u64,
+    crc: u32,
+    modified: SystemTime,
+    offset: u64,
+) -> io::Result<zip::StoredEntry<'_>> {
+    if size > u32::MAX as u64 || offset > u32::MAX as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "zip archives of 4 GiB or more are not supported",
+        ));
+    }
+    Ok(zip::StoredEntry {
+        name,
+        crc,
+        size: size as u32,
+        modified: zip::dos_time(modified),
+        header_offset: offset as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn write_tar_archive() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("out.tar");
+        let long_name = format!("{}/long.txt", "d".repeat(120));
+
+        let mut archive = ArchiveOutput::from_path(&path, ArchiveFormat::Tar)?;
+        let mut entry = archive.entry("a.txt")?;
+        write!(entry, "first")?;
+        entry.finish()?;
+        archive.add(&long_name, &mut &b"second"[..])?;
+        assert_eq!(
+            archive.add("a.txt", &mut io::empty()).err().unwrap().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        archive.finish()?;
+
+        // An entry that fails to start doesn't take up its name.
+        let spool_dir = tmp_dir.path().join("missing");
+        let mut archive = ArchiveOutput::from_path("-", ArchiveFormat::Tar)?.spool_dir(&spool_dir);
+        assert!(archive.entry("a.txt").is_err());
+        assert!(archive.names.is_empty());
+
+        let output = Command::new("tar").arg("-tf").arg(&path).output()?;
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("a.txt\n{}\n", long_name)
+        );
+
+        let mut content = String::new();
+        crate::TarArchive::open_member(&format!("{}::{}", path.display(), long_name))?
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "second");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn write_zip_archive() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("out.zip");
+        assert_eq!(ArchiveFormat::from_path(&path), Some(ArchiveFormat::Zip));
+
+        let mut archive = ArchiveOutput::from_path(&path, ArchiveFormat::Zip)?;
+        archive.add("a.txt", &mut &b"first"[..])?;
+        let mut entry = archive.entry("dir/b.txt")?;
+        entry.write_all(&[b'x'; 10_000])?;
+        drop(entry);
+        archive.finish()?;
+
+        let status = Command::new("unzip").arg("-tq").arg(&path).status()?;
+        assert!(status.success());
+        let mut zip = crate::ZipArchive::from_path(&path)?;
+        let mut content = String::new();
+        zip.by_name("a.txt")?.read_to_string(&mut content)?;
+        assert_eq!(content, "first");
+        let mut content = Vec::new();
+        zip.by_name("dir/b.txt")?.read_to_end(&mut content)?;
+        assert_eq!(content, [b'x'; 10_000]);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
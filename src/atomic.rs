@@ -0,0 +1,694 @@
+use crate::{
+    digest::{to_hex, Sha256},
+    flush, space, temp, xattr, Error, FileStamp, STDIO_FILENAME,
+};
+use std::{
+    fs::{self, File, FileTimes},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+const COMPARE_CHUNK: usize = 64 * 1024;
+
+/// What [`AtomicOutput::commit`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Commit {
+    /// The target was replaced (or stdout flushed).
+    Written,
+    /// The target already had exactly this content and was left untouched.
+    Unchanged,
+}
+
+/// How [`AtomicOutput`] replaces a target that is a symlink or has other hard links.
+///
+/// Renaming a new file into place replaces the directory entry, so it turns a symlink into a
+/// plain file and leaves other hard links pointing at the old content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkPolicy {
+    /// Replace the symlink with the new file, leaving the file it pointed to alone, like
+    /// `sed -i`.
+    #[default]
+    ReplaceLinkItself,
+    /// Replace the file a symlink resolves to, keeping the symlink. It must be on the same file
+    /// system as the symlink. Hard links are still broken.
+    FollowAndReplaceTarget,
+    /// Copy the new content into the existing file, keeping symlinks and hard links intact.
+    /// Readers can see partial content while this happens.
+    WriteThrough,
+}
+
+/// Output that only replaces its target file once everything has been written.
+///
+/// Data goes to a temporary file next to the target, which is renamed over the target by
+/// [`commit`](AtomicOutput::commit). Readers of the target never see partial content, and if the
+/// output is dropped without committing the target is left as it was. An existing target's
+/// permissions are carried over. The path `-` writes straight to stdout. Symlinked and hard
+/// linked targets are handled according to the [`LinkPolicy`].
+pub struct AtomicOutput {
+    target: Target,
+    link_policy: LinkPolicy,
+    write_if_changed: bool,
+    preserve_times: bool,
+    times_from: Option<PathBuf>,
+    mtime: Option<SystemTime>,
+    written: u64,
+    /// A file that mustn't have changed since it was stamped, when committing.
+    unchanged: Option<(PathBuf, FileStamp)>,
+    /// The digest of everything written, when verifying.
+    verify: Option<Sha256>,
+    preserve_xattrs: bool,
+    #[cfg(feature = "selinux")]
+    preserve_selinux: bool,
+}
+
+enum Target {
+    File {
+        path: PathBuf,
+        temp_path: PathBuf,
+        writer: Option<BufWriter<File>>,
+        renamed: bool,
+    },
+    Stdout(io::Stdout),
+}
+
+/// What installing an [`AtomicOutput`] changes on disk.
+pub(crate) enum Replaced {
+    /// Nothing, for stdout.
+    Nothing,
+    /// The directory entry at this path is renamed over.
+    Entry(PathBuf),
+    /// The file at this path is rewritten in place, for [`LinkPolicy::WriteThrough`].
+    InPlace(PathBuf),
+}
+
+impl AtomicOutput {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let target = if path.to_string_lossy() == STDIO_FILENAME {
+            Target::Stdout(io::stdout())
+        } else {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let (file, temp_path) = temp::create_unique(dir, &name)?;
+            Target::File {
+                path: path.to_owned(),
+                temp_path,
+                writer: Some(BufWriter::new(file)),
+                renamed: false,
+            }
+        };
+        Ok(Self {
+            target,
+            link_policy: LinkPolicy::default(),
+            write_if_changed: false,
+            preserve_times: false,
+            times_from: None,
+            mtime: None,
+            written: 0,
+            unchanged: None,
+            verify: None,
+            preserve_xattrs: false,
+            #[cfg(feature = "selinux")]
+            preserve_selinux: false,
+        })
+    }
+
+    /// Leave the target untouched, including its modification time, if the new content is
+    /// identical to what it already contains.
+    pub fn write_if_changed(mut self, yes: bool) -> Self {
+        self.write_if_changed = yes;
+        self
+    }
+
+    /// Defaults to [`LinkPolicy::ReplaceLinkItself`].
+    pub fn link_policy(mut self, policy: LinkPolicy) -> Self {
+        self.link_policy = policy;
+        self
+    }
+
+    /// Give the replacement the existing target's access and modification times, instead of the
+    /// time of the commit.
+    pub fn preserve_times(mut self, yes: bool) -> Self {
+        self.preserve_times = yes;
+        self
+    }
+
+    /// Give the replacement the access and modification times of the file at `path`, read when
+    /// committing.
+    pub fn copy_times_from<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.times_from = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Give the replacement this modification time, e.g. that of the sources it was generated
+    /// from. Takes precedence over the other ways of setting times.
+    pub fn set_mtime(mut self, mtime: SystemTime) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// Copy the existing target's extended attributes to the replacement, including ACLs and
+    /// macOS resource forks. Only supported on Linux and macOS; elsewhere this does nothing.
+    pub fn preserve_xattrs(mut self, yes: bool) -> Self {
+        self.preserve_xattrs = yes;
+        self
+    }
+
+    /// Give the replacement the existing target's SELinux security context, so services
+    /// confined by SELinux can still read it. Does nothing where SELinux isn't in use.
+    ///
+    /// [`preserve_xattrs`](AtomicOutput::preserve_xattrs) copies the context too, along with
+    /// everything else.
+    #[cfg(feature = "selinux")]
+    pub fn preserve_selinux(mut self, yes: bool) -> Self {
+        self.preserve_selinux = yes;
+        self
+    }
+
+    /// Refuse to commit, with [`Error::InputChanged`], if the file at `path` (typically the
+    /// target, for in-place editing) no longer matches `stamp`, taken when it was read. The
+    /// target is then left alone, keeping whatever the other writer put there.
+    pub fn require_unchanged<P: AsRef<Path>>(mut self, path: P, stamp: FileStamp) -> Self {
+        self.unchanged = Some((path.as_ref().to_owned(), stamp));
+        self
+    }
+
+    /// After committing, read the target back and compare its SHA-256 digest with that of what
+    /// was written, failing with [`Error::VerificationFailed`] if they differ, for file systems
+    /// that can't be trusted to store what they're given (flaky network mounts, ...).
+    ///
+    /// A read-back may be served from the operating system's cache, so this catches corruption
+    /// on the way to the file system rather than on the disk itself.
+    pub fn verify(mut self, yes: bool) -> Self {
+        self.verify = if yes { Some(Sha256::new()) } else { None };
+        self
+    }
+
+    /// The final path being written, or `None` for stdout.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.target {
+            Target::File { path, .. } => Some(path),
+            Target::Stdout(_) => None,
+        }
+    }
+
+    /// Finish writing and move the new content into place.
+    ///
+    /// Failures for a file carry an [`Error::Commit`], or an [`Error::StorageFull`] if the disk
+    /// filled up.
+    pub fn commit(mut self) -> io::Result<Commit> {
+        let result = match self.prepare() {
+            Ok(true) => self.install().map(|()| Commit::Written),
+            Ok(false) => Ok(Commit::Unchanged),
+            Err(e) => Err(e),
+        };
+        result.map_err(|source| match self.path() {
+            Some(_)
+                if matches!(
+                    Error::of(&source),
+                    Some(Error::VerificationFailed { .. } | Error::InputChanged { .. })
+                ) =>
+            {
+                source
+            }
+            Some(path) if space::is_storage_full(&source) => {
+                space::storage_full(source, path, self.written, None)
+            }
+            Some(path) => Error::Commit {
+                path: path.to_owned(),
+                source,
+            }
+            .into(),
+            None => source,
+        })
+    }
+
+    /// Flush and sync everything written, returning whether the target needs replacing.
+    pub(crate) fn prepare(&mut self) -> io::Result<bool> {
+        let (path, temp_path, writer) = match &mut self.target {
+            Target::Stdout(stdout) => {
+                stdout.flush()?;
+                return Ok(true);
+            }
+            Target::File {
+                path,
+                temp_path,
+                writer,
+                ..
+            } => (path, temp_path, writer),
+        };
+
+        let file = flush::into_inner(writer.take().expect("output is only committed once"))?;
+
+        if let Some((input, stamp)) = &self.unchanged {
+            stamp.check(input)?;
+        }
+
+        if self.write_if_changed && same_content(temp_path, path)? {
+            // The temporary file is removed on drop.
+            return Ok(false);
+        }
+
+        if let Ok(meta) = fs::metadata(&path) {
+            file.set_permissions(meta.permissions())?;
+            if self.preserve_xattrs {
+                xattr::copy(path, &file)?;
+            }
+            #[cfg(feature = "selinux")]
+            if self.preserve_selinux && !self.preserve_xattrs {
+                xattr::copy_selinux_context(path, &file)?;
+            }
+            if self.preserve_times {
+                let times = FileTimes::new()
+                    .set_accessed(meta.accessed()?)
+                    .set_modified(meta.modified()?);
+                file.set_times(times)?;
+            }
+        }
+        if let Some(from) = &self.times_from {
+            let meta = fs::metadata(from)?;
+            let times = FileTimes::new()
+                .set_accessed(meta.accessed()?)
+                .set_modified(meta.modified()?);
+            file.set_times(times)?;
+        }
+        if let Some(mtime) = self.mtime {
+            file.set_times(FileTimes::new().set_modified(mtime))?;
+        }
+        file.sync_all()?;
+        Ok(true)
+    }
+
+    /// Move the prepared temporary file into place, and verify it if asked to.
+    pub(crate) fn install(&mut self) -> io::Result<()> {
+        self.replace_target()?;
+        self.check_written()
+    }
+
+    /// What [`install`](AtomicOutput::install) would replace, as the link policy decides.
+    pub(crate) fn replaced(&self) -> io::Result<Replaced> {
+        let path = match &self.target {
+            Target::File { path, .. } => path,
+            Target::Stdout(_) => return Ok(Replaced::Nothing),
+        };
+        let is_symlink = fs::symlink_metadata(path).is_ok_and(|meta| meta.is_symlink());
+        Ok(match self.link_policy {
+            LinkPolicy::WriteThrough if is_symlink || has_other_links(path) => {
+                Replaced::InPlace(path.clone())
+            }
+            LinkPolicy::FollowAndReplaceTarget if is_symlink => {
+                Replaced::Entry(fs::canonicalize(path)?)
+            }
+            _ => Replaced::Entry(path.clone()),
+        })
+    }
+
+    /// Rename the prepared temporary file over the target, or copy it in for
+    /// [`LinkPolicy::WriteThrough`].
+    fn replace_target(&mut self) -> io::Result<()> {
+        let replaced = self.replaced()?;
+        let (temp_path, renamed) = match &mut self.target {
+            Target::File {
+                temp_path, renamed, ..
+            } => (temp_path, renamed),
+            Target::Stdout(_) => return Ok(()),
+        };
+        match replaced {
+            Replaced::Nothing => return Ok(()),
+            Replaced::InPlace(path) => {
+                // The temporary file is removed on drop.
+                let mut new = File::open(&temp_path)?;
+                let mut file = File::options().write(true).truncate(true).open(&path)?;
+                io::copy(&mut new, &mut file)?;
+                if self.preserve_times || self.times_from.is_some() || self.mtime.is_some() {
+                    let meta = new.metadata()?;
+                    let times = FileTimes::new()
+                        .set_accessed(meta.accessed()?)
+                        .set_modified(meta.modified()?);
+                    file.set_times(times)?;
+                }
+                return file.sync_all();
+            }
+            Replaced::Entry(path) => fs::rename(&temp_path, path)?,
+        }
+        *renamed = true;
+        Ok(())
+    }
+
+    /// Compare the installed target's digest with that of what was written.
+    fn check_written(&self) -> io::Result<()> {
+        let (sha, path) = match (&self.verify, &self.target) {
+            (Some(sha), Target::File { path, .. }) => (sha, path),
+            _ => return Ok(()),
+        };
+        let expected = sha.clone().finish();
+        let mut actual = Sha256::new();
+        let mut file = File::open(path)?;
+        let mut buf = vec![0; COMPARE_CHUNK];
+        loop {
+            let amt = read_full(&mut file, &mut buf)?;
+            if amt == 0 {
+                break;
+            }
+            actual.update(&buf[..amt]);
+        }
+        let actual = actual.finish();
+        if actual != expected {
+            return Err(Error::VerificationFailed {
+                path: path.clone(),
+                expected: to_hex(&expected),
+                actual: to_hex(&actual),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Throw away everything written and leave the target untouched.
+    pub fn abort(self) {}
+
+    fn storage_full(&self, e: io::Error) -> io::Error {
+        match self.path() {
+            Some(path) => space::storage_full(e, path, self.written, None),
+            None => e,
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.target {
+            Target::File { writer, .. } => writer.as_mut().expect("output not committed yet"),
+            Target::Stdout(stdout) => stdout,
+        }
+    }
+}
+
+impl Write for AtomicOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.writer().write(buf) {
+            Ok(amt) => {
+                self.written += amt as u64;
+                if let Some(sha) = &mut self.verify {
+                    sha.update(&buf[..amt]);
+                }
+                Ok(amt)
+            }
+            Err(e) => Err(self.storage_full(e)),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush().map_err(|e| self.storage_full(e))
+    }
+}
+
+impl Drop for AtomicOutput {
+    fn drop(&mut self) {
+        if let Target::File {
+            temp_path,
+            writer,
+            renamed,
+            ..
+        } = &mut self.target
+        {
+            drop(writer.take());
+            if !*renamed {
+                let _ = fs::remove_file(temp_path);
+            }
+        }
+    }
+}
+
+/// Whether the file at `path` has hard links besides `path` itself.
+#[cfg(unix)]
+fn has_other_links(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(path).is_ok_and(|meta| meta.nlink() > 1)
+}
+
+#[cfg(not(unix))]
+fn has_other_links(_: &Path) -> bool {
+    false
+}
+
+/// Compare the files at `new` and `old` byte for byte.
+fn same_content(new: &Path, old: &Path) -> io::Result<bool> {
+    let mut old = match File::open(old) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let mut new = File::open(new)?;
+    if old.metadata()?.len() != new.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let mut new_buf = vec![0; COMPARE_CHUNK];
+    let mut old_buf = vec![0; COMPARE_CHUNK];
+    loop {
+        let amt = read_full(&mut new, &mut new_buf)?;
+        if read_full(&mut old, &mut old_buf)? != amt || new_buf[..amt] != old_buf[..amt] {
+            return Ok(false);
+        }
+        if amt == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Read until `buf` is full or the end of the file is reached.
+fn read_full(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(amt) => len += amt,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn commit_and_write_if_changed() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("out.txt");
+
+        let mut output = AtomicOutput::from_path(&path)?;
+        output.write_all(b"first")?;
+        assert!(!path.exists());
+        assert_eq!(output.commit()?, Commit::Written);
+        assert_eq!(fs::read_to_string(&path)?, "first");
+
+        let old_mtime = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(old_mtime)?;
+
+        let mut output = AtomicOutput::from_path(&path)?.write_if_changed(true);
+        output.write_all(b"first")?;
+        assert_eq!(output.commit()?, Commit::Unchanged);
+        assert_eq!(fs::metadata(&path)?.modified()?, old_mtime);
+
+        let mut output = AtomicOutput::from_path(&path)?.write_if_changed(true);
+        output.write_all(b"fresh")?;
+        assert_eq!(output.commit()?, Commit::Written);
+        assert_eq!(fs::read_to_string(&path)?, "fresh");
+
+        let mut output = AtomicOutput::from_path(&path)?;
+        output.write_all(b"abandoned")?;
+        output.abort();
+        assert_eq!(fs::read_to_string(&path)?, "fresh");
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 1);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_policies() -> Result<(), io::Error> {
+        use std::os::unix::fs::symlink;
+
+        let tmp_dir = TempDir::new()?;
+        let real = tmp_dir.path().join("real.txt");
+        let link = tmp_dir.path().join("link.txt");
+        let hard = tmp_dir.path().join("hard.txt");
+        let replace = |policy, path: &Path, content: &str| -> io::Result<()> {
+            let mut output = AtomicOutput::from_path(path)?.link_policy(policy);
+            output.write_all(content.as_bytes())?;
+            output.commit().map(drop)
+        };
+
+        fs::write(&real, "real")?;
+        symlink(&real, &link)?;
+        replace(LinkPolicy::FollowAndReplaceTarget, &link, "followed")?;
+        assert!(fs::symlink_metadata(&link)?.is_symlink());
+        assert_eq!(fs::read_to_string(&real)?, "followed");
+
+        fs::hard_link(&real, &hard)?;
+        replace(LinkPolicy::WriteThrough, &link, "through")?;
+        assert!(fs::symlink_metadata(&link)?.is_symlink());
+        assert_eq!(fs::read_to_string(&hard)?, "through");
+        replace(LinkPolicy::WriteThrough, &hard, "both")?;
+        assert_eq!(fs::read_to_string(&real)?, "both");
+
+        replace(LinkPolicy::ReplaceLinkItself, &link, "replaced")?;
+        assert!(!fs::symlink_metadata(&link)?.is_symlink());
+        assert_eq!(fs::read_to_string(&real)?, "both");
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 3);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "selinux")]
+    #[test]
+    fn preserve_selinux_context() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("labeled.txt");
+        fs::write(&path, "old")?;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let context = crate::xattr::selinux_context(&path)?;
+
+        let mut output = AtomicOutput::from_path(&path)?.preserve_selinux(true);
+        output.write_all(b"labeled")?;
+        output.commit()?;
+        assert_eq!(fs::read_to_string(&path)?, "labeled");
+        // Only checked where SELinux labels files.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if context.is_some() {
+            assert_eq!(crate::xattr::selinux_context(&path)?, context);
+        }
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn preserve_metadata() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("edited.txt");
+        fs::write(&path, "old")?;
+        let old_mtime = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(old_mtime)?;
+        // Not every file system allows user attributes.
+        #[cfg(target_os = "linux")]
+        let has_xattr = {
+            use std::os::unix::ffi::OsStrExt;
+            let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+            // SAFETY: the path, name and value are valid for the duration of the call.
+            let ret = unsafe {
+                libc::setxattr(
+                    c_path.as_ptr(),
+                    b"user.origin\0".as_ptr().cast(),
+                    b"kept".as_ptr().cast(),
+                    4,
+                    0,
+                )
+            };
+            ret == 0
+        };
+
+        let mut output = AtomicOutput::from_path(&path)?
+            .preserve_times(true)
+            .preserve_xattrs(true);
+        output.write_all(b"new")?;
+        output.commit()?;
+        assert_eq!(fs::read_to_string(&path)?, "new");
+        assert_eq!(fs::metadata(&path)?.modified()?, old_mtime);
+
+        #[cfg(target_os = "linux")]
+        if has_xattr {
+            use std::os::unix::ffi::OsStrExt;
+            let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+            let mut value = [0u8; 8];
+            // SAFETY: `value` has room for 8 bytes.
+            let len = unsafe {
+                libc::getxattr(
+                    c_path.as_ptr(),
+                    b"user.origin\0".as_ptr().cast(),
+                    value.as_mut_ptr().cast(),
+                    8,
+                )
+            };
+            assert_eq!(&value[..len as usize], b"kept");
+        }
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn set_times() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("generated.txt");
+        let source = tmp_dir.path().join("source");
+        fs::write(&source, "source")?;
+        let source_mtime = SystemTime::now() - Duration::from_secs(7200);
+        File::options()
+            .write(true)
+            .open(&source)?
+            .set_modified(source_mtime)?;
+        let mut output = AtomicOutput::from_path(&path)?.copy_times_from(&source);
+        output.write_all(b"generated")?;
+        output.commit()?;
+        assert_eq!(fs::metadata(&path)?.modified()?, source_mtime);
+
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let mut output = AtomicOutput::from_path(&path)?
+            .copy_times_from(&source)
+            .set_mtime(mtime);
+        output.write_all(b"stamped")?;
+        output.commit()?;
+        assert_eq!(fs::metadata(&path)?.modified()?, mtime);
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn verify_committed() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("verified.txt");
+        let mut output = AtomicOutput::from_path(&path)?.verify(true);
+        output.write_all(b"verified")?;
+        output.commit()?;
+        assert_eq!(fs::read_to_string(&path)?, "verified");
+
+        let mut output = AtomicOutput::from_path(&path)?.verify(true);
+        output.write_all(b"written")?;
+        output.flush()?;
+        // Change the temporary file behind the output's back, as a failing disk might.
+        for entry in fs::read_dir(tmp_dir.path())? {
+            let temp_path = entry?.path();
+            if temp_path != path {
+                fs::write(temp_path, "mangled")?;
+            }
+        }
+        let e = output.commit().expect_err("the digests differ");
+        assert!(matches!(
+            Error::of(&e),
+            Some(Error::VerificationFailed { .. })
+        ));
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
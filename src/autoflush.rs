@@ -0,0 +1,111 @@
+use crate::FileOrStdoutLock;
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// When an [`AutoFlush`] writer flushes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushEvery {
+    /// After at least this many bytes were written since the last flush.
+    Bytes(u64),
+    /// On the first write at least this long after the last flush.
+    Interval(Duration),
+}
+
+/// Writer that flushes itself regularly, so readers following the output (`tail -f`) see it
+/// promptly.
+///
+/// Flushes only happen during writes; nothing is flushed while the writer sits idle.
+pub struct AutoFlush<W> {
+    inner: W,
+    every: FlushEvery,
+    unflushed: u64,
+    last_flush: Instant,
+}
+
+impl<W> AutoFlush<W> {
+    pub fn new(inner: W, every: FlushEvery) -> Self {
+        Self {
+            inner,
+            every,
+            unflushed: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<'a> FileOrStdoutLock<'a> {
+    /// Flush the output regularly as it's written. See [`AutoFlush`].
+    pub fn autoflush_every(self, every: FlushEvery) -> AutoFlush<Self> {
+        AutoFlush::new(self, every)
+    }
+}
+
+impl<W: Write> Write for AutoFlush<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amt = self.inner.write(buf)?;
+        self.unflushed += amt as u64;
+        let due = match self.every {
+            FlushEvery::Bytes(bytes) => self.unflushed >= bytes,
+            FlushEvery::Interval(interval) => self.last_flush.elapsed() >= interval,
+        };
+        if due && self.unflushed > 0 {
+            self.flush()?;
+        }
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.unflushed = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffering, FileOrStdout};
+    use std::{fs, thread};
+    use tempfile::TempDir;
+
+    #[test]
+    fn flush_by_size_and_time() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("progress.log");
+
+        let mut output = FileOrStdout::from_path(&path)?;
+        let mut writer = output
+            .lock_buffered(Buffering::Block)
+            .autoflush_every(FlushEvery::Bytes(8));
+        writer.write_all(b"12345")?;
+        assert_eq!(fs::read_to_string(&path)?, "");
+        writer.write_all(b"678")?;
+        assert_eq!(fs::read_to_string(&path)?, "12345678");
+        drop(writer);
+
+        let mut output = FileOrStdout::from_path(&path)?;
+        let mut writer = output
+            .lock_buffered(Buffering::Block)
+            .autoflush_every(FlushEvery::Interval(Duration::from_millis(20)));
+        writer.write_all(b"early")?;
+        assert_eq!(fs::read_to_string(&path)?, "");
+        thread::sleep(Duration::from_millis(30));
+        writer.write_all(b" late")?;
+        assert_eq!(fs::read_to_string(&path)?, "early late");
+        drop(writer);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,547 @@
+use crate::{
+    exit::warn, report::Counted, with_path, AtomicOutput, FileOrStdin, FileOrStdout,
+    OutputTemplate, Report, STDIO_FILENAME,
+};
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+/// What a [`Batch`] or [`InputChain`](crate::InputChain) does when one of its inputs fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Stop and return the error.
+    #[default]
+    Abort,
+    /// Report the error on stderr, or to the [`set_warning_handler`](crate::set_warning_handler)
+    /// handler, and go on with the next input.
+    Continue,
+    /// Go on with the next input, keeping the error in the [`BatchReport`] (or
+    /// [`InputChain::failures`](crate::InputChain::failures)) only.
+    Collect,
+}
+
+/// What a [`Batch`] does when stdin (`-`) is among its inputs more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RepeatedStdin {
+    /// Read stdin for the first `-`, and treat later ones as empty inputs.
+    #[default]
+    Empty,
+    /// Fail before processing anything, with `InvalidInput`.
+    Error,
+    /// Keep everything read from stdin in memory, and hand it out again for every `-`.
+    Replay,
+}
+
+/// Runs one processing function over many inputs, the core loop of most file-processing tools.
+///
+/// Each input (where `-` is stdin) is opened and handed to the function together with its output:
+/// either one shared sink (stdout by default) or, with an [`OutputTemplate`], a separate file per
+/// input. Per-input files are written atomically, so an input that fails leaves no partial output
+/// behind. Neither does it in a shared sink unless the error policy is [`ErrorPolicy::Abort`]:
+/// each input's output is then buffered and written whole once the input succeeds. What `-`
+/// given more than once means is set with [`repeated_stdin`](Batch::repeated_stdin).
+///
+/// With [`jobs`](Batch::jobs), several inputs are processed at once. Output for a shared sink is
+/// then always buffered per input, and written in input order if [`ordered`](Batch::ordered) is
+/// set and as inputs finish otherwise; the output of an input that fails is discarded.
+pub struct Batch {
+    inputs: Vec<PathBuf>,
+    output: BatchOutput,
+    policy: ErrorPolicy,
+    jobs: usize,
+    ordered: bool,
+    stdin: StdinState,
+}
+
+/// How stdin has been used so far in a [`Batch`] run.
+struct StdinState {
+    policy: RepeatedStdin,
+    used: AtomicBool,
+    replay: Mutex<Option<Arc<[u8]>>>,
+}
+
+enum BatchOutput {
+    Single(PathBuf),
+    Template(OutputTemplate),
+}
+
+/// Summary of a [`Batch`] run, counting the bytes `f` read and wrote.
+pub type BatchReport = Report;
+
+impl Batch {
+    pub fn new<I, P>(inputs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        Self {
+            inputs: inputs.into_iter().map(|p| p.as_ref().to_owned()).collect(),
+            output: BatchOutput::Single(PathBuf::from(STDIO_FILENAME)),
+            policy: ErrorPolicy::default(),
+            jobs: 1,
+            ordered: false,
+            stdin: StdinState {
+                policy: RepeatedStdin::default(),
+                used: AtomicBool::new(false),
+                replay: Mutex::new(None),
+            },
+        }
+    }
+
+    /// Write the output for all inputs to `path`.
+    pub fn output<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.output = BatchOutput::Single(path.as_ref().to_owned());
+        self
+    }
+
+    /// Write each input's output to its own file, named by `template`.
+    pub fn output_template(mut self, template: OutputTemplate) -> Self {
+        self.output = BatchOutput::Template(template);
+        self
+    }
+
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Process up to `jobs` inputs in parallel. 0 means one per available CPU. Defaults to 1.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = match jobs {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs => jobs,
+        };
+        self
+    }
+
+    /// When processing in parallel into a shared sink, write each input's output in input order
+    /// rather than as soon as it is done.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Defaults to [`RepeatedStdin::Empty`].
+    pub fn repeated_stdin(mut self, policy: RepeatedStdin) -> Self {
+        self.stdin.policy = policy;
+        self
+    }
+
+    /// Process every input with `f`.
+    ///
+    /// Errors are prefixed with the path of the input they occurred for.
+    pub fn run<F>(self, f: F) -> io::Result<BatchReport>
+    where
+        F: Fn(&mut dyn BufRead, &mut dyn Write) -> io::Result<()> + Sync,
+    {
+        let is_stdin = |input: &&PathBuf| input.to_string_lossy() == STDIO_FILENAME;
+        if self.stdin.policy == RepeatedStdin::Error
+            && self.inputs.iter().filter(is_stdin).count() > 1
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "stdin (-) is given as an input more than once",
+            ));
+        }
+
+        let mut single = match &self.output {
+            BatchOutput::Single(path) => Some(FileOrStdout::from_path(path)?),
+            BatchOutput::Template(_) => None,
+        };
+        let mut single_lock = single.as_mut().map(FileOrStdout::lock);
+        let single_lock = single_lock.as_mut().map(|lock| lock as &mut dyn Write);
+
+        if self.jobs > 1 && self.inputs.len() > 1 {
+            self.run_parallel(single_lock, &f)
+        } else {
+            self.run_sequential(single_lock, &f)
+        }
+    }
+
+    fn run_sequential<F>(
+        &self,
+        mut single: Option<&mut dyn Write>,
+        f: &F,
+    ) -> io::Result<BatchReport>
+    where
+        F: Fn(&mut dyn BufRead, &mut dyn Write) -> io::Result<()>,
+    {
+        let mut report = Report::default();
+        for input in &self.inputs {
+            let result = match (&mut single, &self.output) {
+                (Some(output), _) if self.policy != ErrorPolicy::Abort => {
+                    let mut buf = Vec::new();
+                    let result = process(input, &self.stdin, &mut buf, f);
+                    if result.is_ok() {
+                        output.write_all(&buf)?;
+                    }
+                    result
+                }
+                (Some(output), _) => process(input, &self.stdin, *output, f),
+                (None, BatchOutput::Template(template)) => {
+                    process_to_file(input, &self.stdin, &template.render(input), f)
+                }
+                (None, BatchOutput::Single(_)) => unreachable!("single output is always open"),
+            };
+            self.record(&mut report, input, result)?;
+        }
+        if let Some(output) = single {
+            output.flush()?;
+        }
+        Ok(report)
+    }
+
+    fn run_parallel<F>(&self, mut single: Option<&mut dyn Write>, f: &F) -> io::Result<BatchReport>
+    where
+        F: Fn(&mut dyn BufRead, &mut dyn Write) -> io::Result<()> + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let (tx, rx) = mpsc::channel();
+
+        let mut report = Report::default();
+        let mut failed_at = Vec::new();
+        thread::scope(|scope| {
+            for _ in 0..self.jobs.min(self.inputs.len()) {
+                let tx = tx.clone();
+                let (next, stop) = (&next, &stop);
+                scope.spawn(move || {
+                    while !stop.load(Ordering::SeqCst) {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        let input = match self.inputs.get(i) {
+                            Some(input) => input,
+                            None => break,
+                        };
+                        let result = match &self.output {
+                            BatchOutput::Single(_) => {
+                                let mut buf = Vec::new();
+                                process(input, &self.stdin, &mut buf, f).map(|bytes| (buf, bytes))
+                            }
+                            BatchOutput::Template(template) => {
+                                process_to_file(input, &self.stdin, &template.render(input), f)
+                                    .map(|bytes| (Vec::new(), bytes))
+                            }
+                        };
+                        if tx.send((i, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            let mut handle =
+                |i: usize, result: io::Result<(Vec<u8>, (u64, u64))>| -> io::Result<()> {
+                    let failures = report.failures().len();
+                    let result = match result {
+                        Ok((buf, bytes)) => {
+                            if let Some(output) = &mut single {
+                                output.write_all(&buf)?;
+                            }
+                            Ok(bytes)
+                        }
+                        Err(e) => Err(e),
+                    };
+                    let recorded = self.record(&mut report, &self.inputs[i], result);
+                    if report.failures().len() != failures {
+                        failed_at.push(i);
+                    }
+                    recorded
+                };
+
+            let mut pending = BTreeMap::new();
+            let mut next_in_order = 0;
+            for (i, result) in rx {
+                let handled = if self.ordered {
+                    pending.insert(i, result);
+                    let mut handled = Ok(());
+                    while let Some(result) = pending.remove(&next_in_order) {
+                        handled = handle(next_in_order, result);
+                        next_in_order += 1;
+                        if handled.is_err() {
+                            break;
+                        }
+                    }
+                    handled
+                } else {
+                    handle(i, result)
+                };
+                if handled.is_err() {
+                    stop.store(true, Ordering::SeqCst);
+                    return handled;
+                }
+            }
+            Ok(())
+        })?;
+
+        // Failures were recorded as inputs finished; report them in input order.
+        let mut failures: Vec<_> = failed_at.into_iter().zip(report.failures).collect();
+        failures.sort_by_key(|(i, _)| *i);
+        report.failures = failures.into_iter().map(|(_, failure)| failure).collect();
+
+        if let Some(output) = single {
+            output.flush()?;
+        }
+        Ok(report)
+    }
+
+    /// Apply the error policy to the result of processing `input`.
+    fn record(
+        &self,
+        report: &mut Report,
+        input: &Path,
+        result: io::Result<(u64, u64)>,
+    ) -> io::Result<()> {
+        match result {
+            Ok((bytes_in, bytes_out)) => {
+                report.add_processed();
+                report.add_bytes(bytes_in, bytes_out);
+            }
+            Err(e) => {
+                let e = with_path(e, input);
+                match self.policy {
+                    ErrorPolicy::Abort => return Err(e),
+                    ErrorPolicy::Continue => warn(&e.to_string()),
+                    ErrorPolicy::Collect => {}
+                }
+                report.add_failure(input, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Process `input` into `output` with `f`, returning the bytes `f` read and wrote.
+fn process<F>(
+    input: &Path,
+    stdin: &StdinState,
+    output: &mut dyn Write,
+    f: &F,
+) -> io::Result<(u64, u64)>
+where
+    F: Fn(&mut dyn BufRead, &mut dyn Write) -> io::Result<()>,
+{
+    let counted = |input: &mut dyn BufRead, output: &mut dyn Write| {
+        let (mut input, mut output) = (Counted::new(input), Counted::new(output));
+        f(&mut input, &mut output)?;
+        Ok((input.count, output.count))
+    };
+    let is_stdin = input.to_string_lossy() == STDIO_FILENAME;
+    let mut input = FileOrStdin::from_path(input)?;
+    let mut lock = input.lock();
+    if is_stdin {
+        stdin.process(&mut lock, output, &counted)
+    } else {
+        counted(&mut lock, output)
+    }
+}
+
+fn process_to_file<F>(
+    input: &Path,
+    stdin: &StdinState,
+    output: &Path,
+    f: &F,
+) -> io::Result<(u64, u64)>
+where
+    F: Fn(&mut dyn BufRead, &mut dyn Write) -> io::Result<()>,
+{
+    let mut output = AtomicOutput::from_path(output)?;
+    let bytes = process(input, stdin, &mut output, f)?;
+    output.commit()?;
+    Ok(bytes)
+}
+
+impl StdinState {
+    /// Process `stdin` with `f` as the repeated stdin policy says.
+    fn process<F, T>(&self, stdin: &mut dyn BufRead, output: &mut dyn Write, f: &F) -> io::Result<T>
+    where
+        F: Fn(&mut dyn BufRead, &mut dyn Write) -> io::Result<T>,
+    {
+        let first = !self.used.swap(true, Ordering::SeqCst);
+        match self.policy {
+            RepeatedStdin::Replay => {
+                let data = {
+                    let mut replay = self.replay.lock().unwrap_or_else(|p| p.into_inner());
+                    match &*replay {
+                        Some(data) => Arc::clone(data),
+                        None => {
+                            let mut data = Vec::new();
+                            stdin.read_to_end(&mut data)?;
+                            Arc::clone(replay.insert(data.into()))
+                        }
+                    }
+                };
+                f(&mut &data[..], output)
+            }
+            _ if first => f(stdin, output),
+            _ => f(&mut io::empty(), output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exit::{replace_warning_handler, WarningHandler};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn upper(input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<()> {
+        let mut string = String::new();
+        input.read_to_string(&mut string)?;
+        output.write_all(string.to_uppercase().as_bytes())
+    }
+
+    /// Puts the previous warning handler back when dropped.
+    struct WarningHandlerGuard(Option<WarningHandler>);
+
+    impl WarningHandlerGuard {
+        fn set<F: Fn(&str) + Send + Sync + 'static>(handler: F) -> Self {
+            Self(replace_warning_handler(Some(Box::new(handler))))
+        }
+    }
+
+    impl Drop for WarningHandlerGuard {
+        fn drop(&mut self) {
+            replace_warning_handler(self.0.take());
+        }
+    }
+
+    #[test]
+    fn batch_error_policies() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let a = tmp_dir.path().join("a.txt");
+        let missing = tmp_dir.path().join("missing.txt");
+        let b = tmp_dir.path().join("b.txt");
+        fs::write(&a, "a")?;
+        fs::write(&b, "b")?;
+        let inputs = [&a, &missing, &b];
+
+        static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        let _handler =
+            WarningHandlerGuard::set(|message| WARNINGS.lock().unwrap().push(message.into()));
+        let all = tmp_dir.path().join("all");
+        let report = Batch::new(inputs)
+            .output(&all)
+            .on_error(ErrorPolicy::Continue)
+            .run(upper)?;
+        let missing_name = missing.to_string_lossy();
+        assert!(WARNINGS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|w| w.contains(&*missing_name)));
+        assert_eq!(
+            report.to_string(),
+            "2 processed, 1 failed, 2 bytes in, 2 bytes out"
+        );
+        assert_eq!(report.failures()[0].0, missing);
+        assert_eq!(fs::read_to_string(&all)?, "AB");
+
+        let template = OutputTemplate::new("{dir}/{stem}.out");
+        let err = Batch::new(inputs)
+            .output_template(template)
+            .run(upper)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("missing.txt"));
+        assert_eq!(fs::read_to_string(tmp_dir.path().join("a.out"))?, "A");
+        assert!(!tmp_dir.path().join("b.out").exists());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn failed_output_discarded() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let mut inputs = Vec::new();
+        for name in ["a", "bad", "b"] {
+            let path = tmp_dir.path().join(name);
+            fs::write(&path, name)?;
+            inputs.push(path);
+        }
+        let partly = |input: &mut dyn BufRead, output: &mut dyn Write| -> io::Result<()> {
+            let mut string = String::new();
+            input.read_to_string(&mut string)?;
+            output.write_all(string.as_bytes())?;
+            match &*string {
+                "bad" => Err(io::Error::other("failed halfway")),
+                _ => Ok(()),
+            }
+        };
+
+        let all = tmp_dir.path().join("all");
+        for jobs in [1, 3] {
+            let report = Batch::new(&inputs)
+                .output(&all)
+                .on_error(ErrorPolicy::Collect)
+                .jobs(jobs)
+                .ordered(true)
+                .run(partly)?;
+            assert_eq!(report.failures().len(), 1);
+            assert_eq!(fs::read_to_string(&all)?, "ab");
+        }
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_stdin() -> Result<(), io::Error> {
+        let state = |policy| StdinState {
+            policy,
+            used: AtomicBool::new(false),
+            replay: Mutex::new(None),
+        };
+        let run_twice = |state: &StdinState| -> io::Result<String> {
+            let mut output = Vec::new();
+            state.process(&mut &b"in,"[..], &mut output, &upper)?;
+            state.process(&mut &b"again,"[..], &mut output, &upper)?;
+            Ok(String::from_utf8(output).unwrap())
+        };
+        assert_eq!(run_twice(&state(RepeatedStdin::Empty))?, "IN,");
+        assert_eq!(run_twice(&state(RepeatedStdin::Replay))?, "IN,IN,");
+
+        let e = Batch::new(["-", "-"])
+            .repeated_stdin(RepeatedStdin::Error)
+            .run(upper)
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[test]
+    fn parallel_ordered_output() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let mut inputs = Vec::new();
+        for n in 0..20 {
+            let path = tmp_dir.path().join(format!("{}.txt", n));
+            fs::write(&path, format!("{},", n))?;
+            inputs.push(path);
+        }
+        inputs.insert(5, tmp_dir.path().join("missing.txt"));
+
+        let all = tmp_dir.path().join("all");
+        let report = Batch::new(&inputs)
+            .output(&all)
+            .on_error(ErrorPolicy::Collect)
+            .jobs(4)
+            .ordered(true)
+            .run(upper)?;
+        assert_eq!(report.processed(), 20);
+        assert_eq!(report.failures().len(), 1);
+        let expected = (0..20).map(|n| format!("{},", n)).collect::<String>();
+        assert_eq!(fs::read_to_string(&all)?, expected);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{exit::warn, with_path, ErrorPolicy, STDIO_FILENAME};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+/// Several inputs read back to back as one stream.
+///
+/// Parts are read in the order they were added. Files are only opened once the chain reaches
+/// them, so an error opening one is returned by the read that gets there, unless an
+/// [`on_error`](InputChain::on_error) policy says to skip it.
+///
+/// ```no_run
+/// # use polymorphio::InputChain;
+/// # use std::io::Read;
+/// let mut content = String::new();
+/// InputChain::new()
+///     .literal("header\n")
+///     .path("body.txt")
+///     .stdin()
+///     .read_to_string(&mut content)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Default)]
+pub struct InputChain {
+    parts: VecDeque<Part>,
+    current: Option<Current>,
+    policy: ErrorPolicy,
+    failures: Vec<(PathBuf, io::Error)>,
+}
+
+/// The part being read, and the file it came from, if any.
+struct Current {
+    reader: Box<dyn BufRead + Send>,
+    path: Option<PathBuf>,
+}
+
+enum Part {
+    Literal(Vec<u8>),
+    Path(PathBuf),
+    Stdin,
+    Reader(Box<dyn Read + Send>),
+}
+
+impl InputChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append fixed data to the chain.
+    pub fn literal<B: Into<Vec<u8>>>(mut self, data: B) -> Self {
+        self.parts.push_back(Part::Literal(data.into()));
+        self
+    }
+
+    /// Append a file to the chain. `-` means stdin.
+    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        let path = path.as_ref();
+        if path.to_string_lossy() == STDIO_FILENAME {
+            self.parts.push_back(Part::Stdin);
+        } else {
+            self.parts.push_back(Part::Path(path.to_owned()));
+        }
+        self
+    }
+
+    pub fn stdin(mut self) -> Self {
+        self.parts.push_back(Part::Stdin);
+        self
+    }
+
+    /// Append any other reader to the chain.
+    pub fn reader<R: Read + Send + 'static>(mut self, reader: R) -> Self {
+        self.parts.push_back(Part::Reader(Box::new(reader)));
+        self
+    }
+
+    /// What to do when a file or stdin in the chain fails to open or read. Defaults to
+    /// [`ErrorPolicy::Abort`].
+    ///
+    /// With `Continue` or `Collect`, the rest of the failed part is skipped and the chain goes on
+    /// with the next one; the errors are kept in [`failures`](InputChain::failures). Errors from
+    /// [`reader`](InputChain::reader) parts are always returned.
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The inputs skipped so far under the error policy, each with its error.
+    pub fn failures(&self) -> &[(PathBuf, io::Error)] {
+        &self.failures
+    }
+
+    /// Make sure there is a current part with data, unless the chain is finished.
+    ///
+    /// An error the policy doesn't skip is returned with the part still current (or, for a file
+    /// that failed to open, still queued), so reading again retries it.
+    fn advance(&mut self) -> io::Result<()> {
+        loop {
+            let policy = self.policy;
+            if let Some(current) = &mut self.current {
+                match current.reader.fill_buf() {
+                    Ok(buf) if !buf.is_empty() => return Ok(()),
+                    Ok(_) => {}
+                    Err(e) if skips(policy, current.path.is_some(), &e) => {
+                        let path = current.path.take().expect("skipped parts have a path");
+                        self.current = None;
+                        self.skip(path, e);
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+                self.current = None;
+            }
+
+            let (reader, path): (Box<dyn BufRead + Send>, _) = match self.parts.pop_front() {
+                None => return Ok(()),
+                Some(Part::Literal(data)) => (Box::new(Cursor::new(data)), None),
+                Some(Part::Path(path)) => match File::open(&path) {
+                    Ok(file) => (Box::new(BufReader::new(file)), Some(path)),
+                    Err(e) if skips(policy, true, &e) => {
+                        self.skip(path, e);
+                        continue;
+                    }
+                    Err(e) => {
+                        self.parts.push_front(Part::Path(path));
+                        return Err(e);
+                    }
+                },
+                Some(Part::Stdin) => (
+                    Box::new(BufReader::new(io::stdin())),
+                    Some(PathBuf::from(STDIO_FILENAME)),
+                ),
+                Some(Part::Reader(reader)) => (Box::new(BufReader::new(reader)), None),
+            };
+            self.current = Some(Current { reader, path });
+        }
+    }
+
+    /// Record the error of the input at `path`, about to be skipped.
+    fn skip(&mut self, path: PathBuf, e: io::Error) {
+        let e = with_path(e, &path);
+        if self.policy == ErrorPolicy::Continue {
+            warn(&e.to_string());
+        }
+        self.failures.push((path, e));
+    }
+}
+
+/// Whether `policy` skips the rest of a part failing with `e`: only a file or stdin, and never
+/// for an interrupted read, which is retried instead.
+fn skips(policy: ErrorPolicy, has_path: bool, e: &io::Error) -> bool {
+    has_path && policy != ErrorPolicy::Abort && e.kind() != io::ErrorKind::Interrupted
+}
+
+impl Read for InputChain {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.fill_buf()?.read(buf)?;
+        self.consume(amt);
+        Ok(amt)
+    }
+}
+
+impl BufRead for InputChain {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.advance()?;
+        match &mut self.current {
+            Some(current) => current.reader.fill_buf(),
+            None => Ok(&[]),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(current) = &mut self.current {
+            current.reader.consume(amt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn chain_literals_and_files() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let first = tmp_dir.path().join("first.txt");
+        let second = tmp_dir.path().join("second.txt");
+        fs::write(&first, "a,1\n")?;
+        fs::write(&second, "b,2\n")?;
+
+        let chain = InputChain::new()
+            .literal("name,value\n")
+            .path(&first)
+            .literal("")
+            .reader(&b"x,0\n"[..])
+            .path(&second);
+        let lines = chain.lines().collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(lines, vec!["name,value", "a,1", "x,0", "b,2"]);
+
+        let mut missing = InputChain::new()
+            .literal("ok")
+            .path(tmp_dir.path().join("missing"));
+        let mut content = String::new();
+        let err = missing.read_to_string(&mut content).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(content, "ok");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn skip_failed_inputs() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let present = tmp_dir.path().join("present.txt");
+        let missing = tmp_dir.path().join("missing.txt");
+        fs::write(&present, "kept\n")?;
+
+        let mut chain = InputChain::new()
+            .path(&missing)
+            .path(&present)
+            .path(tmp_dir.path())
+            .on_error(ErrorPolicy::Collect);
+        let mut content = String::new();
+        chain.read_to_string(&mut content)?;
+        assert_eq!(content, "kept\n");
+
+        let failed: Vec<_> = chain.failures().iter().map(|(p, _)| p.clone()).collect();
+        assert_eq!(failed, [missing, tmp_dir.path().to_owned()]);
+        assert_eq!(chain.failures()[0].1.kind(), io::ErrorKind::NotFound);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    /// Fails with `Interrupted` once, after its first chunk.
+    struct InterruptOnce {
+        chunks: VecDeque<io::Result<&'static [u8]>>,
+    }
+
+    impl Read for InterruptOnce {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.chunks.pop_front() {
+                Some(chunk) => chunk?.read(buf),
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn retry_interrupted_parts() -> Result<(), io::Error> {
+        for policy in [ErrorPolicy::Abort, ErrorPolicy::Collect] {
+            let interrupted = InterruptOnce {
+                chunks: VecDeque::from([
+                    Ok(&b"ab"[..]),
+                    Err(io::ErrorKind::Interrupted.into()),
+                    Ok(&b"cd"[..]),
+                ]),
+            };
+            let mut chain = InputChain::new()
+                .reader(interrupted)
+                .literal("ef")
+                .on_error(policy);
+            let mut content = String::new();
+            chain.read_to_string(&mut content)?;
+            assert_eq!(content, "abcdef");
+            assert!(chain.failures().is_empty());
+        }
+
+        let tmp_dir = TempDir::new()?;
+        let late = tmp_dir.path().join("late.txt");
+        let mut chain = InputChain::new().path(&late).literal("!");
+        let e = chain
+            .read(&mut [0; 8])
+            .expect_err("the file isn't there yet");
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        fs::write(&late, "here")?;
+        let mut content = String::new();
+        chain.read_to_string(&mut content)?;
+        assert_eq!(content, "here!");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    io::{self, BufRead, Read, Write},
+    sync::mpsc::{self, Receiver, SyncSender},
+};
+
+/// How much a [`ChannelWriter`] collects before sending it on.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A reader fed chunks through a channel holding at most `bound` of them, so one thread can own
+/// the real input and pass it on to code expecting a reader: the front end of a GUI wrapping a
+/// command-line core, say.
+///
+/// Send `Err` to fail the read with it. The reader sees the end of input once every sender is
+/// dropped.
+pub fn channel_reader(bound: usize) -> (SyncSender<io::Result<Vec<u8>>>, ChannelReader) {
+    let (sender, receiver) = mpsc::sync_channel(bound);
+    let reader = ChannelReader {
+        receiver,
+        chunk: Vec::new(),
+        pos: 0,
+        done: false,
+    };
+    (sender, reader)
+}
+
+/// A writer sending what's written as chunks through a channel holding at most `bound` of them,
+/// so one thread can own the real output. Writes block while the channel is full.
+///
+/// Writes are collected into chunks of up to 8 KiB, sent when full and on flush and drop. Once
+/// the receiver is dropped, writes fail with `BrokenPipe`.
+pub fn channel_writer(bound: usize) -> (ChannelWriter, Receiver<Vec<u8>>) {
+    let (sender, receiver) = mpsc::sync_channel(bound);
+    let writer = ChannelWriter {
+        sender,
+        buf: Vec::new(),
+    };
+    (writer, receiver)
+}
+
+/// Reading end of a [`channel_reader`].
+pub struct ChannelReader {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+    /// Every sender is gone.
+    done: bool,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.fill_buf()?.read(buf)?;
+        self.consume(amt);
+        Ok(amt)
+    }
+}
+
+impl BufRead for ChannelReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.chunk.len() && !self.done {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.pos = 0;
+                }
+                Err(_) => self.done = true,
+            }
+        }
+        Ok(&self.chunk[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.chunk.len());
+    }
+}
+
+/// Writing end of a [`channel_writer`].
+pub struct ChannelWriter {
+    sender: SyncSender<Vec<u8>>,
+    buf: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn send(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::take(&mut self.buf);
+        self.sender.send(chunk).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "the channel's receiver is gone")
+        })
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        let amt = buf.len().min(CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&buf[..amt]);
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+impl Drop for ChannelWriter {
+    fn drop(&mut self) {
+        let _ = self.send();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn bridge_through_channels() -> Result<(), io::Error> {
+        let (sender, mut reader) = channel_reader(1);
+        let producer = thread::spawn(move || {
+            for chunk in ["hel", "lo"] {
+                sender.send(Ok(chunk.into())).unwrap();
+            }
+        });
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        producer.join().unwrap();
+        assert_eq!(content, "hello");
+
+        let (sender, mut reader) = channel_reader(1);
+        sender
+            .send(Err(io::ErrorKind::UnexpectedEof.into()))
+            .unwrap();
+        let e = reader.read(&mut [0; 4]).expect_err("the sender failed");
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        let (mut writer, receiver) = channel_writer(1);
+        let consumer = thread::spawn(move || receiver.iter().flatten().collect::<Vec<u8>>());
+        let data = vec![7; CHUNK_SIZE * 3 + 5];
+        writer.write_all(&data)?;
+        drop(writer);
+        assert_eq!(consumer.join().unwrap(), data);
+
+        let (mut writer, receiver) = channel_writer(1);
+        drop(receiver);
+        writer.write_all(b"lost")?;
+        let e = writer.flush().expect_err("nobody is listening");
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use crate::FileOrStdinLock;
+use std::io::{self, Read};
+
+/// Iterator over fixed-size records of a reader, from [`FileOrStdinLock::chunks`].
+///
+/// Every record is exactly the chunk size, however the data arrives from the reader, except
+/// that the last one is short if the input doesn't divide evenly.
+pub struct Chunks<R> {
+    inner: R,
+    size: usize,
+    done: bool,
+}
+
+impl<R: Read> Chunks<R> {
+    /// Read `inner` in records of `size` bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `size` is 0.
+    pub fn new(inner: R, size: usize) -> Self {
+        assert!(size != 0, "chunk size must be non-zero");
+        Self {
+            inner,
+            size,
+            done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Read the rest of the input in records of `size` bytes. See [`Chunks`].
+    pub fn chunks(&mut self, size: usize) -> Chunks<&mut Self> {
+        Chunks::new(self, size)
+    }
+}
+
+impl<R: Read> Iterator for Chunks<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut record = vec![0; self.size];
+        let mut filled = 0;
+        while filled < self.size {
+            match self.inner.read(&mut record[filled..]) {
+                Ok(0) => break,
+                Ok(amt) => filled += amt,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        if filled < self.size {
+            self.done = true;
+            if filled == 0 {
+                return None;
+            }
+            record.truncate(filled);
+        }
+        Some(Ok(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out at most three bytes per read, like a slow pipe.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let amt = buf.len().min(3).min(self.0.len());
+            buf[..amt].copy_from_slice(&self.0[..amt]);
+            self.0 = &self.0[amt..];
+            Ok(amt)
+        }
+    }
+
+    #[test]
+    fn fixed_size_records() -> Result<(), io::Error> {
+        let records =
+            Chunks::new(Trickle(b"0123456789abcdefghij"), 8).collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(records, [&b"01234567"[..], b"89abcdef", b"ghij"]);
+
+        let records = Chunks::new(&b"abcdef"[..], 3).collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(records, [b"abc", b"def"]);
+        assert!(Chunks::new(&b""[..], 3).next().is_none());
+        Ok(())
+    }
+}
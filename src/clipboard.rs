@@ -0,0 +1,226 @@
+use std::{
+    env,
+    io::{self, BufRead, Cursor, Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// The input or output spec meaning the system clipboard.
+pub const CLIPBOARD_PATH: &str = "clip:";
+
+/// Whether `path` is [`CLIPBOARD_PATH`].
+pub fn is_clipboard_path<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().to_string_lossy() == CLIPBOARD_PATH
+}
+
+/// Reader for the text on the system clipboard, read when it is opened.
+///
+/// Uses the system's tools: `pbpaste` on macOS, PowerShell's `Get-Clipboard` on Windows, and
+/// `wl-paste`, `xclip` or `xsel` elsewhere, whichever is installed.
+pub struct ClipboardReader {
+    content: Cursor<Vec<u8>>,
+}
+
+/// Writer replacing what's on the system clipboard when finished.
+///
+/// Uses `pbcopy` on macOS, `clip` on Windows, and `wl-copy`, `xclip` or `xsel` elsewhere. Call
+/// [`finish`](ClipboardWriter::finish) to see errors; dropping the writer sets the clipboard
+/// too, but ignores them.
+pub struct ClipboardWriter {
+    content: Option<Vec<u8>>,
+}
+
+impl ClipboardReader {
+    pub fn open() -> io::Result<Self> {
+        let mut last_error = None;
+        for command in paste_commands(env::consts::OS, is_wayland()) {
+            let output = match Command::new(command[0])
+                .args(&command[1..])
+                .stdin(Stdio::null())
+                .stderr(Stdio::null())
+                .output()
+            {
+                Ok(output) => output,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    last_error = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if !output.status.success() {
+                return Err(io::Error::other(format!(
+                    "`{}` failed: {}",
+                    command[0], output.status
+                )));
+            }
+            return Ok(Self {
+                content: Cursor::new(output.stdout),
+            });
+        }
+        Err(no_tool(last_error))
+    }
+}
+
+impl Read for ClipboardReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.content.read(buf)
+    }
+}
+
+impl BufRead for ClipboardReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.content.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.content.consume(amt)
+    }
+}
+
+impl ClipboardWriter {
+    pub fn new() -> Self {
+        Self {
+            content: Some(Vec::new()),
+        }
+    }
+
+    /// Put everything written on the clipboard.
+    pub fn finish(mut self) -> io::Result<()> {
+        let content = self.content.take().expect("writer is only finished once");
+        copy(&content)
+    }
+}
+
+impl Default for ClipboardWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for ClipboardWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.content
+            .as_mut()
+            .expect("writer not finished")
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ClipboardWriter {
+    fn drop(&mut self) {
+        if let Some(content) = self.content.take() {
+            let _ = copy(&content);
+        }
+    }
+}
+
+fn copy(content: &[u8]) -> io::Result<()> {
+    let mut last_error = None;
+    for command in copy_commands(env::consts::OS, is_wayland()) {
+        let mut child = match Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                last_error = Some(e);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let written = child
+            .stdin
+            .take()
+            .expect("clipboard tool stdin is piped")
+            .write_all(content);
+        let status = child.wait()?;
+        written?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "`{}` failed: {}",
+                command[0], status
+            )));
+        }
+        return Ok(());
+    }
+    Err(no_tool(last_error))
+}
+
+fn is_wayland() -> bool {
+    env::var_os("WAYLAND_DISPLAY").is_some_and(|display| !display.is_empty())
+}
+
+/// Commands printing the clipboard, in the order to try them.
+fn paste_commands(os: &str, wayland: bool) -> Vec<&'static [&'static str]> {
+    match os {
+        "macos" => vec![&["pbpaste"]],
+        "windows" => vec![&[
+            "powershell",
+            "-NoProfile",
+            "-Command",
+            "[Console]::Out.Write((Get-Clipboard -Raw))",
+        ]],
+        _ => {
+            let mut commands: Vec<&'static [&'static str]> = vec![
+                &["xclip", "-selection", "clipboard", "-out"],
+                &["xsel", "--clipboard", "--output"],
+            ];
+            if wayland {
+                commands.insert(0, &["wl-paste", "--no-newline"]);
+            }
+            commands
+        }
+    }
+}
+
+/// Commands setting the clipboard to their input, in the order to try them.
+fn copy_commands(os: &str, wayland: bool) -> Vec<&'static [&'static str]> {
+    match os {
+        "macos" => vec![&["pbcopy"]],
+        "windows" => vec![&["clip"]],
+        _ => {
+            let mut commands: Vec<&'static [&'static str]> = vec![
+                &["xclip", "-selection", "clipboard", "-in"],
+                &["xsel", "--clipboard", "--input"],
+            ];
+            if wayland {
+                commands.insert(0, &["wl-copy"]);
+            }
+            commands
+        }
+    }
+}
+
+fn no_tool(last_error: Option<io::Error>) -> io::Error {
+    let reason = last_error.map_or_else(String::new, |e| format!(": {}", e));
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no clipboard tool found{}", reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipboard_tools() {
+        assert!(is_clipboard_path("clip:"));
+        assert!(!is_clipboard_path("clip"));
+
+        assert_eq!(paste_commands("macos", true), [&["pbpaste"][..]]);
+        assert_eq!(copy_commands("windows", false), [&["clip"][..]]);
+        let linux = copy_commands("linux", true);
+        assert_eq!(linux[0], ["wl-copy"]);
+        assert_eq!(linux[1][0], "xclip");
+        assert_eq!(paste_commands("freebsd", false)[0][0], "xclip");
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{Error, STDIO_FILENAME};
+use std::{fs, io, path::Path};
+
+/// Whether the paths `a` and `b` name the same file, through symlinks and hard links.
+///
+/// Compares device and inode numbers on unix, and fully resolved paths elsewhere. Stdio (`-`)
+/// and paths that don't exist are never the same as anything.
+pub fn same_file<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> io::Result<bool> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    if a.to_string_lossy() == STDIO_FILENAME || b.to_string_lossy() == STDIO_FILENAME {
+        return Ok(false);
+    }
+    match (identity(a)?, identity(b)?) {
+        (Some(a), Some(b)) => Ok(a == b),
+        _ => Ok(false),
+    }
+}
+
+/// Fail with `InvalidInput` ([`Error::SamePath`]) if writing to `output` would clobber one of
+/// `inputs`.
+///
+/// Call this before opening the output, which truncates it.
+pub fn check_collision<I, P, Q>(inputs: I, output: Q) -> io::Result<()>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let output = output.as_ref();
+    for input in inputs {
+        let input = input.as_ref();
+        if same_file(input, output)? {
+            return Err(Error::SamePath {
+                input: input.to_owned(),
+                output: output.to_owned(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// What makes a file the same file: device and inode on unix, the resolved path elsewhere.
+#[cfg(unix)]
+pub(crate) type Identity = (u64, u64);
+#[cfg(not(unix))]
+pub(crate) type Identity = std::path::PathBuf;
+
+/// The identity of the file at `path`, or `None` if there's no such file.
+#[cfg(unix)]
+pub(crate) fn identity(path: &Path) -> io::Result<Option<Identity>> {
+    use std::os::unix::fs::MetadataExt;
+
+    match fs::metadata(path) {
+        Ok(meta) => Ok(Some((meta.dev(), meta.ino()))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn identity(path: &Path) -> io::Result<Option<Identity>> {
+    match fs::canonicalize(path) {
+        Ok(path) => Ok(Some(path)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn detect_same_file() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let real = tmp_dir.path().join("real.txt");
+        let other = tmp_dir.path().join("other.txt");
+        fs::write(&real, "data")?;
+        fs::write(&other, "data")?;
+
+        assert!(same_file(&real, tmp_dir.path().join(".").join("real.txt"))?);
+        assert!(!same_file(&real, &other)?);
+        assert!(!same_file(&real, tmp_dir.path().join("missing.txt"))?);
+        assert!(!same_file("-", "-")?);
+
+        #[cfg(unix)]
+        {
+            let link = tmp_dir.path().join("link.txt");
+            std::os::unix::fs::symlink(&real, &link)?;
+            let e = check_collision([&other, &link], &real).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+            let hard = tmp_dir.path().join("hard.txt");
+            fs::hard_link(&real, &hard)?;
+            assert!(same_file(&hard, &link)?);
+        }
+        check_collision([&other], &real)?;
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
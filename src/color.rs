@@ -0,0 +1,249 @@
+use crate::{FileOrStdout, FileOrStdoutLock};
+use std::{
+    env,
+    io::{self, Write},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Ansi256(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Text attributes applied by [`ColoredWrite::set_color`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColorSpec {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl ColorSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_fg(&mut self, color: Option<Color>) -> &mut Self {
+        self.fg = color;
+        self
+    }
+
+    pub fn set_bg(&mut self, color: Option<Color>) -> &mut Self {
+        self.bg = color;
+        self
+    }
+
+    pub fn set_bold(&mut self, yes: bool) -> &mut Self {
+        self.bold = yes;
+        self
+    }
+
+    pub fn set_dimmed(&mut self, yes: bool) -> &mut Self {
+        self.dimmed = yes;
+        self
+    }
+
+    pub fn set_italic(&mut self, yes: bool) -> &mut Self {
+        self.italic = yes;
+        self
+    }
+
+    pub fn set_underline(&mut self, yes: bool) -> &mut Self {
+        self.underline = yes;
+        self
+    }
+
+    fn write_escape<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        let mut params = vec!["0".to_owned()];
+        for (set, code) in &[
+            (self.bold, "1"),
+            (self.dimmed, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+        ] {
+            if *set {
+                params.push((*code).to_owned());
+            }
+        }
+        if let Some(fg) = self.fg {
+            params.push(color_params(fg, 30));
+        }
+        if let Some(bg) = self.bg {
+            params.push(color_params(bg, 40));
+        }
+        write!(w, "\x1b[{}m", params.join(";"))
+    }
+}
+
+fn color_params(color: Color, base: u8) -> String {
+    let simple = |offset: u8| (base + offset).to_string();
+    match color {
+        Color::Black => simple(0),
+        Color::Red => simple(1),
+        Color::Green => simple(2),
+        Color::Yellow => simple(3),
+        Color::Blue => simple(4),
+        Color::Magenta => simple(5),
+        Color::Cyan => simple(6),
+        Color::White => simple(7),
+        Color::Ansi256(n) => format!("{};5;{}", base + 8, n),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+    }
+}
+
+/// Writers that can change the color of the text they write.
+///
+/// This is the crate's own trait, not `termcolor::WriteColor`; the two aren't interchangeable.
+pub trait ColoredWrite: Write {
+    /// Whether escape sequences are actually emitted.
+    fn supports_color(&self) -> bool;
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()>;
+
+    fn reset(&mut self) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Always emit colors.
+    Always,
+    /// Emit colors when writing to a terminal, honoring `NO_COLOR`, `CLICOLOR_FORCE` and
+    /// `TERM=dumb`.
+    Auto,
+    /// Never emit colors.
+    Never,
+}
+
+impl ColorChoice {
+    fn should_color(self, is_terminal: bool) -> bool {
+        self.resolve(is_terminal, |name| {
+            env::var_os(name).map(|v| v.to_string_lossy().into_owned())
+        })
+    }
+
+    fn resolve<F>(self, is_terminal: bool, var: F) -> bool
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                if var("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0") {
+                    return true;
+                }
+                if var("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+                    return false;
+                }
+                is_terminal && var("TERM").is_none_or(|v| v != "dumb")
+            }
+        }
+    }
+}
+
+/// Writer implementing [`ColoredWrite`] with ANSI escapes, or ignoring colors when disabled.
+pub struct ColorWriter<W> {
+    inner: W,
+    enabled: bool,
+}
+
+impl<W: Write> ColorWriter<W> {
+    pub fn new(inner: W, enabled: bool) -> Self {
+        Self { inner, enabled }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for ColorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> ColoredWrite for ColorWriter<W> {
+    fn supports_color(&self) -> bool {
+        self.enabled
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        if self.enabled {
+            spec.write_escape(&mut self.inner)?;
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        if self.enabled {
+            self.inner.write_all(b"\x1b[0m")?;
+        }
+        Ok(())
+    }
+}
+
+impl FileOrStdout {
+    /// Lock for writing with color support decided by `choice`.
+    ///
+    /// With [`ColorChoice::Auto`], files never get colors and stdout gets them only when it is a
+    /// terminal and the environment doesn't say otherwise.
+    pub fn lock_color(&mut self, choice: ColorChoice) -> ColorWriter<FileOrStdoutLock<'_>> {
+        let enabled = choice.should_color(self.is_terminal());
+        ColorWriter::new(self.lock(), enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_escapes() -> Result<(), io::Error> {
+        let mut writer = ColorWriter::new(Vec::new(), true);
+        writer.set_color(ColorSpec::new().set_fg(Some(Color::Red)).set_bold(true))?;
+        write!(writer, "error")?;
+        writer.reset()?;
+        assert_eq!(writer.into_inner(), b"\x1b[0;1;31merror\x1b[0m");
+
+        let mut writer = ColorWriter::new(Vec::new(), false);
+        writer.set_color(ColorSpec::new().set_bg(Some(Color::Rgb(1, 2, 3))))?;
+        write!(writer, "plain")?;
+        writer.reset()?;
+        assert_eq!(writer.into_inner(), b"plain");
+        Ok(())
+    }
+
+    #[test]
+    fn auto_choice_honors_env() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| (*v).to_owned())
+            }
+        };
+        assert!(ColorChoice::Auto.resolve(true, env(&[])));
+        assert!(!ColorChoice::Auto.resolve(false, env(&[])));
+        assert!(!ColorChoice::Auto.resolve(true, env(&[("NO_COLOR", "1")])));
+        assert!(!ColorChoice::Auto.resolve(true, env(&[("TERM", "dumb")])));
+        assert!(ColorChoice::Auto.resolve(false, env(&[("CLICOLOR_FORCE", "1")])));
+        assert!(!ColorChoice::Auto.resolve(false, env(&[("CLICOLOR_FORCE", "0")])));
+        assert!(ColorChoice::Always.resolve(false, env(&[("NO_COLOR", "1")])));
+    }
+}
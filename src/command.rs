@@ -0,0 +1,164 @@
+//! Streams to and from command-line tools, for schemes served by the system's own clients
+//! (`aws`, `gcloud`, `ssh`, `git`, ...).
+
+use std::{
+    io::{self, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+/// Reader for the output of a command, failing at the end if the command does.
+pub(crate) struct CommandReader {
+    program: &'static str,
+    child: Child,
+    stdout: ChildStdout,
+    done: bool,
+}
+
+/// Writer feeding the input of a command. Dropping it unfinished kills the command, so a tool
+/// that publishes what it read once its input ends publishes nothing.
+#[cfg_attr(
+    not(any(feature = "s3", feature = "gcs", feature = "ssh")),
+    allow(dead_code)
+)]
+pub(crate) struct CommandWriter {
+    program: &'static str,
+    child: Child,
+    stdin: Option<ChildStdin>,
+}
+
+fn spawn(program: &'static str, command: &mut Command) -> io::Result<Child> {
+    // The tool's own error messages, like missing credentials, go straight to stderr.
+    command
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("failed to run `{}`: {}", program, e)))
+}
+
+fn wait(program: &str, child: &mut Child) -> io::Result<()> {
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "`{}` failed: {}",
+            program, status
+        )))
+    }
+}
+
+impl CommandReader {
+    pub(crate) fn new(program: &'static str, mut command: Command) -> io::Result<Self> {
+        let mut child = spawn(program, command.stdin(Stdio::null()).stdout(Stdio::piped()))?;
+        let stdout = child.stdout.take().expect("command stdout is piped");
+        Ok(Self {
+            program,
+            child,
+            stdout,
+            done: false,
+        })
+    }
+}
+
+impl Read for CommandReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.stdout.read(buf)?;
+        if amt == 0 && !buf.is_empty() && !self.done {
+            self.done = true;
+            wait(self.program, &mut self.child)?;
+        }
+        Ok(amt)
+    }
+}
+
+impl Drop for CommandReader {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg_attr(
+    not(any(feature = "s3", feature = "gcs", feature = "ssh")),
+    allow(dead_code)
+)]
+impl CommandWriter {
+    pub(crate) fn new(program: &'static str, mut command: Command) -> io::Result<Self> {
+        let mut child = spawn(program, command.stdin(Stdio::piped()).stdout(Stdio::null()))?;
+        let stdin = child.stdin.take();
+        Ok(Self {
+            program,
+            child,
+            stdin,
+        })
+    }
+
+    /// Close the command's input and wait for it to finish.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        drop(self.stdin.take());
+        wait(self.program, &mut self.child)
+    }
+}
+
+impl Write for CommandWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdin.as_mut().expect("writer not finished").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.as_mut().expect("writer not finished").flush()
+    }
+}
+
+impl Drop for CommandWriter {
+    fn drop(&mut self) {
+        if self.stdin.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[test]
+    fn command_streams() -> Result<(), io::Error> {
+        let mut content = String::new();
+        CommandReader::new("sh", sh("echo remote data"))?.read_to_string(&mut content)?;
+        assert_eq!(content, "remote data\n");
+
+        let mut failing = CommandReader::new("sh", sh("echo partial; exit 3"))?;
+        assert!(failing.read_to_string(&mut content).is_err());
+
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("upload.txt");
+        let mut writer = CommandWriter::new("sh", sh(&format!("cat > '{}'", path.display())))?;
+        writer.write_all(b"uploaded")?;
+        writer.finish()?;
+        assert_eq!(fs::read_to_string(&path)?, "uploaded");
+
+        let writer = CommandWriter::new("sh", sh("exit 1"))?;
+        assert!(writer.finish().is_err());
+
+        let dropped = tmp_dir.path().join("dropped.txt");
+        let script = format!("data=$(cat); printf %s \"$data\" > '{}'", dropped.display());
+        let mut writer = CommandWriter::new("sh", sh(&script))?;
+        writer.write_all(b"partial")?;
+        drop(writer);
+        assert!(!dropped.exists());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,509 @@
+use crate::{
+    filter::{Filter, FilterReader, FilterWriter},
+    members::{GzipMember, ZstdFrame},
+    parallel::ParallelWriter,
+};
+use std::{
+    ffi::OsStr,
+    io::{self, Read, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    thread,
+};
+
+/// Leading bytes of each format's streams.
+const MAGIC: &[(&[u8], Codec)] = &[
+    (b"\x1f\x8b", Codec::Gzip),
+    (b"BZh", Codec::Bzip2),
+    (b"\xfd7zXZ\x00", Codec::Xz),
+    (b"\x28\xb5\x2f\xfd", Codec::Zstd),
+    #[cfg(feature = "lz4")]
+    (b"\x04\x22\x4d\x18", Codec::Lz4),
+    #[cfg(feature = "snappy")]
+    (b"\xff\x06\x00\x00sNaPpY", Codec::Snappy),
+];
+
+/// Compression formats, handled by piping data through the format's command-line tool.
+///
+/// The tool (`gzip`, `bzip2`, `xz` or `zstd`, and `brotli`, `lz4` or `snzip` for the codecs
+/// behind features of their own) must be installed and on `PATH`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    #[cfg(feature = "brotli")]
+    Brotli,
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// The snappy framing format, as `snzip` writes it.
+    #[cfg(feature = "snappy")]
+    Snappy,
+}
+
+impl Codec {
+    /// Pick a codec from a path's extension, e.g. `.gz` or `.zst`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension().and_then(OsStr::to_str)? {
+            "gz" | "tgz" => Some(Self::Gzip),
+            "bz2" | "tbz2" => Some(Self::Bzip2),
+            "xz" | "txz" => Some(Self::Xz),
+            "zst" | "tzst" => Some(Self::Zstd),
+            #[cfg(feature = "brotli")]
+            "br" => Some(Self::Brotli),
+            #[cfg(feature = "lz4")]
+            "lz4" => Some(Self::Lz4),
+            #[cfg(feature = "snappy")]
+            "sz" => Some(Self::Snappy),
+            _ => None,
+        }
+    }
+
+    /// Recognize a codec from the first bytes of compressed data. Brotli has no magic bytes, so
+    /// it's never recognized.
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        MAGIC
+            .iter()
+            .find(|(magic, _)| bytes.starts_with(magic))
+            .map(|&(_, codec)| codec)
+    }
+
+    /// File extension for this format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Bzip2 => "bz2",
+            Self::Xz => "xz",
+            Self::Zstd => "zst",
+            #[cfg(feature = "brotli")]
+            Self::Brotli => "br",
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => "lz4",
+            #[cfg(feature = "snappy")]
+            Self::Snappy => "sz",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Bzip2 => "bzip2",
+            Self::Xz => "xz",
+            Self::Zstd => "zstd",
+            #[cfg(feature = "brotli")]
+            Self::Brotli => "brotli",
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => "lz4",
+            #[cfg(feature = "snappy")]
+            Self::Snappy => "snzip",
+        }
+    }
+
+    fn filter(self, decompress: bool) -> Filter {
+        let mut filter = Filter::new(self.program());
+        if decompress {
+            filter = filter.arg("-d");
+        }
+        let filter = filter.arg("-c");
+        match self {
+            Self::Zstd => filter.arg("-q"),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => filter.arg("-q"),
+            _ => filter,
+        }
+    }
+}
+
+/// How a [`CompressWriter`] compresses.
+///
+/// Settings a codec can't use make [`writer`](CompressOptions::writer) fail with `Unsupported`,
+/// and levels out of the codec's range with `InvalidInput`, rather than being ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressOptions {
+    codec: Codec,
+    threads: usize,
+    level: Option<u32>,
+    window_log: Option<u32>,
+    dictionary: Option<PathBuf>,
+    deterministic: bool,
+}
+
+impl CompressOptions {
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            threads: 1,
+            level: None,
+            window_log: None,
+            dictionary: None,
+            deterministic: false,
+        }
+    }
+
+    /// Trade speed for ratio, as the codec's tool takes it: 1 to 9 for gzip and bzip2, 0 to 9
+    /// for xz, 1 to 22 for zstd, 0 to 11 for brotli and 1 to 12 for lz4. Snappy has no levels.
+    /// Defaults to the tool's own default.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Let matches reach back `2^log` bytes, for better ratios on large inputs with distant
+    /// repeats, at the cost of memory on both ends. For zstd (10 to 31, with `--long`; windows
+    /// over 2^27 need `zstd --long` to decompress too), xz (12 to 30) and brotli (10 to 24).
+    pub fn window_log(mut self, log: u32) -> Self {
+        self.window_log = Some(log);
+        self
+    }
+
+    /// Compress with the zstd dictionary at `path`, made with `zstd --train`, for much better
+    /// ratios on small, similar inputs. Decompress with
+    /// [`DecompressReader::with_dictionary`] and the same dictionary.
+    pub fn dictionary<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.dictionary = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Make the output the same on every run, for reproducible builds. For gzip this leaves
+    /// the name and time of the input out of the header; the other codecs store neither.
+    pub fn deterministic(mut self, yes: bool) -> Self {
+        self.deterministic = yes;
+        self
+    }
+
+    /// Compress on `n` threads, or one per CPU for zero. Defaults to one.
+    ///
+    /// zstd and xz use their own worker threads. gzip output is cut into 1 MiB blocks compressed
+    /// side by side and written out as consecutive gzip members, which every gzip decoder
+    /// reads back as one stream (as `pigz` does, but a little larger). The other codecs have no
+    /// threaded encoder, so [`writer`](CompressOptions::writer) fails with `Unsupported` for them
+    /// unless `n` is one.
+    pub fn compression_threads(mut self, n: usize) -> Self {
+        self.threads = n;
+        self
+    }
+
+    /// Start compressing into `inner`.
+    pub fn writer<W: Write + Send + 'static>(&self, inner: W) -> io::Result<CompressWriter<W>> {
+        self.check()?;
+        let inner = match (self.codec, self.threads()) {
+            (Codec::Gzip, threads) if threads > 1 => {
+                Encoder::Parallel(ParallelWriter::new(inner, self.clone(), threads))
+            }
+            _ => Encoder::Filter(FilterWriter::new(inner, self.filter())?),
+        };
+        Ok(CompressWriter { inner })
+    }
+
+    fn threads(&self) -> usize {
+        match self.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        }
+    }
+
+    fn check(&self) -> io::Result<()> {
+        let program = self.codec.program();
+        if let Some(level) = self.level {
+            match level_range(self.codec) {
+                Some(range) if range.contains(&level) => {}
+                Some(range) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "{} levels go from {} to {}, not {}",
+                            program,
+                            range.start(),
+                            range.end(),
+                            level
+                        ),
+                    ))
+                }
+                None => return Err(unsupported(program, "levels")),
+            }
+        }
+        if let Some(log) = self.window_log {
+            if !window_range(self.codec).is_some_and(|range| range.contains(&log)) {
+                return Err(unsupported(
+                    program,
+                    &format!("a window of 2^{} bytes", log),
+                ));
+            }
+        }
+        if self.dictionary.is_some() && self.codec != Codec::Zstd {
+            return Err(unsupported(program, "dictionaries"));
+        }
+        if self.threads != 1 && !matches!(self.codec, Codec::Gzip | Codec::Xz | Codec::Zstd) {
+            return Err(unsupported(program, "threads"));
+        }
+        Ok(())
+    }
+
+    /// The compressing tool to run.
+    pub(crate) fn filter(&self) -> Filter {
+        let mut filter = self.codec.filter(false);
+        if let (Codec::Zstd | Codec::Xz, threads) = (self.codec, self.threads) {
+            if threads != 1 {
+                filter = filter.arg(format!("-T{}", threads));
+            }
+        }
+        match (self.codec, self.level, self.window_log) {
+            (Codec::Xz, level, Some(log)) => {
+                filter = filter.arg(format!(
+                    "--lzma2=preset={},dict={}",
+                    level.unwrap_or(6),
+                    1u64 << log
+                ));
+            }
+            #[cfg(feature = "brotli")]
+            (Codec::Brotli, level, log) => {
+                if let Some(level) = level {
+                    filter = filter.arg(format!("--quality={}", level));
+                }
+                if let Some(log) = log {
+                    filter = filter.arg(format!("--lgwin={}", log));
+                }
+            }
+            (codec, level, log) => {
+                if let Some(level) = level {
+                    if codec == Codec::Zstd && level > 19 {
+                        filter = filter.arg("--ultra");
+                    }
+                    filter = filter.arg(format!("-{}", level));
+                }
+                if let Some(log) = log {
+                    filter = filter.arg(format!("--long={}", log));
+                }
+            }
+        }
+        if let Some(dictionary) = &self.dictionary {
+            filter = filter.arg("-D").arg(dictionary);
+        }
+        if self.deterministic && self.codec == Codec::Gzip {
+            filter = filter.arg("-n");
+        }
+        filter
+    }
+}
+
+fn level_range(codec: Codec) -> Option<RangeInclusive<u32>> {
+    match codec {
+        Codec::Gzip | Codec::Bzip2 => Some(1..=9),
+        Codec::Xz => Some(0..=9),
+        Codec::Zstd => Some(1..=22),
+        #[cfg(feature = "brotli")]
+        Codec::Brotli => Some(0..=11),
+        #[cfg(feature = "lz4")]
+        Codec::Lz4 => Some(1..=12),
+        #[cfg(feature = "snappy")]
+        Codec::Snappy => None,
+    }
+}
+
+fn window_range(codec: Codec) -> Option<RangeInclusive<u32>> {
+    match codec {
+        Codec::Zstd => Some(10..=31),
+        Codec::Xz => Some(12..=30),
+        #[cfg(feature = "brotli")]
+        Codec::Brotli => Some(10..=24),
+        _ => None,
+    }
+}
+
+fn unsupported(program: &str, what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} doesn't support {}", program, what),
+    )
+}
+
+/// Writer that compresses everything written to it into `W`.
+///
+/// Call [`finish`](CompressWriter::finish) to complete the compressed stream and check for
+/// errors. Dropping the writer instead still completes the stream, but ignores any errors.
+pub struct CompressWriter<W> {
+    inner: Encoder<W>,
+}
+
+enum Encoder<W> {
+    Filter(FilterWriter<W>),
+    Parallel(ParallelWriter<W>),
+}
+
+impl<W: Write + Send + 'static> CompressWriter<W> {
+    /// Compress with `codec` and [`CompressOptions`]' defaults.
+    pub fn new(inner: W, codec: Codec) -> io::Result<Self> {
+        CompressOptions::new(codec).writer(inner)
+    }
+
+    /// Complete the compressed stream and return the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.inner {
+            Encoder::Filter(writer) => writer.finish(),
+            Encoder::Parallel(writer) => writer.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Encoder::Filter(writer) => writer.write(buf),
+            Encoder::Parallel(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Encoder::Filter(writer) => writer.flush(),
+            Encoder::Parallel(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Reader that decompresses the data read from `R`.
+///
+/// Like `zcat`, it decompresses every member of concatenated gzip data, every frame of
+/// concatenated zstd data and so on, as log shippers and `cat a.gz b.gz` produce.
+pub struct DecompressReader {
+    inner: Box<dyn Read + Send>,
+}
+
+impl DecompressReader {
+    pub fn new<R: Read + Send + 'static>(inner: R, codec: Codec) -> io::Result<Self> {
+        Self::with_members(inner, codec, true)
+    }
+
+    /// With `all_members` false, decompress only the first gzip member or zstd frame, ignoring
+    /// anything after it, for formats that append other data to a compressed stream.
+    ///
+    /// Fails with `Unsupported` for the other codecs, whose tools can't stop early.
+    pub fn with_members<R: Read + Send + 'static>(
+        inner: R,
+        codec: Codec,
+        all_members: bool,
+    ) -> io::Result<Self> {
+        let inner: Box<dyn Read + Send> = match (codec, all_members) {
+            (_, true) => Box::new(FilterReader::new(inner, codec.filter(true))?),
+            // No need for `gzip` at all.
+            (Codec::Gzip, false) => Box::new(GzipMember::new(inner)?),
+            (Codec::Zstd, false) => Box::new(FilterReader::new(
+                ZstdFrame::new(inner),
+                codec.filter(true),
+            )?),
+            (_, false) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("can't decompress only the first {} stream", codec.program()),
+                ))
+            }
+        };
+        Ok(Self { inner })
+    }
+
+    /// Decompress zstd data made with the dictionary at `path`; see
+    /// [`CompressOptions::dictionary`].
+    pub fn with_dictionary<R: Read + Send + 'static, P: AsRef<Path>>(
+        inner: R,
+        codec: Codec,
+        path: P,
+    ) -> io::Result<Self> {
+        if codec != Codec::Zstd {
+            return Err(unsupported(codec.program(), "dictionaries"));
+        }
+        let filter = codec.filter(true).arg("-D").arg(path.as_ref());
+        Ok(Self {
+            inner: Box::new(FilterReader::new(inner, filter)?),
+        })
+    }
+}
+
+impl Read for DecompressReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gzip_round_trip() -> Result<(), io::Error> {
+        let expected_content = "compress me ".repeat(1000);
+
+        let mut writer = CompressWriter::new(Vec::new(), Codec::Gzip)?;
+        writer.write_all(expected_content.as_bytes())?;
+        let compressed = writer.finish()?;
+        assert!(compressed.starts_with(b"\x1f\x8b"));
+        assert_eq!(Codec::from_magic(&compressed), Some(Codec::Gzip));
+        assert_eq!(Codec::from_magic(b"plain"), None);
+        for codec in MAGIC.iter().map(|&(_, codec)| codec) {
+            assert_eq!(
+                Codec::from_path(format!("file.{}", codec.extension())),
+                Some(codec)
+            );
+        }
+        assert!(compressed.len() < expected_content.len());
+
+        let mut actual_content = String::new();
+        DecompressReader::new(io::Cursor::new(compressed), Codec::Gzip)?
+            .read_to_string(&mut actual_content)?;
+        assert_eq!(actual_content, expected_content);
+
+        let mut concatenated = CompressWriter::new(Vec::new(), Codec::Gzip)?;
+        concatenated.write_all(b"first\n")?;
+        let mut concatenated = concatenated.finish()?;
+        let mut second = CompressWriter::new(Vec::new(), Codec::Gzip)?;
+        second.write_all(b"second\n")?;
+        concatenated.extend(second.finish()?);
+        let mut all = String::new();
+        DecompressReader::new(io::Cursor::new(concatenated.clone()), Codec::Gzip)?
+            .read_to_string(&mut all)?;
+        assert_eq!(all, "first\nsecond\n");
+        let mut first = String::new();
+        DecompressReader::with_members(io::Cursor::new(concatenated), Codec::Gzip, false)?
+            .read_to_string(&mut first)?;
+        assert_eq!(first, "first\n");
+
+        let blocks = (0..300_000)
+            .map(|n| format!("{} ", n % 7919))
+            .collect::<String>();
+        let mut writer = CompressOptions::new(Codec::Gzip)
+            .compression_threads(2)
+            .writer(Vec::new())?;
+        writer.write_all(blocks.as_bytes())?;
+        let compressed = writer.finish()?;
+        let mut actual_content = String::new();
+        DecompressReader::new(io::Cursor::new(compressed), Codec::Gzip)?
+            .read_to_string(&mut actual_content)?;
+        assert_eq!(actual_content, blocks);
+
+        let fast = CompressOptions::new(Codec::Gzip)
+            .level(1)
+            .writer(Vec::new())?
+            .finish()?;
+        assert!(fast.starts_with(b"\x1f\x8b"));
+        let e = CompressOptions::new(Codec::Gzip)
+            .level(10)
+            .writer(Vec::new())
+            .err()
+            .expect("gzip has no level 10");
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        for options in [
+            CompressOptions::new(Codec::Gzip).window_log(20),
+            CompressOptions::new(Codec::Gzip).dictionary("dict"),
+            CompressOptions::new(Codec::Zstd).window_log(40),
+            CompressOptions::new(Codec::Bzip2).compression_threads(2),
+            CompressOptions::new(Codec::Bzip2).compression_threads(0),
+        ] {
+            let e = options.writer(Vec::new()).err().expect("not supported");
+            assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        }
+
+        let mut garbage = DecompressReader::new(&b"not gzip data"[..], Codec::Gzip)?;
+        assert!(garbage.read_to_end(&mut Vec::new()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{instrument, FileOrStdinLock, FileOrStdoutLock, OutputLock};
+use std::io::{self, BufRead, Write};
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Copy the rest of the input to `output`, returning how many bytes were copied.
+    ///
+    /// Unlike `io::copy`, the input's buffer is written straight to the underlying file or stdout
+    /// handle instead of being copied again into the output's buffer. Anything already buffered
+    /// in `output` is flushed first, so it stays in order.
+    pub fn copy_to(&mut self, output: &mut FileOrStdoutLock<'_>) -> io::Result<u64> {
+        output.flush()?;
+        let key = output.key();
+        let unbuffered = matches!(output.inner, OutputLock::StdoutUnbuffered(_));
+        let writer = output.unbuffered_writer();
+
+        let mut copied = 0;
+        loop {
+            let buf = match self.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() {
+                break;
+            }
+            let amt = buf.len();
+            write_all(key, writer, buf)?;
+            if unbuffered {
+                writer.flush()?;
+            }
+            self.consume(amt);
+            copied += amt as u64;
+        }
+        Ok(copied)
+    }
+}
+
+impl<'a> FileOrStdoutLock<'a> {
+    /// The handle under the output's buffer. Only write to it once the buffer is flushed.
+    fn unbuffered_writer(&mut self) -> &mut dyn Write {
+        match &mut self.inner {
+            OutputLock::FileBufWriter(writer) => writer.get_mut(),
+            OutputLock::FileLineWriter(writer) => writer.get_mut(),
+            OutputLock::FileUnbuffered(file) => file,
+            OutputLock::StdoutLock(stdout) => stdout,
+            OutputLock::StdoutBufWriter(writer) => writer.get_mut(),
+            OutputLock::StdoutUnbuffered(stdout) => stdout,
+        }
+    }
+}
+
+fn write_all(key: instrument::Key, writer: &mut dyn Write, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        match instrument::write_with(key, writer, buf) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(amt) => buf = &buf[amt..],
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Buffering, FileOrStdin, FileOrStdout};
+    use std::{
+        fs,
+        io::{self, Write},
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn copy_input_to_output() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let src = tmp_dir.path().join("src.bin");
+        let dst = tmp_dir.path().join("dst.bin");
+        let data = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&src, &data)?;
+
+        let mut input = FileOrStdin::from_path(&src)?;
+        let mut input = input.lock();
+        assert_eq!(input.peek(4)?, &data[..4]);
+        for buffering in [Buffering::Block, Buffering::Line, Buffering::None] {
+            let mut output = FileOrStdout::from_path(&dst)?;
+            let mut output = output.lock_buffered(buffering);
+            output.write_all(b"head:")?;
+            let mut input = FileOrStdin::from_path(&src)?;
+            assert_eq!(input.lock().copy_to(&mut output)?, data.len() as u64);
+            output.write_all(b":tail")?;
+            output.flush()?;
+            drop(output);
+            assert_eq!(fs::read(&dst)?, [&b"head:"[..], &data, b":tail"].concat());
+        }
+
+        let mut output = FileOrStdout::from_path(&dst)?;
+        assert_eq!(input.copy_to(&mut output.lock())?, data.len() as u64);
+        assert_eq!(fs::read(&dst)?, data);
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{
+    filter::{Filter, FilterReader, FilterWriter},
+    STDIO_FILENAME,
+};
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Encryption formats, handled by piping data through the format's command-line tool.
+///
+/// The tool (`age` or `gpg`) must be installed and on `PATH`. Prompts for passphrases go to the
+/// terminal, not stdin, so they work while data is being piped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    #[cfg(feature = "age")]
+    Age,
+    #[cfg(feature = "gpg")]
+    Gpg,
+}
+
+/// What to encrypt to or decrypt with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CipherKey {
+    /// Public keys to encrypt to: age recipients, or gpg key IDs or user IDs.
+    Recipients(Vec<String>),
+    /// An age identity file to decrypt with. gpg reads secret keys from its keyring instead.
+    IdentityFile(PathBuf),
+    /// A file whose first line is the passphrase, for gpg's symmetric encryption. age only reads
+    /// passphrases from the terminal.
+    PassphraseFile(PathBuf),
+    /// Let the tool prompt on the terminal: for a passphrase when encrypting, and for whatever it
+    /// needs when decrypting (gpg also tries its keyring and agent).
+    Prompt,
+}
+
+/// Writer that encrypts everything written to it into `W`.
+///
+/// Call [`finish`](EncryptWriter::finish) to complete the encrypted stream and check for errors.
+/// Dropping the writer instead still completes the stream, but ignores any errors.
+pub struct EncryptWriter<W> {
+    inner: FilterWriter<W>,
+}
+
+/// Reader that decrypts the data read from `R`.
+pub struct DecryptReader {
+    inner: FilterReader,
+}
+
+impl Cipher {
+    /// Pick a cipher from a path's extension: `.age`, or `.gpg`, `.pgp` or `.asc`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension().and_then(OsStr::to_str)? {
+            #[cfg(feature = "age")]
+            "age" => Some(Self::Age),
+            #[cfg(feature = "gpg")]
+            "gpg" | "pgp" | "asc" => Some(Self::Gpg),
+            _ => None,
+        }
+    }
+
+    /// File extension for this format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "age")]
+            Self::Age => "age",
+            #[cfg(feature = "gpg")]
+            Self::Gpg => "gpg",
+        }
+    }
+
+    fn filter(self, key: &CipherKey, decrypt: bool) -> io::Result<Filter> {
+        let unsupported = || {
+            let action = if decrypt { "decrypting" } else { "encrypting" };
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} can't be used for {} with {:?}", key, action, self),
+            )
+        };
+        match self {
+            #[cfg(feature = "age")]
+            Self::Age => {
+                let filter = Filter::new("age").arg(if decrypt { "-d" } else { "-e" });
+                match (key, decrypt) {
+                    (CipherKey::Recipients(recipients), false) if !recipients.is_empty() => {
+                        Ok(recipients
+                            .iter()
+                            .fold(filter, |filter, r| filter.arg("-r").arg(r)))
+                    }
+                    (CipherKey::IdentityFile(path), true) => Ok(filter.arg("-i").arg(path)),
+                    (CipherKey::Prompt, false) => Ok(filter.arg("-p")),
+                    (CipherKey::Prompt, true) => Ok(filter),
+                    _ => Err(unsupported()),
+                }
+            }
+            #[cfg(feature = "gpg")]
+            Self::Gpg => {
+                let filter = Filter::new("gpg").arg("--quiet");
+                match (key, decrypt) {
+                    (CipherKey::Recipients(recipients), false) if !recipients.is_empty() => {
+                        Ok(recipients
+                            .iter()
+                            .fold(filter.arg("--encrypt"), |filter, r| {
+                                filter.arg("--recipient").arg(r)
+                            }))
+                    }
+                    (CipherKey::PassphraseFile(path), _) => {
+                        let filter = filter
+                            .arg("--batch")
+                            .arg("--pinentry-mode")
+                            .arg("loopback")
+                            .arg("--passphrase-file")
+                            .arg(path);
+                        Ok(filter.arg(if decrypt { "--decrypt" } else { "--symmetric" }))
+                    }
+                    (CipherKey::Prompt, false) => Ok(filter.arg("--symmetric")),
+                    (CipherKey::Prompt, true) => Ok(filter.arg("--decrypt")),
+                    _ => Err(unsupported()),
+                }
+            }
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> EncryptWriter<W> {
+    pub fn new(inner: W, cipher: Cipher, key: &CipherKey) -> io::Result<Self> {
+        Ok(Self {
+            inner: FilterWriter::new(inner, cipher.filter(key, false)?)?,
+        })
+    }
+
+    /// Complete the encrypted stream and return the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.inner.finish()
+    }
+}
+
+impl<W> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl DecryptReader {
+    pub fn new<R: Read + Send + 'static>(
+        inner: R,
+        cipher: Cipher,
+        key: &CipherKey,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            inner: FilterReader::new(inner, cipher.filter(key, true)?)?,
+        })
+    }
+
+    /// Open the input at `path`, or stdin for `-`, decrypting it if its extension names a
+    /// [`Cipher`] and reading it as-is otherwise.
+    pub fn from_path<P: AsRef<Path>>(path: P, key: &CipherKey) -> io::Result<Box<dyn Read + Send>> {
+        let path = path.as_ref();
+        let input: Box<dyn Read + Send> = if path.to_string_lossy() == STDIO_FILENAME {
+            Box::new(io::stdin())
+        } else {
+            Box::new(File::open(path)?)
+        };
+        Ok(match Cipher::from_path(path) {
+            Some(cipher) => Box::new(Self::new(input, cipher, key)?),
+            None => input,
+        })
+    }
+}
+
+impl Read for DecryptReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(all(test, feature = "gpg"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn gpg_symmetric_round_trip() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let home = tmp_dir.path().join("gnupg");
+        fs::create_dir(&home)?;
+        let passphrase = tmp_dir.path().join("passphrase");
+        fs::write(&passphrase, "correct horse\n")?;
+        let key = CipherKey::PassphraseFile(passphrase);
+        // A keyring of the test's own, without touching the environment of other tests.
+        let gpg = |decrypt| -> io::Result<Filter> {
+            Ok(Cipher::Gpg
+                .filter(&key, decrypt)?
+                .arg("--homedir")
+                .arg(&home))
+        };
+
+        let mut writer = EncryptWriter {
+            inner: FilterWriter::new(Vec::new(), gpg(false)?)?,
+        };
+        writer.write_all(b"top secret")?;
+        let encrypted = writer.finish()?;
+        assert!(!encrypted.windows(6).any(|w| w == b"secret"));
+
+        let mut content = String::new();
+        let mut reader = DecryptReader {
+            inner: FilterReader::new(io::Cursor::new(encrypted.clone()), gpg(true)?)?,
+        };
+        reader.read_to_string(&mut content)?;
+        assert_eq!(content, "top secret");
+
+        fs::write(tmp_dir.path().join("passphrase"), "wrong\n")?;
+        let mut wrong = DecryptReader {
+            inner: FilterReader::new(io::Cursor::new(encrypted), gpg(true)?)?,
+        };
+        assert!(wrong.read_to_end(&mut Vec::new()).is_err());
+
+        assert_eq!(Cipher::from_path("secret.txt.gpg"), Some(Cipher::Gpg));
+        assert!(EncryptWriter::new(
+            Vec::new(),
+            Cipher::Gpg,
+            &CipherKey::IdentityFile("id".into())
+        )
+        .is_err());
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(any(feature = "tar", feature = "zip"))]
+use std::{
+    env,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The time to stamp archive entries with: now, or for reproducible output
+/// `SOURCE_DATE_EPOCH` if it's set, or else the epoch (1980 for zip).
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub(crate) fn timestamp(deterministic: bool) -> SystemTime {
+    if deterministic {
+        source_date(env::var("SOURCE_DATE_EPOCH").ok().as_deref())
+    } else {
+        SystemTime::now()
+    }
+}
+
+/// The time given by a `SOURCE_DATE_EPOCH` value, or the epoch.
+#[cfg(any(feature = "tar", feature = "zip"))]
+fn source_date(epoch: Option<&str>) -> SystemTime {
+    let secs = epoch.and_then(|secs| secs.trim().parse().ok()).unwrap_or(0);
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[cfg(all(test, any(feature = "tar", feature = "zip")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_date_epoch() {
+        let secs = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(secs(source_date(Some("1700000000"))), 1_700_000_000);
+        assert_eq!(secs(source_date(Some("yesterday"))), 0);
+        assert_eq!(secs(source_date(None)), 0);
+        assert!(timestamp(false) > source_date(Some("1700000000")));
+    }
+
+    #[cfg(all(feature = "tar", feature = "zip", feature = "compress"))]
+    #[test]
+    fn reproducible_outputs() -> Result<(), std::io::Error> {
+        use crate::{ArchiveFormat, ArchiveOutput, Codec, CompressOptions};
+        use std::{fs, io::Write, thread};
+        use tempfile::TempDir;
+
+        let tmp_dir = TempDir::new()?;
+        let run = |n: usize| -> std::io::Result<Vec<Vec<u8>>> {
+            let mut outputs = Vec::new();
+            for (format, ext) in [(ArchiveFormat::Tar, "tar"), (ArchiveFormat::Zip, "zip")] {
+                let path = tmp_dir.path().join(format!("{}.{}", n, ext));
+                let mut archive = ArchiveOutput::from_path(&path, format)?.deterministic(true);
+                archive.add("a.txt", &mut &b"content"[..])?;
+                archive.finish()?;
+                outputs.push(fs::read(&path)?);
+            }
+            let path = tmp_dir.path().join(format!("{}.gz", n));
+            let mut writer = CompressOptions::new(Codec::Gzip)
+                .deterministic(true)
+                .writer(fs::File::create(&path)?)?;
+            writer.write_all(b"content")?;
+            writer.finish()?;
+            outputs.push(fs::read(&path)?);
+            Ok(outputs)
+        };
+
+        let first = run(1)?;
+        // Far enough apart for the times in tar, zip and gzip headers to differ.
+        thread::sleep(std::time::Duration::from_millis(2100));
+        assert_eq!(run(2)?, first);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
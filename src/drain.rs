@@ -0,0 +1,101 @@
+use crate::{exit::warn, FileOrStdinLock, InputLock};
+use std::io::{self, BufRead, IsTerminal, Seek, SeekFrom};
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Read and discard the rest of the input, returning how many bytes were left.
+    ///
+    /// Reading a piped stdin to the end means whoever writes into the pipe doesn't get a
+    /// `SIGPIPE` (or `BrokenPipe`) for output nobody was going to read. An interactive terminal
+    /// is left alone, since draining it would wait for the user to type end-of-file. A regular
+    /// file isn't read at all: it's skipped to the end, and the remainder worked out from its
+    /// size.
+    pub fn drain(&mut self) -> io::Result<u64> {
+        match &mut self.inner {
+            InputLock::StdinLock(_) if io::stdin().is_terminal() => return Ok(0),
+            InputLock::FileBufReader(reader) => {
+                let metadata = reader.get_ref().metadata()?;
+                if metadata.is_file() {
+                    let buffered = reader.buffer().len() as u64;
+                    let pos = reader.get_mut().stream_position()?;
+                    reader.seek(SeekFrom::End(0))?;
+                    let peeked = (self.peeked.len() - self.peeked_pos) as u64;
+                    self.peeked.clear();
+                    self.peeked_pos = 0;
+                    return Ok(peeked + buffered + metadata.len().saturating_sub(pos));
+                }
+            }
+            InputLock::StdinLock(_) => {}
+        }
+        let mut drained = 0;
+        loop {
+            let amt = match self.fill_buf() {
+                Ok(buf) => buf.len(),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if amt == 0 {
+                return Ok(drained);
+            }
+            self.consume(amt);
+            drained += amt as u64;
+        }
+    }
+
+    /// Drain the input once done with it, warning on stderr (or to the handler from
+    /// [`set_warning_handler`](crate::set_warning_handler)) if anything was left unread.
+    ///
+    /// Tools that only read a prefix of their input by design, like `head`, can warn in their own
+    /// words (or not at all) with [`ensure_drained_with`](FileOrStdinLock::ensure_drained_with).
+    pub fn ensure_drained(&mut self) -> io::Result<()> {
+        self.ensure_drained_with(|unread| {
+            warn(&format!(
+                "warning: {} bytes of input were left unread",
+                unread
+            ))
+        })
+    }
+
+    /// Drain the input once done with it, calling `warn` with the number of bytes that were left
+    /// unread, if any.
+    pub fn ensure_drained_with<F: FnOnce(u64)>(&mut self, warn: F) -> io::Result<()> {
+        let unread = self.drain()?;
+        if unread > 0 {
+            warn(unread);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FileOrStdin;
+    use std::{
+        fs,
+        io::{self, Read},
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn drain_unread_input() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("data.txt");
+        fs::write(&path, "header\nrest of the data\n")?;
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        assert_eq!(lock.peek(6)?, b"header");
+        let mut header = [0; 7];
+        lock.read_exact(&mut header)?;
+
+        let mut unread = None;
+        lock.ensure_drained_with(|n| unread = Some(n))?;
+        assert_eq!(unread, Some(17));
+
+        lock.ensure_drained_with(|_| panic!("nothing is left to drain"))?;
+        assert_eq!(lock.drain()?, 0);
+        drop(lock);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
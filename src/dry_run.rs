@@ -0,0 +1,362 @@
+use crate::STDIO_FILENAME;
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Lines of unchanged context shown around each change.
+const CONTEXT: usize = 3;
+
+/// Output that records what would have been written to a file instead of writing it.
+///
+/// Swap it in for the real output to implement `--dry-run`: nothing on disk is touched, and
+/// [`diff`](DryRun::diff) shows what the write would have changed as a unified diff.
+pub struct DryRun {
+    path: PathBuf,
+    contents: Vec<u8>,
+}
+
+impl DryRun {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            contents: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Everything written so far.
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
+
+    /// Whether writing for real would change the file.
+    pub fn would_change(&self) -> io::Result<bool> {
+        Ok(self.current()?.as_deref() != Some(&self.contents[..]))
+    }
+
+    /// Unified diff from the file's current contents to what was written, or an empty string if
+    /// they are the same. A missing file is shown as `/dev/null`.
+    pub fn diff(&self) -> io::Result<String> {
+        let current = self.current()?;
+        if current.as_deref() == Some(&self.contents[..]) {
+            return Ok(String::new());
+        }
+
+        let name = self.path.to_string_lossy();
+        let old_label = if current.is_some() {
+            &*name
+        } else {
+            "/dev/null"
+        };
+        let old = String::from_utf8_lossy(current.as_deref().unwrap_or_default());
+        let new = String::from_utf8_lossy(&self.contents);
+        Ok(unified_diff(old_label, &name, &old, &new))
+    }
+
+    fn current(&self) -> io::Result<Option<Vec<u8>>> {
+        if self.path.to_string_lossy() == STDIO_FILENAME {
+            return Ok(None);
+        }
+        match fs::read(&self.path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Write for DryRun {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.contents.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+fn unified_diff(old_label: &str, new_label: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = edit_script(&old, &new);
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    for (start, end) in hunks(&edits) {
+        let hunk = &edits[start..end];
+        let (old_start, new_start) = position(&edits[..start]);
+        let old_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Insert(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Delete(_)))
+            .count();
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(old_start, old_len),
+            range(new_start, new_len)
+        );
+        for edit in hunk {
+            let (prefix, line) = match *edit {
+                Edit::Equal(i, _) => (' ', old[i]),
+                Edit::Delete(i) => ('-', old[i]),
+                Edit::Insert(j) => ('+', new[j]),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// Shortest edit script from `a` to `b`, using the linear-space variant of Myers' algorithm.
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let max_d = (a.len() + b.len()).div_ceil(2) + 1;
+    let mut forward = Diagonals::new(max_d);
+    let mut backward = Diagonals::new(max_d);
+    let mut edits = Vec::new();
+    diff_ranges(
+        a,
+        (0, a.len()),
+        b,
+        (0, b.len()),
+        &mut forward,
+        &mut backward,
+        &mut edits,
+    );
+    // Show each replaced run of lines as its deletions, then its insertions.
+    for run in edits.split_mut(|e| matches!(e, Edit::Equal(..))) {
+        run.sort_by_key(|e| matches!(e, Edit::Insert(_)));
+    }
+    edits
+}
+
+/// Furthest `x` reached on each diagonal `k = x - y`, for `k` in `-max_d..=max_d`.
+struct Diagonals {
+    offset: isize,
+    x: Vec<usize>,
+}
+
+impl Diagonals {
+    fn new(max_d: usize) -> Self {
+        Self {
+            offset: max_d as isize,
+            x: vec![0; 2 * max_d + 1],
+        }
+    }
+}
+
+impl std::ops::Index<isize> for Diagonals {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.x[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for Diagonals {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.x[(k + self.offset) as usize]
+    }
+}
+
+/// Append the edits from `a[a_lo..a_hi]` to `b[b_lo..b_hi]`, splitting at the middle snake of
+/// an optimal path and recursing on either side.
+fn diff_ranges(
+    a: &[&str],
+    (mut a_lo, mut a_hi): (usize, usize),
+    b: &[&str],
+    (mut b_lo, mut b_hi): (usize, usize),
+    forward: &mut Diagonals,
+    backward: &mut Diagonals,
+    edits: &mut Vec<Edit>,
+) {
+    while a_lo < a_hi && b_lo < b_hi && a[a_lo] == b[b_lo] {
+        edits.push(Edit::Equal(a_lo, b_lo));
+        a_lo += 1;
+        b_lo += 1;
+    }
+    let mut suffix = 0;
+    while a_lo < a_hi && b_lo < b_hi && a[a_hi - 1] == b[b_hi - 1] {
+        a_hi -= 1;
+        b_hi -= 1;
+        suffix += 1;
+    }
+
+    if a_lo == a_hi {
+        edits.extend((b_lo..b_hi).map(Edit::Insert));
+    } else if b_lo == b_hi {
+        edits.extend((a_lo..a_hi).map(Edit::Delete));
+    } else {
+        let (x, y) = middle_snake(a, (a_lo, a_hi), b, (b_lo, b_hi), forward, backward);
+        diff_ranges(a, (a_lo, x), b, (b_lo, y), forward, backward, edits);
+        diff_ranges(a, (x, a_hi), b, (y, b_hi), forward, backward, edits);
+    }
+    edits.extend((0..suffix).map(|i| Edit::Equal(a_hi + i, b_hi + i)));
+}
+
+/// Where an optimal path from the start to the end of the ranges crosses its middle, found by
+/// searching from both ends at once. Both ranges must be non-empty.
+fn middle_snake(
+    a: &[&str],
+    (a_lo, a_hi): (usize, usize),
+    b: &[&str],
+    (b_lo, b_hi): (usize, usize),
+    forward: &mut Diagonals,
+    backward: &mut Diagonals,
+) -> (usize, usize) {
+    let (n, m) = (a_hi - a_lo, b_hi - b_lo);
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    forward[1] = 0;
+    backward[1] = 0;
+
+    for d in 0..=(n + m).div_ceil(2) as isize {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[k - 1] < forward[k + 1]) {
+                forward[k + 1]
+            } else {
+                forward[k - 1] + 1
+            };
+            let (start_x, start_y) = (x, (x as isize - k) as usize);
+            let mut y = start_y;
+            while x < n && y < m && a[a_lo + x] == b[b_lo + y] {
+                x += 1;
+                y += 1;
+            }
+            forward[k] = x;
+            if odd && (k - delta).abs() < d && x + backward[delta - k] >= n {
+                return (a_lo + start_x, b_lo + start_y);
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[k - 1] < backward[k + 1]) {
+                backward[k + 1]
+            } else {
+                backward[k - 1] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            while x < n && y < m && a[a_hi - 1 - x] == b[b_hi - 1 - y] {
+                x += 1;
+                y += 1;
+            }
+            backward[k] = x;
+            if !odd && (k - delta).abs() <= d && x + forward[delta - k] >= n {
+                return (a_hi - x, b_hi - y);
+            }
+        }
+    }
+    unreachable!("the searches from both ends always meet")
+}
+
+/// Ranges of `edits` to show as hunks: each change plus surrounding context, with nearby
+/// changes merged.
+fn hunks(edits: &[Edit]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, edit) in edits.iter().enumerate() {
+        if matches!(edit, Edit::Equal(..)) {
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + 1 + CONTEXT).min(edits.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    hunks
+}
+
+/// Number of old and new lines covered by `edits`.
+fn position(edits: &[Edit]) -> (usize, usize) {
+    edits.iter().fold((0, 0), |(old, new), edit| match edit {
+        Edit::Equal(..) => (old + 1, new + 1),
+        Edit::Delete(_) => (old + 1, new),
+        Edit::Insert(_) => (old, new + 1),
+    })
+}
+
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn diff_against_file() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("config");
+        let old = (1..=10).map(|n| format!("{}\n", n)).collect::<String>();
+        fs::write(&path, &old)?;
+
+        let mut dry_run = DryRun::new(&path);
+        dry_run.write_all(old.replace("2\n", "two\n").replace("10\n", "10").as_bytes())?;
+        assert!(dry_run.would_change()?);
+
+        let name = path.to_string_lossy();
+        let expected = format!(
+            "--- {0}\n+++ {0}\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+             @@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+10\n\\ No newline at end of file\n",
+            name
+        );
+        assert_eq!(dry_run.diff()?, expected);
+        assert_eq!(fs::read_to_string(&path)?, old);
+
+        let mut unchanged = DryRun::new(&path);
+        unchanged.write_all(old.as_bytes())?;
+        assert_eq!(unchanged.diff()?, "");
+
+        let mut created = DryRun::new(tmp_dir.path().join("new"));
+        created.write_all(b"hello\n")?;
+        assert!(created.diff()?.starts_with("--- /dev/null\n"));
+        assert!(created.diff()?.ends_with("@@ -0,0 +1 @@\n+hello\n"));
+
+        // The example from Myers' paper, whose shortest edit script has 5 changes.
+        let a: Vec<&str> = "abcabba".matches(|_| true).collect();
+        let b: Vec<&str> = "cbabac".matches(|_| true).collect();
+        let (mut old, mut new, mut changes) = (String::new(), String::new(), 0);
+        for edit in edit_script(&a, &b) {
+            match edit {
+                Edit::Equal(i, j) => {
+                    assert_eq!(a[i], b[j]);
+                    old.push_str(a[i]);
+                    new.push_str(b[j]);
+                }
+                Edit::Delete(i) => old.push_str(a[i]),
+                Edit::Insert(j) => new.push_str(b[j]),
+            }
+            changes += !matches!(edit, Edit::Equal(..)) as usize;
+        }
+        assert_eq!((&*old, &*new, changes), ("abcabba", "cbabac", 5));
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
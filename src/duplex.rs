@@ -0,0 +1,163 @@
+use crate::{instrument, FileOrStdin, FileOrStdout, STDIO_FILENAME};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// One endpoint both read from and written to: a file opened for reading and writing, or the
+/// stdin and stdout pair.
+///
+/// For protocol-like tools, such as language servers or filters with a handshake, that talk
+/// over stdio as one channel. [`split`](FileOrStdio::split) gives separate halves, e.g. for a
+/// reader thread and a writer thread, and [`join`](FileOrStdio::join) puts them back together.
+pub enum FileOrStdio {
+    File(File),
+    Stdio(io::Stdin, io::Stdout),
+}
+
+impl FileOrStdio {
+    /// Open the file at `path` for reading and writing, without truncating it. `-` means stdin
+    /// and stdout.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        Ok(if path.to_string_lossy() == STDIO_FILENAME {
+            instrument::open(instrument::STDIN, path);
+            instrument::open(instrument::STDOUT, path);
+            Self::stdio()
+        } else {
+            let open = |p: &Path| OpenOptions::new().read(true).write(true).open(p);
+            instrument::open_file(path, open)?.into()
+        })
+    }
+
+    pub fn stdio() -> Self {
+        Self::Stdio(io::stdin(), io::stdout())
+    }
+
+    /// Separate the reading and writing halves. A file is shared by both halves, through a
+    /// duplicated handle.
+    pub fn split(self) -> io::Result<(FileOrStdin, FileOrStdout)> {
+        match self {
+            Self::File(file) => Ok((file.try_clone()?.into(), file.into())),
+            Self::Stdio(stdin, stdout) => Ok((stdin.into(), stdout.into())),
+        }
+    }
+
+    /// Put halves from [`split`](FileOrStdio::split) back together.
+    ///
+    /// Fails with `InvalidInput` unless both are stdio, or both are handles to the same file.
+    pub fn join(input: FileOrStdin, output: FileOrStdout) -> io::Result<Self> {
+        match (input.into_inner(), output.into_inner()) {
+            (Err(stdin), Err(stdout)) => Ok(Self::Stdio(stdin, stdout)),
+            (Ok(input), Ok(output)) if same_file(&input, &output)? => Ok(Self::File(output)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "input and output are different endpoints",
+            )),
+        }
+    }
+
+    /// Whether the endpoint is an interactive terminal (for stdio, both stdin and stdout).
+    pub fn is_terminal(&self) -> bool {
+        use std::io::IsTerminal;
+
+        match self {
+            Self::File(file) => file.is_terminal(),
+            Self::Stdio(stdin, stdout) => stdin.is_terminal() && stdout.is_terminal(),
+        }
+    }
+}
+
+impl From<File> for FileOrStdio {
+    fn from(file: File) -> Self {
+        Self::File(file)
+    }
+}
+
+impl Read for FileOrStdio {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Stdio(stdin, _) => stdin.read(buf),
+        }
+    }
+}
+
+impl Write for FileOrStdio {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.write(buf),
+            Self::Stdio(_, stdout) => stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::File(file) => file.flush(),
+            Self::Stdio(_, stdout) => stdout.flush(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &File, b: &File) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (a, b) = (a.metadata()?, b.metadata()?);
+    Ok((a.dev(), a.ino()) == (b.dev(), b.ino()))
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &File, _: &File) -> io::Result<bool> {
+    // Without file identities to compare, trust that the halves came from `split`.
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        io::{Seek, SeekFrom},
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn split_and_join_duplex() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("channel");
+        fs::write(&path, "hello")?;
+
+        let mut duplex = FileOrStdio::from_path(&path)?;
+        let mut greeting = [0; 5];
+        duplex.read_exact(&mut greeting)?;
+        assert_eq!(&greeting, b"hello");
+        duplex.write_all(b", world")?;
+        assert_eq!(fs::read_to_string(&path)?, "hello, world");
+
+        let (input, mut output) = duplex.split()?;
+        output.lock().write_all(b"!")?;
+        let mut duplex = FileOrStdio::join(input, output)?;
+        if let FileOrStdio::File(file) = &mut duplex {
+            file.seek(SeekFrom::Start(0))?;
+        }
+        let mut content = String::new();
+        duplex.read_to_string(&mut content)?;
+        assert_eq!(content, "hello, world!");
+
+        #[cfg(unix)]
+        {
+            let other = tmp_dir.path().join("other");
+            fs::write(&other, "")?;
+            let (_, output) = FileOrStdio::from_path(&path)?.split()?;
+            let e = FileOrStdio::join(FileOrStdin::from_path(&other)?, output).err();
+            assert_eq!(e.map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+        }
+        let stdio = FileOrStdio::join(io::stdin().into(), io::stdout().into())?;
+        assert!(matches!(stdio, FileOrStdio::Stdio(..)));
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
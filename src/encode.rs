@@ -0,0 +1,490 @@
+use std::io::{self, Read, Write};
+
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const INVALID: u8 = 0xff;
+const CHUNK: usize = 8 * 1024;
+
+/// The 64 characters base64 encodes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Base64Alphabet {
+    /// RFC 4648 standard alphabet, with `+` and `/`.
+    #[default]
+    Standard,
+    /// RFC 4648 URL and filename safe alphabet, with `-` and `_`.
+    UrlSafe,
+}
+
+/// Writer that base64-encodes everything written to it into `W`.
+///
+/// Call [`finish`](Base64Writer::finish) to write the final, padded group. Dropping the writer
+/// instead still writes it, but ignores any errors.
+pub struct Base64Writer<W: Write> {
+    inner: Option<W>,
+    alphabet: &'static [u8; 64],
+    padding: bool,
+    lines: LineWrap,
+    pending: [u8; 3],
+    pending_len: usize,
+}
+
+/// Reader that decodes the base64 read from `R`, skipping whitespace.
+pub struct Base64Reader<R> {
+    inner: R,
+    table: [u8; 256],
+    decoded: Decoded,
+    group: [u8; 4],
+    group_len: usize,
+    /// Set once padding is seen; only more padding and whitespace may follow.
+    padded: bool,
+}
+
+/// Writer that hex-encodes everything written to it into `W`.
+///
+/// Call [`finish`](HexWriter::finish) to end the last wrapped line and get `W` back.
+pub struct HexWriter<W: Write> {
+    inner: Option<W>,
+    digits: &'static [u8; 16],
+    lines: LineWrap,
+}
+
+/// Reader that decodes the hex digits read from `R`, in either case, skipping whitespace.
+pub struct HexReader<R> {
+    inner: R,
+    decoded: Decoded,
+    high: Option<u8>,
+}
+
+/// Breaks encoded output into lines of at most `width` characters.
+struct LineWrap {
+    width: usize,
+    column: usize,
+}
+
+/// Decoded bytes waiting to be read.
+#[derive(Default)]
+struct Decoded {
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl Base64Alphabet {
+    fn chars(self) -> &'static [u8; 64] {
+        match self {
+            Self::Standard => STANDARD,
+            Self::UrlSafe => URL_SAFE,
+        }
+    }
+
+    fn table(self) -> [u8; 256] {
+        let mut table = [INVALID; 256];
+        for (value, c) in self.chars().iter().enumerate() {
+            table[*c as usize] = value as u8;
+        }
+        table
+    }
+}
+
+impl<W: Write> Base64Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            alphabet: STANDARD,
+            padding: true,
+            lines: LineWrap::new(0),
+            pending: [0; 3],
+            pending_len: 0,
+        }
+    }
+
+    pub fn alphabet(mut self, alphabet: Base64Alphabet) -> Self {
+        self.alphabet = alphabet.chars();
+        self
+    }
+
+    /// Whether to pad the final group with `=`. Defaults to true.
+    pub fn padding(mut self, padding: bool) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Break the output into lines of `width` characters, e.g. 76 for MIME or 64 for PEM. The
+    /// default of 0 writes a single line with no newline.
+    pub fn wrap(mut self, width: usize) -> Self {
+        self.lines = LineWrap::new(width);
+        self
+    }
+
+    /// Write the final group and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_final()?;
+        Ok(self.inner.take().expect("writer is only finished once"))
+    }
+
+    fn write_final(&mut self) -> io::Result<()> {
+        let mut encoded = Vec::with_capacity(5);
+        if self.pending_len > 0 {
+            let mut bytes = [0; 3];
+            bytes[..self.pending_len].copy_from_slice(&self.pending[..self.pending_len]);
+            let group = self.encode_group(bytes);
+            encoded.extend_from_slice(&group[..self.pending_len + 1]);
+            if self.padding {
+                encoded.resize(4, b'=');
+            }
+        }
+        self.pending_len = 0;
+        let inner = self.inner.as_mut().expect("writer not finished");
+        self.lines.write(inner, &encoded)?;
+        self.lines.end(inner)?;
+        inner.flush()
+    }
+
+    fn encode_group(&self, bytes: [u8; 3]) -> [u8; 4] {
+        let n = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+        [
+            self.alphabet[n >> 18],
+            self.alphabet[(n >> 12) & 0x3f],
+            self.alphabet[(n >> 6) & 0x3f],
+            self.alphabet[n & 0x3f],
+        ]
+    }
+}
+
+impl<W: Write> Write for Base64Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut encoded = Vec::with_capacity(buf.len() / 3 * 4 + 4);
+        let mut rest = buf;
+        while !rest.is_empty() {
+            let take = (3 - self.pending_len).min(rest.len());
+            self.pending[self.pending_len..self.pending_len + take].copy_from_slice(&rest[..take]);
+            self.pending_len += take;
+            rest = &rest[take..];
+            if self.pending_len == 3 {
+                encoded.extend_from_slice(&self.encode_group(self.pending));
+                self.pending_len = 0;
+            }
+        }
+        let inner = self.inner.as_mut().expect("writer not finished");
+        self.lines.write(inner, &encoded)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.as_mut().expect("writer not finished").flush()
+    }
+}
+
+impl<W: Write> Drop for Base64Writer<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_final();
+        }
+    }
+}
+
+impl<R: Read> Base64Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            table: Base64Alphabet::Standard.table(),
+            decoded: Decoded::default(),
+            group: [0; 4],
+            group_len: 0,
+            padded: false,
+        }
+    }
+
+    pub fn alphabet(mut self, alphabet: Base64Alphabet) -> Self {
+        self.table = alphabet.table();
+        self
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let mut raw = [0; CHUNK];
+        let amt = self.decoded.read_raw(&mut self.inner, &mut raw)?;
+        if amt == 0 {
+            // Unpadded input may end mid-group.
+            return self.end_group();
+        }
+        for &c in &raw[..amt] {
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            if c == b'=' {
+                if !self.padded {
+                    self.end_group()?;
+                    self.padded = true;
+                }
+                continue;
+            }
+            let value = self.table[c as usize];
+            if value == INVALID || self.padded {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid base64 character {:?}", c as char),
+                ));
+            }
+            self.group[self.group_len] = value;
+            self.group_len += 1;
+            if self.group_len == 4 {
+                self.end_group()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn end_group(&mut self) -> io::Result<()> {
+        let len = std::mem::take(&mut self.group_len);
+        if len == 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated base64 input",
+            ));
+        }
+        let g = &self.group;
+        let n = (g[0] as u32) << 18 | (g[1] as u32) << 12 | (g[2] as u32) << 6 | g[3] as u32;
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        self.decoded
+            .buf
+            .extend_from_slice(&bytes[..len.saturating_sub(1)]);
+        self.group = [0; 4];
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Base64Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.decoded.is_empty() && !self.decoded.eof {
+            self.fill()?;
+        }
+        Ok(self.decoded.take(buf))
+    }
+}
+
+impl<W: Write> HexWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            digits: b"0123456789abcdef",
+            lines: LineWrap::new(0),
+        }
+    }
+
+    /// Write `A`-`F` instead of `a`-`f`.
+    pub fn uppercase(mut self, uppercase: bool) -> Self {
+        self.digits = if uppercase {
+            b"0123456789ABCDEF"
+        } else {
+            b"0123456789abcdef"
+        };
+        self
+    }
+
+    /// Break the output into lines of `width` characters. The default of 0 writes a single line
+    /// with no newline.
+    pub fn wrap(mut self, width: usize) -> Self {
+        self.lines = LineWrap::new(width);
+        self
+    }
+
+    /// End the last line and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut inner = self.inner.take().expect("writer is only finished once");
+        self.lines.end(&mut inner)?;
+        inner.flush()?;
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for HexWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let encoded: Vec<u8> = buf
+            .iter()
+            .flat_map(|b| {
+                [
+                    self.digits[(b >> 4) as usize],
+                    self.digits[(b & 0xf) as usize],
+                ]
+            })
+            .collect();
+        let inner = self.inner.as_mut().expect("writer not finished");
+        self.lines.write(inner, &encoded)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.as_mut().expect("writer not finished").flush()
+    }
+}
+
+impl<W: Write> Drop for HexWriter<W> {
+    fn drop(&mut self) {
+        if let Some(inner) = &mut self.inner {
+            let _ = self.lines.end(inner);
+        }
+    }
+}
+
+impl<R: Read> HexReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decoded: Decoded::default(),
+            high: None,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let mut raw = [0; CHUNK];
+        let amt = self.decoded.read_raw(&mut self.inner, &mut raw)?;
+        if amt == 0 && self.high.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "odd number of hex digits",
+            ));
+        }
+        for &c in &raw[..amt] {
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            let digit = (c as char).to_digit(16).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid hex digit {:?}", c as char),
+                )
+            })? as u8;
+            match self.high.take() {
+                Some(high) => self.decoded.buf.push(high << 4 | digit),
+                None => self.high = Some(digit),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for HexReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.decoded.is_empty() && !self.decoded.eof {
+            self.fill()?;
+        }
+        Ok(self.decoded.take(buf))
+    }
+}
+
+impl LineWrap {
+    fn new(width: usize) -> Self {
+        Self { width, column: 0 }
+    }
+
+    fn write<W: Write>(&mut self, inner: &mut W, mut encoded: &[u8]) -> io::Result<()> {
+        if self.width == 0 {
+            return inner.write_all(encoded);
+        }
+        let mut out = Vec::with_capacity(encoded.len() + encoded.len() / self.width + 1);
+        while !encoded.is_empty() {
+            if self.column == self.width {
+                out.push(b'\n');
+                self.column = 0;
+            }
+            let take = (self.width - self.column).min(encoded.len());
+            out.extend_from_slice(&encoded[..take]);
+            self.column += take;
+            encoded = &encoded[take..];
+        }
+        inner.write_all(&out)
+    }
+
+    /// Finish the current line, if wrapping and anything is on it.
+    fn end<W: Write>(&mut self, inner: &mut W) -> io::Result<()> {
+        if self.width > 0 && self.column > 0 {
+            self.column = 0;
+            inner.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl Decoded {
+    fn is_empty(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    fn read_raw<R: Read>(&mut self, inner: &mut R, raw: &mut [u8]) -> io::Result<usize> {
+        self.buf.clear();
+        self.pos = 0;
+        let amt = loop {
+            match inner.read(raw) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                result => break result?,
+            }
+        };
+        self.eof = amt == 0;
+        Ok(amt)
+    }
+
+    fn take(&mut self, buf: &mut [u8]) -> usize {
+        let amt = buf.len().min(self.buf.len() - self.pos);
+        buf[..amt].copy_from_slice(&self.buf[self.pos..self.pos + amt]);
+        self.pos += amt;
+        amt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode<R: Read>(mut reader: R) -> io::Result<Vec<u8>> {
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn base64_round_trip() -> Result<(), io::Error> {
+        for (data, expected) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xfb\xff", "+/8="),
+        ] {
+            let mut writer = Base64Writer::new(Vec::new());
+            for b in data {
+                writer.write_all(&[*b])?;
+            }
+            assert_eq!(writer.finish()?, expected.as_bytes());
+            assert_eq!(decode(Base64Reader::new(expected.as_bytes()))?, data);
+        }
+
+        let mut writer = Base64Writer::new(Vec::new())
+            .alphabet(Base64Alphabet::UrlSafe)
+            .padding(false)
+            .wrap(4);
+        writer.write_all(b"\xfb\xffhello")?;
+        let encoded = writer.finish()?;
+        assert_eq!(encoded, b"-_9o\nZWxs\nbw\n");
+        let reader = Base64Reader::new(&encoded[..]).alphabet(Base64Alphabet::UrlSafe);
+        assert_eq!(decode(reader)?, b"\xfb\xffhello");
+
+        assert!(decode(Base64Reader::new(&b"Zm9v!"[..])).is_err());
+        assert!(decode(Base64Reader::new(&b"Zg==Zg=="[..])).is_err());
+        assert!(decode(Base64Reader::new(&b"Zm9vY"[..])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn hex_round_trip() -> Result<(), io::Error> {
+        let mut writer = HexWriter::new(Vec::new()).uppercase(true).wrap(6);
+        writer.write_all(b"\x00\x1f\xab\xcd")?;
+        let encoded = writer.finish()?;
+        assert_eq!(encoded, b"001FAB\nCD\n");
+        assert_eq!(decode(HexReader::new(&encoded[..]))?, b"\x00\x1f\xab\xcd");
+        assert_eq!(decode(HexReader::new(&b"ab cd"[..]))?, b"\xab\xcd");
+        assert!(decode(HexReader::new(&b"abc"[..])).is_err());
+        assert!(decode(HexReader::new(&b"zz"[..])).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+use crate::PartialPolicy;
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// What failed, by category, carried inside the `io::Error`s this crate returns.
+///
+/// Find it with [`Error::of`] to branch on the kind of failure instead of matching messages.
+/// New variants may be added, so matches need a catch-all arm.
+///
+/// ```
+/// # use std::io;
+/// # fn report(e: io::Error) {
+/// match polymorphio::Error::of(&e) {
+///     Some(polymorphio::Error::SamePath { input, .. }) => {
+///         eprintln!("refusing to overwrite {}", input.display())
+///     }
+///     Some(other) => eprintln!("{} failed: {}", other.operation(), other),
+///     None => eprintln!("{}", e),
+/// }
+/// # }
+/// ```
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    OpenInput {
+        path: PathBuf,
+        source: io::Error,
+    },
+    OpenOutput {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Write {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Flush {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Replacing the target of an [`AtomicOutput`](crate::AtomicOutput) failed.
+    Commit {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A path named a `scheme:` this build doesn't support.
+    UnsupportedScheme {
+        scheme: String,
+    },
+    /// Writing the output would clobber one of the inputs.
+    SamePath {
+        input: PathBuf,
+        output: PathBuf,
+    },
+    /// Writing a [`GuardedOutput`](crate::GuardedOutput) failed after `written` bytes reached
+    /// the file, and the partial file was dealt with according to `policy`.
+    Incomplete {
+        path: PathBuf,
+        written: u64,
+        policy: PartialPolicy,
+        source: io::Error,
+    },
+    /// The file read back after writing isn't what was written; the digests are in hex.
+    VerificationFailed {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    /// A file changed while it was being processed; see [`FileStamp`](crate::FileStamp). The
+    /// path is unknown for changes found while reading.
+    InputChanged {
+        path: Option<PathBuf>,
+    },
+    /// There isn't room for an output of the size it was announced to be.
+    InsufficientSpace {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
+    /// The file system filled up, or the quota ran out, after `written` bytes of an output.
+    ///
+    /// `left_behind` is the partial file that remains, if any. An
+    /// [`AtomicOutput`](crate::AtomicOutput) leaves nothing behind: its temporary file is
+    /// removed when it is dropped.
+    StorageFull {
+        path: PathBuf,
+        written: u64,
+        left_behind: Option<PathBuf>,
+        source: io::Error,
+    },
+}
+
+impl Error {
+    /// The first `Error` in `e` or the errors it wraps.
+    pub fn of(e: &io::Error) -> Option<&Error> {
+        causes(e).into_iter().find_map(|e| e.downcast_ref())
+    }
+
+    /// The `io::ErrorKind` of the `io::Error` carrying this error.
+    pub fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            Error::OpenInput { source, .. }
+            | Error::OpenOutput { source, .. }
+            | Error::Read { source, .. }
+            | Error::Write { source, .. }
+            | Error::Flush { source, .. }
+            | Error::Commit { source, .. }
+            | Error::Incomplete { source, .. }
+            | Error::StorageFull { source, .. } => source.kind(),
+            Error::UnsupportedScheme { .. } => io::ErrorKind::Unsupported,
+            Error::SamePath { .. } => io::ErrorKind::InvalidInput,
+            Error::InsufficientSpace { .. } => io::ErrorKind::StorageFull,
+            Error::VerificationFailed { .. } => io::ErrorKind::InvalidData,
+            Error::InputChanged { .. } => io::ErrorKind::Other,
+        }
+    }
+
+    /// A name for the failed operation, like `open_input`, e.g. for machine-readable output.
+    pub fn operation(&self) -> &'static str {
+        match self {
+            Error::OpenInput { .. } => "open_input",
+            Error::OpenOutput { .. } => "open_output",
+            Error::Read { .. } => "read",
+            Error::Write { .. } => "write",
+            Error::Flush { .. } => "flush",
+            Error::Commit { .. } => "commit",
+            Error::UnsupportedScheme { .. } => "unsupported_scheme",
+            Error::SamePath { .. } => "same_path",
+            Error::Incomplete { .. } => "incomplete",
+            Error::InsufficientSpace { .. } => "insufficient_space",
+            Error::VerificationFailed { .. } => "verification_failed",
+            Error::InputChanged { .. } => "input_changed",
+            Error::StorageFull { .. } => "storage_full",
+        }
+    }
+
+    /// The file the error is about; for `SamePath`, the output.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::OpenInput { path, .. }
+            | Error::OpenOutput { path, .. }
+            | Error::Read { path, .. }
+            | Error::Write { path, .. }
+            | Error::Flush { path, .. }
+            | Error::Commit { path, .. }
+            | Error::Incomplete { path, .. }
+            | Error::InsufficientSpace { path, .. }
+            | Error::VerificationFailed { path, .. }
+            | Error::StorageFull { path, .. } => Some(path),
+            Error::SamePath { output, .. } => Some(output),
+            Error::InputChanged { path } => path.as_deref(),
+            Error::UnsupportedScheme { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::OpenInput { path, source } => {
+                write!(f, "can't open {}: {}", path.display(), source)
+            }
+            Error::OpenOutput { path, source } => {
+                write!(f, "can't create {}: {}", path.display(), source)
+            }
+            Error::Read { path, source } => {
+                write!(f, "error reading {}: {}", path.display(), source)
+            }
+            Error::Write { path, source } => {
+                write!(f, "error writing {}: {}", path.display(), source)
+            }
+            Error::Flush { path, source } => {
+                write!(f, "error flushing {}: {}", path.display(), source)
+            }
+            Error::Commit { path, source } => {
+                write!(f, "can't replace {}: {}", path.display(), source)
+            }
+            Error::UnsupportedScheme { scheme } => write!(f, "unsupported scheme {}:", scheme),
+            Error::SamePath { input, output } => write!(
+                f,
+                "input {} is the same file as output {}",
+                input.display(),
+                output.display()
+            ),
+            Error::Incomplete {
+                path,
+                written,
+                policy,
+                source,
+            } => {
+                let left = match policy {
+                    PartialPolicy::Keep => "kept",
+                    PartialPolicy::Truncate => "truncated",
+                    PartialPolicy::Delete => "deleted",
+                };
+                write!(
+                    f,
+                    "error writing {} after {} bytes (partial file {}): {}",
+                    path.display(),
+                    written,
+                    left,
+                    source
+                )
+            }
+            Error::InputChanged { path: Some(path) } => {
+                write!(f, "{} changed while it was being processed", path.display())
+            }
+            Error::InputChanged { path: None } => {
+                write!(f, "the input changed while it was being read")
+            }
+            Error::VerificationFailed {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} doesn't read back as written: SHA-256 {} instead of {}",
+                path.display(),
+                actual,
+                expected
+            ),
+            Error::InsufficientSpace {
+                path,
+                needed,
+                available,
+            } => write!(
+                f,
+                "not enough space for {}: {} bytes needed, {} available",
+                path.display(),
+                needed,
+                available
+            ),
+            Error::StorageFull {
+                path,
+                written,
+                left_behind,
+                ..
+            } => {
+                write!(
+                    f,
+                    "ran out of space writing {} after {} bytes",
+                    path.display(),
+                    written
+                )?;
+                match left_behind {
+                    Some(partial) => write!(f, "; partial output left in {}", partial.display()),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::OpenInput { source, .. }
+            | Error::OpenOutput { source, .. }
+            | Error::Read { source, .. }
+            | Error::Write { source, .. }
+            | Error::Flush { source, .. }
+            | Error::Commit { source, .. }
+            | Error::Incomplete { source, .. }
+            | Error::StorageFull { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        io::Error::new(e.io_kind(), e)
+    }
+}
+
+/// The errors wrapped inside `e`, outermost first, looking into `io::Error`s along the way.
+pub(crate) fn causes(e: &io::Error) -> Vec<&(dyn error::Error + 'static)> {
+    let mut causes = Vec::new();
+    let mut next = e.get_ref().map(|e| e as &(dyn error::Error + 'static));
+    while let Some(error) = next {
+        causes.push(error);
+        next = match error.downcast_ref::<io::Error>() {
+            Some(e) => e.get_ref().map(|e| e as &(dyn error::Error + 'static)),
+            None => error.source(),
+        };
+    }
+    causes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error_json, with_path, FileOrStdin, FileOrStdout};
+    use tempfile::TempDir;
+
+    #[test]
+    fn find_categorized_errors() {
+        let e: io::Error = Error::Commit {
+            path: "out.txt".into(),
+            source: io::ErrorKind::PermissionDenied.into(),
+        }
+        .into();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        let e = with_path(e, "job.toml");
+
+        let found = Error::of(&e).expect("the error is found through with_path");
+        assert!(matches!(found, Error::Commit { .. }));
+        assert_eq!(found.operation(), "commit");
+        assert_eq!(found.path(), Some(Path::new("out.txt")));
+        assert!(e
+            .to_string()
+            .starts_with("job.toml: can't replace out.txt: "));
+
+        let e: io::Error = Error::UnsupportedScheme {
+            scheme: "gopher".into(),
+        }
+        .into();
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        assert!(Error::of(&io::Error::other("plain")).is_none());
+    }
+
+    #[test]
+    fn categorize_file_errors() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let missing = tmp_dir.path().join("missing.txt");
+        let e = FileOrStdin::from_path(&missing)
+            .err()
+            .expect("the input is missing");
+        assert!(matches!(Error::of(&e), Some(Error::OpenInput { path, .. }) if *path == missing));
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert!(error_json(&e).contains(r#""operation":"open_input""#));
+
+        let e = FileOrStdout::from_path(missing.join("out.txt"))
+            .err()
+            .expect("the parent directory is missing");
+        assert!(matches!(Error::of(&e), Some(Error::OpenOutput { .. })));
+
+        #[cfg(unix)]
+        {
+            let e =
+                FileOrStdin::read_to_string(tmp_dir.path()).expect_err("a directory can't be read");
+            assert!(matches!(Error::of(&e), Some(Error::Read { .. })));
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let e = FileOrStdout::write_all("/dev/full", b"data").expect_err("the device is full");
+            assert!(matches!(
+                Error::of(&e),
+                Some(Error::Write { .. } | Error::Flush { .. })
+            ));
+            assert_eq!(e.kind(), io::ErrorKind::StorageFull);
+        }
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
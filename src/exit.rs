@@ -0,0 +1,240 @@
+use crate::{error::causes, report::json_string, Error};
+use std::{
+    env, error, fmt, io,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+};
+
+/// How [`run_main`] prints the error a tool failed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// `<program>: <error>`, for people.
+    #[default]
+    Text,
+    /// One line of JSON from [`error_json`], for editors, CI and other programs.
+    Json,
+}
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Set how [`run_main`] prints errors, e.g. from a `--error-format=json` flag.
+pub fn set_error_format(format: ErrorFormat) {
+    JSON_ERRORS.store(format == ErrorFormat::Json, Ordering::SeqCst);
+}
+
+pub(crate) type WarningHandler = Box<dyn Fn(&str) + Send + Sync>;
+
+static WARNING_HANDLER: RwLock<Option<WarningHandler>> = RwLock::new(None);
+
+/// Send the warnings this crate would print on stderr to `handler` instead, e.g. to log them or
+/// print them as JSON. That's the input errors [`ErrorPolicy::Continue`](crate::ErrorPolicy)
+/// goes on after, and unread input from
+/// [`ensure_drained`](crate::FileOrStdinLock::ensure_drained).
+pub fn set_warning_handler<F: Fn(&str) + Send + Sync + 'static>(handler: F) {
+    *WARNING_HANDLER.write().unwrap_or_else(|p| p.into_inner()) = Some(Box::new(handler));
+}
+
+/// Install `handler`, returning the one it replaces, so tests can put it back.
+#[cfg(test)]
+pub(crate) fn replace_warning_handler(handler: Option<WarningHandler>) -> Option<WarningHandler> {
+    std::mem::replace(
+        &mut *WARNING_HANDLER.write().unwrap_or_else(|p| p.into_inner()),
+        handler,
+    )
+}
+
+/// Print a warning on stderr, or pass it to the handler from [`set_warning_handler`].
+pub(crate) fn warn(message: &str) {
+    match &*WARNING_HANDLER.read().unwrap_or_else(|p| p.into_inner()) {
+        Some(handler) => handler(message),
+        None => eprintln!("{}", message),
+    }
+}
+
+/// An error about the file at `path`, from [`with_path`].
+#[derive(Debug)]
+pub struct PathError {
+    path: PathBuf,
+    error: io::Error,
+}
+
+impl PathError {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl error::Error for PathError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The conventional exit status for a command-line tool failing with `e`.
+///
+/// A broken pipe counts as success, since it just means whoever was reading the output (`head`,
+/// say) has stopped. Other errors map to the BSD `sysexits.h` codes:
+///
+/// | Error kind                              | Code | Name           |
+/// |-----------------------------------------|------|----------------|
+/// | `InvalidInput`                          | 64   | `EX_USAGE`     |
+/// | `InvalidData`, `UnexpectedEof`          | 65   | `EX_DATAERR`   |
+/// | `NotFound`                              | 66   | `EX_NOINPUT`   |
+/// | `AlreadyExists`                         | 73   | `EX_CANTCREAT` |
+/// | `Interrupted`, `WouldBlock`, `TimedOut` | 75   | `EX_TEMPFAIL`  |
+/// | `PermissionDenied`                      | 77   | `EX_NOPERM`    |
+/// | anything else                           | 74   | `EX_IOERR`     |
+pub fn exit_code_for(e: &io::Error) -> i32 {
+    use io::ErrorKind::*;
+    match e.kind() {
+        BrokenPipe => 0,
+        InvalidInput => 64,
+        InvalidData | UnexpectedEof => 65,
+        NotFound => 66,
+        AlreadyExists => 73,
+        Interrupted | WouldBlock | TimedOut => 75,
+        PermissionDenied => 77,
+        _ => 74,
+    }
+}
+
+/// Prefix `e`'s message with `path`, keeping its kind. Errors from `std::fs` don't say which
+/// file they were about. An `e` whose [`Error`] already names `path` is returned as it is.
+///
+/// The result carries a [`PathError`], so the path and OS error code are still there for
+/// [`error_json`].
+pub fn with_path<P: AsRef<Path>>(e: io::Error, path: P) -> io::Error {
+    if Error::of(&e).and_then(Error::path) == Some(path.as_ref()) {
+        return e;
+    }
+    let error = PathError {
+        path: path.as_ref().to_owned(),
+        error: e,
+    };
+    io::Error::new(error.error.kind(), error)
+}
+
+/// `e` as a JSON object on one line: its `kind` (as named by `io::ErrorKind`), `message`, and
+/// the failed `operation` (see [`Error::operation`]), `path` and `os_error` code when known.
+///
+/// ```
+/// # use std::io;
+/// let e = polymorphio::with_path(io::Error::from_raw_os_error(2), "in.txt");
+/// let json = polymorphio::error_json(&e);
+/// assert!(json.contains(r#""path":"in.txt","os_error":2"#));
+/// ```
+pub fn error_json(e: &io::Error) -> String {
+    let (mut operation, mut path, mut os_error) = (None, None, e.raw_os_error());
+    // Look through wrapped errors, such as from `with_path`, for the details.
+    for error in causes(e) {
+        if let Some(error) = error.downcast_ref::<Error>() {
+            operation = operation.or(Some(error.operation()));
+            path = path.or(error.path());
+        }
+        if let Some(error) = error.downcast_ref::<PathError>() {
+            path = path.or(Some(&error.path));
+        }
+        if let Some(error) = error.downcast_ref::<io::Error>() {
+            os_error = os_error.or_else(|| error.raw_os_error());
+        }
+    }
+
+    let mut json = format!(
+        "{{\"kind\":{},\"message\":{}",
+        json_string(&format!("{:?}", e.kind())),
+        json_string(&e.to_string())
+    );
+    if let Some(operation) = operation {
+        json += &format!(",\"operation\":{}", json_string(operation));
+    }
+    if let Some(path) = path {
+        json += &format!(",\"path\":{}", json_string(&path.to_string_lossy()));
+    }
+    if let Some(code) = os_error {
+        json += &format!(",\"os_error\":{}", code);
+    }
+    json + "}"
+}
+
+/// Run a tool's real `main` and exit with the status its result calls for.
+///
+/// On an error, prints `<program>: <error>` to stderr (nothing for a broken pipe) and exits with
+/// [`exit_code_for`] the error. Use [`with_path`] to say which file an error was about, and
+/// [`set_error_format`] to print the error as JSON instead.
+pub fn run_main<F: FnOnce() -> io::Result<()>>(main: F) -> ! {
+    let e = match main() {
+        Ok(()) => process::exit(0),
+        Err(e) => e,
+    };
+    let code = exit_code_for(&e);
+    if code != 0 && JSON_ERRORS.load(Ordering::SeqCst) {
+        eprintln!("{}", error_json(&e));
+    } else if code != 0 {
+        let program = env::args_os()
+            .next()
+            .map(PathBuf::from)
+            .and_then(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| env!("CARGO_PKG_NAME").into());
+        eprintln!("{}: {}", program, e);
+    }
+    process::exit(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileOrStdin;
+    use tempfile::TempDir;
+
+    #[test]
+    fn exit_codes() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let missing = tmp_dir.path().join("missing.txt");
+        let e = FileOrStdin::from_path(&missing).err().unwrap();
+        assert_eq!(exit_code_for(&e), 66);
+
+        // The error already names the path, so it isn't named twice.
+        let message = format!("can't open {}: ", missing.display());
+        assert!(e.to_string().starts_with(&message));
+        let e = with_path(e, &missing);
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert!(e.to_string().starts_with(&message));
+        let json = error_json(&e);
+        assert!(json.starts_with(r#"{"kind":"NotFound","message":""#));
+        assert!(json.contains(&format!(
+            r#""operation":"open_input","path":{}"#,
+            json_string(&missing.to_string_lossy())
+        )));
+        #[cfg(unix)]
+        assert!(json.ends_with(&format!(r#","os_error":{}}}"#, libc::ENOENT)));
+        let e = with_path(io::ErrorKind::NotFound.into(), "other.txt");
+        assert!(e.to_string().starts_with("other.txt: "));
+        assert_eq!(
+            error_json(&io::Error::other("disk on fire")),
+            r#"{"kind":"Other","message":"disk on fire"}"#
+        );
+
+        assert_eq!(exit_code_for(&io::ErrorKind::BrokenPipe.into()), 0);
+        assert_eq!(exit_code_for(&io::ErrorKind::PermissionDenied.into()), 77);
+        assert_eq!(exit_code_for(&io::Error::other("disk on fire")), 74);
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
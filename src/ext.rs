@@ -0,0 +1,426 @@
+use crate::{
+    digest::{to_hex, Crc32, Sha256},
+    report::Counted,
+};
+#[cfg(feature = "compress")]
+use crate::{Codec, CompressWriter, DecompressReader};
+#[cfg(feature = "compress")]
+use std::io::BufReader;
+use std::{
+    io::{self, BufRead, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Adapters for any reader, including the lock types, as methods that chain:
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use polymorphio::{FileOrStdin, HashAlgorithm, PolymorphReadExt};
+/// use std::io;
+///
+/// let mut input = FileOrStdin::from_path("Cargo.toml")?;
+/// let mut reader = input
+///     .lock()
+///     .limited(1 << 20)
+///     .hashing(HashAlgorithm::Sha256)
+///     .counted();
+/// io::copy(&mut reader, &mut io::sink())?;
+/// eprintln!("{} bytes, sha256 {}", reader.count(), reader.get_ref().hex_digest());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Each adapter keeps `BufRead` if the reader has it. Types that are both readers and writers,
+/// like `File`, need the trait spelled out (`PolymorphReadExt::counted(file)`) when both this
+/// and [`PolymorphWriteExt`] are in scope.
+pub trait PolymorphReadExt: Read {
+    /// Count the bytes read.
+    fn counted(self) -> Counted<Self>
+    where
+        Self: Sized,
+    {
+        Counted::new(self)
+    }
+
+    /// Fail with `FileTooLarge` once more than `limit` bytes are read, rather than quietly
+    /// stopping at the limit like [`Read::take`].
+    fn limited(self, limit: u64) -> Limited<Self>
+    where
+        Self: Sized,
+    {
+        Limited::new(self, limit)
+    }
+
+    /// Hash the bytes read.
+    fn hashing(self, algorithm: HashAlgorithm) -> Hashing<Self>
+    where
+        Self: Sized,
+    {
+        Hashing::new(self, algorithm)
+    }
+
+    /// Read at most `bytes_per_sec` on average.
+    fn throttled(self, bytes_per_sec: u64) -> Throttled<Self>
+    where
+        Self: Sized,
+    {
+        Throttled::new(self, bytes_per_sec)
+    }
+
+    /// Decompress with `codec`, or with `None`, with the codec whose magic bytes the data starts
+    /// with, passing data that doesn't look compressed through as it is.
+    #[cfg(feature = "compress")]
+    fn decompressed(self, codec: Option<Codec>) -> io::Result<Box<dyn BufRead + Send>>
+    where
+        Self: Sized + Send + 'static,
+    {
+        let mut reader = BufReader::new(self);
+        let codec = match codec {
+            Some(codec) => Some(codec),
+            None => Codec::from_magic(reader.fill_buf()?),
+        };
+        Ok(match codec {
+            Some(codec) => Box::new(BufReader::new(DecompressReader::new(reader, codec)?)),
+            None => Box::new(reader),
+        })
+    }
+}
+
+impl<R: Read> PolymorphReadExt for R {}
+
+/// Adapters for any writer, including the lock types; see [`PolymorphReadExt`].
+pub trait PolymorphWriteExt: Write {
+    /// Count the bytes written.
+    fn counted(self) -> Counted<Self>
+    where
+        Self: Sized,
+    {
+        Counted::new(self)
+    }
+
+    /// Fail with `FileTooLarge` rather than write more than `limit` bytes.
+    fn limited(self, limit: u64) -> Limited<Self>
+    where
+        Self: Sized,
+    {
+        Limited::new(self, limit)
+    }
+
+    /// Hash the bytes written.
+    fn hashing(self, algorithm: HashAlgorithm) -> Hashing<Self>
+    where
+        Self: Sized,
+    {
+        Hashing::new(self, algorithm)
+    }
+
+    /// Write at most `bytes_per_sec` on average.
+    fn throttled(self, bytes_per_sec: u64) -> Throttled<Self>
+    where
+        Self: Sized,
+    {
+        Throttled::new(self, bytes_per_sec)
+    }
+
+    /// Compress with `codec`.
+    #[cfg(feature = "compress")]
+    fn compressed(self, codec: Codec) -> io::Result<CompressWriter<Self>>
+    where
+        Self: Sized + Send + 'static,
+    {
+        CompressWriter::new(self, codec)
+    }
+}
+
+impl<W: Write> PolymorphWriteExt for W {}
+
+/// Reader or writer failing once more than a set number of bytes pass through it.
+pub struct Limited<T> {
+    inner: T,
+    remaining: u64,
+    limit: u64,
+}
+
+impl<T> Limited<T> {
+    pub fn new(inner: T, limit: u64) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+            limit,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn too_large(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::FileTooLarge,
+            format!("more than {} bytes", self.limit),
+        )
+    }
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // One byte past the limit, to find out whether there's more.
+        let max = buf.len().min(self.remaining.saturating_add(1) as usize);
+        let amt = self.inner.read(&mut buf[..max])?;
+        if amt as u64 > self.remaining {
+            self.remaining = 0;
+            return Err(self.too_large());
+        }
+        self.remaining -= amt as u64;
+        Ok(amt)
+    }
+}
+
+impl<R: BufRead> BufRead for Limited<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let remaining = self.remaining;
+        if remaining == 0 && !self.inner.fill_buf()?.is_empty() {
+            return Err(self.too_large());
+        }
+        let buf = self.inner.fill_buf()?;
+        Ok(&buf[..buf.len().min(remaining as usize)])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.remaining -= amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+impl<W: Write> Write for Limited<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.remaining == 0 && !buf.is_empty() {
+            return Err(self.too_large());
+        }
+        let max = buf.len().min(self.remaining as usize);
+        let amt = self.inner.write(&buf[..max])?;
+        self.remaining -= amt as u64;
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Hash functions for [`Hashing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    /// CRC-32 (IEEE), as used by zip and gzip. Catches corruption, but not tampering.
+    Crc32,
+}
+
+#[derive(Clone)]
+enum Hasher {
+    Sha256(Sha256),
+    Crc32(Crc32),
+}
+
+/// Reader or writer hashing the bytes passing through it.
+pub struct Hashing<T> {
+    inner: T,
+    hasher: Hasher,
+}
+
+impl<T> Hashing<T> {
+    pub fn new(inner: T, algorithm: HashAlgorithm) -> Self {
+        let hasher = match algorithm {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Crc32 => Hasher::Crc32(Crc32::new()),
+        };
+        Self { inner, hasher }
+    }
+
+    /// The hash of the bytes so far; a CRC-32 is big-endian.
+    pub fn digest(&self) -> Vec<u8> {
+        match &self.hasher {
+            Hasher::Sha256(sha) => sha.clone().finish().to_vec(),
+            Hasher::Crc32(crc) => crc.finish().to_be_bytes().to_vec(),
+        }
+    }
+
+    /// [`digest`](Hashing::digest) in lowercase hexadecimal, as `sha256sum` prints it.
+    pub fn hex_digest(&self) -> String {
+        to_hex(&self.digest())
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match &mut self.hasher {
+            Hasher::Sha256(sha) => sha.update(data),
+            Hasher::Crc32(crc) => crc.update(data),
+        }
+    }
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.inner.read(buf)?;
+        self.update(&buf[..amt]);
+        Ok(amt)
+    }
+}
+
+impl<R: BufRead> BufRead for Hashing<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The consumed bytes are still in the inner buffer, so filling it again is free.
+        if let Ok(buf) = self.inner.fill_buf() {
+            let consumed = &buf[..amt.min(buf.len())];
+            match &mut self.hasher {
+                Hasher::Sha256(sha) => sha.update(consumed),
+                Hasher::Crc32(crc) => crc.update(consumed),
+            }
+        }
+        self.inner.consume(amt);
+    }
+}
+
+impl<W: Write> Write for Hashing<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amt = self.inner.write(buf)?;
+        self.update(&buf[..amt]);
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader or writer sleeping as needed to keep to an average rate.
+///
+/// The rate is averaged from when the adapter was made, and single reads and writes are cut to
+/// a second's worth, so bursts stay short.
+pub struct Throttled<T> {
+    inner: T,
+    bytes_per_sec: u64,
+    start: Instant,
+    bytes: u64,
+}
+
+impl<T> Throttled<T> {
+    /// A rate of zero is taken as one byte per second.
+    pub fn new(inner: T, bytes_per_sec: u64) -> Self {
+        Self {
+            inner,
+            bytes_per_sec: bytes_per_sec.max(1),
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Wait until the bytes so far are due, and return how many may go next.
+    fn pace(&self, wanted: usize) -> usize {
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.bytes_per_sec as f64);
+        if let Some(wait) = due.checked_sub(self.start.elapsed()) {
+            thread::sleep(wait);
+        }
+        wanted.min(self.bytes_per_sec.min(usize::MAX as u64) as usize)
+    }
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = self.pace(buf.len());
+        let amt = self.inner.read(&mut buf[..max])?;
+        self.bytes += amt as u64;
+        Ok(amt)
+    }
+}
+
+impl<R: BufRead> BufRead for Throttled<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let max = self.pace(usize::MAX);
+        let buf = self.inner.fill_buf()?;
+        Ok(&buf[..buf.len().min(max)])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.bytes += amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+impl<W: Write> Write for Throttled<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let max = self.pace(buf.len());
+        let amt = self.inner.write(&buf[..max])?;
+        self.bytes += amt as u64;
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chained_adapters() -> Result<(), io::Error> {
+        let mut reader = &b"hello world"[..];
+        let mut reader = (&mut reader)
+            .limited(11)
+            .hashing(HashAlgorithm::Sha256)
+            .counted();
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        assert_eq!(line, "hello world");
+        assert_eq!(reader.count(), 11);
+        assert_eq!(
+            reader.get_ref().hex_digest(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+
+        let e = (&b"too long"[..])
+            .limited(3)
+            .read_to_end(&mut Vec::new())
+            .expect_err("over the limit");
+        assert_eq!(e.kind(), io::ErrorKind::FileTooLarge);
+
+        let mut writer = Vec::new().hashing(HashAlgorithm::Crc32).limited(4);
+        writer.write_all(b"1234")?;
+        assert!(writer.write_all(b"5").is_err());
+        assert_eq!(writer.get_ref().hex_digest(), "9be3e0a3");
+
+        let start = Instant::now();
+        let mut writer = Vec::new().throttled(1000);
+        writer.write_all(&[0; 100])?;
+        writer.write_all(&[0; 1])?;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+        assert_eq!(writer.into_inner().len(), 101);
+        Ok(())
+    }
+}
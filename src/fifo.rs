@@ -0,0 +1,310 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// How [`InputOptions`](crate::InputOptions) and [`OutputOptions`](crate::OutputOptions) open
+/// named pipes (FIFOs).
+///
+/// Opening a FIFO normally blocks until the other end is opened too, which can be forever. With
+/// a timeout the open fails with `TimedOut` instead; with `nonblocking` the file is left in
+/// non-blocking mode, so reads and writes fail with `WouldBlock` rather than wait.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct FifoOptions {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) nonblocking: bool,
+}
+
+impl FifoOptions {
+    /// Open `path` with `open`, which is passed extra `open(2)` flags to add.
+    #[cfg(unix)]
+    pub(crate) fn open<F>(&self, path: &Path, write: bool, open: F) -> io::Result<File>
+    where
+        F: Fn(i32) -> io::Result<File>,
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if *self == Self::default() {
+            return open(0);
+        }
+        let is_fifo = path.metadata().is_ok_and(|meta| meta.file_type().is_fifo());
+        if !is_fifo {
+            return open(if self.nonblocking {
+                libc::O_NONBLOCK
+            } else {
+                0
+            });
+        }
+
+        // Opening with `O_NONBLOCK` doesn't wait for the other end: for reading it succeeds right
+        // away, and for writing it fails with `ENXIO` while there's no reader.
+        let file = if write {
+            self.open_writer(path, open)?
+        } else {
+            let file = open(libc::O_NONBLOCK)?;
+            if let Some(timeout) = self.timeout {
+                wait_for_writer(&file, path, timeout)?;
+            }
+            file
+        };
+        if !self.nonblocking {
+            set_nonblocking(&file, false)?;
+        }
+        Ok(file)
+    }
+
+    /// Open `path` with `open`, unless it's a Windows named pipe, which is connected to
+    /// directly, waiting for the server while all its pipe instances are busy.
+    #[cfg(not(unix))]
+    pub(crate) fn open<F>(&self, path: &Path, write: bool, open: F) -> io::Result<File>
+    where
+        F: Fn(i32) -> io::Result<File>,
+    {
+        #[cfg(windows)]
+        if is_named_pipe(path) {
+            return connect_pipe(path, write, self.timeout);
+        }
+        let _ = (path, write);
+        open(0)
+    }
+
+    #[cfg(unix)]
+    fn open_writer<F>(&self, path: &Path, open: F) -> io::Result<File>
+    where
+        F: Fn(i32) -> io::Result<File>,
+    {
+        let deadline = self
+            .timeout
+            .map(|timeout| std::time::Instant::now() + timeout);
+        loop {
+            match open(libc::O_NONBLOCK) {
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => match deadline {
+                    Some(deadline) if std::time::Instant::now() < deadline => {
+                        std::thread::sleep(Duration::from_millis(10));
+                    }
+                    Some(_) => return Err(timed_out(path, "reader")),
+                    None => return Err(io::ErrorKind::WouldBlock.into()),
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn wait_for_writer(file: &File, path: &Path, timeout: Duration) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut poll = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let millis = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    loop {
+        // SAFETY: `poll` is one valid pollfd.
+        match unsafe { libc::poll(&mut poll, 1, millis) } {
+            0 => return Err(timed_out(path, "writer")),
+            n if n > 0 => return Ok(()),
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_nonblocking(file: &File, yes: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    // SAFETY: F_GETFL and F_SETFL only read and change the descriptor's status flags.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        let flags = if yes {
+            flags | libc::O_NONBLOCK
+        } else {
+            flags & !libc::O_NONBLOCK
+        };
+        if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` names a Windows named pipe: `\\.\pipe\NAME`, or `\\SERVER\pipe\NAME` on
+/// another machine.
+#[cfg(any(windows, test))]
+fn is_named_pipe(path: &Path) -> bool {
+    let path = path.to_string_lossy().replace('/', "\\");
+    let mut parts = match path.strip_prefix("\\\\") {
+        Some(rest) => rest.split('\\'),
+        None => return false,
+    };
+    let (server, share, name) = (parts.next(), parts.next(), parts.next());
+    server.is_some_and(|server| !server.is_empty())
+        && share.is_some_and(|share| share.eq_ignore_ascii_case("pipe"))
+        && name.is_some_and(|name| !name.is_empty())
+}
+
+/// Connect to the named pipe at `path` as a client.
+///
+/// Pipes can only be opened, never created or truncated, by clients. While every instance of
+/// the pipe is busy with another client, wait for one to be free, up to `timeout` or else the
+/// server's default timeout.
+#[cfg(windows)]
+fn connect_pipe(path: &Path, write: bool, timeout: Option<Duration>) -> io::Result<File> {
+    use std::{fs::OpenOptions, os::windows::ffi::OsStrExt, time::Instant};
+
+    const ERROR_PIPE_BUSY: i32 = 231;
+    const ERROR_SEM_TIMEOUT: i32 = 121;
+    const NMPWAIT_USE_DEFAULT_WAIT: u32 = 0;
+    extern "system" {
+        fn WaitNamedPipeW(name: *const u16, timeout: u32) -> i32;
+    }
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        match OpenOptions::new().read(!write).write(write).open(path) {
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                let wait = match deadline {
+                    Some(deadline) => {
+                        let left = deadline.saturating_duration_since(Instant::now());
+                        if left.is_zero() {
+                            return Err(timed_out(path, "pipe instance"));
+                        }
+                        left.as_millis().clamp(1, u32::MAX as u128 - 1) as u32
+                    }
+                    None => NMPWAIT_USE_DEFAULT_WAIT,
+                };
+                // SAFETY: `wide` is a NUL-terminated UTF-16 string.
+                if unsafe { WaitNamedPipeW(wide.as_ptr(), wait) } == 0 {
+                    let e = io::Error::last_os_error();
+                    return Err(match e.raw_os_error() {
+                        Some(ERROR_SEM_TIMEOUT) => timed_out(path, "pipe instance"),
+                        _ => e,
+                    });
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(any(unix, windows))]
+fn timed_out(path: &Path, peer: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("no {} opened {}", peer, path.display()),
+    )
+}
+
+/// Reader for a named pipe that outlives its writers.
+///
+/// A FIFO reads as ended once its writer closes it. Instead of ending, this reopens the FIFO
+/// and waits for the next writer, for long-running consumers fed by one short-lived writer
+/// after another.
+pub struct FifoReader {
+    path: PathBuf,
+    file: File,
+}
+
+impl FifoReader {
+    /// Open the FIFO at `path`, waiting for a writer.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let file = File::open(&path)?;
+        Ok(Self { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Read for FifoReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let amt = self.file.read(buf)?;
+            if amt > 0 || buf.is_empty() {
+                return Ok(amt);
+            }
+            self.file = File::open(&self.path)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognize_named_pipes() {
+        assert!(is_named_pipe(Path::new(r"\\.\pipe\server")));
+        assert!(is_named_pipe(Path::new(r"\\host\PIPE\server")));
+        assert!(is_named_pipe(Path::new("//./pipe/server")));
+        assert!(!is_named_pipe(Path::new(r"\\.\pipe\")));
+        assert!(!is_named_pipe(Path::new(r"\\host\share\pipe")));
+        assert!(!is_named_pipe(Path::new("pipe/server")));
+    }
+
+    #[cfg(unix)]
+    fn mkfifo(path: &Path) -> io::Result<()> {
+        use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        // SAFETY: `c_path` is a valid C string.
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fifo_timeouts_and_reopening() -> Result<(), io::Error> {
+        use crate::{InputOptions, OutputOptions};
+        use std::{io::Write, thread};
+        use tempfile::TempDir;
+
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("fifo");
+        mkfifo(&path)?;
+
+        let timeout = Duration::from_millis(20);
+        let e = OutputOptions::new().fifo_timeout(timeout).open(&path).err();
+        assert_eq!(e.map(|e| e.kind()), Some(io::ErrorKind::TimedOut));
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let e = InputOptions::new().fifo_timeout(timeout).open(&path).err();
+            assert_eq!(e.map(|e| e.kind()), Some(io::ErrorKind::TimedOut));
+        }
+
+        let writer_path = path.clone();
+        let writers = thread::spawn(move || -> io::Result<()> {
+            for message in ["one ", "two"] {
+                let options = OutputOptions::new().fifo_timeout(Duration::from_secs(10));
+                options
+                    .open(&writer_path)?
+                    .lock()
+                    .write_all(message.as_bytes())?;
+            }
+            Ok(())
+        });
+        let mut reader = FifoReader::open(&path)?;
+        let mut content = [0; 7];
+        reader.read_exact(&mut content)?;
+        assert_eq!(&content, b"one two");
+        writers.join().expect("writers don't panic")?;
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    io::{self, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    thread::{self, JoinHandle},
+};
+
+/// A command-line tool that data is piped through, such as `gzip -c`.
+pub(crate) struct Filter {
+    program: &'static str,
+    command: Command,
+}
+
+/// Writer piping everything written to it through a [`Filter`] into `W`.
+pub(crate) struct FilterWriter<W> {
+    program: &'static str,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    pump: Option<JoinHandle<io::Result<W>>>,
+}
+
+/// Reader piping the data read from another reader through a [`Filter`].
+pub(crate) struct FilterReader {
+    program: &'static str,
+    child: Child,
+    stdout: ChildStdout,
+    pump: Option<JoinHandle<io::Result<()>>>,
+    done: bool,
+}
+
+impl Filter {
+    pub(crate) fn new(program: &'static str) -> Self {
+        Self {
+            program,
+            command: Command::new(program),
+        }
+    }
+
+    pub(crate) fn arg<S: AsRef<std::ffi::OsStr>>(mut self, arg: S) -> Self {
+        self.command.arg(arg);
+        self
+    }
+
+    fn spawn(mut self) -> io::Result<(&'static str, Child)> {
+        let program = self.program;
+        let child = self
+            .command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("failed to run `{}`: {}", program, e)))?;
+        Ok((program, child))
+    }
+}
+
+fn wait(program: &str, child: &mut Child) -> io::Result<()> {
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("`{}` failed: {}", program, status),
+        ))
+    }
+}
+
+impl<W: Write + Send + 'static> FilterWriter<W> {
+    pub(crate) fn new(inner: W, filter: Filter) -> io::Result<Self> {
+        let (program, mut child) = filter.spawn()?;
+        let stdin = child.stdin.take();
+        let mut stdout = child.stdout.take().expect("filter stdout is piped");
+        let pump = thread::spawn(move || {
+            let mut inner = inner;
+            io::copy(&mut stdout, &mut inner)?;
+            inner.flush()?;
+            Ok(inner)
+        });
+        Ok(Self {
+            program,
+            child,
+            stdin,
+            pump: Some(pump),
+        })
+    }
+
+    /// Close the tool's input, wait for it to finish and return the inner writer.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        drop(self.stdin.take());
+        let inner = self
+            .pump
+            .take()
+            .expect("filter is only finished once")
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("filter output thread panicked")));
+        wait(self.program, &mut self.child)?;
+        inner
+    }
+}
+
+impl<W> Write for FilterWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdin.as_mut().expect("writer not finished").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.as_mut().expect("writer not finished").flush()
+    }
+}
+
+impl<W> Drop for FilterWriter<W> {
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        if let Some(pump) = self.pump.take() {
+            let _ = pump.join();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+impl FilterReader {
+    pub(crate) fn new<R: Read + Send + 'static>(inner: R, filter: Filter) -> io::Result<Self> {
+        let (program, mut child) = filter.spawn()?;
+        let mut stdin = child.stdin.take().expect("filter stdin is piped");
+        let stdout = child.stdout.take().expect("filter stdout is piped");
+        let pump = thread::spawn(move || {
+            let mut inner = inner;
+            match io::copy(&mut inner, &mut stdin) {
+                // The tool may stop reading early, e.g. after trailing garbage.
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                result => result.map(|_| ()),
+            }
+        });
+        Ok(Self {
+            program,
+            child,
+            stdout,
+            pump: Some(pump),
+            done: false,
+        })
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.done = true;
+        if let Some(pump) = self.pump.take() {
+            pump.join()
+                .unwrap_or_else(|_| Err(io::Error::other("filter input thread panicked")))?;
+        }
+        wait(self.program, &mut self.child)
+    }
+}
+
+impl Read for FilterReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done {
+            return Ok(0);
+        }
+        let amt = self.stdout.read(buf)?;
+        if amt == 0 && !buf.is_empty() {
+            self.finish()?;
+        }
+        Ok(amt)
+    }
+}
+
+impl Drop for FilterReader {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
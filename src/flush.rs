@@ -0,0 +1,150 @@
+use crate::{instrument, FileOrStdoutLock, OutputLock};
+use std::{
+    error, fmt,
+    io::{self, BufWriter, Write},
+    mem,
+};
+
+/// Failure to write out an output's buffer, carrying the bytes that never made it.
+///
+/// Converting it into an `io::Error` keeps it as the inner error, so callers that only see the
+/// `io::Error` (e.g. from [`AtomicOutput::commit`](crate::AtomicOutput::commit)) can still get
+/// it back with `get_ref()` and `downcast_ref`.
+#[derive(Debug)]
+pub struct FlushError {
+    error: io::Error,
+    unwritten: Vec<u8>,
+}
+
+impl FlushError {
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// The buffered data that wasn't written, in order. Everything before it was written.
+    pub fn unwritten(&self) -> &[u8] {
+        &self.unwritten
+    }
+
+    pub fn into_parts(self) -> (io::Error, Vec<u8>) {
+        (self.error, self.unwritten)
+    }
+}
+
+impl fmt::Display for FlushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} buffered bytes not written)",
+            self.error,
+            self.unwritten.len()
+        )
+    }
+}
+
+impl error::Error for FlushError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<FlushError> for io::Error {
+    fn from(e: FlushError) -> Self {
+        io::Error::new(e.error.kind(), e)
+    }
+}
+
+/// Flush `writer` and unwrap it, or hand back what's left in its buffer.
+///
+/// Unlike `BufWriter::into_inner`, a failure doesn't leave the data in a writer that tries to
+/// write it again when dropped.
+pub(crate) fn into_inner<W: Write>(mut writer: BufWriter<W>) -> Result<W, FlushError> {
+    match writer.flush() {
+        Ok(()) => Ok(writer.into_parts().0),
+        Err(error) => {
+            let (_, unwritten) = writer.into_parts();
+            Err(FlushError {
+                error,
+                // Only a writer that panicked mid-write loses track of its buffer.
+                unwritten: unwritten.unwrap_or_default(),
+            })
+        }
+    }
+}
+
+impl<'a> FileOrStdoutLock<'a> {
+    /// Flush and release the output, returning any data that couldn't be written.
+    ///
+    /// Dropping the lock flushes too, but ignores errors. Line-buffered file output can't give up
+    /// its buffer, so its [`unwritten`](FlushError::unwritten) data is always empty.
+    pub fn finish(mut self) -> Result<(), FlushError> {
+        let key = self.key();
+        // Swap in an empty writer to unwrap the real one, since the lock still closes on drop.
+        let result = match &mut self.inner {
+            OutputLock::FileBufWriter(writer) => {
+                let file = *writer.get_ref();
+                into_inner(mem::replace(writer, BufWriter::with_capacity(0, file))).map(drop)
+            }
+            OutputLock::StdoutBufWriter(writer) => {
+                let empty = BufWriter::with_capacity(0, io::stdout().lock());
+                into_inner(mem::replace(writer, empty)).map(drop)
+            }
+            _ => {
+                return self.flush().map_err(|error| FlushError {
+                    error,
+                    unwritten: Vec::new(),
+                })
+            }
+        };
+        instrument::flushed(key);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accepts `room` bytes, then fails.
+    struct Full {
+        written: Vec<u8>,
+        room: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"));
+            }
+            let amt = buf.len().min(self.room);
+            self.written.extend_from_slice(&buf[..amt]);
+            self.room -= amt;
+            Ok(amt)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn unwritten_bytes_on_failure() -> Result<(), io::Error> {
+        let mut writer = BufWriter::new(Full {
+            written: Vec::new(),
+            room: 4,
+        });
+        writer.write_all(b"abcdefgh")?;
+        let e = into_inner(writer).err().expect("the writer is full");
+        assert_eq!(e.unwritten(), b"efgh");
+        assert_eq!(e.error().kind(), io::ErrorKind::StorageFull);
+
+        let e = io::Error::from(e);
+        assert_eq!(e.kind(), io::ErrorKind::StorageFull);
+        let inner = e.get_ref().and_then(|e| e.downcast_ref::<FlushError>());
+        assert_eq!(inner.map(FlushError::unwritten), Some(&b"efgh"[..]));
+
+        let written = into_inner(BufWriter::new(Vec::new()))?;
+        assert!(written.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::{self, Write};
+
+/// Writer that wraps everything written in a fixed prologue and epilogue.
+///
+/// The prologue is written just before the first data, and the epilogue by
+/// [`finish`](Framed::finish) (or on drop, ignoring errors). Each is written exactly once no
+/// matter how often the writer is flushed; output with no data still gets both, so e.g. a JSON
+/// array framed by `[` and `]` is never left unterminated.
+pub struct Framed<W: Write> {
+    inner: Option<W>,
+    prologue: Vec<u8>,
+    epilogue: Vec<u8>,
+    started: bool,
+    finished: bool,
+}
+
+impl<W: Write> Framed<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            prologue: Vec::new(),
+            epilogue: Vec::new(),
+            started: false,
+            finished: false,
+        }
+    }
+
+    pub fn prologue<B: Into<Vec<u8>>>(mut self, prologue: B) -> Self {
+        self.prologue = prologue.into();
+        self
+    }
+
+    pub fn epilogue<B: Into<Vec<u8>>>(mut self, epilogue: B) -> Self {
+        self.epilogue = epilogue.into();
+        self
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner
+            .as_ref()
+            .expect("inner writer is present until finished")
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner
+            .as_mut()
+            .expect("inner writer is present until finished")
+    }
+
+    /// Write the epilogue (and the prologue, if nothing was written), flush, and return the
+    /// inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_epilogue()?;
+        Ok(self
+            .inner
+            .take()
+            .expect("inner writer is present until finished"))
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        if !self.started {
+            let prologue = &self.prologue;
+            self.inner.as_mut().unwrap().write_all(prologue)?;
+            self.started = true;
+        }
+        Ok(())
+    }
+
+    fn write_epilogue(&mut self) -> io::Result<()> {
+        // Never retried, even if it fails, so the epilogue can't end up written twice.
+        self.finished = true;
+        self.start()?;
+        let inner = self.inner.as_mut().unwrap();
+        inner.write_all(&self.epilogue)?;
+        inner.flush()
+    }
+}
+
+impl<W: Write> Write for Framed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.start()?;
+        self.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for Framed<W> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.write_epilogue();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prologue_and_epilogue_once() -> Result<(), io::Error> {
+        let mut framed = Framed::new(Vec::new()).prologue("[").epilogue("]\n");
+        framed.flush()?;
+        framed.write_all(b"1,")?;
+        framed.flush()?;
+        framed.write_all(b"2")?;
+        assert_eq!(framed.finish()?, b"[1,2]\n");
+
+        let empty = Framed::new(Vec::new()).prologue("[").epilogue("]");
+        assert_eq!(empty.finish()?, b"[]");
+
+        let mut dropped = Vec::new();
+        {
+            let mut framed = Framed::new(&mut dropped).prologue("id,name\n");
+            framed.write_all(b"1,a\n")?;
+        }
+        assert_eq!(dropped, b"id,name\n1,a\n");
+        Ok(())
+    }
+}
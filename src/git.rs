@@ -0,0 +1,74 @@
+use crate::{command::CommandReader, temp, FileOrStdin};
+use std::{io, path::Path, process::Command};
+
+/// Prefix of input specs naming a file at a git revision: `git:REV:PATH`.
+pub const GIT_PREFIX: &str = "git:";
+
+/// Whether `path` is a `git:REV:PATH` spec.
+pub fn is_git_spec<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().to_string_lossy().starts_with(GIT_PREFIX)
+}
+
+impl FileOrStdin {
+    /// Open a file's content at a git revision, given as `git:REV:PATH`.
+    ///
+    /// `REV` is anything `git` takes, like `HEAD`, `main~2` or a tag, and `PATH` is relative to
+    /// the repository root, or to the current directory when it starts with `./`. Uses the
+    /// system's `git` in the current directory's repository. The content is copied to a
+    /// temporary file, so it's read like any other file; [`FileOrStdin::from_path`] opens these
+    /// specs too.
+    pub fn from_git_spec(spec: &str) -> io::Result<Self> {
+        let object = object_name(spec)?;
+        let mut command = Command::new("git");
+        command.args(["cat-file", "blob", object]);
+        let mut reader = CommandReader::new("git", command)?;
+        temp::spool(&mut reader)
+    }
+}
+
+/// The `REV:PATH` object name for `git:REV:PATH`.
+fn object_name(spec: &str) -> io::Result<&str> {
+    spec.strip_prefix(GIT_PREFIX)
+        .filter(|object| {
+            object
+                .split_once(':')
+                .is_some_and(|(rev, path)| !rev.is_empty() && !path.is_empty())
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected `git:REV:PATH`, got `{}`", spec),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn read_committed_content() -> Result<(), io::Error> {
+        assert!(is_git_spec("git:HEAD:README.md"));
+        assert_eq!(object_name("git:v1.0:src/lib.rs")?, "v1.0:src/lib.rs");
+        for spec in ["git:HEAD", "git::README.md", "git:HEAD:", "HEAD:README.md"] {
+            let e = object_name(spec).expect_err("spec is invalid");
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        // Only when the tests run from a checkout with `git` installed.
+        let in_checkout = Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet", "HEAD:Cargo.toml"])
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if in_checkout {
+            let mut content = String::new();
+            FileOrStdin::from_path("git:HEAD:Cargo.toml")?
+                .lock()
+                .read_to_string(&mut content)?;
+            assert!(content.contains("[package]"));
+            assert!(FileOrStdin::from_git_spec("git:HEAD:no/such/file").is_err());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use crate::glob::Pattern;
+use std::{fs, io, path::Path};
+
+/// The rules of one `.gitignore` file.
+pub(crate) struct GitIgnore {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    pattern: Pattern,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl GitIgnore {
+    /// Load `dir/.gitignore`, if there is one.
+    pub(crate) fn load(dir: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(dir.join(".gitignore")) {
+            Ok(content) => Ok(Some(Self::parse(&content))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub(crate) fn parse(content: &str) -> Self {
+        let rules = content
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim_end();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                // Patterns without a slash match at any depth; others are relative to the file.
+                let pattern = match line.strip_prefix('/') {
+                    Some(anchored) => anchored.to_owned(),
+                    None if line.contains('/') => line.to_owned(),
+                    None => format!("**/{}", line),
+                };
+                Some(Rule {
+                    pattern: Pattern::new(&pattern),
+                    negated,
+                    dir_only,
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether `path`, relative to the `.gitignore`'s directory, is ignored, or `None` if no rule
+    /// mentions it.
+    pub(crate) fn is_ignored(&self, path: &str, is_dir: bool) -> Option<bool> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.pattern.matches(path))
+            .map(|rule| !rule.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gitignore_rules() {
+        let ignore = GitIgnore::parse("# build output\n*.log\n!keep.log\n/target/\ndocs/*.html\n");
+        assert_eq!(ignore.is_ignored("a/b/debug.log", false), Some(true));
+        assert_eq!(ignore.is_ignored("keep.log", false), Some(false));
+        assert_eq!(ignore.is_ignored("target", true), Some(true));
+        assert_eq!(ignore.is_ignored("target", false), None);
+        assert_eq!(ignore.is_ignored("src/target", true), None);
+        assert_eq!(ignore.is_ignored("docs/index.html", false), Some(true));
+        assert_eq!(ignore.is_ignored("src/main.rs", false), None);
+    }
+}
@@ -0,0 +1,157 @@
+/// A compiled shell-style glob pattern, matched against `/`-separated paths.
+///
+/// `*` matches anything but `/`, `?` one character other than `/`, `[a-z]` (or `[!a-z]`) one
+/// character in (or not in) a set, and `**` any number of whole path segments. A backslash makes
+/// the next character literal. A `[` without a closing `]` is a literal.
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Char(char),
+    Any,
+    Star,
+    /// `**` on its own, matching anything including `/`.
+    AnyPath,
+    /// `**/`, matching nothing or anything ending in `/`.
+    AnyDirs,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Pattern {
+    pub(crate) fn new(pattern: &str) -> Self {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let token = match chars[i] {
+                '\\' if i + 1 < chars.len() => {
+                    i += 1;
+                    Token::Char(chars[i])
+                }
+                '?' => Token::Any,
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    let at_start = i == 0 || chars[i - 1] == '/';
+                    match chars.get(i + 2) {
+                        Some('/') if at_start => {
+                            i += 2;
+                            Token::AnyDirs
+                        }
+                        None if at_start => {
+                            i += 1;
+                            Token::AnyPath
+                        }
+                        // `**` inside a segment is just a `*`.
+                        _ => {
+                            i += 1;
+                            Token::Star
+                        }
+                    }
+                }
+                '*' => Token::Star,
+                '[' => match parse_class(&chars[i + 1..]) {
+                    Some((token, len)) => {
+                        i += len;
+                        token
+                    }
+                    None => Token::Char('['),
+                },
+                c => Token::Char(c),
+            };
+            tokens.push(token);
+            i += 1;
+        }
+        Self { tokens }
+    }
+
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        let text: Vec<char> = path.chars().collect();
+        let n = text.len();
+
+        // next[j]: whether tokens[i + 1..] match text[j..], for the `i` being computed.
+        let mut next = vec![false; n + 1];
+        next[n] = true;
+        for token in self.tokens.iter().rev() {
+            let mut current = vec![false; n + 1];
+            // Whether some `/` at or after `j` is followed by a match of the remaining tokens.
+            let mut dirs_match = false;
+            for j in (0..=n).rev() {
+                let c = text.get(j).copied();
+                let single = |ok: bool| ok && next[j + 1];
+                current[j] = match token {
+                    Token::Char(expected) => single(c == Some(*expected)),
+                    Token::Any => single(c.is_some_and(|c| c != '/')),
+                    Token::Class { negated, ranges } => single(c.is_some_and(|c| {
+                        c != '/'
+                            && ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *negated
+                    })),
+                    Token::Star => next[j] || (c.is_some_and(|c| c != '/') && current[j + 1]),
+                    Token::AnyPath => next[j] || (c.is_some() && current[j + 1]),
+                    Token::AnyDirs => {
+                        dirs_match |= c == Some('/') && next[j + 1];
+                        next[j] || dirs_match
+                    }
+                };
+            }
+            next = current;
+        }
+        next[0]
+    }
+}
+
+/// Parse the inside of a `[...]` class, returning it and the number of characters used
+/// including the closing `]`.
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        first = false;
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|c| *c != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matching() {
+        let matches = |pattern: &str, path: &str| Pattern::new(pattern).matches(path);
+        assert!(matches("*.rs", "lib.rs"));
+        assert!(!matches("*.rs", "src/lib.rs"));
+        assert!(matches("src/*.rs", "src/lib.rs"));
+        assert!(matches("**/*.rs", "lib.rs"));
+        assert!(matches("**/*.rs", "a/b/lib.rs"));
+        assert!(matches("data/**/*.json", "data/x.json"));
+        assert!(matches("data/**/*.json", "data/a/b/x.json"));
+        assert!(!matches("data/**/*.json", "other/x.json"));
+        assert!(matches("target/**", "target/debug/app"));
+        assert!(matches("file?.[ch]", "file1.c"));
+        assert!(!matches("file?.[!ch]", "file1.c"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches("[", "["));
+        assert!(matches("", ""));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{report::Counted, Error, OutputOptions};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// What a [`GuardedOutput`] does with its partly written file when writing fails, or when it is
+/// dropped without being finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartialPolicy {
+    /// Leave what was written, e.g. so a log can be inspected.
+    #[default]
+    Keep,
+    /// Cut the file to zero length, keeping the file itself and its permissions.
+    Truncate,
+    /// Remove the file.
+    Delete,
+}
+
+/// File output that cleans up after itself if it doesn't complete, for files that are written
+/// in place rather than atomically (see [`AtomicOutput`](crate::AtomicOutput) for that).
+///
+/// Errors while writing apply the [`PartialPolicy`] right away and come as an
+/// [`Error::Incomplete`], which records what was done, so callers can tell users exactly what is
+/// left on disk. The output is unusable after that. The path `-` writes to stdout, where there
+/// is nothing to clean up.
+pub struct GuardedOutput {
+    target: Target,
+    policy: PartialPolicy,
+}
+
+enum Target {
+    File {
+        path: PathBuf,
+        /// `None` once finished, or once writing failed and the policy was applied.
+        writer: Option<BufWriter<Counted<File>>>,
+    },
+    Stdout(io::Stdout),
+}
+
+impl GuardedOutput {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        OutputOptions::new().open_guarded(path)
+    }
+
+    /// Defaults to [`PartialPolicy::Keep`].
+    pub fn on_partial(mut self, policy: PartialPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The file being written, or `None` for stdout.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.target {
+            Target::File { path, .. } => Some(path),
+            Target::Stdout(_) => None,
+        }
+    }
+
+    /// Flush everything written and close the file, keeping it as complete.
+    pub fn finish(mut self) -> io::Result<()> {
+        let result = match &mut self.target {
+            Target::File {
+                writer: Some(writer),
+                ..
+            } => writer
+                .flush()
+                .and_then(|()| writer.get_ref().get_ref().sync_all()),
+            _ => return self.writer()?.flush(),
+        };
+        match result {
+            Ok(()) => {
+                if let Target::File { writer, .. } = &mut self.target {
+                    *writer = None;
+                }
+                Ok(())
+            }
+            Err(e) => Err(self.fail(e)),
+        }
+    }
+
+    /// Apply the policy to the partial file, and wrap `e` in an [`Error::Incomplete`] saying so.
+    fn fail(&mut self, e: io::Error) -> io::Error {
+        let writer = match &mut self.target {
+            Target::File { writer, .. } => writer.take(),
+            Target::Stdout(_) => return e,
+        };
+        let (path, writer) = match (self.path(), writer) {
+            (Some(path), Some(writer)) => (path.to_owned(), writer),
+            // Failed before, and already cleaned up.
+            _ => return e,
+        };
+        let (file, _) = writer.into_parts();
+        // If cleaning up fails too, the partial file is still there.
+        let policy = match clean_up(&path, file.get_ref(), self.policy) {
+            Ok(()) => self.policy,
+            Err(_) => PartialPolicy::Keep,
+        };
+        Error::Incomplete {
+            path,
+            written: file.count,
+            policy,
+            source: e,
+        }
+        .into()
+    }
+
+    fn writer(&mut self) -> io::Result<&mut dyn Write> {
+        match &mut self.target {
+            Target::File {
+                writer: Some(writer),
+                ..
+            } => Ok(writer),
+            Target::File { path, .. } => Err(io::Error::other(format!(
+                "{} was abandoned after an earlier error",
+                path.display()
+            ))),
+            Target::Stdout(stdout) => Ok(stdout),
+        }
+    }
+}
+
+impl OutputOptions {
+    /// Open `path` like [`open`](OutputOptions::open), as a [`GuardedOutput`].
+    pub fn open_guarded<P: AsRef<Path>>(&self, path: P) -> io::Result<GuardedOutput> {
+        let path = path.as_ref();
+        let target = match self.open(path)?.into_inner() {
+            Ok(file) => Target::File {
+                path: path.to_owned(),
+                writer: Some(BufWriter::new(Counted::new(file))),
+            },
+            Err(stdout) => Target::Stdout(stdout),
+        };
+        Ok(GuardedOutput {
+            target,
+            policy: PartialPolicy::default(),
+        })
+    }
+}
+
+impl Write for GuardedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.writer()?.write(buf);
+        result.map_err(|e| self.fail(e))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.writer()?.flush();
+        result.map_err(|e| self.fail(e))
+    }
+}
+
+impl Drop for GuardedOutput {
+    fn drop(&mut self) {
+        if let Target::File { path, writer } = &mut self.target {
+            if let Some(writer) = writer.take() {
+                // Unfinished, so the buffer is thrown away rather than added to the file.
+                let (file, _) = writer.into_parts();
+                let _ = clean_up(path, file.get_ref(), self.policy);
+            }
+        }
+    }
+}
+
+fn clean_up(path: &Path, file: &File, policy: PartialPolicy) -> io::Result<()> {
+    match policy {
+        PartialPolicy::Keep => Ok(()),
+        PartialPolicy::Truncate => file.set_len(0),
+        PartialPolicy::Delete => fs::remove_file(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn clean_up_partial_outputs() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("out.txt");
+
+        let mut output = GuardedOutput::create(&path)?.on_partial(PartialPolicy::Delete);
+        output.write_all(b"complete")?;
+        output.finish()?;
+        assert_eq!(fs::read_to_string(&path)?, "complete");
+
+        let mut output = GuardedOutput::create(&path)?.on_partial(PartialPolicy::Truncate);
+        output.write_all(b"partial")?;
+        output.flush()?;
+        drop(output);
+        assert_eq!(fs::read_to_string(&path)?, "");
+
+        let mut output = GuardedOutput::create(&path)?.on_partial(PartialPolicy::Delete);
+        output.write_all(b"partial")?;
+        drop(output);
+        assert!(!path.exists());
+
+        let mut output = GuardedOutput::create(&path)?;
+        output.write_all(b"kept")?;
+        output.flush()?;
+        output.write_all(b", but not this")?;
+        drop(output);
+        assert_eq!(fs::read_to_string(&path)?, "kept");
+
+        let mut output = GuardedOutput::create(&path)?.on_partial(PartialPolicy::Delete);
+        output.write_all(b"written")?;
+        output.flush()?;
+        let e = output.fail(io::ErrorKind::StorageFull.into());
+        assert!(matches!(
+            Error::of(&e),
+            Some(Error::Incomplete {
+                written: 7,
+                policy: PartialPolicy::Delete,
+                ..
+            })
+        ));
+        assert!(!path.exists());
+        assert!(output.write_all(b"more").is_err());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
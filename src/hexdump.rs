@@ -0,0 +1,146 @@
+use std::{
+    env,
+    io::{self, Write},
+};
+
+/// Environment variable that turns on [`HexdumpWriter::from_env`] when set to anything but `0`.
+pub const HEXDUMP_ENV: &str = "POLYMORPHIO_HEXDUMP";
+
+const LINE_LEN: usize = 16;
+
+/// Writer that passes everything through to `W` while mirroring it as a hexdump, by default to
+/// stderr.
+///
+/// Lines look like `hexdump -C`, with offsets counting from the first byte written. A partial
+/// last line is dumped on flush and drop. Errors writing the dump are ignored, so turning it on
+/// never changes what happens to the actual output.
+pub struct HexdumpWriter<W, D: Write = io::Stderr> {
+    inner: W,
+    dump: D,
+    enabled: bool,
+    offset: u64,
+    line: Vec<u8>,
+}
+
+impl<W: Write> HexdumpWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_dump(inner, io::stderr())
+    }
+
+    /// Dump to stderr only if `POLYMORPHIO_HEXDUMP` is set, for switching the dump on without a
+    /// rebuild.
+    pub fn from_env(inner: W) -> Self {
+        let enabled = env::var_os(HEXDUMP_ENV).is_some_and(|v| !v.is_empty() && v != "0");
+        Self::new(inner).enabled(enabled)
+    }
+}
+
+impl<W: Write, D: Write> HexdumpWriter<W, D> {
+    /// Mirror the data to `dump` instead of stderr.
+    pub fn with_dump(inner: W, dump: D) -> Self {
+        Self {
+            inner,
+            dump,
+            enabled: true,
+            offset: 0,
+            line: Vec::with_capacity(LINE_LEN),
+        }
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
+impl<W, D: Write> HexdumpWriter<W, D> {
+    fn dump_line(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+        let mut text = format!("{:08x}  ", self.offset);
+        for i in 0..LINE_LEN {
+            match self.line.get(i) {
+                Some(b) => text.push_str(&format!("{:02x} ", b)),
+                None => text.push_str("   "),
+            }
+            if i == LINE_LEN / 2 - 1 {
+                text.push(' ');
+            }
+        }
+        text.push_str(" |");
+        text.extend(self.line.iter().map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                *b as char
+            } else {
+                '.'
+            }
+        }));
+        text.push_str("|\n");
+        let _ = self.dump.write_all(text.as_bytes());
+
+        self.offset += self.line.len() as u64;
+        self.line.clear();
+    }
+}
+
+impl<W: Write, D: Write> Write for HexdumpWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amt = self.inner.write(buf)?;
+        if self.enabled {
+            for b in &buf[..amt] {
+                self.line.push(*b);
+                if self.line.len() == LINE_LEN {
+                    self.dump_line();
+                }
+            }
+        }
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.enabled {
+            self.dump_line();
+            let _ = self.dump.flush();
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W, D: Write> Drop for HexdumpWriter<W, D> {
+    fn drop(&mut self) {
+        if self.enabled {
+            self.dump_line();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_written_bytes() -> Result<(), io::Error> {
+        let mut dump = Vec::new();
+        let mut output = Vec::new();
+        {
+            let mut writer = HexdumpWriter::with_dump(&mut output, &mut dump);
+            writer.write_all(b"0123456789abcdef")?;
+            writer.write_all(b"Hello\n\x00")?;
+        }
+        assert_eq!(output, b"0123456789abcdefHello\n\x00");
+        assert_eq!(
+            String::from_utf8_lossy(&dump),
+            "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|\n\
+             00000010  48 65 6c 6c 6f 0a 00                              |Hello..|\n"
+        );
+
+        let mut dump = Vec::new();
+        let mut writer = HexdumpWriter::with_dump(Vec::new(), &mut dump).enabled(false);
+        writer.write_all(b"quiet")?;
+        writer.flush()?;
+        drop(writer);
+        assert!(dump.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+use std::io::{self, BufReader, Read};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which code length code lengths are stored in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Streaming decoder for raw DEFLATE data (RFC 1951), as used in zip archives and gzip.
+pub(crate) struct Inflate<R> {
+    bits: BitReader<R>,
+    window: Vec<u8>,
+    /// Total bytes output so far.
+    written: u64,
+    state: State,
+    last_block: bool,
+    literals: Huffman,
+    distances: Huffman,
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    BlockStart,
+    Stored(usize),
+    Codes,
+    Copy { len: usize, dist: usize },
+    Done,
+}
+
+enum Symbol {
+    Literal(u8),
+    EndOfBlock,
+    Copy { len: usize, dist: usize },
+}
+
+struct BitReader<R> {
+    inner: BufReader<R>,
+    buf: u32,
+    count: u32,
+}
+
+/// Canonical Huffman code, decoded one bit at a time.
+#[derive(Default)]
+struct Huffman {
+    /// Number of codes of each length.
+    counts: [u16; 16],
+    /// Symbols ordered by code.
+    symbols: Vec<u16>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid deflate data: {}", msg),
+    )
+}
+
+impl<R: Read> Inflate<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            bits: BitReader {
+                inner: BufReader::new(inner),
+                buf: 0,
+                count: 0,
+            },
+            window: vec![0; WINDOW_SIZE],
+            written: 0,
+            state: State::BlockStart,
+            last_block: false,
+            literals: Huffman::default(),
+            distances: Huffman::default(),
+        }
+    }
+
+    fn start_block(&mut self) -> io::Result<State> {
+        if self.last_block {
+            return Ok(State::Done);
+        }
+        self.last_block = self.bits.bits(1)? == 1;
+        match self.bits.bits(2)? {
+            0 => {
+                self.bits.align();
+                let len = self.bits.bits(16)?;
+                let nlen = self.bits.bits(16)?;
+                if len != !nlen & 0xffff {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                Ok(State::Stored(len as usize))
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                self.literals = Huffman::new(&lengths);
+                self.distances = Huffman::new(&[5; 30]);
+                Ok(State::Codes)
+            }
+            2 => {
+                self.read_dynamic_tables()?;
+                Ok(State::Codes)
+            }
+            _ => Err(invalid("reserved block type")),
+        }
+    }
+
+    fn read_dynamic_tables(&mut self) -> io::Result<()> {
+        let literal_count = self.bits.bits(5)? as usize + 257;
+        let distance_count = self.bits.bits(5)? as usize + 1;
+        let code_length_count = self.bits.bits(4)? as usize + 4;
+
+        let mut code_lengths = [0u8; 19];
+        for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_lengths[i] = self.bits.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_lengths);
+
+        let mut lengths = vec![0u8; literal_count + distance_count];
+        let mut i = 0;
+        while i < lengths.len() {
+            let (value, repeat) = match code_lengths.decode(&mut self.bits)? {
+                len @ 0..=15 => (len as u8, 1),
+                16 => {
+                    let previous = *i
+                        .checked_sub(1)
+                        .and_then(|p| lengths.get(p))
+                        .ok_or_else(|| invalid("repeat with no previous length"))?;
+                    (previous, 3 + self.bits.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.bits.bits(3)? as usize),
+                _ => (0, 11 + self.bits.bits(7)? as usize),
+            };
+            if i + repeat > lengths.len() {
+                return Err(invalid("too many code lengths"));
+            }
+            lengths[i..i + repeat].fill(value);
+            i += repeat;
+        }
+
+        self.literals = Huffman::new(&lengths[..literal_count]);
+        self.distances = Huffman::new(&lengths[literal_count..]);
+        Ok(())
+    }
+
+    fn decode_symbol(&mut self) -> io::Result<Symbol> {
+        let symbol = self.literals.decode(&mut self.bits)? as usize;
+        match symbol {
+            0..=255 => Ok(Symbol::Literal(symbol as u8)),
+            256 => Ok(Symbol::EndOfBlock),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(invalid("bad length symbol"));
+                }
+                let len =
+                    LENGTH_BASE[i] as usize + self.bits.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = self.distances.decode(&mut self.bits)? as usize;
+                if d >= DIST_BASE.len() {
+                    return Err(invalid("bad distance symbol"));
+                }
+                let dist = DIST_BASE[d] as usize + self.bits.bits(DIST_EXTRA[d] as u32)? as usize;
+                if dist as u64 > self.written {
+                    return Err(invalid("distance too far back"));
+                }
+                Ok(Symbol::Copy { len, dist })
+            }
+        }
+    }
+
+    /// Read the bytes following the deflate data, such as a gzip trailer, once it's all been
+    /// read.
+    #[cfg(feature = "compress")]
+    pub(crate) fn read_trailer(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.bits.align();
+        for byte in buf {
+            *byte = self.bits.bits(8)? as u8;
+        }
+        Ok(())
+    }
+
+    /// Record `byte` in the window and return it.
+    fn output(&mut self, byte: u8) -> u8 {
+        self.window[self.written as usize % WINDOW_SIZE] = byte;
+        self.written += 1;
+        byte
+    }
+}
+
+impl<R: Read> Read for Inflate<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            match self.state {
+                State::Done => break,
+                State::BlockStart => self.state = self.start_block()?,
+                State::Stored(0) => self.state = State::BlockStart,
+                State::Stored(remaining) => {
+                    let byte = self.bits.bits(8)? as u8;
+                    buf[n] = self.output(byte);
+                    n += 1;
+                    self.state = State::Stored(remaining - 1);
+                }
+                State::Codes => match self.decode_symbol()? {
+                    Symbol::Literal(byte) => {
+                        buf[n] = self.output(byte);
+                        n += 1;
+                    }
+                    Symbol::EndOfBlock => self.state = State::BlockStart,
+                    Symbol::Copy { len, dist } => self.state = State::Copy { len, dist },
+                },
+                State::Copy { len, dist } => {
+                    let byte =
+                        self.window[(self.written as usize + WINDOW_SIZE - dist) % WINDOW_SIZE];
+                    buf[n] = self.output(byte);
+                    n += 1;
+                    self.state = if len == 1 {
+                        State::Codes
+                    } else {
+                        State::Copy { len: len - 1, dist }
+                    };
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Read> BitReader<R> {
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let mut byte = [0];
+            self.inner.read_exact(&mut byte)?;
+            self.buf |= (byte[0] as u32) << self.count;
+            self.count += 8;
+        }
+        let value = if n == 32 {
+            self.buf
+        } else {
+            self.buf & ((1 << n) - 1)
+        };
+        self.buf = self.buf.checked_shr(n).unwrap_or(0);
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        let skip = self.count % 8;
+        self.buf >>= skip;
+        self.count -= skip;
+    }
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(invalid("bad Huffman code"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    /// Raw deflate data, made by stripping the gzip framing from `gzip`'s output.
+    fn deflate(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut child = Command::new("gzip")
+            .arg("-cn")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let input = data.to_vec();
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+        let output = child.wait_with_output()?;
+        writer.join().unwrap()?;
+        // 10-byte header (no name with -n), 8-byte trailer.
+        Ok(output.stdout[10..output.stdout.len() - 8].to_vec())
+    }
+
+    #[test]
+    fn inflate_gzip_output() -> Result<(), io::Error> {
+        let text = (0..2000)
+            .map(|n| format!("line {} of some repetitive text\n", n % 37))
+            .collect::<String>();
+        // Pseudo-random words, so matches reach back across the whole window.
+        let mut seed = 1u32;
+        let words = (0..100_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                format!("{:x} ", seed >> 20)
+            })
+            .collect::<String>();
+        for data in [&b""[..], b"a", text.as_bytes(), words.as_bytes()] {
+            let mut inflated = Vec::new();
+            Inflate::new(&deflate(data)?[..]).read_to_end(&mut inflated)?;
+            assert_eq!(inflated, data);
+        }
+
+        // Stored block: final, type 0, length 3.
+        let stored = [0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c'];
+        let mut inflated = Vec::new();
+        Inflate::new(&stored[..]).read_to_end(&mut inflated)?;
+        assert_eq!(inflated, b"abc");
+
+        assert!(Inflate::new(&[0x07u8][..])
+            .read_to_end(&mut Vec::new())
+            .is_err());
+        Ok(())
+    }
+}
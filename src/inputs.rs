@@ -0,0 +1,641 @@
+#[cfg(feature = "ignore")]
+use crate::gitignore::GitIgnore;
+use crate::glob::Pattern;
+use crate::{collide, STDIO_FILENAME};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+    vec,
+};
+
+#[cfg(feature = "glob")]
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// A lazily-expanded list of input paths.
+///
+/// Iterating yields the paths of the files to read, each of which can be opened with
+/// [`FileOrStdin::from_path`](crate::FileOrStdin::from_path). Errors, e.g. from reading a
+/// directory, are yielded in place of the paths they prevented from being listed.
+pub struct Inputs {
+    source: Source,
+    filters: Filters,
+    dedup: Option<Dedup>,
+}
+
+/// Files already yielded, for [`Inputs::dedup`].
+#[derive(Default)]
+struct Dedup {
+    seen: HashMap<collide::Identity, PathBuf>,
+    on_duplicate: Option<DuplicateHook>,
+}
+
+/// Called with a skipped duplicate and the path the file was first yielded as.
+type DuplicateHook = Box<dyn FnMut(&Path, &Path) + Send>;
+
+enum Source {
+    Dir(DirWalk),
+    List(PathList),
+    #[cfg(feature = "glob")]
+    Specs(SpecExpansion),
+}
+
+#[derive(Default)]
+struct Filters {
+    include: Vec<Filter>,
+    exclude: Vec<Filter>,
+    #[cfg(feature = "ignore")]
+    git_ignore: bool,
+}
+
+struct Filter {
+    pattern: Pattern,
+    /// Whether to match the whole relative path rather than just the file name.
+    whole_path: bool,
+}
+
+struct DirWalk {
+    root: PathBuf,
+    recursive: bool,
+    /// Glob the path relative to `root` must match, when expanding a pattern.
+    pattern: Option<Pattern>,
+    started: bool,
+    stack: Vec<Frame>,
+}
+
+/// Paths read one by one from a list.
+struct PathList {
+    reader: Box<dyn BufRead + Send>,
+    delim: u8,
+}
+
+/// Input specs being expanded one after the other.
+#[cfg(feature = "glob")]
+struct SpecExpansion {
+    specs: vec::IntoIter<String>,
+    /// The spec currently being expanded, its walk, and whether it matched anything yet.
+    current: Option<(String, DirWalk, bool)>,
+}
+
+/// A directory being listed.
+struct Frame {
+    /// Path relative to the root, `/`-separated, empty for the root itself.
+    relative: String,
+    entries: vec::IntoIter<Entry>,
+    #[cfg(feature = "ignore")]
+    ignore: Option<GitIgnore>,
+}
+
+struct Entry {
+    name: String,
+    is_dir: bool,
+}
+
+impl Inputs {
+    /// All files in the directory `path`, and with `recursive` in its subdirectories too.
+    ///
+    /// Files are yielded in a fixed order: sorted by name within each directory, with the
+    /// contents of subdirectories yielded where they sort. Symbolic links to directories are not
+    /// followed.
+    pub fn from_dir<P: AsRef<Path>>(path: P, recursive: bool) -> Self {
+        Self::new(Source::Dir(DirWalk::new(
+            path.as_ref().to_owned(),
+            recursive,
+            None,
+        )))
+    }
+
+    /// Paths listed in the file `path` (`-` for stdin), separated by `delim`: `b'\n'` for one per
+    /// line, or `b'\0'` for lists made with e.g. `find -print0`.
+    ///
+    /// The list is read as iteration goes, so a long list from a pipe is processed while it is
+    /// still being produced. Empty entries are skipped, as are carriage returns ending lines.
+    pub fn from_list<P: AsRef<Path>>(path: P, delim: u8) -> io::Result<Self> {
+        let path = path.as_ref();
+        let reader: Box<dyn BufRead + Send> = if path.to_string_lossy() == STDIO_FILENAME {
+            Box::new(BufReader::new(io::stdin()))
+        } else {
+            Box::new(BufReader::new(File::open(path)?))
+        };
+        Ok(Self::new(Source::List(PathList { reader, delim })))
+    }
+
+    /// Input specs as given on a command line, with glob patterns such as `*.log` or
+    /// `data/**/*.json` expanded to the files they match.
+    ///
+    /// Patterns use `/` as separator (on Windows, `\` too) and are expanded in the same fixed
+    /// order as [`from_dir`](Inputs::from_dir). A pattern that matches nothing yields a
+    /// `NotFound` error. Other specs, including `-`, are passed through unchanged.
+    #[cfg(feature = "glob")]
+    pub fn from_specs<I, S>(specs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let specs: Vec<String> = specs.into_iter().map(|s| s.as_ref().to_owned()).collect();
+        Self::new(Source::Specs(SpecExpansion {
+            specs: specs.into_iter(),
+            current: None,
+        }))
+    }
+
+    fn new(source: Source) -> Self {
+        Self {
+            source,
+            filters: Filters::default(),
+            dedup: None,
+        }
+    }
+
+    /// Only yield files matching the glob `pattern`. May be given several times.
+    ///
+    /// Patterns containing a `/` are matched against the path relative to the directory being
+    /// expanded; others only against the file name, so `*.rs` matches `src/lib.rs`.
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.filters.include.push(Filter::new(pattern));
+        self
+    }
+
+    /// Skip files, and whole directories, matching the glob `pattern`. May be given several
+    /// times, and takes precedence over [`include`](Inputs::include).
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.filters.exclude.push(Filter::new(pattern));
+        self
+    }
+
+    /// Skip files ignored by `.gitignore` files in the directories being expanded, and `.git`
+    /// directories themselves.
+    #[cfg(feature = "ignore")]
+    pub fn git_ignore(mut self, yes: bool) -> Self {
+        self.filters.git_ignore = yes;
+        self
+    }
+
+    /// Skip files that were already yielded under another path, e.g. given twice or reached
+    /// through a symlink.
+    ///
+    /// Files are compared by identity (device and inode on unix, the resolved path elsewhere).
+    /// `-` is never skipped.
+    pub fn dedup(mut self, yes: bool) -> Self {
+        self.dedup = if yes { Some(Dedup::default()) } else { None };
+        self
+    }
+
+    /// Skip duplicates as with [`dedup`](Inputs::dedup), calling `f` with each skipped path and
+    /// the path the file was first yielded as, e.g. to warn about it.
+    pub fn on_duplicate<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(&Path, &Path) + Send + 'static,
+    {
+        self.dedup.get_or_insert_with(Dedup::default).on_duplicate = Some(Box::new(f));
+        self
+    }
+
+    fn next_path(&mut self) -> Option<io::Result<PathBuf>> {
+        match &mut self.source {
+            Source::Dir(walk) => walk.next(&self.filters),
+            Source::List(list) => list.next(&self.filters),
+            #[cfg(feature = "glob")]
+            Source::Specs(expansion) => expansion.next(&self.filters),
+        }
+    }
+}
+
+impl Iterator for Inputs {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let path = match self.next_path()? {
+                Ok(path) => path,
+                Err(e) => return Some(Err(e)),
+            };
+            if !self
+                .dedup
+                .as_mut()
+                .is_some_and(|dedup| dedup.is_duplicate(&path))
+            {
+                return Some(Ok(path));
+            }
+        }
+    }
+}
+
+impl Dedup {
+    /// Whether `path` was yielded before, remembering it if it wasn't.
+    fn is_duplicate(&mut self, path: &Path) -> bool {
+        if path.to_string_lossy() == STDIO_FILENAME {
+            return false;
+        }
+        // Files that can't be identified are left for opening them to report.
+        let identity = match collide::identity(path) {
+            Ok(Some(identity)) => identity,
+            _ => return false,
+        };
+        match self.seen.get(&identity) {
+            Some(first) => {
+                if let Some(on_duplicate) = &mut self.on_duplicate {
+                    on_duplicate(path, first);
+                }
+                true
+            }
+            None => {
+                self.seen.insert(identity, path.to_owned());
+                false
+            }
+        }
+    }
+}
+
+impl Filter {
+    fn new(pattern: &str) -> Self {
+        Self {
+            pattern: Pattern::new(pattern),
+            whole_path: pattern.contains('/'),
+        }
+    }
+
+    fn matches(&self, relative: &str) -> bool {
+        if self.whole_path {
+            self.pattern.matches(relative)
+        } else {
+            self.pattern.matches(file_name(relative))
+        }
+    }
+}
+
+impl Filters {
+    fn is_excluded(&self, relative: &str) -> bool {
+        self.exclude.iter().any(|filter| filter.matches(relative))
+    }
+
+    fn is_included(&self, relative: &str) -> bool {
+        self.include.is_empty() || self.include.iter().any(|filter| filter.matches(relative))
+    }
+
+    #[cfg(feature = "ignore")]
+    fn is_ignored(&self, stack: &[Frame], relative: &str, is_dir: bool) -> bool {
+        if !self.git_ignore {
+            return false;
+        }
+        if is_dir && file_name(relative) == ".git" {
+            return true;
+        }
+        // Deeper `.gitignore` files take precedence.
+        stack
+            .iter()
+            .rev()
+            .find_map(|frame| {
+                let within = match frame.relative.as_str() {
+                    "" => relative,
+                    dir => &relative[dir.len() + 1..],
+                };
+                frame.ignore.as_ref()?.is_ignored(within, is_dir)
+            })
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(feature = "ignore"))]
+    fn is_ignored(&self, _: &[Frame], _: &str, _: bool) -> bool {
+        false
+    }
+}
+
+impl PathList {
+    fn next(&mut self, filters: &Filters) -> Option<io::Result<PathBuf>> {
+        let mut entry = Vec::new();
+        loop {
+            entry.clear();
+            match self.reader.read_until(self.delim, &mut entry) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            if entry.last() == Some(&self.delim) {
+                entry.pop();
+            }
+            if self.delim == b'\n' && entry.last() == Some(&b'\r') {
+                entry.pop();
+            }
+            if entry.is_empty() {
+                continue;
+            }
+
+            let path = path_from_bytes(&entry);
+            let spec = path.to_string_lossy();
+            if !filters.is_excluded(&spec) && filters.is_included(&spec) {
+                return Some(Ok(path));
+            }
+        }
+    }
+}
+
+/// A path from raw bytes, as found in path lists and archives.
+#[cfg(unix)]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(feature = "glob")]
+impl SpecExpansion {
+    fn next(&mut self, filters: &Filters) -> Option<io::Result<PathBuf>> {
+        loop {
+            if let Some((spec, walk, matched)) = &mut self.current {
+                match walk.next(filters) {
+                    Some(Ok(path)) => {
+                        *matched = true;
+                        return Some(Ok(path));
+                    }
+                    Some(Err(e)) if e.kind() != io::ErrorKind::NotFound || *matched => {
+                        return Some(Err(e));
+                    }
+                    Some(Err(_)) | None if !*matched => {
+                        let e = io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("{}: no matching files", spec),
+                        );
+                        self.current = None;
+                        return Some(Err(e));
+                    }
+                    _ => self.current = None,
+                }
+            }
+
+            let spec = self.specs.next()?;
+            #[cfg(windows)]
+            let spec = spec.replace('\\', "/");
+            let segments: Vec<&str> = spec.split('/').collect();
+            let first_glob = match segments.iter().position(|s| s.contains(GLOB_CHARS)) {
+                Some(first_glob) => first_glob,
+                None => return Some(Ok(PathBuf::from(spec))),
+            };
+            let root = segments[..first_glob].join("/");
+            let rest = segments[first_glob..].join("/");
+            let root = match root.as_str() {
+                "" if first_glob > 0 => PathBuf::from("/"),
+                root => PathBuf::from(root),
+            };
+            let recursive = rest.contains('/') || rest.contains("**");
+            let walk = DirWalk::new(root, recursive, Some(Pattern::new(&rest)));
+            self.current = Some((spec, walk, false));
+        }
+    }
+}
+
+impl DirWalk {
+    fn new(root: PathBuf, recursive: bool, pattern: Option<Pattern>) -> Self {
+        Self {
+            root,
+            recursive,
+            pattern,
+            started: false,
+            stack: Vec::new(),
+        }
+    }
+
+    fn next(&mut self, filters: &Filters) -> Option<io::Result<PathBuf>> {
+        if !self.started {
+            self.started = true;
+            if let Err(e) = self.enter(filters, String::new()) {
+                return Some(Err(e));
+            }
+        }
+
+        loop {
+            let frame = self.stack.last_mut()?;
+            let entry = match frame.entries.next() {
+                Some(entry) => entry,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            let relative = if frame.relative.is_empty() {
+                entry.name
+            } else {
+                format!("{}/{}", frame.relative, entry.name)
+            };
+
+            if filters.is_ignored(&self.stack, &relative, entry.is_dir)
+                || filters.is_excluded(&relative)
+            {
+                continue;
+            }
+            if entry.is_dir {
+                if self.recursive {
+                    if let Err(e) = self.enter(filters, relative) {
+                        return Some(Err(e));
+                    }
+                }
+                continue;
+            }
+            let wanted = self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.matches(&relative));
+            if wanted && filters.is_included(&relative) {
+                return Some(Ok(self.root.join(relative)));
+            }
+        }
+    }
+
+    /// Start listing the directory at `relative`.
+    fn enter(&mut self, filters: &Filters, relative: String) -> io::Result<()> {
+        let mut dir = self.root.join(&relative);
+        if dir.as_os_str().is_empty() {
+            dir = PathBuf::from(".");
+        }
+        let mut entries = fs::read_dir(&dir)?
+            .map(|entry| {
+                let entry = entry?;
+                Ok(Entry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    is_dir: entry.file_type()?.is_dir(),
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        #[cfg(feature = "ignore")]
+        let ignore = if filters.git_ignore {
+            GitIgnore::load(&dir)?
+        } else {
+            None
+        };
+        #[cfg(not(feature = "ignore"))]
+        let _ = filters;
+
+        self.stack.push(Frame {
+            relative,
+            entries: entries.into_iter(),
+            #[cfg(feature = "ignore")]
+            ignore,
+        });
+        Ok(())
+    }
+}
+
+fn file_name(relative: &str) -> &str {
+    relative.rsplit('/').next().unwrap_or(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tree() -> io::Result<TempDir> {
+        let tmp_dir = TempDir::new()?;
+        for path in &[
+            "b.rs",
+            "a.txt",
+            "src/lib.rs",
+            "src/gen/out.rs",
+            "target/app.rs",
+        ] {
+            let path = tmp_dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, "")?;
+        }
+        Ok(tmp_dir)
+    }
+
+    fn relative(tmp_dir: &TempDir, inputs: Inputs) -> io::Result<Vec<String>> {
+        inputs
+            .map(|path| {
+                let path = path?;
+                let relative = path.strip_prefix(tmp_dir.path()).unwrap();
+                Ok(relative.to_string_lossy().replace('\\', "/"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expand_dir() -> Result<(), io::Error> {
+        let tmp_dir = tree()?;
+
+        let flat = Inputs::from_dir(tmp_dir.path(), false);
+        assert_eq!(relative(&tmp_dir, flat)?, vec!["a.txt", "b.rs"]);
+
+        let filtered = Inputs::from_dir(tmp_dir.path(), true)
+            .include("*.rs")
+            .exclude("target")
+            .exclude("src/gen/*");
+        assert_eq!(relative(&tmp_dir, filtered)?, vec!["b.rs", "src/lib.rs"]);
+
+        assert!(Inputs::from_dir(tmp_dir.path().join("missing"), true)
+            .next()
+            .unwrap()
+            .is_err());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn read_list() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let lines = tmp_dir.path().join("lines");
+        let nul = tmp_dir.path().join("nul");
+        fs::write(&lines, "a.txt\r\n\nsub dir/b.rs\n")?;
+        fs::write(&nul, "a.txt\0line\nbreak.rs\0")?;
+
+        let paths = Inputs::from_list(&lines, b'\n')?.collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(paths, vec![Path::new("a.txt"), Path::new("sub dir/b.rs")]);
+
+        let paths = Inputs::from_list(&nul, b'\0')?
+            .include("*.rs")
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(paths, vec![Path::new("line\nbreak.rs")]);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn dedup_inputs() -> Result<(), io::Error> {
+        let tmp_dir = tree()?;
+        let list = tmp_dir.path().join("list");
+        let a = tmp_dir.path().join("a.txt");
+        let again = tmp_dir.path().join("src/../a.txt");
+        let entries = [&a, &again, Path::new("-"), Path::new("-")];
+        let entries: Vec<_> = entries.iter().map(|p| p.to_string_lossy()).collect();
+        fs::write(&list, entries.join("\n"))?;
+
+        let skipped = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let record = std::sync::Arc::clone(&skipped);
+        let paths = Inputs::from_list(&list, b'\n')?
+            .on_duplicate(move |path, first| {
+                record
+                    .lock()
+                    .unwrap()
+                    .push((path.to_owned(), first.to_owned()))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(paths, vec![&a, Path::new("-"), Path::new("-")]);
+        assert_eq!(*skipped.lock().unwrap(), vec![(again, a.clone())]);
+
+        let paths = Inputs::from_list(&list, b'\n')?.collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(paths.len(), 4);
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn expand_globs() -> Result<(), io::Error> {
+        let tmp_dir = tree()?;
+        let root = tmp_dir.path().to_string_lossy().replace('\\', "/");
+
+        let specs = [
+            format!("{}/*.rs", root),
+            "-".to_owned(),
+            format!("{}/src/**/*.rs", root),
+        ];
+        let inputs = Inputs::from_specs(&specs).map(|path| {
+            let path = path?;
+            Ok(match path.strip_prefix(tmp_dir.path()) {
+                Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+                Err(_) => path.to_string_lossy().into_owned(),
+            })
+        });
+        assert_eq!(
+            inputs.collect::<io::Result<Vec<_>>>()?,
+            vec!["b.rs", "-", "src/gen/out.rs", "src/lib.rs"]
+        );
+
+        let mut none = Inputs::from_specs(&[format!("{}/*.json", root)]);
+        assert_eq!(
+            none.next().unwrap().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(none.next().is_none());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[cfg(feature = "ignore")]
+    #[test]
+    fn respect_gitignore() -> Result<(), io::Error> {
+        let tmp_dir = tree()?;
+        fs::write(tmp_dir.path().join(".gitignore"), "/target/\n*.txt\n")?;
+        fs::write(tmp_dir.path().join("src/.gitignore"), "gen/\n")?;
+
+        let inputs = Inputs::from_dir(tmp_dir.path(), true)
+            .git_ignore(true)
+            .exclude(".gitignore");
+        assert_eq!(relative(&tmp_dir, inputs)?, vec!["b.rs", "src/lib.rs"]);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,465 @@
+//! Stream events for `FileOrStdin` and `FileOrStdout`, which do nothing without the
+//! `io-events` feature.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Identifies a stream across events: a number given out when its file was opened, or stdin or
+/// stdout. Unlike a file descriptor, it isn't reused by the next file opened.
+pub(crate) type Key = u64;
+
+pub(crate) const STDIN: Key = u64::MAX;
+pub(crate) const STDOUT: Key = u64::MAX - 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    Read,
+    Write,
+    Flush,
+}
+
+#[cfg(feature = "io-events")]
+pub(crate) use self::enabled::{
+    close, file_key, flushed, open, open_failed, open_handle, timed, transferred,
+};
+#[cfg(feature = "io-events")]
+pub use self::enabled::{set_event_log, IO_EVENTS_ENV};
+
+#[cfg(feature = "io-events")]
+mod enabled {
+    use super::{handle_id, Key, Op, STDIN, STDOUT};
+    use std::{
+        collections::HashMap,
+        env,
+        fs::File,
+        io::{self, Write},
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Mutex, MutexGuard, Once,
+        },
+        time::{Duration, Instant},
+    };
+
+    /// Environment variable that sends events to stderr when set to anything but `0`, unless a
+    /// log was installed with [`set_event_log`].
+    pub const IO_EVENTS_ENV: &str = "POLYMORPHIO_IO_EVENTS";
+
+    static LOG: Mutex<Option<Log>> = Mutex::new(None);
+    /// Whether `LOG` holds a log, so I/O without one never takes the lock.
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    static FROM_ENV: Once = Once::new();
+    static NEXT_KEY: AtomicU64 = AtomicU64::new(0);
+
+    struct Log {
+        out: Box<dyn Write + Send>,
+        streams: HashMap<Key, Stream>,
+        /// The key of the file open under each file descriptor (or handle).
+        keys: HashMap<u64, Key>,
+    }
+
+    struct Stream {
+        path: String,
+        opened: Instant,
+        read: u64,
+        written: u64,
+        io: Duration,
+    }
+
+    impl Stream {
+        fn new(path: String) -> Self {
+            Self {
+                path,
+                opened: Instant::now(),
+                read: 0,
+                written: 0,
+                io: Duration::ZERO,
+            }
+        }
+    }
+
+    /// Send I/O events for `FileOrStdin` and `FileOrStdout` streams to `out`, replacing any
+    /// previous log.
+    ///
+    /// Streams report when they're opened, first read or written, flushed and closed (locks close
+    /// when dropped), and any errors. Each event is one line like
+    ///
+    /// ```text
+    /// polymorphio event=flush path="out.txt" read=0 written=8192 io_us=41 elapsed_us=1502
+    /// ```
+    ///
+    /// where `io_us` is the time spent inside reads, writes and flushes, and `elapsed_us` the time
+    /// since the stream was opened. Without a log, events go to stderr if `POLYMORPHIO_IO_EVENTS`
+    /// is set, and are otherwise skipped.
+    pub fn set_event_log<W: Write + Send + 'static>(out: W) {
+        FROM_ENV.call_once(|| {});
+        install(Some(Box::new(out)));
+    }
+
+    fn install(out: Option<Box<dyn Write + Send>>) {
+        let mut log = lock();
+        INSTALLED.store(out.is_some(), Ordering::Release);
+        *log = out.map(|out| Log {
+            out,
+            streams: HashMap::new(),
+            keys: HashMap::new(),
+        });
+    }
+
+    /// Remove the log again, so later tests' I/O isn't logged.
+    #[cfg(test)]
+    pub(crate) fn clear_event_log() {
+        install(None);
+    }
+
+    fn lock() -> MutexGuard<'static, Option<Log>> {
+        // Events are diagnostic only, so a panic while logging must not stop later I/O.
+        LOG.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn installed() -> bool {
+        FROM_ENV.call_once(|| {
+            if env::var_os(IO_EVENTS_ENV).is_some_and(|v| !v.is_empty() && v != "0") {
+                install(Some(Box::new(io::stderr())));
+            }
+        });
+        INSTALLED.load(Ordering::Acquire)
+    }
+
+    fn log() -> Option<MutexGuard<'static, Option<Log>>> {
+        if !installed() {
+            return None;
+        }
+        let log = lock();
+        log.is_some().then_some(log)
+    }
+
+    impl Log {
+        fn stream(&mut self, key: Key) -> &mut Stream {
+            self.streams.entry(key).or_insert_with(|| {
+                Stream::new(match key {
+                    STDIN | STDOUT => "-".into(),
+                    key => format!("stream {}", key),
+                })
+            })
+        }
+
+        fn emit(&mut self, event: &str, key: Key, extra: &str) {
+            let stream = self.stream(key);
+            let line = format!(
+                "polymorphio event={} path={:?} read={} written={} io_us={} elapsed_us={}{}\n",
+                event,
+                stream.path,
+                stream.read,
+                stream.written,
+                stream.io.as_micros(),
+                stream.opened.elapsed().as_micros(),
+                extra
+            );
+            let _ = self.out.write_all(line.as_bytes());
+        }
+    }
+
+    fn op_name(op: Op) -> &'static str {
+        match op {
+            Op::Read => "read",
+            Op::Write => "write",
+            Op::Flush => "flush",
+        }
+    }
+
+    pub(crate) fn open(key: Key, path: &Path) {
+        if let Some(mut log) = log() {
+            let log = log.as_mut().unwrap();
+            log.streams
+                .insert(key, Stream::new(path.to_string_lossy().into_owned()));
+            log.emit("open", key, "");
+        }
+    }
+
+    /// Report `file` as opened from `path`, under a new key.
+    pub(crate) fn open_handle(file: &File, path: &Path) {
+        if let Some(mut log) = log() {
+            let key = NEXT_KEY.fetch_add(1, Ordering::Relaxed);
+            log.as_mut().unwrap().keys.insert(handle_id(file), key);
+            drop(log);
+            open(key, path);
+        }
+    }
+
+    /// The key `file` was reported under when it was opened, or a new one.
+    pub(crate) fn file_key(file: &File) -> Key {
+        match log() {
+            Some(mut log) => *log
+                .as_mut()
+                .unwrap()
+                .keys
+                .entry(handle_id(file))
+                .or_insert_with(|| NEXT_KEY.fetch_add(1, Ordering::Relaxed)),
+            None => 0,
+        }
+    }
+
+    pub(crate) fn open_failed(path: &Path, e: &io::Error) {
+        if let Some(mut log) = log() {
+            let line = format!(
+                "polymorphio event=error op=open path={:?} error={:?}\n",
+                path.to_string_lossy(),
+                e.kind()
+            );
+            let _ = log.as_mut().unwrap().out.write_all(line.as_bytes());
+        }
+    }
+
+    /// Run a read, write or flush, adding its time to the stream and reporting any error.
+    pub(crate) fn timed<T>(key: Key, op: Op, f: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+        if !installed() {
+            return f();
+        }
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        if let Some(mut log) = log() {
+            let log = log.as_mut().unwrap();
+            log.stream(key).io += elapsed;
+            if let Err(e) = &result {
+                if e.kind() != io::ErrorKind::Interrupted {
+                    let extra = format!(" op={} error={:?}", op_name(op), e.kind());
+                    log.emit("error", key, &extra);
+                }
+            }
+        }
+        result
+    }
+
+    /// Count bytes handed to the reader or accepted from the writer.
+    pub(crate) fn transferred(key: Key, op: Op, amt: usize) {
+        if amt == 0 {
+            return;
+        }
+        if let Some(mut log) = log() {
+            let log = log.as_mut().unwrap();
+            let stream = log.stream(key);
+            let total = match op {
+                Op::Read => &mut stream.read,
+                _ => &mut stream.written,
+            };
+            let first = *total == 0;
+            *total += amt as u64;
+            if first {
+                let event = if op == Op::Read {
+                    "first_read"
+                } else {
+                    "first_write"
+                };
+                log.emit(event, key, "");
+            }
+        }
+    }
+
+    pub(crate) fn flushed(key: Key) {
+        if let Some(mut log) = log() {
+            let log = log.as_mut().unwrap();
+            log.emit("flush", key, "");
+            let _ = log.out.flush();
+        }
+    }
+
+    pub(crate) fn close(key: Key) {
+        if let Some(mut log) = log() {
+            let log = log.as_mut().unwrap();
+            log.emit("close", key, "");
+            let _ = log.out.flush();
+            log.streams.remove(&key);
+            log.keys.retain(|_, k| *k != key);
+        }
+    }
+}
+
+#[cfg(not(feature = "io-events"))]
+mod disabled {
+    use super::{Key, Op};
+    use std::{fs::File, io, path::Path};
+
+    #[inline]
+    pub(crate) fn open(_: Key, _: &Path) {}
+
+    #[inline]
+    pub(crate) fn open_handle(_: &File, _: &Path) {}
+
+    #[inline]
+    pub(crate) fn file_key(_: &File) -> Key {
+        0
+    }
+
+    #[inline]
+    pub(crate) fn open_failed(_: &Path, _: &io::Error) {}
+
+    #[inline]
+    pub(crate) fn timed<T>(_: Key, _: Op, f: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+        f()
+    }
+
+    #[inline]
+    pub(crate) fn transferred(_: Key, _: Op, _: usize) {}
+
+    #[inline]
+    pub(crate) fn flushed(_: Key) {}
+
+    #[inline]
+    pub(crate) fn close(_: Key) {}
+}
+
+#[cfg(not(feature = "io-events"))]
+pub(crate) use self::disabled::{
+    close, file_key, flushed, open, open_failed, open_handle, timed, transferred,
+};
+
+/// The file descriptor (or handle) of `file`.
+#[cfg_attr(not(feature = "io-events"), allow(dead_code))]
+fn handle_id(file: &File) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        file.as_raw_fd() as u64
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle;
+        file.as_raw_handle() as usize as u64
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        file as *const File as usize as u64
+    }
+}
+
+/// Open `path` with `open_fn`, reporting the open or its failure.
+pub(crate) fn open_file(
+    path: &Path,
+    open_fn: impl FnOnce(&Path) -> io::Result<File>,
+) -> io::Result<File> {
+    match open_fn(path) {
+        Ok(file) => {
+            open_handle(&file, path);
+            Ok(file)
+        }
+        Err(e) => {
+            open_failed(path, &e);
+            Err(e)
+        }
+    }
+}
+
+/// Write to `writer`, reporting the write under `key`.
+pub(crate) fn write_with<W: Write + ?Sized>(
+    key: Key,
+    writer: &mut W,
+    buf: &[u8],
+) -> io::Result<usize> {
+    let amt = timed(key, Op::Write, || writer.write(buf))?;
+    transferred(key, Op::Write, amt);
+    Ok(amt)
+}
+
+pub(crate) fn flush_with<W: Write + ?Sized>(key: Key, writer: &mut W) -> io::Result<()> {
+    timed(key, Op::Flush, || writer.flush())?;
+    flushed(key);
+    Ok(())
+}
+
+#[cfg(all(test, feature = "io-events"))]
+mod tests {
+    use crate::{FileOrStdin, FileOrStdout};
+    use std::{
+        fs,
+        io::{self, Read, Write},
+        sync::{Arc, Mutex},
+    };
+    use tempfile::TempDir;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Removes the event log when dropped, even if the test fails.
+    struct Installed;
+
+    impl Drop for Installed {
+        fn drop(&mut self) {
+            super::enabled::clear_event_log();
+        }
+    }
+
+    #[test]
+    fn reports_stream_events() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let events = Shared::default();
+        super::set_event_log(events.clone());
+        let _installed = Installed;
+
+        let out_path = tmp_dir.path().join("out.txt");
+        let mut output = FileOrStdout::from_path(&out_path)?;
+        let mut lock = output.lock();
+        lock.write_all(b"hello ")?;
+        lock.write_all(b"world")?;
+        lock.flush()?;
+        drop(lock);
+        drop(output);
+
+        let mut input = FileOrStdin::from_path(&out_path)?;
+        io::copy(&mut input.lock(), &mut io::sink())?;
+        assert!(FileOrStdin::from_path(tmp_dir.path().join("missing")).is_err());
+        let mut content = String::new();
+        FileOrStdin::from_path(&out_path)?
+            .lock()
+            .read_to_string(&mut content)?;
+
+        let events = String::from_utf8(events.0.lock().unwrap().clone()).unwrap();
+        let path = format!("path={:?}", out_path.to_string_lossy());
+        let mine: Vec<&str> = events
+            .lines()
+            .filter(|l| l.contains(&path))
+            .map(|l| l.split(' ').nth(1).unwrap())
+            .collect();
+        assert_eq!(
+            mine[..8],
+            [
+                "event=open",
+                "event=first_write",
+                "event=flush",
+                "event=close",
+                "event=open",
+                "event=first_read",
+                "event=close",
+                "event=open",
+            ]
+        );
+        let flush = events.lines().find(|l| l.contains("event=flush")).unwrap();
+        assert!(flush.contains(" written=11 "), "{}", flush);
+        let mut closes = events
+            .lines()
+            .filter(|l| l.contains("event=close") && l.contains(&path));
+        let close = closes.next().unwrap();
+        assert!(close.contains(" written=11 "), "{}", close);
+        let close = closes.next().unwrap();
+        assert!(close.contains(" read=11 "), "{}", close);
+        assert!(events.contains("event=error op=open"));
+
+        assert_eq!(fs::read_to_string(&out_path)?, "hello world");
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -1,27 +1,260 @@
 use std::{
     fs::File,
-    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, LineWriter, Read, Write},
     path::Path,
 };
 
+#[cfg(unix)]
+mod activation;
+mod addressed;
+mod ansi;
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
+mod atomic;
+mod autoflush;
+mod batch;
+mod chain;
+mod channel;
+mod chunks;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod collide;
+#[cfg(feature = "color")]
+mod color;
+#[cfg(any(feature = "s3", feature = "gcs", feature = "ssh", feature = "git"))]
+mod command;
+#[cfg(feature = "compress")]
+mod compress;
+mod copy;
+#[cfg(any(feature = "age", feature = "gpg"))]
+mod crypt;
+mod deterministic;
+mod digest;
+mod drain;
+mod dry_run;
+mod duplex;
+mod encode;
+mod error;
+mod exit;
+mod ext;
+mod fifo;
+#[cfg(any(feature = "compress", feature = "age", feature = "gpg"))]
+mod filter;
+mod flush;
+mod framed;
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "ignore")]
+mod gitignore;
+mod glob;
+mod guarded;
+mod hexdump;
+#[cfg(any(feature = "compress", feature = "zip"))]
+mod inflate;
+mod inputs;
+mod instrument;
+mod lines;
+#[cfg(feature = "magic")]
+mod magic;
+#[cfg(feature = "compress")]
+mod members;
+mod merge;
+#[cfg(any(feature = "s3", feature = "gcs"))]
+mod object;
+mod observe;
+mod open;
+mod owned;
+mod pager;
+#[cfg(feature = "compress")]
+mod parallel;
+mod positioned;
+mod prompt;
+mod range;
+mod raw;
+#[cfg(feature = "readline")]
+mod readline;
+mod registry;
+mod report;
+mod resume;
+mod retry;
+mod rewind;
+mod root;
+mod rotate;
+mod scheme;
+mod secret;
+mod session;
+#[cfg(feature = "ssh")]
+mod sftp;
+#[cfg(all(unix, feature = "signal"))]
+mod signal;
+mod sniff;
+mod space;
+mod split;
+mod stamp;
+mod stdin_cache;
+mod tail;
+#[cfg(feature = "tar")]
+mod tar;
+mod target;
+mod tee;
+mod temp;
+mod template;
+mod term;
+mod text;
+mod trace;
+mod track;
+mod transaction;
+mod utf8;
+mod watch;
+mod xattr;
+#[cfg(feature = "zip")]
+mod zip;
+
+#[cfg(target_os = "macos")]
+pub use activation::launchd_sockets;
+#[cfg(unix)]
+pub use activation::{activated_sockets, ActivatedSocket};
+pub use addressed::ContentAddressed;
+pub use ansi::{StripAnsiReader, StripAnsiWriter};
+#[cfg(any(feature = "tar", feature = "zip"))]
+pub use archive::{ArchiveEntry, ArchiveFormat, ArchiveOutput};
+pub use atomic::{AtomicOutput, Commit, LinkPolicy};
+pub use autoflush::{AutoFlush, FlushEvery};
+pub use batch::{Batch, BatchReport, ErrorPolicy, RepeatedStdin};
+pub use chain::InputChain;
+pub use channel::{channel_reader, channel_writer, ChannelReader, ChannelWriter};
+pub use chunks::Chunks;
+#[cfg(feature = "clipboard")]
+pub use clipboard::{is_clipboard_path, ClipboardReader, ClipboardWriter, CLIPBOARD_PATH};
+pub use collide::{check_collision, same_file};
+#[cfg(feature = "color")]
+pub use color::{Color, ColorChoice, ColorSpec, ColorWriter, ColoredWrite};
+#[cfg(feature = "compress")]
+pub use compress::{Codec, CompressOptions, CompressWriter, DecompressReader};
+#[cfg(any(feature = "age", feature = "gpg"))]
+pub use crypt::{Cipher, CipherKey, DecryptReader, EncryptWriter};
+pub use dry_run::DryRun;
+pub use duplex::FileOrStdio;
+pub use encode::{Base64Alphabet, Base64Reader, Base64Writer, HexReader, HexWriter};
+pub use error::Error;
+pub use exit::{
+    error_json, exit_code_for, run_main, set_error_format, set_warning_handler, with_path,
+    ErrorFormat, PathError,
+};
+pub use ext::{HashAlgorithm, Hashing, Limited, PolymorphReadExt, PolymorphWriteExt, Throttled};
+pub use fifo::FifoReader;
+pub use flush::FlushError;
+pub use framed::Framed;
+#[cfg(feature = "git")]
+pub use git::{is_git_spec, GIT_PREFIX};
+pub use guarded::{GuardedOutput, PartialPolicy};
+pub use hexdump::{HexdumpWriter, HEXDUMP_ENV};
+pub use inputs::Inputs;
+#[cfg(feature = "io-events")]
+pub use instrument::{set_event_log, IO_EVENTS_ENV};
+#[cfg(feature = "magic")]
+pub use magic::Format;
+pub use merge::{merge_sorted, MergeSorted};
+#[cfg(any(feature = "s3", feature = "gcs"))]
+pub use object::{is_object_url, ObjectInput, ObjectOutput};
+pub use observe::{IoObserver, Observed};
+pub use open::{InputOptions, OutputOptions, SymlinkError};
+pub use owned::{FileOrStdinOwnedLock, FileOrStdoutOwnedLock};
+pub use pager::PagedOutput;
+pub use prompt::{confirm_overwrite, OverwritePolicy};
+pub use range::TakeLines;
+pub use raw::{Key, RawInput};
+#[cfg(feature = "readline")]
+pub use readline::InteractiveLines;
+pub use registry::{set_duplicate_outputs, DuplicateOutputs};
+pub use report::{Counted, Report};
+pub use retry::{RetryPolicy, Retrying};
+pub use rewind::Rewindable;
+pub use root::Root;
+pub use rotate::{ReopenHandle, RotatingOutput};
+pub use session::Session;
+#[cfg(feature = "ssh")]
+pub use sftp::{is_sftp_url, SftpInput, SftpOutput};
+#[cfg(all(unix, feature = "signal"))]
+pub use signal::{ResizeWatch, SignalFlush, SignalPolicy};
+pub use sniff::{ContentKind, SNIFF_LEN};
+pub use space::check_free_space;
+pub use split::SplitOutput;
+pub use stamp::FileStamp;
+pub use stdin_cache::StdinCache;
+pub use tail::ReverseLines;
+#[cfg(feature = "tar")]
+pub use tar::{TarArchive, TarEntry, TarMember};
+pub use target::TargetKind;
+pub use tee::TeeReader;
+pub use temp::TempOutput;
+pub use template::OutputTemplate;
+pub use text::{FinalNewline, Newline, TextWriter};
+pub use trace::{Replay, TraceLog, Traced};
+pub use track::{Position, Tracked};
+pub use transaction::OutputTransaction;
+pub use utf8::{Utf8Error, Utf8Reader};
+pub use watch::{watch, Watch};
+#[cfg(feature = "zip")]
+pub use zip::{ZipArchive, ZipEntry, ZipEntryWriter};
+
 const STDIO_FILENAME: &str = "-";
 
+/// Separates the archive path from the member path in specs like `archive.tar::dir/file.txt`.
+#[cfg(any(feature = "tar", feature = "zip"))]
+const MEMBER_SEPARATOR: &str = "::";
+
+/// Split `archive.tar::member` into its two parts.
+#[cfg(any(feature = "tar", feature = "zip"))]
+fn split_member_spec(spec: &str) -> Option<(&str, &str)> {
+    let (archive, member) = spec.split_once(MEMBER_SEPARATOR)?;
+    if archive.is_empty() || member.is_empty() {
+        return None;
+    }
+    Some((archive, member))
+}
+
 pub enum FileOrStdin {
     File(File),
     Stdin(io::Stdin),
 }
 
-pub enum FileOrStdinLock<'a> {
+pub struct FileOrStdinLock<'a> {
+    inner: InputLock<'a>,
+    key: instrument::Key,
+    stamp: Option<stamp::FileStamp>,
+    peeked: Vec<u8>,
+    peeked_pos: usize,
+}
+
+enum InputLock<'a> {
     FileBufReader(BufReader<&'a File>),
     StdinLock(io::StdinLock<'a>),
 }
 
 impl FileOrStdin {
     pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        Ok(if path.as_ref().to_string_lossy() == STDIO_FILENAME {
+        let path = path.as_ref();
+        Ok(if path.to_string_lossy() == STDIO_FILENAME {
+            instrument::open(instrument::STDIN, path);
             io::stdin().into()
         } else {
-            File::open(path)?.into()
+            if let Some(input) = scheme::open_input(path) {
+                return input.map_err(|source| {
+                    Error::OpenInput {
+                        path: path.to_owned(),
+                        source,
+                    }
+                    .into()
+                });
+            }
+            let open = |p: &Path| fifo::FifoOptions::default().open(p, false, |_| File::open(p));
+            instrument::open_file(path, open)
+                .map_err(|source| Error::OpenInput {
+                    path: path.to_owned(),
+                    source,
+                })?
+                .into()
         })
     }
 
@@ -30,20 +263,52 @@ impl FileOrStdin {
         handle.into()
     }
 
-    pub fn lock<'a>(&'a mut self) -> FileOrStdinLock<'a> {
+    /// Whether the input is an interactive terminal.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            Self::File(file) => file.is_terminal(),
+            Self::Stdin(stdin) => stdin.is_terminal(),
+        }
+    }
+
+    /// The file being read, or `None` for stdin.
+    pub fn as_file(&self) -> Option<&File> {
+        match self {
+            Self::File(file) => Some(file),
+            Self::Stdin(_) => None,
+        }
+    }
+
+    /// The underlying file, or `Err` with the stdin handle.
+    pub fn into_inner(self) -> Result<File, io::Stdin> {
         match self {
-            Self::File(file) => FileOrStdinLock::FileBufReader(BufReader::new(file)),
-            Self::Stdin(stdin) => FileOrStdinLock::StdinLock(stdin.lock()),
+            Self::File(file) => Ok(file),
+            Self::Stdin(stdin) => Err(stdin),
         }
     }
 
+    pub fn lock<'a>(&'a mut self) -> FileOrStdinLock<'a> {
+        let inner = match self {
+            Self::File(file) => InputLock::FileBufReader(BufReader::new(file)),
+            Self::Stdin(stdin) => InputLock::StdinLock(stdin.lock()),
+        };
+        inner.into()
+    }
+
     /// Read the entire contents into a string.
     ///
     /// This is a convenience function similar to
     /// [`std::fs::read_to_string`](https://doc.rust-lang.org/std/fs/fn.read_to_string.html).
+    /// Invalid UTF-8 is reported with its line and byte offset (see [`Utf8Error`]).
     pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+        let path = path.as_ref();
         let mut string = String::new();
-        Self::from_path(path)?.lock().read_to_string(&mut string)?;
+        Utf8Reader::new(Self::from_path(path)?.lock())
+            .read_to_string(&mut string)
+            .map_err(|source| Error::Read {
+                path: path.to_owned(),
+                source,
+            })?;
         Ok(string)
     }
 }
@@ -60,7 +325,105 @@ impl From<io::Stdin> for FileOrStdin {
     }
 }
 
+impl<'a> FileOrStdinLock<'a> {
+    /// Return up to `n` bytes from the front of the stream without consuming them.
+    ///
+    /// Fewer than `n` bytes are returned only at end of input. Peeked bytes are handed out
+    /// again by subsequent reads.
+    pub fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.peeked_pos == self.peeked.len() {
+            self.peeked.clear();
+            self.peeked_pos = 0;
+
+            if self.inner.fill_buf()?.len() >= n {
+                return Ok(&self.inner.fill_buf()?[..n]);
+            }
+        }
+
+        while self.peeked.len() - self.peeked_pos < n {
+            let buf = match self.inner.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() {
+                break;
+            }
+            let amt = buf.len().min(n - (self.peeked.len() - self.peeked_pos));
+            self.peeked.extend_from_slice(&buf[..amt]);
+            self.inner.consume(amt);
+        }
+
+        let available = &self.peeked[self.peeked_pos..];
+        Ok(&available[..n.min(available.len())])
+    }
+}
+
+impl<'a> From<InputLock<'a>> for FileOrStdinLock<'a> {
+    fn from(inner: InputLock<'a>) -> Self {
+        let key = match &inner {
+            InputLock::FileBufReader(reader) => instrument::file_key(reader.get_ref()),
+            InputLock::StdinLock(_) => instrument::STDIN,
+        };
+        Self {
+            inner,
+            key,
+            stamp: None,
+            peeked: Vec::new(),
+            peeked_pos: 0,
+        }
+    }
+}
+
 impl<'a> Read for FileOrStdinLock<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.peeked_pos < self.peeked.len() {
+            let amt = (&self.peeked[self.peeked_pos..]).read(buf)?;
+            self.peeked_pos += amt;
+            instrument::transferred(self.key, instrument::Op::Read, amt);
+            return Ok(amt);
+        }
+        let inner = &mut self.inner;
+        let amt = instrument::timed(self.key, instrument::Op::Read, || inner.read(buf))?;
+        if amt == 0 && !buf.is_empty() {
+            self.check_unchanged()?;
+        }
+        instrument::transferred(self.key, instrument::Op::Read, amt);
+        Ok(amt)
+    }
+}
+
+impl<'a> BufRead for FileOrStdinLock<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.peeked_pos < self.peeked.len() {
+            return Ok(&self.peeked[self.peeked_pos..]);
+        }
+        if self.stamp.is_some() && self.inner.fill_buf()?.is_empty() {
+            self.check_unchanged()?;
+        }
+        let inner = &mut self.inner;
+        instrument::timed(self.key, instrument::Op::Read, move || inner.fill_buf())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.peeked_pos < self.peeked.len() {
+            let end = (self.peeked_pos + amt).min(self.peeked.len());
+            instrument::transferred(self.key, instrument::Op::Read, end - self.peeked_pos);
+            self.peeked_pos = end;
+        } else {
+            instrument::transferred(self.key, instrument::Op::Read, amt);
+            self.inner.consume(amt);
+        }
+    }
+}
+
+impl<'a> Drop for FileOrStdinLock<'a> {
+    fn drop(&mut self) {
+        instrument::close(self.key);
+    }
+}
+
+impl<'a> Read for InputLock<'a> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self {
             Self::FileBufReader(reader) => reader.read(buf),
@@ -69,7 +432,7 @@ impl<'a> Read for FileOrStdinLock<'a> {
     }
 }
 
-impl<'a> BufRead for FileOrStdinLock<'a> {
+impl<'a> BufRead for InputLock<'a> {
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
         match self {
             Self::FileBufReader(reader) => reader.fill_buf(),
@@ -90,17 +453,53 @@ pub enum FileOrStdout {
     Stdout(io::Stdout),
 }
 
-pub enum FileOrStdoutLock<'a> {
+pub struct FileOrStdoutLock<'a> {
+    inner: OutputLock<'a>,
+    key: instrument::Key,
+}
+
+enum OutputLock<'a> {
     FileBufWriter(BufWriter<&'a File>),
+    FileLineWriter(LineWriter<&'a File>),
+    FileUnbuffered(&'a File),
+    /// Stdout as the standard library buffers it, by line.
     StdoutLock(io::StdoutLock<'a>),
+    StdoutBufWriter(BufWriter<io::StdoutLock<'a>>),
+    /// Stdout flushed after every write.
+    StdoutUnbuffered(io::StdoutLock<'a>),
+}
+
+/// How a locked [`FileOrStdout`] buffers what is written to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Buffering {
+    /// Line buffering for an interactive terminal, block buffering otherwise, like C's stdio.
+    #[default]
+    Auto,
+    /// Write in large blocks, when the buffer fills or on flush.
+    Block,
+    /// Write each complete line as soon as it ends.
+    Line,
+    /// Write everything straight away.
+    None,
 }
 
 impl FileOrStdout {
     pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        Ok(if path.as_ref().to_string_lossy() == STDIO_FILENAME {
-            io::stdout().into()
-        } else {
-            File::create(path)?.into()
+        registry::open_output(path.as_ref(), |path| {
+            Ok(if path.to_string_lossy() == STDIO_FILENAME {
+                instrument::open(instrument::STDOUT, path);
+                io::stdout().into()
+            } else {
+                scheme::check_output(path)?;
+                let open =
+                    |p: &Path| fifo::FifoOptions::default().open(p, true, |_| File::create(p));
+                instrument::open_file(path, open)
+                    .map_err(|source| Error::OpenOutput {
+                        path: path.to_owned(),
+                        source,
+                    })?
+                    .into()
+            })
         })
     }
 
@@ -109,10 +508,64 @@ impl FileOrStdout {
         handle.into()
     }
 
-    pub fn lock<'a>(&'a mut self) -> FileOrStdoutLock<'a> {
+    /// Whether the output is an interactive terminal.
+    pub fn is_terminal(&self) -> bool {
         match self {
-            Self::File(file) => FileOrStdoutLock::FileBufWriter(BufWriter::new(file)),
-            Self::Stdout(stdout) => FileOrStdoutLock::StdoutLock(stdout.lock()),
+            Self::File(file) => file.is_terminal(),
+            Self::Stdout(stdout) => stdout.is_terminal(),
+        }
+    }
+
+    /// The file being written, or `None` for stdout.
+    pub fn as_file(&self) -> Option<&File> {
+        match self {
+            Self::File(file) => Some(file),
+            Self::Stdout(_) => None,
+        }
+    }
+
+    /// The underlying file, or `Err` with the stdout handle.
+    pub fn into_inner(self) -> Result<File, io::Stdout> {
+        match self {
+            Self::File(file) => Ok(file),
+            Self::Stdout(stdout) => Err(stdout),
+        }
+    }
+
+    /// Lock the output with [`Buffering::Auto`].
+    pub fn lock<'a>(&'a mut self) -> FileOrStdoutLock<'a> {
+        self.lock_buffered(Buffering::Auto)
+    }
+
+    pub fn lock_buffered<'a>(&'a mut self, buffering: Buffering) -> FileOrStdoutLock<'a> {
+        let buffering = self.resolve_buffering(buffering);
+        let inner = match (self, buffering) {
+            (Self::File(file), Buffering::Line) => {
+                OutputLock::FileLineWriter(LineWriter::new(file))
+            }
+            (Self::File(file), Buffering::None) => OutputLock::FileUnbuffered(file),
+            (Self::File(file), _) => OutputLock::FileBufWriter(BufWriter::new(file)),
+            (Self::Stdout(stdout), Buffering::Block) => {
+                OutputLock::StdoutBufWriter(BufWriter::new(stdout.lock()))
+            }
+            (Self::Stdout(stdout), Buffering::None) => OutputLock::StdoutUnbuffered(stdout.lock()),
+            (Self::Stdout(stdout), _) => OutputLock::StdoutLock(stdout.lock()),
+        };
+        let key = match &inner {
+            OutputLock::FileBufWriter(writer) => instrument::file_key(writer.get_ref()),
+            OutputLock::FileLineWriter(writer) => instrument::file_key(writer.get_ref()),
+            OutputLock::FileUnbuffered(file) => instrument::file_key(file),
+            _ => instrument::STDOUT,
+        };
+        FileOrStdoutLock { inner, key }
+    }
+
+    /// What [`Buffering::Auto`] means for this output.
+    fn resolve_buffering(&self, buffering: Buffering) -> Buffering {
+        match buffering {
+            Buffering::Auto if self.is_terminal() => Buffering::Line,
+            Buffering::Auto => Buffering::Block,
+            buffering => buffering,
         }
     }
 
@@ -120,9 +573,20 @@ impl FileOrStdout {
     ///
     /// This is a convenience function that is the complementary to `FileOrStdin::read_to_string`.
     pub fn write_all<P: AsRef<Path>>(path: P, buf: &[u8]) -> io::Result<()> {
+        let path = path.as_ref();
         let mut writer = Self::from_path(path)?;
         let mut write_buf = writer.lock();
-        write_buf.write_all(buf)
+        write_buf.write_all(buf).map_err(|source| Error::Write {
+            path: path.to_owned(),
+            source,
+        })?;
+        write_buf.flush().map_err(|source| {
+            Error::Flush {
+                path: path.to_owned(),
+                source,
+            }
+            .into()
+        })
     }
 }
 
@@ -138,19 +602,61 @@ impl From<io::Stdout> for FileOrStdout {
     }
 }
 
+impl<'a> FileOrStdoutLock<'a> {
+    /// Whether the output is an interactive terminal.
+    pub fn is_terminal(&self) -> bool {
+        match self.file() {
+            Some(file) => file.is_terminal(),
+            None => io::stdout().is_terminal(),
+        }
+    }
+
+    fn file(&self) -> Option<&File> {
+        match &self.inner {
+            OutputLock::FileBufWriter(writer) => Some(writer.get_ref()),
+            OutputLock::FileLineWriter(writer) => Some(writer.get_ref()),
+            OutputLock::FileUnbuffered(file) => Some(file),
+            OutputLock::StdoutLock(_)
+            | OutputLock::StdoutBufWriter(_)
+            | OutputLock::StdoutUnbuffered(_) => None,
+        }
+    }
+
+    fn key(&self) -> instrument::Key {
+        self.key
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.inner {
+            OutputLock::FileBufWriter(writer) => writer,
+            OutputLock::FileLineWriter(writer) => writer,
+            OutputLock::FileUnbuffered(file) => file,
+            OutputLock::StdoutLock(stdout) => stdout,
+            OutputLock::StdoutBufWriter(writer) => writer,
+            OutputLock::StdoutUnbuffered(stdout) => stdout,
+        }
+    }
+}
+
+impl<'a> Drop for FileOrStdoutLock<'a> {
+    fn drop(&mut self) {
+        instrument::close(self.key());
+    }
+}
+
 impl<'a> Write for FileOrStdoutLock<'a> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self {
-            Self::FileBufWriter(file) => file.write(buf),
-            Self::StdoutLock(stdout) => stdout.write(buf),
+        let key = self.key();
+        let amt = instrument::write_with(key, self.writer(), buf)?;
+        if let OutputLock::StdoutUnbuffered(stdout) = &mut self.inner {
+            stdout.flush()?;
         }
+        Ok(amt)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        match self {
-            Self::FileBufWriter(file) => file.flush(),
-            Self::StdoutLock(stdout) => stdout.flush(),
-        }
+        let key = self.key();
+        instrument::flush_with(key, self.writer())
     }
 }
 
@@ -198,16 +704,37 @@ mod tests {
         })
     }
 
+    #[test]
+    fn peek_does_not_consume() -> Result<(), io::Error> {
+        with_temp_dir(|tmp_dir| {
+            let test_file_path = tmp_dir.path().join("test_peek.txt");
+            fs::write(&test_file_path, "{\"key\": \"value\"}")?;
+
+            let mut input = FileOrStdin::from_path(&test_file_path)?;
+            let mut lock = input.lock();
+            assert_eq!(lock.peek(2)?, b"{\"");
+            assert_eq!(lock.peek(100)?, b"{\"key\": \"value\"}");
+
+            let mut actual_content = String::new();
+            lock.read_to_string(&mut actual_content)?;
+            assert_eq!(actual_content, "{\"key\": \"value\"}");
+            assert_eq!(lock.peek(1)?, b"");
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn write_file() -> Result<(), io::Error> {
         with_temp_dir(|tmp_dir| {
             let expected_content = "Test write file content";
 
             let test_file_path = tmp_dir.path().join("test_write_file.txt");
-            FileOrStdout::from_path(&test_file_path)
+            let written = FileOrStdout::from_path(&test_file_path)
                 .unwrap()
                 .lock()
                 .write(expected_content.as_bytes())?;
+            assert_eq!(written, expected_content.len());
             let actual_content = fs::read_to_string(test_file_path)?;
             assert_eq!(actual_content, expected_content);
 
@@ -220,5 +747,43 @@ mod tests {
         })
     }
 
+    #[test]
+    fn inner_handles() -> Result<(), io::Error> {
+        with_temp_dir(|tmp_dir| {
+            let test_file_path = tmp_dir.path().join("test_inner.txt");
+            let output = FileOrStdout::from_path(&test_file_path)?;
+            output.as_file().unwrap().set_len(3)?;
+            assert!(output.into_inner().is_ok());
+            assert!(FileOrStdout::from_path("-")?.as_file().is_none());
+
+            let input = FileOrStdin::from_path(&test_file_path)?;
+            assert_eq!(input.as_file().unwrap().metadata()?.len(), 3);
+            assert!(FileOrStdin::from_path("-")?.into_inner().is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn buffering_modes() -> Result<(), io::Error> {
+        with_temp_dir(|tmp_dir| {
+            let path = tmp_dir.path().join("test_buffering.txt");
+            for (buffering, visible) in [
+                (Buffering::Auto, ""),
+                (Buffering::Block, ""),
+                (Buffering::Line, "done\n"),
+                (Buffering::None, "done\nwork"),
+            ] {
+                let mut output = FileOrStdout::from_path(&path)?;
+                let mut lock = output.lock_buffered(buffering);
+                lock.write_all(b"done\nwork")?;
+                assert_eq!(fs::read_to_string(&path)?, visible, "{:?}", buffering);
+                drop(lock);
+                assert_eq!(fs::read_to_string(&path)?, "done\nwork");
+            }
+            Ok(())
+        })
+    }
+
     // TODO: stdin/stdout
 }
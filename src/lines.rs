@@ -0,0 +1,99 @@
+use crate::FileOrStdinLock;
+use std::{
+    io::{self, BufRead},
+    ops::ControlFlow,
+};
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Call `f` with each remaining line, without its `\n` or `\r\n` ending, until it returns
+    /// `Break`.
+    ///
+    /// Lines are handed out straight from the read buffer where possible, and otherwise from a
+    /// single reused buffer, so nothing is allocated per line. Returns `Break` with `f`'s value
+    /// if it stopped early.
+    pub fn for_line_bytes<B, F>(&mut self, mut f: F) -> io::Result<ControlFlow<B>>
+    where
+        F: FnMut(&[u8]) -> ControlFlow<B>,
+    {
+        let mut long_line = Vec::new();
+        loop {
+            let buf = match self.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() {
+                if !long_line.is_empty() {
+                    if let ControlFlow::Break(b) = f(trim_cr(&long_line)) {
+                        return Ok(ControlFlow::Break(b));
+                    }
+                }
+                return Ok(ControlFlow::Continue(()));
+            }
+
+            let (flow, used) = match buf.iter().position(|b| *b == b'\n') {
+                Some(i) if long_line.is_empty() => (f(trim_cr(&buf[..i])), i + 1),
+                Some(i) => {
+                    long_line.extend_from_slice(&buf[..i]);
+                    let flow = f(trim_cr(&long_line));
+                    long_line.clear();
+                    (flow, i + 1)
+                }
+                None => {
+                    // The line continues past the buffer, so it has to be copied.
+                    long_line.extend_from_slice(buf);
+                    (ControlFlow::Continue(()), buf.len())
+                }
+            };
+            self.consume(used);
+            if let ControlFlow::Break(b) = flow {
+                return Ok(ControlFlow::Break(b));
+            }
+        }
+    }
+}
+
+fn trim_cr(line: &[u8]) -> &[u8] {
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FileOrStdin;
+    use std::{fs, io, ops::ControlFlow};
+    use tempfile::TempDir;
+
+    #[test]
+    fn iterate_line_bytes() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("lines.txt");
+        let long = "x".repeat(20000);
+        fs::write(&path, format!("one\r\n{}\nthree\nfour", long))?;
+
+        let mut lines = Vec::new();
+        let mut input = FileOrStdin::from_path(&path)?;
+        let flow = input.lock().for_line_bytes(|line| {
+            lines.push(line.len());
+            ControlFlow::<()>::Continue(())
+        })?;
+        assert_eq!(flow, ControlFlow::Continue(()));
+        assert_eq!(lines, [3, 20000, 5, 4]);
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        let found = lock.for_line_bytes(|line| {
+            if line.starts_with(b"th") {
+                ControlFlow::Break(String::from_utf8_lossy(line).into_owned())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?;
+        assert_eq!(found, ControlFlow::Break("three".to_string()));
+        let mut rest = String::new();
+        io::Read::read_to_string(&mut lock, &mut rest)?;
+        assert_eq!(rest, "four");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use crate::FileOrStdinLock;
+use std::io;
+
+const TAR_MAGIC_OFFSET: usize = 257;
+const MAGIC_LEN: usize = TAR_MAGIC_OFFSET + 8;
+
+/// File formats recognized by their leading "magic" bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+    Lz4,
+    Tar,
+    Zip,
+    Png,
+    Jpeg,
+    Gif,
+    Pdf,
+    Elf,
+    Utf8Bom,
+    Utf16LeBom,
+    Utf16BeBom,
+    Utf32LeBom,
+    Utf32BeBom,
+}
+
+const SIGNATURES: &[(&[u8], Format)] = &[
+    (b"\x1f\x8b", Format::Gzip),
+    (b"\x28\xb5\x2f\xfd", Format::Zstd),
+    (b"BZh", Format::Bzip2),
+    (b"\xfd7zXZ\x00", Format::Xz),
+    (b"\x04\x22\x4d\x18", Format::Lz4),
+    (b"PK\x03\x04", Format::Zip),
+    (b"PK\x05\x06", Format::Zip),
+    (b"PK\x07\x08", Format::Zip),
+    (b"\x89PNG\r\n\x1a\n", Format::Png),
+    (b"\xff\xd8\xff", Format::Jpeg),
+    (b"GIF87a", Format::Gif),
+    (b"GIF89a", Format::Gif),
+    (b"%PDF-", Format::Pdf),
+    (b"\x7fELF", Format::Elf),
+    (b"\xef\xbb\xbf", Format::Utf8Bom),
+    // UTF-32 must come before UTF-16, which shares its first two bytes.
+    (b"\xff\xfe\x00\x00", Format::Utf32LeBom),
+    (b"\x00\x00\xfe\xff", Format::Utf32BeBom),
+    (b"\xff\xfe", Format::Utf16LeBom),
+    (b"\xfe\xff", Format::Utf16BeBom),
+];
+
+impl Format {
+    /// Identify the format of a stream from its first bytes.
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        if let Some((_, format)) = SIGNATURES.iter().find(|(sig, _)| bytes.starts_with(sig)) {
+            return Some(*format);
+        }
+        match bytes.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5) {
+            Some(b"ustar") => Some(Self::Tar),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Gzip => "application/gzip",
+            Self::Zstd => "application/zstd",
+            Self::Bzip2 => "application/x-bzip2",
+            Self::Xz => "application/x-xz",
+            Self::Lz4 => "application/x-lz4",
+            Self::Tar => "application/x-tar",
+            Self::Zip => "application/zip",
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::Pdf => "application/pdf",
+            Self::Elf => "application/x-executable",
+            Self::Utf8Bom
+            | Self::Utf16LeBom
+            | Self::Utf16BeBom
+            | Self::Utf32LeBom
+            | Self::Utf32BeBom => "text/plain",
+        }
+    }
+
+    /// Whether this is a compression wrapper around some other content.
+    pub fn is_compressed(self) -> bool {
+        matches!(
+            self,
+            Self::Gzip | Self::Zstd | Self::Bzip2 | Self::Xz | Self::Lz4
+        )
+    }
+}
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Peek at the start of the input and identify its format by magic bytes.
+    pub fn detect_format(&mut self) -> io::Result<Option<Format>> {
+        Ok(Format::from_magic(self.peek(MAGIC_LEN)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_magic() {
+        assert_eq!(Format::from_magic(b"\x1f\x8b\x08\x00"), Some(Format::Gzip));
+        assert_eq!(Format::from_magic(b"%PDF-1.7"), Some(Format::Pdf));
+        assert_eq!(Format::from_magic(b"\xff\xfeh\x00"), Some(Format::Utf16LeBom));
+        assert_eq!(Format::from_magic(b"\xff\xfe\x00\x00"), Some(Format::Utf32LeBom));
+        assert_eq!(Format::from_magic(b"plain text"), None);
+
+        let mut tar_header = vec![0; 512];
+        tar_header[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 6].copy_from_slice(b"ustar\0");
+        assert_eq!(Format::from_magic(&tar_header), Some(Format::Tar));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{digest::Crc32, inflate::Inflate};
+use std::io::{self, BufRead, BufReader, Read};
+
+fn invalid(format: &str, msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid {} data: {}", format, msg),
+    )
+}
+
+/// Decoder for the first member of gzip data (RFC 1952), ignoring anything after it.
+pub(crate) struct GzipMember<R> {
+    inflate: Inflate<BufReader<R>>,
+    crc: Crc32,
+    len: u64,
+    done: bool,
+}
+
+impl<R: Read> GzipMember<R> {
+    pub(crate) fn new(inner: R) -> io::Result<Self> {
+        let mut inner = BufReader::new(inner);
+        let mut header = [0; 10];
+        inner.read_exact(&mut header)?;
+        if header[..3] != [0x1f, 0x8b, 8] {
+            return Err(invalid("gzip", "not a gzip member"));
+        }
+        let flags = header[3];
+        if flags & 0x04 != 0 {
+            let mut len = [0; 2];
+            inner.read_exact(&mut len)?;
+            io::copy(
+                &mut (&mut inner).take(u16::from_le_bytes(len).into()),
+                &mut io::sink(),
+            )?;
+        }
+        // File name, then comment, each NUL-terminated.
+        for flag in [0x08, 0x10] {
+            if flags & flag != 0 {
+                inner.read_until(0, &mut Vec::new())?;
+            }
+        }
+        if flags & 0x02 != 0 {
+            inner.read_exact(&mut [0; 2])?;
+        }
+        Ok(Self {
+            inflate: Inflate::new(inner),
+            crc: Crc32::new(),
+            len: 0,
+            done: false,
+        })
+    }
+
+    fn check_trailer(&mut self) -> io::Result<()> {
+        let mut trailer = [0; 8];
+        self.inflate.read_trailer(&mut trailer)?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let len = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if crc != self.crc.finish() {
+            return Err(invalid("gzip", "CRC mismatch"));
+        }
+        if len != self.len as u32 {
+            return Err(invalid("gzip", "length mismatch"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for GzipMember<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done {
+            return Ok(0);
+        }
+        let amt = self.inflate.read(buf)?;
+        self.crc.update(&buf[..amt]);
+        self.len += amt as u64;
+        if amt == 0 && !buf.is_empty() {
+            self.check_trailer()?;
+            self.done = true;
+        }
+        Ok(amt)
+    }
+}
+
+const ZSTD_MAGIC: u32 = 0xfd2f_b528;
+
+/// Reader passing on the bytes of the first zstd frame of `R` (and any skippable frames before
+/// it), found by walking the frame and block headers, and nothing after it.
+pub(crate) struct ZstdFrame<R> {
+    inner: R,
+    /// Header bytes read to find the frame's layout, to be passed on.
+    header: Vec<u8>,
+    header_pos: usize,
+    /// Bytes to pass on straight from `inner` after the header.
+    remaining: u64,
+    part: Part,
+}
+
+#[derive(Clone, Copy)]
+enum Part {
+    FrameStart,
+    Block { checksum: bool },
+    Checksum,
+    Done,
+}
+
+impl<R: Read> ZstdFrame<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            header: Vec::new(),
+            header_pos: 0,
+            remaining: 0,
+            part: Part::FrameStart,
+        }
+    }
+
+    /// Read the next header into `self.header`, returning whether there is more to pass on.
+    fn next_part(&mut self) -> io::Result<bool> {
+        self.header.clear();
+        self.header_pos = 0;
+        match self.part {
+            Part::FrameStart => {
+                let magic = u32::from_le_bytes(self.read_header::<4>()?);
+                if magic & 0xffff_fff0 == 0x184d_2a50 {
+                    self.remaining = u32::from_le_bytes(self.read_header::<4>()?).into();
+                    return Ok(true);
+                }
+                if magic != ZSTD_MAGIC {
+                    return Err(invalid("zstd", "not a zstd frame"));
+                }
+                let [descriptor] = self.read_header::<1>()?;
+                let single_segment = descriptor & 0x20 != 0;
+                let dict_id_len = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+                let content_size_len = match descriptor >> 6 {
+                    0 if single_segment => 1,
+                    0 => 0,
+                    flag => 1 << flag,
+                };
+                let window_len = if single_segment { 0 } else { 1 };
+                self.remaining = window_len + dict_id_len + content_size_len;
+                self.part = Part::Block {
+                    checksum: descriptor & 0x04 != 0,
+                };
+            }
+            Part::Block { checksum } => {
+                let [a, b, c] = self.read_header::<3>()?;
+                let block = u32::from_le_bytes([a, b, c, 0]);
+                let size = (block >> 3) as u64;
+                self.remaining = match (block >> 1) & 0x03 {
+                    0 | 2 => size,
+                    1 => 1,
+                    _ => return Err(invalid("zstd", "reserved block type")),
+                };
+                if block & 1 != 0 {
+                    self.part = if checksum { Part::Checksum } else { Part::Done };
+                }
+            }
+            Part::Checksum => {
+                self.remaining = 4;
+                self.part = Part::Done;
+            }
+            Part::Done => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn read_header<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.inner.read_exact(&mut bytes)?;
+        self.header.extend_from_slice(&bytes);
+        Ok(bytes)
+    }
+}
+
+impl<R: Read> Read for ZstdFrame<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.header_pos < self.header.len() {
+                let amt = (&self.header[self.header_pos..]).read(buf)?;
+                self.header_pos += amt;
+                return Ok(amt);
+            }
+            if self.remaining > 0 {
+                let max = buf
+                    .len()
+                    .min(self.remaining.min(usize::MAX as u64) as usize);
+                let amt = self.inner.read(&mut buf[..max])?;
+                if amt == 0 && max > 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                self.remaining -= amt as u64;
+                return Ok(amt);
+            }
+            if !self.next_part()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    fn gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut child = Command::new("gzip")
+            .arg("-c")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let input = data.to_vec();
+        let writer = std::thread::spawn(move || io::Write::write_all(&mut stdin, &input));
+        let output = child.wait_with_output()?;
+        writer.join().unwrap()?;
+        Ok(output.stdout)
+    }
+
+    #[test]
+    fn first_members() -> Result<(), io::Error> {
+        let mut concatenated = gzip(&b"first ".repeat(1000))?;
+        concatenated.extend(gzip(b"second")?);
+        let mut content = Vec::new();
+        GzipMember::new(&concatenated[..])?.read_to_end(&mut content)?;
+        assert_eq!(content, b"first ".repeat(1000));
+
+        let mut corrupt = gzip(b"data")?;
+        let crc = corrupt.len() - 8;
+        corrupt[crc] ^= 1;
+        let e = GzipMember::new(&corrupt[..])?
+            .read_to_end(&mut Vec::new())
+            .expect_err("the CRC is wrong");
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        // Single segment with a one-byte content size, then a raw and a last RLE block, then a
+        // checksum.
+        let frame = [
+            &[0x28, 0xb5, 0x2f, 0xfd, 0x24, 5][..],
+            &[2 << 3, 0, 0, b'a', b'b'],
+            &[1 | 1 << 1 | 3 << 3, 0, 0, b'c'],
+            &[1, 2, 3, 4],
+        ]
+        .concat();
+        let skippable = [&[0x50, 0x2a, 0x4d, 0x18, 2, 0, 0, 0][..], b"hi"].concat();
+        let stream = [&skippable[..], &frame, &frame].concat();
+        let mut passed = Vec::new();
+        ZstdFrame::new(&stream[..]).read_to_end(&mut passed)?;
+        assert_eq!(passed, [&skippable[..], &frame].concat());
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use crate::STDIO_FILENAME;
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+/// Merge already-sorted, line-oriented inputs into one sorted stream, like `sort -m`.
+///
+/// Only one line per input is held in memory. Lines are compared without their line terminator;
+/// lines that compare equal come out in input order, and a last line missing its newline gets
+/// one. `-` means stdin. All inputs are opened right away, so a missing file is reported here
+/// rather than partway through the merge.
+///
+/// ```no_run
+/// # use polymorphio::merge_sorted;
+/// # use std::io::Read;
+/// let mut merged = String::new();
+/// merge_sorted(&["a.log", "b.log"], Ord::cmp)?.read_to_string(&mut merged)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn merge_sorted<I, P, F>(paths: I, cmp: F) -> io::Result<MergeSorted<F>>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    F: FnMut(&[u8], &[u8]) -> Ordering,
+{
+    let sources = paths
+        .into_iter()
+        .map(|path| -> io::Result<Box<dyn BufRead + Send>> {
+            let path = path.as_ref();
+            Ok(if path.to_string_lossy() == STDIO_FILENAME {
+                Box::new(BufReader::new(io::stdin()))
+            } else {
+                Box::new(BufReader::new(File::open(path)?))
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(MergeSorted {
+        heads: Vec::with_capacity(sources.len()),
+        sources,
+        cmp,
+        line: Vec::new(),
+        pos: 0,
+    })
+}
+
+/// Reader returned by [`merge_sorted`].
+pub struct MergeSorted<F> {
+    sources: Vec<Box<dyn BufRead + Send>>,
+    /// The next line of each source, or `None` once it is exhausted. Empty until the first read.
+    heads: Vec<Option<Vec<u8>>>,
+    cmp: F,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl<F: FnMut(&[u8], &[u8]) -> Ordering> MergeSorted<F> {
+    fn next_line(source: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+        let mut line = Vec::new();
+        if source.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        Ok(Some(line))
+    }
+
+    /// Move the smallest head into `line`, returning `false` once all inputs are exhausted.
+    fn advance(&mut self) -> io::Result<bool> {
+        if self.heads.is_empty() {
+            for source in &mut self.sources {
+                self.heads.push(Self::next_line(source.as_mut())?);
+            }
+        }
+
+        let mut smallest: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let head = match head {
+                Some(head) => head,
+                None => continue,
+            };
+            let is_smaller = match smallest {
+                None => true,
+                Some(s) => {
+                    let current = self.heads[s].as_deref().unwrap();
+                    (self.cmp)(key(head), key(current)) == Ordering::Less
+                }
+            };
+            if is_smaller {
+                smallest = Some(i);
+            }
+        }
+
+        let i = match smallest {
+            Some(i) => i,
+            None => return Ok(false),
+        };
+        self.line = self.heads[i].take().unwrap();
+        self.pos = 0;
+        self.heads[i] = Self::next_line(self.sources[i].as_mut())?;
+        Ok(true)
+    }
+}
+
+/// A line without its terminator.
+fn key(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+impl<F: FnMut(&[u8], &[u8]) -> Ordering> Read for MergeSorted<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.fill_buf()?.read(buf)?;
+        self.consume(amt);
+        Ok(amt)
+    }
+}
+
+impl<F: FnMut(&[u8], &[u8]) -> Ordering> BufRead for MergeSorted<F> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.line.len() && !self.advance()? {
+            return Ok(&[]);
+        }
+        Ok(&self.line[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.line.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn merge_in_order() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let a = tmp_dir.path().join("a");
+        let b = tmp_dir.path().join("b");
+        let c = tmp_dir.path().join("c");
+        fs::write(&a, "1 a\n4 a\n")?;
+        fs::write(&b, "2 b\n4 b\n5 b")?;
+        fs::write(&c, "")?;
+
+        let mut merged = String::new();
+        merge_sorted([&a, &b, &c], |x, y| x[..1].cmp(&y[..1]))?.read_to_string(&mut merged)?;
+        assert_eq!(merged, "1 a\n2 b\n4 a\n4 b\n5 b\n");
+
+        assert!(merge_sorted([tmp_dir.path().join("missing")], Ord::cmp).is_err());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
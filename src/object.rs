@@ -0,0 +1,139 @@
+use crate::{
+    command::{CommandReader, CommandWriter},
+    Error,
+};
+use std::{
+    io::{self, Read, Write},
+    process::Command,
+};
+
+/// Streaming download of a cloud storage object: `s3://bucket/key` (with the `s3` feature)
+/// or `gs://bucket/object` (with the `gcs` feature).
+///
+/// Uses the system's `aws` or `gcloud` tool, so the credentials those are set up with apply.
+/// Other schemes fail with [`Error::UnsupportedScheme`].
+pub struct ObjectInput {
+    inner: CommandReader,
+}
+
+/// Streaming upload of a cloud storage object, like [`ObjectInput`].
+///
+/// The tools upload large objects in parts as data arrives. The object only appears once
+/// [`finish`](ObjectOutput::finish) succeeds; dropping the writer instead kills the tool, so
+/// nothing is uploaded, though the parts of a large upload may be left for the bucket's
+/// lifecycle rules to clean up.
+pub struct ObjectOutput {
+    inner: CommandWriter,
+}
+
+/// Whether `path` is a cloud storage URL, supported in this build or not.
+pub fn is_object_url<P: AsRef<std::path::Path>>(path: P) -> bool {
+    let path = path.as_ref().to_string_lossy();
+    ["s3://", "gs://"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+impl ObjectInput {
+    pub fn open(url: &str) -> io::Result<Self> {
+        let (program, args) = download_command(url)?;
+        let mut command = Command::new(program);
+        command.args(&args);
+        Ok(Self {
+            inner: CommandReader::new(program, command)?,
+        })
+    }
+}
+
+impl Read for ObjectInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl ObjectOutput {
+    pub fn create(url: &str) -> io::Result<Self> {
+        let (program, args) = upload_command(url)?;
+        let mut command = Command::new(program);
+        command.args(&args);
+        Ok(Self {
+            inner: CommandWriter::new(program, command)?,
+        })
+    }
+
+    /// Complete the upload.
+    pub fn finish(self) -> io::Result<()> {
+        self.inner.finish()
+    }
+}
+
+impl Write for ObjectOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The scheme of `url`, checked to be supported in this build.
+fn scheme(url: &str) -> io::Result<&str> {
+    let scheme = url.split_once("://").map_or("", |(scheme, _)| scheme);
+    match scheme {
+        #[cfg(feature = "s3")]
+        "s3" => Ok(scheme),
+        #[cfg(feature = "gcs")]
+        "gs" => Ok(scheme),
+        _ => Err(Error::UnsupportedScheme {
+            scheme: scheme.to_owned(),
+        }
+        .into()),
+    }
+}
+
+fn download_command(url: &str) -> io::Result<(&'static str, Vec<&str>)> {
+    Ok(match scheme(url)? {
+        "s3" => ("aws", vec!["s3", "cp", "--quiet", url, "-"]),
+        _ => ("gcloud", vec!["storage", "cat", url]),
+    })
+}
+
+fn upload_command(url: &str) -> io::Result<(&'static str, Vec<&str>)> {
+    Ok(match scheme(url)? {
+        "s3" => ("aws", vec!["s3", "cp", "--quiet", "-", url]),
+        _ => ("gcloud", vec!["storage", "cp", "-", url]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_commands() -> Result<(), io::Error> {
+        assert!(is_object_url("s3://bucket/key"));
+        assert!(!is_object_url("bucket/key"));
+
+        #[cfg(feature = "s3")]
+        assert_eq!(
+            upload_command("s3://bucket/a.csv")?,
+            ("aws", vec!["s3", "cp", "--quiet", "-", "s3://bucket/a.csv"])
+        );
+        #[cfg(feature = "gcs")]
+        assert_eq!(
+            download_command("gs://bucket/a.csv")?,
+            ("gcloud", vec!["storage", "cat", "gs://bucket/a.csv"])
+        );
+
+        let e = ObjectInput::open("ftp://host/a.csv")
+            .err()
+            .expect("ftp isn't supported");
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        assert!(matches!(
+            Error::of(&e),
+            Some(Error::UnsupportedScheme { scheme }) if scheme == "ftp"
+        ));
+        Ok(())
+    }
+}
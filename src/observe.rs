@@ -0,0 +1,254 @@
+use crate::STDIO_FILENAME;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// Hooks called as an [`Observed`] stream is used, for metrics, auditing or content scanning.
+///
+/// Every method defaults to doing nothing. `on_read` sees the bytes handed to the reader and
+/// `on_write` the bytes the inner writer accepted. Interrupted calls aren't reported as errors,
+/// since they're retried.
+pub trait IoObserver {
+    fn on_open(&mut self, _path: &Path) {}
+
+    fn on_read(&mut self, _data: &[u8]) {}
+
+    fn on_write(&mut self, _data: &[u8]) {}
+
+    fn on_close(&mut self) {}
+
+    fn on_error(&mut self, _error: &io::Error) {}
+}
+
+impl<O: IoObserver + ?Sized> IoObserver for &mut O {
+    fn on_open(&mut self, path: &Path) {
+        (**self).on_open(path)
+    }
+
+    fn on_read(&mut self, data: &[u8]) {
+        (**self).on_read(data)
+    }
+
+    fn on_write(&mut self, data: &[u8]) {
+        (**self).on_write(data)
+    }
+
+    fn on_close(&mut self) {
+        (**self).on_close()
+    }
+
+    fn on_error(&mut self, error: &io::Error) {
+        (**self).on_error(error)
+    }
+}
+
+impl<O: IoObserver + ?Sized> IoObserver for Box<O> {
+    fn on_open(&mut self, path: &Path) {
+        (**self).on_open(path)
+    }
+
+    fn on_read(&mut self, data: &[u8]) {
+        (**self).on_read(data)
+    }
+
+    fn on_write(&mut self, data: &[u8]) {
+        (**self).on_write(data)
+    }
+
+    fn on_close(&mut self) {
+        (**self).on_close()
+    }
+
+    fn on_error(&mut self, error: &io::Error) {
+        (**self).on_error(error)
+    }
+}
+
+/// A reader or writer reporting its use to an [`IoObserver`].
+///
+/// `on_close` is called when it's dropped, before the inner stream is. Pass `&mut observer` to
+/// look at the observer's state afterwards.
+pub struct Observed<T, O: IoObserver> {
+    inner: T,
+    observer: O,
+}
+
+impl<O: IoObserver> Observed<Box<dyn BufRead + Send>, O> {
+    /// Open the input at `path`, or stdin for `-`, reporting the open or its failure.
+    pub fn input<P: AsRef<Path>>(path: P, mut observer: O) -> io::Result<Self> {
+        let path = path.as_ref();
+        let inner: Box<dyn BufRead + Send> = if path.to_string_lossy() == STDIO_FILENAME {
+            Box::new(BufReader::new(io::stdin()))
+        } else {
+            match File::open(path) {
+                Ok(file) => Box::new(BufReader::new(file)),
+                Err(e) => {
+                    observer.on_error(&e);
+                    return Err(e);
+                }
+            }
+        };
+        observer.on_open(path);
+        Ok(Self::new(inner, observer))
+    }
+}
+
+impl<O: IoObserver> Observed<Box<dyn Write + Send>, O> {
+    /// Create the output at `path`, or stdout for `-`, reporting the open or its failure.
+    pub fn output<P: AsRef<Path>>(path: P, mut observer: O) -> io::Result<Self> {
+        let path = path.as_ref();
+        let inner: Box<dyn Write + Send> = if path.to_string_lossy() == STDIO_FILENAME {
+            Box::new(io::stdout())
+        } else {
+            match File::create(path) {
+                Ok(file) => Box::new(BufWriter::new(file)),
+                Err(e) => {
+                    observer.on_error(&e);
+                    return Err(e);
+                }
+            }
+        };
+        observer.on_open(path);
+        Ok(Self::new(inner, observer))
+    }
+}
+
+impl<T, O: IoObserver> Observed<T, O> {
+    /// Observe an already open stream. `on_open` isn't called.
+    pub fn new(inner: T, observer: O) -> Self {
+        Self { inner, observer }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    fn report<R>(&mut self, result: io::Result<R>) -> io::Result<R> {
+        if let Err(e) = &result {
+            if e.kind() != io::ErrorKind::Interrupted {
+                self.observer.on_error(e);
+            }
+        }
+        result
+    }
+}
+
+impl<T: Read, O: IoObserver> Read for Observed<T, O> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.inner.read(buf);
+        let amt = self.report(result)?;
+        self.observer.on_read(&buf[..amt]);
+        Ok(amt)
+    }
+}
+
+impl<T: BufRead, O: IoObserver> BufRead for Observed<T, O> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // Filled twice to satisfy the borrow checker; the second call just returns the buffer.
+        if let Err(e) = self.inner.fill_buf() {
+            if e.kind() != io::ErrorKind::Interrupted {
+                self.observer.on_error(&e);
+            }
+            return Err(e);
+        }
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The consumed bytes are still in the inner buffer, so filling it again is free.
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.observer.on_read(&buf[..amt.min(buf.len())]);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+impl<T: Write, O: IoObserver> Write for Observed<T, O> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        let amt = self.report(result)?;
+        self.observer.on_write(&buf[..amt]);
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.flush();
+        self.report(result)
+    }
+}
+
+impl<T, O: IoObserver> Drop for Observed<T, O> {
+    fn drop(&mut self) {
+        self.observer.on_close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[derive(Default)]
+    struct Audit {
+        events: Vec<String>,
+        bytes: usize,
+    }
+
+    impl IoObserver for Audit {
+        fn on_open(&mut self, path: &Path) {
+            self.events.push(format!(
+                "open {}",
+                path.file_name().unwrap().to_string_lossy()
+            ));
+        }
+
+        fn on_read(&mut self, data: &[u8]) {
+            self.bytes += data.len();
+        }
+
+        fn on_write(&mut self, data: &[u8]) {
+            self.bytes += data.len();
+        }
+
+        fn on_close(&mut self) {
+            self.events.push(format!("close {}", self.bytes));
+        }
+
+        fn on_error(&mut self, error: &io::Error) {
+            self.events.push(format!("error {:?}", error.kind()));
+        }
+    }
+
+    #[test]
+    fn observe_reads_and_writes() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("out.txt");
+
+        let mut audit = Audit::default();
+        let mut output = Observed::output(&path, &mut audit)?;
+        output.write_all(b"one\ntwo\n")?;
+        drop(output);
+        assert_eq!(audit.events, ["open out.txt", "close 8"]);
+
+        let mut audit = Audit::default();
+        let mut input = Observed::input(&path, &mut audit)?;
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+        assert_eq!(line, "one\n");
+        drop(input);
+        assert_eq!(audit.events, ["open out.txt", "close 4"]);
+
+        let mut audit = Audit::default();
+        assert!(Observed::input(tmp_dir.path().join("missing"), &mut audit).is_err());
+        assert_eq!(audit.events, ["error NotFound"]);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+use crate::{
+    fifo::FifoOptions, instrument, registry, retry::OpenRetry, scheme, space, Error, FileOrStdin,
+    FileOrStdout, STDIO_FILENAME,
+};
+use std::{
+    error, fmt,
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Options for opening a [`FileOrStdin`], like `OpenOptions` for inputs.
+///
+/// `InputOptions::new().open(path)` is the same as [`FileOrStdin::from_path`].
+#[derive(Debug, Clone)]
+pub struct InputOptions {
+    noatime: bool,
+    follow_symlinks: bool,
+    retry: Option<OpenRetry>,
+    fifo: FifoOptions,
+}
+
+/// Options for opening a [`FileOrStdout`], like `OpenOptions` for outputs.
+///
+/// `OutputOptions::new().open(path)` is the same as [`FileOrStdout::from_path`].
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    mode: u32,
+    secret: bool,
+    resume: bool,
+    size_hint: Option<u64>,
+    retry: Option<OpenRetry>,
+    fifo: FifoOptions,
+}
+
+/// An input refused because it is a symlink, from [`InputOptions::follow_symlinks`].
+///
+/// Carried inside an `InvalidInput` error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymlinkError {
+    path: PathBuf,
+}
+
+impl SymlinkError {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl fmt::Display for SymlinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is a symlink", self.path.display())
+    }
+}
+
+impl error::Error for SymlinkError {}
+
+impl Default for InputOptions {
+    fn default() -> Self {
+        Self {
+            noatime: false,
+            follow_symlinks: true,
+            retry: None,
+            fifo: FifoOptions::default(),
+        }
+    }
+}
+
+impl InputOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Don't update the file's access time by reading it, so backup and indexing tools leave
+    /// atime-based workflows alone.
+    ///
+    /// Uses `O_NOATIME` on Linux, which is only permitted for the file's owner (or root); for
+    /// other files, and on other platforms, this has no effect.
+    pub fn noatime(mut self, yes: bool) -> Self {
+        self.noatime = yes;
+        self
+    }
+
+    /// Whether to follow symlinks to the file they point to, which is the default; `false`
+    /// refuses them with a [`SymlinkError`].
+    ///
+    /// Only the last component of the path is checked; [`Root`](crate::Root) confines the whole
+    /// path to a directory. On unix this uses `O_NOFOLLOW`, so the check can't be raced.
+    pub fn follow_symlinks(mut self, yes: bool) -> Self {
+        self.follow_symlinks = yes;
+        self
+    }
+
+    /// Retry up to `attempts` times while the file is locked or busy, e.g. by an antivirus
+    /// scanner or an editor on Windows, sleeping about `backoff` before the first retry and
+    /// twice as long before each one after it.
+    pub fn retry(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.retry = Some(OpenRetry { attempts, backoff });
+        self
+    }
+
+    /// Give up opening a named pipe (FIFO) with `TimedOut` if no writer opens it within
+    /// `timeout`, instead of waiting forever. Only has an effect on unix; on platforms other
+    /// than Linux, a FIFO nobody has written to yet may count as opened.
+    pub fn fifo_timeout(mut self, timeout: Duration) -> Self {
+        self.fifo.timeout = Some(timeout);
+        self
+    }
+
+    /// Open without waiting for a FIFO's writer, and leave the file non-blocking, so reads fail
+    /// with `WouldBlock` instead of waiting for data. Only has an effect on unix.
+    pub fn nonblocking(mut self, yes: bool) -> Self {
+        self.fifo.nonblocking = yes;
+        self
+    }
+
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<FileOrStdin> {
+        let path = path.as_ref();
+        if path.to_string_lossy() == STDIO_FILENAME {
+            instrument::open(instrument::STDIN, path);
+            return Ok(io::stdin().into());
+        }
+        if let Some(input) = scheme::open_input(path) {
+            return input.map_err(|source| {
+                Error::OpenInput {
+                    path: path.to_owned(),
+                    source,
+                }
+                .into()
+            });
+        }
+        let open = |p: &Path| self.fifo.open(p, false, |flags| self.open_file(p, flags));
+        Ok(instrument::open_file(path, |p| match &self.retry {
+            Some(retry) => retry.run(|| open(p)),
+            None => open(p),
+        })
+        .map_err(|source| Error::OpenInput {
+            path: path.to_owned(),
+            source,
+        })?
+        .into())
+    }
+
+    #[cfg(unix)]
+    fn open_file(&self, path: &Path, mut flags: libc::c_int) -> io::Result<File> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if !self.follow_symlinks {
+            flags |= libc::O_NOFOLLOW;
+        }
+        let open = |flags| {
+            let result = OpenOptions::new().read(true).custom_flags(flags).open(path);
+            match result {
+                Err(e) if !self.follow_symlinks && e.raw_os_error() == Some(libc::ELOOP) => {
+                    Err(symlink_error(path))
+                }
+                result => result,
+            }
+        };
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if self.noatime {
+            match open(flags | libc::O_NOATIME) {
+                Err(e) if e.raw_os_error() == Some(libc::EPERM) => {}
+                result => return result,
+            }
+        }
+        open(flags)
+    }
+
+    #[cfg(not(unix))]
+    fn open_file(&self, path: &Path, _flags: i32) -> io::Result<File> {
+        if !self.follow_symlinks && path.symlink_metadata()?.file_type().is_symlink() {
+            return Err(symlink_error(path));
+        }
+        File::open(path)
+    }
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            mode: 0o666,
+            secret: false,
+            resume: false,
+            size_hint: None,
+            retry: None,
+            fifo: FifoOptions::default(),
+        }
+    }
+}
+
+impl OutputOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Permission bits for a newly created file, before the umask is applied. Defaults to
+    /// `0o666`. Existing files keep their permissions. Ignored on non-unix platforms.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Make the file readable and writable only by its owner (`0o600`), e.g. for credentials,
+    /// instead of using the [`mode`](OutputOptions::mode).
+    ///
+    /// A new file is created with these permissions, and an existing one is changed to them
+    /// before anything is written, so there is no moment when others can read what's written.
+    /// Opening fails if an existing file's permissions can't be changed. Other kinds of file,
+    /// like FIFOs and devices, are left alone. Ignored on non-unix platforms.
+    pub fn secret(mut self, yes: bool) -> Self {
+        self.secret = yes;
+        self
+    }
+
+    /// Append to an existing file instead of replacing it, to carry on an interrupted job. Skip
+    /// the part of the input that's already there with
+    /// [`FileOrStdin::resume_from`](crate::FileOrStdin::resume_from).
+    pub fn resume(mut self, yes: bool) -> Self {
+        self.resume = yes;
+        self
+    }
+
+    /// Expect to write about `bytes` bytes, and fail to open with
+    /// [`Error::InsufficientSpace`](crate::Error::InsufficientSpace) if they wouldn't fit, rather
+    /// than fill up the disk partway through. See [`check_free_space`](crate::check_free_space).
+    pub fn size_hint(mut self, bytes: u64) -> Self {
+        self.size_hint = Some(bytes);
+        self
+    }
+
+    /// Retry up to `attempts` times while the file is locked or busy, like
+    /// [`InputOptions::retry`].
+    pub fn retry(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.retry = Some(OpenRetry { attempts, backoff });
+        self
+    }
+
+    /// Give up opening a named pipe (FIFO) with `TimedOut` if no reader opens it within
+    /// `timeout`, like [`InputOptions::fifo_timeout`].
+    pub fn fifo_timeout(mut self, timeout: Duration) -> Self {
+        self.fifo.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail with `WouldBlock` instead of waiting for a FIFO's reader, and leave the file
+    /// non-blocking, like [`InputOptions::nonblocking`].
+    pub fn nonblocking(mut self, yes: bool) -> Self {
+        self.fifo.nonblocking = yes;
+        self
+    }
+
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<FileOrStdout> {
+        registry::open_output(path.as_ref(), |path| {
+            if path.to_string_lossy() == STDIO_FILENAME {
+                instrument::open(instrument::STDOUT, path);
+                return Ok(io::stdout().into());
+            }
+            scheme::check_output(path)?;
+            if let Some(bytes) = self.size_hint {
+                space::check_free_space(path, bytes)?;
+            }
+            let open_file = |p: &Path, _flags| {
+                let mut options = OpenOptions::new();
+                if self.resume {
+                    options.append(true).create(true);
+                } else {
+                    options.write(true).create(true).truncate(true);
+                }
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::OpenOptionsExt;
+                    let mode = if self.secret { 0o600 } else { self.mode };
+                    options.mode(mode).custom_flags(_flags);
+                }
+                let file = options.open(p)?;
+                #[cfg(unix)]
+                if self.secret && file.metadata()?.is_file() {
+                    use std::os::unix::fs::PermissionsExt;
+                    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+                }
+                Ok(file)
+            };
+            let open = |p: &Path| self.fifo.open(p, true, |flags| open_file(p, flags));
+            Ok(instrument::open_file(path, |p| match &self.retry {
+                Some(retry) => retry.run(|| open(p)),
+                None => open(p),
+            })
+            .map_err(|source| Error::OpenOutput {
+                path: path.to_owned(),
+                source,
+            })?
+            .into())
+        })
+    }
+}
+
+fn symlink_error(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        SymlinkError {
+            path: path.to_owned(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        io::Read,
+        time::{Duration, SystemTime},
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn open_options() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("data.txt");
+        fs::write(&path, "data")?;
+        let old_atime = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_times(fs::FileTimes::new().set_accessed(old_atime))?;
+
+        let mut input = InputOptions::new().noatime(true).open(&path)?;
+        let mut content = String::new();
+        input.lock().read_to_string(&mut content)?;
+        assert_eq!(content, "data");
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert_eq!(fs::metadata(&path)?.accessed()?, old_atime);
+
+        assert!(InputOptions::new().open("-")?.as_file().is_none());
+
+        #[cfg(unix)]
+        {
+            let link = tmp_dir.path().join("link.txt");
+            std::os::unix::fs::symlink(&path, &link)?;
+            InputOptions::new().open(&link)?;
+            let e = InputOptions::new()
+                .follow_symlinks(false)
+                .open(&link)
+                .err()
+                .expect("symlinks are refused");
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+            let inner = crate::error::causes(&e)
+                .into_iter()
+                .find_map(|e| e.downcast_ref::<SymlinkError>());
+            assert_eq!(inner.map(SymlinkError::path), Some(link.as_path()));
+            InputOptions::new().follow_symlinks(false).open(&path)?;
+        }
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secret_output_mode() -> Result<(), io::Error> {
+        use std::{io::Write, os::unix::fs::PermissionsExt};
+
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("token");
+        let mut output = OutputOptions::new().secret(true).open(&path)?;
+        output.lock().write_all(b"hunter2")?;
+        assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path)?, "hunter2");
+
+        let shared = tmp_dir.path().join("shared");
+        fs::write(&shared, "public")?;
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o644))?;
+        OutputOptions::new().secret(true).open(&shared)?;
+        assert_eq!(fs::metadata(&shared)?.permissions().mode() & 0o777, 0o600);
+
+        let path = tmp_dir.path().join("script");
+        OutputOptions::new().mode(0o700).secret(false).open(&path)?;
+        assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o700, 0o700);
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{instrument, Buffering, FileOrStdin, FileOrStdout};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, LineWriter, Read, Write},
+};
+
+/// A [`FileOrStdin`] locked for reading by value, so it's `Send + 'static` and can be moved into
+/// a worker thread or task, unlike [`FileOrStdinLock`](crate::FileOrStdinLock), which borrows
+/// the handle and, for stdin, holds a lock tied to the current thread.
+///
+/// Stdin is buffered here rather than locked: other readers of stdin take turns with this one
+/// between reads, and miss what this one has buffered.
+pub struct FileOrStdinOwnedLock {
+    inner: OwnedInput,
+    key: instrument::Key,
+}
+
+enum OwnedInput {
+    File(BufReader<File>),
+    Stdin(BufReader<io::Stdin>),
+}
+
+/// A [`FileOrStdout`] locked for writing by value, so it's `Send + 'static`; see
+/// [`FileOrStdinOwnedLock`].
+///
+/// Stdout is written through the standard library's handle, so writes from other threads
+/// interleave with this one's at flushes, or at every write when unbuffered.
+pub struct FileOrStdoutOwnedLock {
+    inner: OwnedOutput,
+    key: instrument::Key,
+}
+
+enum OwnedOutput {
+    FileBufWriter(BufWriter<File>),
+    FileLineWriter(LineWriter<File>),
+    FileUnbuffered(File),
+    /// Stdout as the standard library buffers it, by line.
+    Stdout(io::Stdout),
+    StdoutBufWriter(BufWriter<io::Stdout>),
+    /// Stdout flushed after every write.
+    StdoutUnbuffered(io::Stdout),
+}
+
+impl FileOrStdin {
+    /// Like [`lock`](FileOrStdin::lock), but consuming the handle.
+    pub fn into_lock(self) -> FileOrStdinOwnedLock {
+        let (inner, key) = match self {
+            Self::File(file) => {
+                let key = instrument::file_key(&file);
+                (OwnedInput::File(BufReader::new(file)), key)
+            }
+            Self::Stdin(stdin) => (OwnedInput::Stdin(BufReader::new(stdin)), instrument::STDIN),
+        };
+        FileOrStdinOwnedLock { inner, key }
+    }
+}
+
+impl FileOrStdout {
+    /// Like [`lock`](FileOrStdout::lock), but consuming the handle.
+    pub fn into_lock(self) -> FileOrStdoutOwnedLock {
+        self.into_lock_buffered(Buffering::Auto)
+    }
+
+    /// Like [`lock_buffered`](FileOrStdout::lock_buffered), but consuming the handle.
+    pub fn into_lock_buffered(self, buffering: Buffering) -> FileOrStdoutOwnedLock {
+        let buffering = self.resolve_buffering(buffering);
+        let key = self
+            .as_file()
+            .map_or(instrument::STDOUT, instrument::file_key);
+        let inner = match (self, buffering) {
+            (Self::File(file), Buffering::Line) => {
+                OwnedOutput::FileLineWriter(LineWriter::new(file))
+            }
+            (Self::File(file), Buffering::None) => OwnedOutput::FileUnbuffered(file),
+            (Self::File(file), _) => OwnedOutput::FileBufWriter(BufWriter::new(file)),
+            (Self::Stdout(stdout), Buffering::Block) => {
+                OwnedOutput::StdoutBufWriter(BufWriter::new(stdout))
+            }
+            (Self::Stdout(stdout), Buffering::None) => OwnedOutput::StdoutUnbuffered(stdout),
+            (Self::Stdout(stdout), _) => OwnedOutput::Stdout(stdout),
+        };
+        FileOrStdoutOwnedLock { inner, key }
+    }
+}
+
+impl FileOrStdinOwnedLock {
+    /// Whether the input is an interactive terminal.
+    pub fn is_terminal(&self) -> bool {
+        match &self.inner {
+            OwnedInput::File(reader) => reader.get_ref().is_terminal(),
+            OwnedInput::Stdin(reader) => reader.get_ref().is_terminal(),
+        }
+    }
+
+    fn reader(&mut self) -> &mut dyn BufRead {
+        match &mut self.inner {
+            OwnedInput::File(reader) => reader,
+            OwnedInput::Stdin(reader) => reader,
+        }
+    }
+}
+
+impl Read for FileOrStdinOwnedLock {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let key = self.key;
+        let reader = self.reader();
+        let amt = instrument::timed(key, instrument::Op::Read, || reader.read(buf))?;
+        instrument::transferred(key, instrument::Op::Read, amt);
+        Ok(amt)
+    }
+}
+
+impl BufRead for FileOrStdinOwnedLock {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let key = self.key;
+        let reader = self.reader();
+        instrument::timed(key, instrument::Op::Read, move || reader.fill_buf())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        instrument::transferred(self.key, instrument::Op::Read, amt);
+        self.reader().consume(amt);
+    }
+}
+
+impl Drop for FileOrStdinOwnedLock {
+    fn drop(&mut self) {
+        instrument::close(self.key);
+    }
+}
+
+impl FileOrStdoutOwnedLock {
+    /// Whether the output is an interactive terminal.
+    pub fn is_terminal(&self) -> bool {
+        match &self.inner {
+            OwnedOutput::FileBufWriter(writer) => writer.get_ref().is_terminal(),
+            OwnedOutput::FileLineWriter(writer) => writer.get_ref().is_terminal(),
+            OwnedOutput::FileUnbuffered(file) => file.is_terminal(),
+            OwnedOutput::Stdout(_)
+            | OwnedOutput::StdoutBufWriter(_)
+            | OwnedOutput::StdoutUnbuffered(_) => io::stdout().is_terminal(),
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.inner {
+            OwnedOutput::FileBufWriter(writer) => writer,
+            OwnedOutput::FileLineWriter(writer) => writer,
+            OwnedOutput::FileUnbuffered(file) => file,
+            OwnedOutput::Stdout(stdout) => stdout,
+            OwnedOutput::StdoutBufWriter(writer) => writer,
+            OwnedOutput::StdoutUnbuffered(stdout) => stdout,
+        }
+    }
+}
+
+impl Write for FileOrStdoutOwnedLock {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amt = instrument::write_with(self.key, self.writer(), buf)?;
+        if let OwnedOutput::StdoutUnbuffered(stdout) = &mut self.inner {
+            stdout.flush()?;
+        }
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        instrument::flush_with(self.key, self.writer())
+    }
+}
+
+impl Drop for FileOrStdoutOwnedLock {
+    fn drop(&mut self) {
+        instrument::close(self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, thread};
+    use tempfile::TempDir;
+
+    fn assert_send_static<T: Send + 'static>(value: T) -> T {
+        value
+    }
+
+    #[test]
+    fn move_handles_to_threads() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let input_path = tmp_dir.path().join("in.txt");
+        let output_path = tmp_dir.path().join("out.txt");
+        fs::write(&input_path, "one\ntwo\n")?;
+
+        let input = assert_send_static(FileOrStdin::from_path(&input_path)?);
+        let output = assert_send_static(FileOrStdout::from_path(&output_path)?);
+        let mut reader = assert_send_static(input.into_lock());
+        let mut writer = assert_send_static(output.into_lock_buffered(Buffering::Line));
+        thread::spawn(move || -> io::Result<()> {
+            for line in reader.by_ref().lines() {
+                writeln!(writer, "{}", line?.to_uppercase())?;
+            }
+            writer.flush()
+        })
+        .join()
+        .expect("the worker panicked")?;
+        assert_eq!(fs::read_to_string(&output_path)?, "ONE\nTWO\n");
+
+        assert_send_static(FileOrStdin::from_path("-")?.into_lock());
+        assert_send_static(FileOrStdout::from_path("-")?.into_lock());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{term, FileOrStdout};
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    mem,
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+const DEFAULT_PAGER: &str = "less";
+const DEFAULT_LESS_FLAGS: &str = "FRX";
+
+/// Output that is shown through a pager when it doesn't fit on the terminal.
+///
+/// When the output is stdout and stdout is a terminal, data is held back until it exceeds one
+/// screen. Short output is then written directly; longer output is piped into `$PAGER` (or
+/// `less` with `LESS=FRX` by default), like git does. Setting `PAGER` to an empty string or `cat`
+/// disables paging. [`finish`](PagedOutput::finish) waits for the pager to exit.
+///
+/// Quitting the pager before the end is a normal way to stop reading: everything written after
+/// that is discarded, and [`has_quit`](PagedOutput::has_quit) tells the tool it can stop early.
+pub struct PagedOutput {
+    state: State,
+    fallback: bool,
+}
+
+enum State {
+    File(BufWriter<File>),
+    Stdout(io::Stdout),
+    Buffering {
+        buf: Vec<u8>,
+        lines: usize,
+        threshold: usize,
+        command: String,
+    },
+    Paging {
+        child: Child,
+        stdin: ChildStdin,
+    },
+    /// The pager exited before reading everything.
+    Quit(Child),
+    Finished,
+}
+
+impl PagedOutput {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(FileOrStdout::from_path(path)?))
+    }
+
+    pub fn new<T: Into<FileOrStdout>>(output: T) -> Self {
+        let output = output.into();
+        let is_terminal = output.is_terminal();
+
+        let state = match output {
+            FileOrStdout::File(file) => State::File(BufWriter::new(file)),
+            FileOrStdout::Stdout(stdout) => match pager_command() {
+                Some(command) if is_terminal => {
+                    let (_, rows) = term::size_or_default();
+                    State::Buffering {
+                        buf: Vec::new(),
+                        lines: 0,
+                        threshold: usize::from(rows).saturating_sub(1),
+                        command,
+                    }
+                }
+                _ => State::Stdout(stdout),
+            },
+        };
+        Self {
+            state,
+            fallback: true,
+        }
+    }
+
+    /// Whether to write to stdout directly when the pager can't be started. Defaults to `true`;
+    /// with `false`, writing fails with the error starting it instead.
+    pub fn fallback(mut self, yes: bool) -> Self {
+        self.fallback = yes;
+        self
+    }
+
+    /// Whether output is currently going to a pager process.
+    pub fn is_paging(&self) -> bool {
+        matches!(self.state, State::Paging { .. })
+    }
+
+    /// Whether the user quit the pager, so further output is discarded.
+    pub fn has_quit(&self) -> bool {
+        matches!(self.state, State::Quit(_))
+    }
+
+    /// Flush remaining output and wait for the pager, if one was started, to exit.
+    ///
+    /// Fails if the pager exits with a non-zero status.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.close()
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        match mem::replace(&mut self.state, State::Finished) {
+            State::File(mut writer) => writer.flush(),
+            State::Stdout(mut stdout) => stdout.flush(),
+            State::Buffering { buf, .. } => {
+                let mut stdout = io::stdout();
+                stdout.write_all(&buf)?;
+                stdout.flush()
+            }
+            State::Paging { child, stdin } => {
+                drop(stdin);
+                wait(child)
+            }
+            State::Quit(child) => wait(child),
+            State::Finished => Ok(()),
+        }
+    }
+
+    fn start_pager(&mut self) -> io::Result<()> {
+        let (buf, command) = match &mut self.state {
+            State::Buffering { buf, command, .. } => (mem::take(buf), mem::take(command)),
+            _ => return Ok(()),
+        };
+
+        let mut child = match spawn_pager(&command) {
+            Ok(child) => child,
+            Err(e) if !self.fallback => {
+                self.state = State::Finished;
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("failed to start pager `{}`: {}", command, e),
+                ));
+            }
+            Err(_) => {
+                // No usable pager: fall back to plain stdout.
+                let mut stdout = io::stdout();
+                stdout.write_all(&buf)?;
+                self.state = State::Stdout(stdout);
+                return Ok(());
+            }
+        };
+        let mut stdin = child.stdin.take().expect("pager stdin is piped");
+        let result = stdin.write_all(&buf);
+        self.state = State::Paging { child, stdin };
+        result.or_else(|e| self.quit_on_broken_pipe(e))
+    }
+
+    /// Treat the pager closing its input as the user quitting it, rather than an error.
+    fn quit_on_broken_pipe(&mut self, e: io::Error) -> io::Result<()> {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            return Err(e);
+        }
+        if let State::Paging { child, .. } = mem::replace(&mut self.state, State::Finished) {
+            self.state = State::Quit(child);
+        }
+        Ok(())
+    }
+}
+
+impl Write for PagedOutput {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match &mut self.state {
+            State::File(writer) => writer.write(data),
+            State::Stdout(stdout) => stdout.write(data),
+            State::Paging { stdin, .. } => match stdin.write(data) {
+                Ok(amt) => Ok(amt),
+                Err(e) => self.quit_on_broken_pipe(e).map(|()| data.len()),
+            },
+            State::Quit(_) => Ok(data.len()),
+            State::Buffering {
+                buf,
+                lines,
+                threshold,
+                ..
+            } => {
+                buf.extend_from_slice(data);
+                *lines += data.iter().filter(|b| **b == b'\n').count();
+                if *lines > *threshold {
+                    self.start_pager()?;
+                }
+                Ok(data.len())
+            }
+            State::Finished => Err(io::Error::other("write after pager finished")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.state {
+            State::File(writer) => writer.flush(),
+            State::Stdout(stdout) => stdout.flush(),
+            State::Paging { stdin, .. } => match stdin.flush() {
+                Ok(()) => Ok(()),
+                Err(e) => self.quit_on_broken_pipe(e),
+            },
+            // Flushing would defeat the fits-on-one-screen check.
+            State::Buffering { .. } | State::Quit(_) | State::Finished => Ok(()),
+        }
+    }
+}
+
+impl Drop for PagedOutput {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+fn pager_command() -> Option<String> {
+    let command = match env::var("PAGER") {
+        Ok(pager) => pager,
+        Err(_) => DEFAULT_PAGER.to_owned(),
+    };
+    let command = command.trim();
+    if command.is_empty() || command == "cat" {
+        None
+    } else {
+        Some(command.to_owned())
+    }
+}
+
+fn wait(mut child: Child) -> io::Result<()> {
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("pager failed: {}", status)))
+    }
+}
+
+fn spawn_pager(command: &str) -> io::Result<Child> {
+    let mut cmd = if cfg!(windows) {
+        let mut parts = command.split_whitespace();
+        let mut cmd = Command::new(parts.next().unwrap_or(DEFAULT_PAGER));
+        cmd.args(parts);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    if env::var_os("LESS").is_none() {
+        cmd.env("LESS", DEFAULT_LESS_FLAGS);
+    }
+    cmd.stdin(Stdio::piped()).spawn()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn pages_only_past_threshold() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let paged_path = tmp_dir.path().join("paged.txt");
+
+        let mut output = PagedOutput {
+            state: State::Buffering {
+                buf: Vec::new(),
+                lines: 0,
+                threshold: 2,
+                command: format!("cat > '{}'", paged_path.display()),
+            },
+            fallback: true,
+        };
+        output.write_all(b"one\ntwo\n")?;
+        assert!(!output.is_paging());
+        output.write_all(b"three\n")?;
+        assert!(output.is_paging());
+        output.write_all(b"four\n")?;
+        output.finish()?;
+
+        assert_eq!(fs::read_to_string(paged_path)?, "one\ntwo\nthree\nfour\n");
+
+        let paging = |command: &str| PagedOutput {
+            state: State::Buffering {
+                buf: Vec::new(),
+                lines: 0,
+                threshold: 0,
+                command: command.to_owned(),
+            },
+            fallback: true,
+        };
+        // Quitting early isn't an error, and a pager failing is.
+        let mut quit = paging("exit 0");
+        for _ in 0..1000 {
+            quit.write_all(&[b'\n'; 4096])?;
+            if quit.has_quit() {
+                break;
+            }
+        }
+        assert!(quit.has_quit());
+        quit.finish()?;
+        let mut failed = paging("cat > /dev/null; exit 3");
+        failed.write_all(b"line\n")?;
+        assert!(failed.finish().is_err());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{compress::CompressOptions, filter::FilterWriter};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    mem,
+    thread::{self, JoinHandle},
+};
+
+/// Uncompressed bytes per independently compressed block.
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Writer compressing blocks of its input on up to `threads` threads at once, each into a
+/// stream of its own, and writing the streams out in order.
+///
+/// Only for formats whose concatenated streams decode as one, like gzip members.
+pub(crate) struct ParallelWriter<W> {
+    /// `None` once finished.
+    inner: Option<W>,
+    options: CompressOptions,
+    threads: usize,
+    block: Vec<u8>,
+    /// Blocks being compressed, oldest first.
+    pending: VecDeque<JoinHandle<io::Result<Vec<u8>>>>,
+    /// Whether any block was started, since even empty input needs one stream.
+    started: bool,
+    /// [`complete`](ParallelWriter::complete), ignoring errors, for `Drop`, which can't require
+    /// `W: Write`.
+    complete_on_drop: fn(&mut Self),
+}
+
+impl<W: Write> ParallelWriter<W> {
+    pub(crate) fn new(inner: W, options: CompressOptions, threads: usize) -> Self {
+        Self {
+            inner: Some(inner),
+            options,
+            threads,
+            block: Vec::with_capacity(BLOCK_SIZE),
+            pending: VecDeque::new(),
+            started: false,
+            complete_on_drop: |writer| {
+                let _ = writer.complete();
+            },
+        }
+    }
+
+    /// Compress and write everything that's left, and return the inner writer.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        let result = self.complete();
+        let inner = self.inner.take().expect("writer is only finished once");
+        result.map(|()| inner)
+    }
+
+    fn complete(&mut self) -> io::Result<()> {
+        if !self.block.is_empty() || !self.started {
+            self.start_block()?;
+        }
+        while !self.pending.is_empty() {
+            self.write_oldest()?;
+        }
+        self.inner_mut().flush()
+    }
+
+    /// Hand the current block to a new thread, once one is free.
+    fn start_block(&mut self) -> io::Result<()> {
+        if self.pending.len() >= self.threads {
+            self.write_oldest()?;
+        }
+        let block = mem::replace(&mut self.block, Vec::with_capacity(BLOCK_SIZE));
+        let filter = self.options.filter();
+        self.pending.push_back(thread::spawn(move || {
+            let mut writer = FilterWriter::new(Vec::new(), filter)?;
+            writer.write_all(&block)?;
+            writer.finish()
+        }));
+        self.started = true;
+        Ok(())
+    }
+
+    fn write_oldest(&mut self) -> io::Result<()> {
+        let compressed = match self.pending.pop_front() {
+            Some(handle) => handle
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("compression thread panicked")))?,
+            None => return Ok(()),
+        };
+        self.inner_mut().write_all(&compressed)
+    }
+
+    fn inner_mut(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer not finished")
+    }
+}
+
+impl<W: Write> Write for ParallelWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.block.len() == BLOCK_SIZE {
+            self.start_block()?;
+        }
+        let amt = buf.len().min(BLOCK_SIZE - self.block.len());
+        self.block.extend_from_slice(&buf[..amt]);
+        Ok(amt)
+    }
+
+    /// Flushes what's been compressed so far; the current block waits until it's full.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner_mut().flush()
+    }
+}
+
+impl<W> Drop for ParallelWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            (self.complete_on_drop)(self);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{FileOrStdin, FileOrStdout};
+use std::{fs::File, io};
+
+impl FileOrStdin {
+    /// Read into `buf` from `offset` in the file, returning how many bytes were read.
+    ///
+    /// On unix this doesn't move the file's position; on Windows it does. Fails with
+    /// `Unsupported` for stdin, which can't be read out of order.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        match self {
+            Self::File(file) => read_at(file, buf, offset),
+            Self::Stdin(_) => Err(stdio("stdin")),
+        }
+    }
+
+    /// Fill `buf` from `offset` in the file, failing with `UnexpectedEof` if it's too short.
+    pub fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(amt) => {
+                    buf = &mut buf[amt..];
+                    offset += amt as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FileOrStdout {
+    /// Write `buf` at `offset` in the file, returning how many bytes were written.
+    ///
+    /// On unix this doesn't move the file's position; on Windows it does. Fails with
+    /// `Unsupported` for stdout.
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        match self {
+            Self::File(file) => write_at(file, buf, offset),
+            Self::Stdout(_) => Err(stdio("stdout")),
+        }
+    }
+
+    /// Write all of `buf` at `offset` in the file.
+    pub fn write_all_at(&self, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write_at(buf, offset) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(amt) => {
+                    buf = &buf[amt..];
+                    offset += amt as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn stdio(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("positioned I/O needs a file, not {}", name),
+    )
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::write_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_write(file, buf, offset)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_at(_: &File, _: &mut [u8], _: u64) -> io::Result<usize> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn write_at(_: &File, _: &[u8], _: u64) -> io::Result<usize> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn positioned_reads_and_writes() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("data.bin");
+
+        let output = FileOrStdout::from_path(&path)?;
+        output.write_all_at(b"world", 6)?;
+        output.write_all_at(b"hello ", 0)?;
+        drop(output);
+        assert_eq!(fs::read(&path)?, b"hello world");
+
+        let input = FileOrStdin::from_path(&path)?;
+        let mut buf = [0; 5];
+        input.read_exact_at(&mut buf, 6)?;
+        assert_eq!(&buf, b"world");
+        let e = input.read_exact_at(&mut buf, 8).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        let e = FileOrStdin::from(io::stdin()).read_at(&mut buf, 0);
+        assert_eq!(e.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
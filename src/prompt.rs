@@ -0,0 +1,120 @@
+use crate::{FileOrStdout, STDIO_FILENAME};
+use std::{
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+};
+
+/// What [`confirm_overwrite`] does about an existing file when it can't ask the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Return an `AlreadyExists` error.
+    Fail,
+    /// Overwrite the file.
+    Overwrite,
+    /// Leave the file alone.
+    Skip,
+}
+
+/// Decide whether it is okay to write to `path`, asking the user if it already exists.
+///
+/// The question is only asked when both stdin and stderr are terminals; otherwise `policy` is
+/// applied. Returns `Ok(false)` if the file should be skipped. Stdout (`-`) and paths that don't
+/// exist yet are always okay.
+pub fn confirm_overwrite<P: AsRef<Path>>(path: P, policy: OverwritePolicy) -> io::Result<bool> {
+    let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+    decide(path.as_ref(), policy, interactive, || {
+        ask(path.as_ref(), &mut io::stdin().lock(), &mut io::stderr())
+    })
+}
+
+fn decide<F>(path: &Path, policy: OverwritePolicy, interactive: bool, ask: F) -> io::Result<bool>
+where
+    F: FnOnce() -> io::Result<bool>,
+{
+    if path.to_string_lossy() == STDIO_FILENAME || !path.exists() {
+        return Ok(true);
+    }
+    if interactive {
+        return ask();
+    }
+    match policy {
+        OverwritePolicy::Overwrite => Ok(true),
+        OverwritePolicy::Skip => Ok(false),
+        OverwritePolicy::Fail => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        )),
+    }
+}
+
+fn ask<R: BufRead, W: Write>(path: &Path, input: &mut R, prompt: &mut W) -> io::Result<bool> {
+    write!(prompt, "overwrite {}? [y/N] ", path.display())?;
+    prompt.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes"))
+}
+
+impl FileOrStdout {
+    /// Like [`from_path`](FileOrStdout::from_path), but check with [`confirm_overwrite`] first.
+    ///
+    /// Returns `Ok(None)` if the existing file should be skipped.
+    pub fn from_path_confirmed<P: AsRef<Path>>(
+        path: P,
+        policy: OverwritePolicy,
+    ) -> io::Result<Option<Self>> {
+        if confirm_overwrite(&path, policy)? {
+            Self::from_path(path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn policies_and_prompt() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let existing = tmp_dir.path().join("existing.txt");
+        fs::write(&existing, "keep me")?;
+        let missing = tmp_dir.path().join("missing.txt");
+
+        let never_asked = || -> io::Result<bool> { panic!("should not prompt") };
+        assert!(decide(&missing, OverwritePolicy::Fail, true, never_asked)?);
+        assert!(decide(
+            Path::new("-"),
+            OverwritePolicy::Fail,
+            true,
+            never_asked
+        )?);
+        assert!(decide(
+            &existing,
+            OverwritePolicy::Overwrite,
+            false,
+            never_asked
+        )?);
+        assert!(!decide(
+            &existing,
+            OverwritePolicy::Skip,
+            false,
+            never_asked
+        )?);
+        let err = decide(&existing, OverwritePolicy::Fail, false, never_asked).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        let mut prompt = Vec::new();
+        assert!(ask(&existing, &mut &b"Y\n"[..], &mut prompt)?);
+        assert!(String::from_utf8_lossy(&prompt).ends_with("existing.txt? [y/N] "));
+        assert!(!ask(&existing, &mut &b"\n"[..], &mut Vec::new())?);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
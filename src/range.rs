@@ -0,0 +1,197 @@
+use crate::{FileOrStdinLock, InputLock};
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+/// Reader over the first lines of a `BufRead`, from [`FileOrStdinLock::take_lines`].
+///
+/// A last line without a trailing newline still counts as a line.
+pub struct TakeLines<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Skip the next `n` bytes, returning how many there were.
+    ///
+    /// Regular files seek past them instead of reading them.
+    pub fn skip_bytes(&mut self, n: u64) -> io::Result<u64> {
+        let mut skipped = 0;
+        // Buffered bytes are dropped first, so a file's position is where the data left off.
+        while skipped < n {
+            let available = self.buffered_len();
+            if available == 0 {
+                break;
+            }
+            let amt = available.min((n - skipped) as usize);
+            self.consume(amt);
+            skipped += amt as u64;
+        }
+        if skipped == n {
+            return Ok(skipped);
+        }
+
+        if let InputLock::FileBufReader(reader) = &mut self.inner {
+            let mut file = *reader.get_ref();
+            let meta = file.metadata()?;
+            if meta.is_file() {
+                let pos = file.stream_position()?;
+                let amt = (n - skipped).min(meta.len().saturating_sub(pos));
+                reader.seek(SeekFrom::Current(amt as i64))?;
+                return Ok(skipped + amt);
+            }
+        }
+        Ok(skipped + io::copy(&mut Read::take(&mut *self, n - skipped), &mut io::sink())?)
+    }
+
+    /// Read at most the next `n` bytes.
+    pub fn take_bytes(self, n: u64) -> io::Take<Self> {
+        self.take(n)
+    }
+
+    /// Skip the next `n` lines, returning how many there were.
+    pub fn skip_lines(&mut self, n: u64) -> io::Result<u64> {
+        let mut skipped = 0;
+        while skipped < n {
+            let buf = match self.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() {
+                break;
+            }
+            let (amt, lines) = line_prefix(buf, n - skipped);
+            if amt == buf.len() && lines < n - skipped {
+                let last = buf[amt - 1];
+                self.consume(amt);
+                skipped += lines;
+                // A partial line at end of input still counts as one.
+                if last != b'\n' && self.fill_buf()?.is_empty() {
+                    skipped += 1;
+                }
+                continue;
+            }
+            self.consume(amt);
+            skipped += lines;
+        }
+        Ok(skipped)
+    }
+
+    /// Read at most the next `n` lines.
+    pub fn take_lines(self, n: u64) -> TakeLines<Self> {
+        TakeLines {
+            inner: self,
+            remaining: n,
+        }
+    }
+
+    /// Length of the data that the next `consume` can drop without reading more.
+    fn buffered_len(&self) -> usize {
+        if self.peeked_pos < self.peeked.len() {
+            return self.peeked.len() - self.peeked_pos;
+        }
+        match &self.inner {
+            InputLock::FileBufReader(reader) => reader.buffer().len(),
+            InputLock::StdinLock(_) => 0,
+        }
+    }
+}
+
+/// Length of the start of `buf` holding up to `n` whole lines, and how many lines that is.
+fn line_prefix(buf: &[u8], n: u64) -> (usize, u64) {
+    let mut lines = 0;
+    for (i, b) in buf.iter().enumerate() {
+        if *b == b'\n' {
+            lines += 1;
+            if lines == n {
+                return (i + 1, lines);
+            }
+        }
+    }
+    (buf.len(), lines)
+}
+
+impl<R> TakeLines<R> {
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: BufRead> Read for TakeLines<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.fill_buf()?.read(buf)?;
+        self.consume(amt);
+        Ok(amt)
+    }
+}
+
+impl<R: BufRead> BufRead for TakeLines<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.remaining == 0 {
+            return Ok(&[]);
+        }
+        let buf = self.inner.fill_buf()?;
+        let (amt, _) = line_prefix(buf, self.remaining);
+        Ok(&buf[..amt])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buf) = self.inner.fill_buf() {
+            let amt = amt.min(buf.len());
+            let lines = buf[..amt].iter().filter(|b| **b == b'\n').count() as u64;
+            self.remaining = self.remaining.saturating_sub(lines);
+            self.inner.consume(amt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FileOrStdin;
+    use std::{fs, io::Read};
+    use tempfile::TempDir;
+
+    #[test]
+    fn skip_and_take_ranges() -> Result<(), std::io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("input.txt");
+        fs::write(&path, "one\ntwo\nthree\nfour\nfive")?;
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        assert_eq!(lock.peek(2)?, b"on");
+        assert_eq!(lock.skip_bytes(4)?, 4);
+        assert_eq!(lock.skip_lines(1)?, 1);
+        let mut content = String::new();
+        lock.take_lines(2).read_to_string(&mut content)?;
+        assert_eq!(content, "three\nfour\n");
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        assert_eq!(lock.skip_bytes(20)?, 20);
+        assert_eq!(lock.skip_bytes(100)?, 3);
+        assert_eq!(lock.skip_lines(1)?, 0);
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        assert_eq!(lock.skip_lines(10)?, 5);
+
+        let mut content = String::new();
+        let mut input = FileOrStdin::from_path(&path)?;
+        input.lock().take_bytes(6).read_to_string(&mut content)?;
+        assert_eq!(content, "one\ntw");
+
+        // Past the read buffer, so the rest is skipped by seeking.
+        let big = tmp_dir.path().join("big.txt");
+        fs::write(&big, format!("{}\ntail", "x".repeat(20000)))?;
+        let mut input = FileOrStdin::from_path(&big)?;
+        let mut lock = input.lock();
+        lock.peek(1)?;
+        assert_eq!(lock.skip_bytes(20001)?, 20001);
+        let mut content = String::new();
+        lock.read_to_string(&mut content)?;
+        assert_eq!(content, "tail");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
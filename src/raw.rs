@@ -0,0 +1,304 @@
+use crate::FileOrStdin;
+#[cfg(unix)]
+use std::time::Instant;
+use std::{io, time::Duration};
+
+/// How long to wait for the rest of an escape sequence before treating `ESC` as a key press.
+#[cfg(unix)]
+const ESCAPE_TIMEOUT: Duration = Duration::from_millis(25);
+
+/// A key press decoded from raw terminal input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    /// A control character, e.g. `Ctrl('d')` for `^D`.
+    Ctrl(char),
+    Enter,
+    Tab,
+    Backspace,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Input that isn't recognized as any other key.
+    Byte(u8),
+}
+
+impl Key {
+    /// Decode the first key in `bytes`, returning it with the number of bytes it used.
+    ///
+    /// Returns `None` if `bytes` is empty or ends in the middle of a multi-byte character.
+    pub(crate) fn parse(bytes: &[u8]) -> Option<(Self, usize)> {
+        let first = *bytes.first()?;
+        let key = match first {
+            b'\r' | b'\n' => Self::Enter,
+            b'\t' => Self::Tab,
+            0x7f | 0x08 => Self::Backspace,
+            0x1b => {
+                return Some(match bytes.get(1..3) {
+                    Some([b'[', c]) | Some([b'O', c]) => match c {
+                        b'A' => (Self::Up, 3),
+                        b'B' => (Self::Down, 3),
+                        b'C' => (Self::Right, 3),
+                        b'D' => (Self::Left, 3),
+                        _ => (Self::Esc, 1),
+                    },
+                    _ => (Self::Esc, 1),
+                });
+            }
+            0x01..=0x1a => Self::Ctrl((b'a' + first - 1) as char),
+            0x00..=0x7f => Self::Char(first as char),
+            _ => {
+                let len = match first {
+                    0xc0..=0xdf => 2,
+                    0xe0..=0xef => 3,
+                    0xf0..=0xf7 => 4,
+                    _ => return Some((Self::Byte(first), 1)),
+                };
+                let encoded = bytes.get(..len)?;
+                return Some(match std::str::from_utf8(encoded) {
+                    Ok(s) => (Self::Char(s.chars().next().unwrap()), len),
+                    Err(_) => (Self::Byte(first), 1),
+                });
+            }
+        };
+        Some((key, 1))
+    }
+}
+
+/// Stdin in raw (non-canonical, no echo) mode, delivering key presses as they happen.
+///
+/// Signal keys don't raise signals: Ctrl-C arrives as `Key::Ctrl('c')`, Ctrl-Z as
+/// `Key::Ctrl('z')`, and so on, leaving it to the caller to act on them. The terminal's previous
+/// settings are restored on drop.
+pub struct RawInput<'a> {
+    #[cfg(unix)]
+    _guard: crate::term::TermiosGuard,
+    pending: Vec<u8>,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    input: &'a mut FileOrStdin,
+}
+
+impl FileOrStdin {
+    /// Put the terminal into raw mode. Fails if the input is not an interactive terminal.
+    pub fn raw_mode(&mut self) -> io::Result<RawInput<'_>> {
+        if !self.is_terminal() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "raw mode requires an interactive terminal",
+            ));
+        }
+        RawInput::new(self)
+    }
+}
+
+#[cfg(unix)]
+impl<'a> RawInput<'a> {
+    fn new(input: &'a mut FileOrStdin) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = match input {
+            FileOrStdin::File(file) => file.as_raw_fd(),
+            FileOrStdin::Stdin(stdin) => stdin.as_raw_fd(),
+        };
+        // Reads return straight away with whatever there is, so waiting is left to `poll`.
+        let guard = crate::term::TermiosGuard::modify(fd, |termios| {
+            termios.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+            termios.c_cc[libc::VMIN] = 0;
+            termios.c_cc[libc::VTIME] = 0;
+        })?;
+        Ok(Self {
+            _guard: guard,
+            pending: Vec::new(),
+            input,
+        })
+    }
+
+    /// Wait up to `timeout` (forever if `None`) for a key press.
+    pub fn read_key(&mut self, timeout: Option<Duration>) -> io::Result<Option<Key>> {
+        loop {
+            if let Some((key, len)) = Key::parse(&self.pending) {
+                let incomplete_escape = key == Key::Esc
+                    && matches!(self.pending.get(1), None | Some(b'[') | Some(b'O'))
+                    && self.pending.len() < 3;
+                if !incomplete_escape || !self.fill(Some(ESCAPE_TIMEOUT))? {
+                    self.pending.drain(..len);
+                    return Ok(Some(key));
+                }
+                continue;
+            }
+            if !self.fill(timeout)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the next raw byte.
+    pub fn read_byte(&mut self, timeout: Option<Duration>) -> io::Result<Option<u8>> {
+        if self.pending.is_empty() && !self.fill(timeout)? {
+            return Ok(None);
+        }
+        Ok(Some(self.pending.remove(0)))
+    }
+
+    /// Wait up to `timeout` for input and read what's available into `pending`. Returns `false`
+    /// on timeout.
+    ///
+    /// Signals don't end the wait early, and input stdin's buffer already holds is read first.
+    fn fill(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if self.read_available()? {
+                return Ok(true);
+            }
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return Ok(false),
+                },
+                None => None,
+            };
+            match self.poll(remaining) {
+                Ok(true) if self.read_available()? => return Ok(true),
+                // Readable with nothing to read is the end of input.
+                Ok(true) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "terminal closed",
+                    ))
+                }
+                Ok(false) => return Ok(false),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Wait up to `timeout` (forever if `None`) for the terminal to be readable.
+    fn poll(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        use std::os::unix::io::AsRawFd;
+
+        let timeout_ms = timeout.map_or(-1, |t| {
+            t.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+        });
+        let mut pollfd = libc::pollfd {
+            fd: match &*self.input {
+                FileOrStdin::File(file) => file.as_raw_fd(),
+                FileOrStdin::Stdin(stdin) => stdin.as_raw_fd(),
+            },
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: polling a single, valid pollfd.
+        match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+            ready if ready < 0 => Err(io::Error::last_os_error()),
+            ready => Ok(ready > 0),
+        }
+    }
+
+    /// Read what input there is without waiting, through stdin's buffer for stdin. Returns
+    /// whether there was any.
+    fn read_available(&mut self) -> io::Result<bool> {
+        use std::io::{BufRead, Read};
+
+        loop {
+            let result = match &mut *self.input {
+                FileOrStdin::File(file) => {
+                    let mut buf = [0; 64];
+                    file.read(&mut buf)
+                        .inspect(|&amt| self.pending.extend_from_slice(&buf[..amt]))
+                }
+                FileOrStdin::Stdin(stdin) => {
+                    let mut lock = stdin.lock();
+                    let amt = lock.fill_buf().map(|buf| {
+                        self.pending.extend_from_slice(buf);
+                        buf.len()
+                    });
+                    if let Ok(amt) = amt {
+                        lock.consume(amt);
+                    }
+                    amt
+                }
+            };
+            match result {
+                Ok(amt) => return Ok(amt > 0),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl<'a> RawInput<'a> {
+    fn new(_input: &'a mut FileOrStdin) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "raw terminal mode is not supported on this platform",
+        ))
+    }
+
+    pub fn read_key(&mut self, _timeout: Option<Duration>) -> io::Result<Option<Key>> {
+        Ok(Key::parse(&self.pending).map(|(key, _)| key))
+    }
+
+    pub fn read_byte(&mut self, _timeout: Option<Duration>) -> io::Result<Option<u8>> {
+        Ok(self.pending.first().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(Key::parse(b"q"), Some((Key::Char('q'), 1)));
+        assert_eq!(Key::parse(b"\x1b[A"), Some((Key::Up, 3)));
+        assert_eq!(Key::parse(b"\x1bq"), Some((Key::Esc, 1)));
+        assert_eq!(Key::parse(b"\x04"), Some((Key::Ctrl('d'), 1)));
+        assert_eq!(Key::parse(b"\x03"), Some((Key::Ctrl('c'), 1)));
+        assert_eq!(Key::parse(b"\r"), Some((Key::Enter, 1)));
+        assert_eq!(
+            Key::parse("\u{e9}x".as_bytes()),
+            Some((Key::Char('\u{e9}'), 2))
+        );
+        assert_eq!(Key::parse(b"\xc3"), None);
+        assert_eq!(Key::parse(b"\xff"), Some((Key::Byte(0xff), 1)));
+        assert_eq!(Key::parse(b""), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_keys_from_a_terminal() -> Result<(), io::Error> {
+        use std::{fs::File, io::Write, os::unix::io::FromRawFd, ptr};
+
+        let (mut master, slave) = {
+            let (mut master, mut slave) = (0, 0);
+            // SAFETY: `openpty` only fills in the two descriptors; the rest may be null.
+            let opened = unsafe {
+                libc::openpty(
+                    &mut master,
+                    &mut slave,
+                    ptr::null_mut(),
+                    ptr::null(),
+                    ptr::null(),
+                )
+            };
+            if opened != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // SAFETY: both descriptors were just opened, and nothing else owns them.
+            unsafe { (File::from_raw_fd(master), File::from_raw_fd(slave)) }
+        };
+        let mut input = FileOrStdin::File(slave);
+        let mut raw = input.raw_mode()?;
+        assert_eq!(raw.read_key(Some(Duration::from_millis(20)))?, None);
+        master.write_all(b"q\x1b[A")?;
+        let key = raw.read_key(Some(Duration::from_secs(5)))?;
+        assert_eq!(key, Some(Key::Char('q')));
+        assert_eq!(raw.read_key(None)?, Some(Key::Up));
+        Ok(())
+    }
+}
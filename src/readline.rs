@@ -0,0 +1,243 @@
+use crate::{FileOrStdin, FileOrStdinLock, Key};
+use std::io::{self, BufRead, Write};
+
+/// Line iterator that offers line editing and history when reading from a terminal.
+///
+/// On a terminal, each line is read with a small built-in editor (arrow keys, backspace,
+/// Ctrl-A/E/U, Up/Down history, Ctrl-D on an empty line to end input) and `prompt` is shown on
+/// stderr. Ctrl-C ends the line with an `Interrupted` error, since the terminal is in raw mode and
+/// doesn't raise `SIGINT`. Files and pipes are read line by line with no prompt, so both behave
+/// identically.
+pub struct InteractiveLines<'a> {
+    source: Source<'a>,
+    prompt: String,
+    history: Vec<String>,
+}
+
+enum Source<'a> {
+    Terminal(&'a mut FileOrStdin),
+    Plain(FileOrStdinLock<'a>),
+}
+
+impl FileOrStdin {
+    pub fn interactive_lines(&mut self, prompt: &str) -> InteractiveLines<'_> {
+        let source = if self.is_terminal() {
+            Source::Terminal(self)
+        } else {
+            Source::Plain(self.lock())
+        };
+        InteractiveLines {
+            source,
+            prompt: prompt.to_owned(),
+            history: Vec::new(),
+        }
+    }
+}
+
+impl<'a> InteractiveLines<'a> {
+    /// Lines entered so far at the terminal, oldest first.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    fn read_edited(&mut self) -> io::Result<Option<String>> {
+        let input = match &mut self.source {
+            Source::Terminal(input) => input,
+            Source::Plain(_) => unreachable!(),
+        };
+        let mut raw = input.raw_mode()?;
+        let mut editor = Editor::new(self.history.len());
+        let mut stderr = io::stderr();
+        editor.render(&mut stderr, &self.prompt)?;
+
+        loop {
+            let key = match raw.read_key(None)? {
+                Some(key) => key,
+                None => continue,
+            };
+            match editor.handle(key, &self.history) {
+                Action::Continue => editor.render(&mut stderr, &self.prompt)?,
+                Action::Submit => {
+                    writeln!(stderr)?;
+                    let line: String = editor.buf.iter().collect();
+                    if !line.trim().is_empty() {
+                        self.history.push(line.clone());
+                    }
+                    return Ok(Some(line));
+                }
+                Action::Eof => {
+                    writeln!(stderr)?;
+                    return Ok(None);
+                }
+                Action::Interrupt => {
+                    writeln!(stderr)?;
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for InteractiveLines<'a> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            Source::Terminal(_) => self.read_edited().transpose(),
+            Source::Plain(lock) => {
+                let mut line = String::new();
+                match lock.read_line(&mut line) {
+                    Ok(0) => None,
+                    Ok(_) => {
+                        if line.ends_with('\n') {
+                            line.pop();
+                            if line.ends_with('\r') {
+                                line.pop();
+                            }
+                        }
+                        Some(Ok(line))
+                    }
+                    Err(e) => Some(Err(e)),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Submit,
+    Eof,
+    Interrupt,
+}
+
+struct Editor {
+    buf: Vec<char>,
+    cursor: usize,
+    history_pos: usize,
+    /// The line being typed before browsing history, restored when moving back past the end.
+    draft: Vec<char>,
+}
+
+impl Editor {
+    fn new(history_len: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            cursor: 0,
+            history_pos: history_len,
+            draft: Vec::new(),
+        }
+    }
+
+    fn handle(&mut self, key: Key, history: &[String]) -> Action {
+        match key {
+            Key::Enter => return Action::Submit,
+            Key::Ctrl('d') if self.buf.is_empty() => return Action::Eof,
+            Key::Ctrl('c') => return Action::Interrupt,
+            Key::Char(c) => {
+                self.buf.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Tab => {
+                self.buf.insert(self.cursor, '\t');
+                self.cursor += 1;
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.buf.remove(self.cursor);
+            }
+            Key::Ctrl('d') if self.cursor < self.buf.len() => {
+                self.buf.remove(self.cursor);
+            }
+            Key::Left if self.cursor > 0 => self.cursor -= 1,
+            Key::Right if self.cursor < self.buf.len() => self.cursor += 1,
+            Key::Ctrl('a') => self.cursor = 0,
+            Key::Ctrl('e') => self.cursor = self.buf.len(),
+            Key::Ctrl('u') => {
+                self.buf.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::Up if self.history_pos > 0 => {
+                if self.history_pos == history.len() {
+                    self.draft = self.buf.clone();
+                }
+                self.history_pos -= 1;
+                self.set_buf(history[self.history_pos].chars().collect());
+            }
+            Key::Down if self.history_pos < history.len() => {
+                self.history_pos += 1;
+                let line = match history.get(self.history_pos) {
+                    Some(line) => line.chars().collect(),
+                    None => self.draft.clone(),
+                };
+                self.set_buf(line);
+            }
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn set_buf(&mut self, buf: Vec<char>) {
+        self.cursor = buf.len();
+        self.buf = buf;
+    }
+
+    fn render<W: Write>(&self, w: &mut W, prompt: &str) -> io::Result<()> {
+        let line: String = self.buf.iter().collect();
+        write!(w, "\r\x1b[K{}{}", prompt, line)?;
+        let back = self.buf.len() - self.cursor;
+        if back > 0 {
+            write!(w, "\x1b[{}D", back)?;
+        }
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editing_and_history() {
+        let history = vec!["first".to_owned(), "second".to_owned()];
+        let mut editor = Editor::new(history.len());
+
+        for key in &[Key::Char('a'), Key::Char('c'), Key::Left, Key::Char('b')] {
+            assert_eq!(editor.handle(*key, &history), Action::Continue);
+        }
+        assert_eq!(editor.buf.iter().collect::<String>(), "abc");
+
+        editor.handle(Key::Up, &history);
+        assert_eq!(editor.buf.iter().collect::<String>(), "second");
+        editor.handle(Key::Up, &history);
+        editor.handle(Key::Backspace, &history);
+        assert_eq!(editor.buf.iter().collect::<String>(), "firs");
+        editor.handle(Key::Down, &history);
+        editor.handle(Key::Down, &history);
+        assert_eq!(editor.buf.iter().collect::<String>(), "abc");
+
+        assert_eq!(editor.handle(Key::Enter, &history), Action::Submit);
+        assert_eq!(Editor::new(0).handle(Key::Ctrl('d'), &[]), Action::Eof);
+        assert_eq!(
+            Editor::new(0).handle(Key::Ctrl('c'), &[]),
+            Action::Interrupt
+        );
+    }
+
+    #[test]
+    fn plain_lines() -> Result<(), io::Error> {
+        let tmp_dir = tempfile::TempDir::new()?;
+        let path = tmp_dir.path().join("lines.txt");
+        std::fs::write(&path, "one\r\ntwo\n")?;
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let lines = input
+            .interactive_lines("> ")
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(lines, vec!["one", "two"]);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
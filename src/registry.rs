@@ -0,0 +1,193 @@
+use crate::{collide, FileOrStdout, STDIO_FILENAME};
+use std::{
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex, MutexGuard},
+};
+
+/// What opening an output that is already open in this process does, set with
+/// [`set_duplicate_outputs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateOutputs {
+    /// Open it again without checking. For a file this truncates what was already written.
+    #[default]
+    Allow,
+    /// Hand out another handle to the output that is already open, sharing its file position, so
+    /// writes through either handle follow each other instead of overwriting.
+    Share,
+    /// Fail with `AlreadyExists`.
+    Error,
+}
+
+struct Registry {
+    policy: DuplicateOutputs,
+    stdout: bool,
+    files: Vec<(collide::Identity, File)>,
+    /// Paths being opened, with the registry unlocked meanwhile.
+    opening: Vec<PathBuf>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    policy: DuplicateOutputs::Allow,
+    stdout: false,
+    files: Vec::new(),
+    opening: Vec::new(),
+});
+
+/// Signaled whenever a path is done opening.
+static OPENED: Condvar = Condvar::new();
+
+/// Detect outputs opened twice in this process, e.g. `-o out.txt -o ./out.txt` or `-` twice,
+/// through [`FileOrStdout::from_path`] and [`OutputOptions`](crate::OutputOptions).
+///
+/// Outputs are recognized by file identity, so links to the same file count as the same output.
+/// While detection is on, every output file opened stays open until the process exits or
+/// detection is set back to [`DuplicateOutputs::Allow`], which forgets them.
+pub fn set_duplicate_outputs(policy: DuplicateOutputs) {
+    let mut registry = lock(&REGISTRY);
+    registry.policy = policy;
+    if policy == DuplicateOutputs::Allow {
+        registry.stdout = false;
+        registry.files.clear();
+    }
+}
+
+/// Open the output at `path` with `open`, applying the duplicate policy.
+pub(crate) fn open_output<F>(path: &Path, open: F) -> io::Result<FileOrStdout>
+where
+    F: FnOnce(&Path) -> io::Result<FileOrStdout>,
+{
+    open_in(&REGISTRY, &OPENED, path, open)
+}
+
+/// Open the output at `path` with `open`, applying the duplicate policy of `registry`.
+///
+/// The registry isn't locked while a file opens, which can take a while, e.g. for a FIFO
+/// without a reader. Opening the same path again meanwhile waits for that open to finish.
+fn open_in<F>(
+    registry: &Mutex<Registry>,
+    opened: &Condvar,
+    path: &Path,
+    open: F,
+) -> io::Result<FileOrStdout>
+where
+    F: FnOnce(&Path) -> io::Result<FileOrStdout>,
+{
+    let mut guard = lock(registry);
+    if guard.policy == DuplicateOutputs::Allow {
+        drop(guard);
+        return open(path);
+    }
+    if path.to_string_lossy() == STDIO_FILENAME {
+        if guard.stdout {
+            guard.duplicate(path)?;
+        }
+        guard.stdout = true;
+        return open(path);
+    }
+
+    while guard.opening.iter().any(|opening| opening == path) {
+        guard = opened.wait(guard).unwrap_or_else(|p| p.into_inner());
+    }
+    if let Some(shared) = guard.shared(path)? {
+        return Ok(shared);
+    }
+    guard.opening.push(path.to_owned());
+    drop(guard);
+
+    let result = open(path);
+    let mut guard = lock(registry);
+    let pos = guard.opening.iter().position(|opening| opening == path);
+    guard
+        .opening
+        .swap_remove(pos.expect("the path is being opened"));
+    opened.notify_all();
+    let output = result?;
+    if let (FileOrStdout::File(file), Some(identity)) = (&output, collide::identity(path)?) {
+        guard.files.push((identity, file.try_clone()?));
+    }
+    Ok(output)
+}
+
+impl Registry {
+    /// Another handle to the file at `path` if it's already open, or `None`.
+    fn shared(&self, path: &Path) -> io::Result<Option<FileOrStdout>> {
+        if let Some(identity) = collide::identity(path)? {
+            if let Some((_, file)) = self.files.iter().find(|(id, _)| *id == identity) {
+                let file = file.try_clone()?;
+                self.duplicate(path)?;
+                return Ok(Some(file.into()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Fail if duplicates are errors.
+    fn duplicate(&self, path: &Path) -> io::Result<()> {
+        match self.policy {
+            DuplicateOutputs::Error => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is already open as an output", path.display()),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn lock(registry: &Mutex<Registry>) -> MutexGuard<'_, Registry> {
+    registry
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn duplicate_outputs() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("out.txt");
+        let same = tmp_dir.path().join(".").join("out.txt");
+        let create = |path: &Path| File::create(path).map(FileOrStdout::from);
+        let stdout = |_: &Path| Ok(FileOrStdout::from(io::stdout()));
+        // A registry of its own, so other tests opening outputs aren't affected.
+        let registry = Mutex::new(Registry {
+            policy: DuplicateOutputs::Share,
+            stdout: false,
+            files: Vec::new(),
+            opening: Vec::new(),
+        });
+        let opened = Condvar::new();
+        let open = |path: &Path, open: &dyn Fn(&Path) -> io::Result<FileOrStdout>| {
+            open_in(&registry, &opened, path, open)
+        };
+
+        let mut first = open(&path, &create)?;
+        first.lock().write_all(b"first ")?;
+        let mut second = open(&same, &create)?;
+        second.lock().write_all(b"second")?;
+        assert_eq!(fs::read_to_string(&path)?, "first second");
+        open(Path::new("-"), &stdout)?;
+        open(Path::new("-"), &stdout)?;
+
+        // A failed open doesn't leave the path reserved.
+        let missing = tmp_dir.path().join("missing").join("out.txt");
+        assert!(open(&missing, &create).is_err());
+        assert!(lock(&registry).opening.is_empty());
+
+        lock(&registry).policy = DuplicateOutputs::Error;
+        let e = open(&same, &create).err().expect("duplicates fail");
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert!(open(Path::new("-"), &stdout).is_err());
+        open(&tmp_dir.path().join("other.txt"), &create)?;
+        assert_eq!(fs::read_to_string(&path)?, "first second");
+
+        drop((first, second, registry));
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
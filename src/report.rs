@@ -0,0 +1,256 @@
+use std::{
+    fmt,
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Summary of a run over many inputs: how many were processed or skipped, the bytes read and
+/// written, and the inputs that failed.
+///
+/// [`Batch::run`](crate::Batch::run) returns one, and tools doing their own loop can fill one in
+/// with the `add_*` methods. `Display` gives a one-line summary for `-v` output, and the
+/// alternate form (`{:#}`) adds a line for each failure. [`to_json`](Report::to_json) gives the
+/// same as a JSON object.
+#[derive(Debug, Default)]
+pub struct Report {
+    processed: usize,
+    skipped: usize,
+    bytes_in: u64,
+    bytes_out: u64,
+    pub(crate) failures: Vec<(PathBuf, io::Error)>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of inputs processed successfully.
+    pub fn processed(&self) -> usize {
+        self.processed
+    }
+
+    /// Number of inputs deliberately left alone, e.g. duplicates or filtered out.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Bytes read from the inputs.
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in
+    }
+
+    /// Bytes written to the outputs.
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out
+    }
+
+    /// Inputs that failed, with their errors, in input order.
+    pub fn failures(&self) -> &[(PathBuf, io::Error)] {
+        &self.failures
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn add_processed(&mut self) {
+        self.processed += 1;
+    }
+
+    pub fn add_skipped(&mut self) {
+        self.skipped += 1;
+    }
+
+    pub fn add_bytes(&mut self, bytes_in: u64, bytes_out: u64) {
+        self.bytes_in += bytes_in;
+        self.bytes_out += bytes_out;
+    }
+
+    pub fn add_failure<P: AsRef<Path>>(&mut self, path: P, error: io::Error) {
+        self.failures.push((path.as_ref().to_owned(), error));
+    }
+
+    /// Add up the counts of `other`, appending its failures.
+    pub fn merge(&mut self, other: Report) {
+        self.processed += other.processed;
+        self.skipped += other.skipped;
+        self.bytes_in += other.bytes_in;
+        self.bytes_out += other.bytes_out;
+        self.failures.extend(other.failures);
+    }
+
+    /// The report as a JSON object, for machine-readable output.
+    ///
+    /// Failures are objects with the `path`, the error `kind` (as named by `io::ErrorKind`) and
+    /// the `error` message. Quotes, backslashes and control characters in paths and messages are
+    /// escaped. This is the only serialized form; there is no serde support.
+    pub fn to_json(&self) -> String {
+        let failures: Vec<_> = self
+            .failures
+            .iter()
+            .map(|(path, e)| {
+                format!(
+                    "{{\"path\":{},\"kind\":{},\"error\":{}}}",
+                    json_string(&path.to_string_lossy()),
+                    json_string(&format!("{:?}", e.kind())),
+                    json_string(&e.to_string())
+                )
+            })
+            .collect();
+        format!(
+            "{{\"processed\":{},\"skipped\":{},\"bytes_in\":{},\"bytes_out\":{},\"failures\":[{}]}}",
+            self.processed,
+            self.skipped,
+            self.bytes_in,
+            self.bytes_out,
+            failures.join(",")
+        )
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} processed", self.processed)?;
+        if self.skipped > 0 {
+            write!(f, ", {} skipped", self.skipped)?;
+        }
+        write!(f, ", {} failed", self.failures.len())?;
+        if self.bytes_in > 0 || self.bytes_out > 0 {
+            write!(
+                f,
+                ", {} bytes in, {} bytes out",
+                self.bytes_in, self.bytes_out
+            )?;
+        }
+        if f.alternate() {
+            for (_, e) in &self.failures {
+                write!(f, "\n{}", e)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `s` as a quoted JSON string.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Reader or writer counting the bytes passing through it, for filling in a [`Report`].
+///
+/// A reader counts the bytes it handed out, and a writer those the inner writer accepted.
+pub struct Counted<T> {
+    inner: T,
+    pub(crate) count: u64,
+}
+
+impl<T> Counted<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    /// Bytes read or written so far.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.inner.read(buf)?;
+        self.count += amt as u64;
+        Ok(amt)
+    }
+}
+
+impl<R: BufRead> BufRead for Counted<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.count += amt as u64;
+        self.inner.consume(amt);
+    }
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amt = self.inner.write(buf)?;
+        self.count += amt as u64;
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_summaries() {
+        let mut report = Report::new();
+        report.add_processed();
+        report.add_bytes(10, 4);
+        let mut other = Report::new();
+        other.add_skipped();
+        other.add_failure(
+            "dir/\"odd\".txt",
+            io::Error::new(io::ErrorKind::NotFound, "dir/\"odd\".txt: not found"),
+        );
+        report.merge(other);
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report.to_string(),
+            "1 processed, 1 skipped, 1 failed, 10 bytes in, 4 bytes out"
+        );
+        assert_eq!(
+            format!("{:#}", report),
+            "1 processed, 1 skipped, 1 failed, 10 bytes in, 4 bytes out\n\
+             dir/\"odd\".txt: not found"
+        );
+        assert_eq!(
+            report.to_json(),
+            "{\"processed\":1,\"skipped\":1,\"bytes_in\":10,\"bytes_out\":4,\"failures\":[\
+             {\"path\":\"dir/\\\"odd\\\".txt\",\"kind\":\"NotFound\",\
+             \"error\":\"dir/\\\"odd\\\".txt: not found\"}]}"
+        );
+
+        let mut odd = Report::new();
+        odd.add_failure(
+            "C:\\new\tline\n\u{1}.txt",
+            io::Error::other("bad \"name\"\r"),
+        );
+        assert!(odd.to_json().contains(
+            "{\"path\":\"C:\\\\new\\tline\\n\\u0001.txt\",\"kind\":\"Other\",\
+             \"error\":\"bad \\\"name\\\"\\r\"}"
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{FileOrStdin, FileOrStdout};
+use std::io::{self, Read, Seek, SeekFrom};
+
+impl FileOrStdin {
+    /// Skip as much of the input as `output` already holds, returning how many bytes that is,
+    /// so an interrupted job picks up where it stopped, like `curl --continue-at -`.
+    ///
+    /// Meant for outputs opened with [`OutputOptions::resume`](crate::OutputOptions::resume),
+    /// by jobs that write their input through unchanged (downloads, copies, ...). A file input is
+    /// seeked forward; stdin is read and thrown away. Fails with `InvalidData` if the input is
+    /// shorter than the output, since then the output can't be a prefix of it. Stdout holds
+    /// nothing, so nothing is skipped.
+    pub fn resume_from(&mut self, output: &FileOrStdout) -> io::Result<u64> {
+        let offset = match output.as_file() {
+            Some(file) => file.metadata()?.len(),
+            None => return Ok(0),
+        };
+        let skipped = match self {
+            Self::File(file) if file.metadata()?.is_file() => {
+                let len = file.metadata()?.len();
+                file.seek(SeekFrom::Start(offset.min(len)))?;
+                len.min(offset)
+            }
+            Self::File(file) => io::copy(&mut file.take(offset), &mut io::sink())?,
+            Self::Stdin(stdin) => io::copy(&mut stdin.lock().take(offset), &mut io::sink())?,
+        };
+        if skipped < offset {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "can't resume: the output has {} bytes, but the input only {}",
+                    offset, skipped
+                ),
+            ));
+        }
+        Ok(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OutputOptions;
+    use std::{fs, io::Write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn resume_interrupted_copy() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let input_path = tmp_dir.path().join("input.bin");
+        let output_path = tmp_dir.path().join("output.bin");
+        fs::write(&input_path, "0123456789")?;
+        fs::write(&output_path, "0123")?;
+
+        let mut output = OutputOptions::new().resume(true).open(&output_path)?;
+        let mut input = FileOrStdin::from_path(&input_path)?;
+        assert_eq!(input.resume_from(&output)?, 4);
+        io::copy(&mut input.lock(), &mut output.lock())?;
+        drop(output);
+        assert_eq!(fs::read_to_string(&output_path)?, "0123456789");
+
+        fs::write(&input_path, "01")?;
+        let output = OutputOptions::new().resume(true).open(&output_path)?;
+        let e = FileOrStdin::from_path(&input_path)?
+            .resume_from(&output)
+            .expect_err("the input is shorter");
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        let mut output = OutputOptions::new().resume(true).open(&output_path)?;
+        output.lock().write_all(b"!")?;
+        drop(output);
+        assert_eq!(fs::read_to_string(&output_path)?, "0123456789!");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
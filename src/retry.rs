@@ -0,0 +1,269 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    io::{self, BufRead, Read, Write},
+    thread,
+    time::Duration,
+};
+
+/// Which transient errors a [`Retrying`] stream retries, and how.
+///
+/// `Interrupted` is always retried straight away. `WouldBlock` is only retried after
+/// [`would_block`](RetryPolicy::would_block) turns it on, sleeping between attempts with a
+/// backoff that doubles up to [`max_backoff`](RetryPolicy::max_backoff).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    backoff: Option<Duration>,
+    max_backoff: Duration,
+    max_retries: Option<u32>,
+}
+
+/// A reader or writer whose reads, writes and flushes are retried according to a [`RetryPolicy`].
+pub struct Retrying<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            backoff: None,
+            max_backoff: Duration::from_secs(1),
+            max_retries: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry `WouldBlock` errors too, first sleeping for `backoff`.
+    pub fn would_block(mut self, backoff: Duration) -> Self {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// Longest sleep between `WouldBlock` retries. Defaults to one second.
+    pub fn max_backoff(mut self, max: Duration) -> Self {
+        self.max_backoff = max;
+        self
+    }
+
+    /// Give up and return the error after this many `WouldBlock` retries in a row. Defaults to
+    /// no limit.
+    pub fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = Some(retries);
+        self
+    }
+
+    /// Wrap `inner` so its operations are retried under this policy.
+    pub fn wrap<T>(self, inner: T) -> Retrying<T> {
+        Retrying::new(inner, self)
+    }
+
+    fn run<R>(&self, mut op: impl FnMut() -> io::Result<R>) -> io::Result<R> {
+        let mut retries = 0;
+        let mut sleep = self.backoff.unwrap_or_default();
+        loop {
+            match op() {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock && self.backoff.is_some() => {
+                    if self.max_retries.is_some_and(|max| retries >= max) {
+                        return Err(e);
+                    }
+                    retries += 1;
+                    thread::sleep(sleep);
+                    sleep = (sleep * 2).min(self.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<T> Retrying<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read> Read for Retrying<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.policy.run(|| inner.read(buf))
+    }
+}
+
+impl<T: BufRead> BufRead for Retrying<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let inner = &mut self.inner;
+        self.policy.run(|| inner.fill_buf().map(|buf| buf.len()))?;
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl<T: Write> Write for Retrying<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.policy.run(|| inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let inner = &mut self.inner;
+        self.policy.run(|| inner.flush())
+    }
+}
+
+/// How often and how patiently to retry opening a file that is transiently locked, from
+/// [`InputOptions::retry`](crate::InputOptions::retry) and
+/// [`OutputOptions::retry`](crate::OutputOptions::retry).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OpenRetry {
+    pub(crate) attempts: u32,
+    pub(crate) backoff: Duration,
+}
+
+impl OpenRetry {
+    /// Run `open`, retrying errors from files being locked or busy.
+    ///
+    /// The sleep before retry `n` is a random duration between half and all of
+    /// `backoff * 2^n`, so several processes waiting for the same file don't wake up together.
+    pub(crate) fn run<T>(&self, mut open: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let random = RandomState::new();
+        let mut sleep = self.backoff;
+        for attempt in 0.. {
+            match open() {
+                Err(e) if attempt < self.attempts && is_contended(&e) => {
+                    let half = sleep / 2;
+                    let jitter = random.hash_one(attempt) % (half.as_nanos() as u64 + 1);
+                    thread::sleep(half + Duration::from_nanos(jitter));
+                    sleep = sleep.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+        unreachable!("attempts are limited")
+    }
+}
+
+/// Whether opening failed because someone else has the file busy, so trying again can help.
+fn is_contended(e: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    match e.kind() {
+        Interrupted | WouldBlock | ResourceBusy | ExecutableFileBusy => true,
+        // Antivirus scanners and editors holding a file open without sharing it.
+        _ if cfg!(windows) => {
+            const ERROR_SHARING_VIOLATION: i32 = 32;
+            const ERROR_LOCK_VIOLATION: i32 = 33;
+            e.kind() == PermissionDenied
+                || matches!(
+                    e.raw_os_error(),
+                    Some(ERROR_SHARING_VIOLATION) | Some(ERROR_LOCK_VIOLATION)
+                )
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails each call with the given errors before succeeding.
+    struct Flaky {
+        errors: Vec<io::ErrorKind>,
+        written: Vec<u8>,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if let Some(kind) = self.errors.pop() {
+                return Err(kind.into());
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn flaky(errors: &[io::ErrorKind]) -> Flaky {
+        Flaky {
+            errors: errors.to_vec(),
+            written: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn retry_transient_errors() -> Result<(), io::Error> {
+        use io::ErrorKind::{Interrupted, WouldBlock};
+
+        let mut writer = RetryPolicy::new().wrap(flaky(&[Interrupted, Interrupted]));
+        writer.write_all(b"data")?;
+        assert_eq!(writer.get_ref().written, b"data");
+
+        let mut writer = RetryPolicy::new().wrap(flaky(&[WouldBlock]));
+        assert_eq!(writer.write(b"data").unwrap_err().kind(), WouldBlock);
+
+        let policy = RetryPolicy::new().would_block(Duration::from_millis(1));
+        let mut writer = policy.wrap(flaky(&[WouldBlock, Interrupted, WouldBlock]));
+        writer.write_all(b"data")?;
+        assert_eq!(writer.into_inner().written, b"data");
+
+        let mut writer = policy.max_retries(1).wrap(flaky(&[WouldBlock, WouldBlock]));
+        assert_eq!(writer.write(b"data").unwrap_err().kind(), WouldBlock);
+        Ok(())
+    }
+
+    #[test]
+    fn retry_busy_open() {
+        let retry = OpenRetry {
+            attempts: 2,
+            backoff: Duration::from_millis(1),
+        };
+        let mut errors = vec![io::ErrorKind::ResourceBusy, io::ErrorKind::ResourceBusy];
+        let mut open = || match errors.pop() {
+            Some(kind) => Err(io::Error::from(kind)),
+            None => Ok("opened"),
+        };
+        assert_eq!(retry.run(&mut open).ok(), Some("opened"));
+
+        let mut calls = 0;
+        let e = retry
+            .run(|| -> io::Result<()> {
+                calls += 1;
+                Err(io::ErrorKind::ResourceBusy.into())
+            })
+            .unwrap_err();
+        assert_eq!((e.kind(), calls), (io::ErrorKind::ResourceBusy, 3));
+
+        calls = 0;
+        let e = retry
+            .run(|| -> io::Result<()> {
+                calls += 1;
+                Err(io::ErrorKind::NotFound.into())
+            })
+            .unwrap_err();
+        assert_eq!((e.kind(), calls), (io::ErrorKind::NotFound, 1));
+    }
+}
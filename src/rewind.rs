@@ -0,0 +1,228 @@
+use crate::{temp, FileOrStdin};
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_MEMORY_LIMIT: usize = 1024 * 1024;
+
+/// An input that can be rewound and read again, even when it is stdin.
+///
+/// Files are rewound with a plain seek. Stdin is teed into memory as it is read, spilling into a
+/// temporary file once more than `memory_limit` bytes have been buffered, so that anything already
+/// read can be replayed. Seeking stdin past the data read so far is an error.
+pub struct Rewindable {
+    source: Source,
+}
+
+enum Source {
+    File(File),
+    Stdin(Tee<io::Stdin>),
+}
+
+impl Rewindable {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(FileOrStdin::from_path(path)?))
+    }
+
+    pub fn new<T: Into<FileOrStdin>>(input: T) -> Self {
+        let source = match input.into() {
+            FileOrStdin::File(file) => Source::File(file),
+            FileOrStdin::Stdin(stdin) => Source::Stdin(Tee::new(stdin)),
+        };
+        Self { source }
+    }
+
+    /// Set how many bytes of stdin are kept in memory before spilling to a temporary file.
+    pub fn memory_limit(mut self, limit: usize) -> Self {
+        if let Source::Stdin(tee) = &mut self.source {
+            tee.memory_limit = limit;
+        }
+        self
+    }
+
+    /// Set the directory the stdin spill file is created in. Defaults to `std::env::temp_dir()`.
+    pub fn spill_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        if let Source::Stdin(tee) = &mut self.source {
+            tee.spill_dir = dir.as_ref().to_owned();
+        }
+        self
+    }
+}
+
+impl Read for Rewindable {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.source {
+            Source::File(file) => file.read(buf),
+            Source::Stdin(tee) => tee.read(buf),
+        }
+    }
+}
+
+impl Seek for Rewindable {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut self.source {
+            Source::File(file) => file.seek(pos),
+            Source::Stdin(tee) => tee.seek(pos),
+        }
+    }
+}
+
+/// Reader that records everything read from `inner` so it can be replayed.
+struct Tee<R> {
+    inner: R,
+    spill: Spill,
+    pos: u64,
+    memory_limit: usize,
+    spill_dir: PathBuf,
+}
+
+enum Spill {
+    Memory(Vec<u8>),
+    File { file: File, path: PathBuf, len: u64 },
+}
+
+impl<R: Read> Tee<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            spill: Spill::Memory(Vec::new()),
+            pos: 0,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            spill_dir: env::temp_dir(),
+        }
+    }
+
+    fn record(&mut self, data: &[u8]) -> io::Result<()> {
+        match &mut self.spill {
+            Spill::Memory(buf) if buf.len() + data.len() <= self.memory_limit => {
+                buf.extend_from_slice(data);
+            }
+            Spill::Memory(buf) => {
+                let (mut file, path) = temp::create_unique(&self.spill_dir, "polymorphio-spill")?;
+                let result = file.write_all(buf).and_then(|_| file.write_all(data));
+                if let Err(e) = result {
+                    let _ = fs::remove_file(&path);
+                    return Err(e);
+                }
+                let len = (buf.len() + data.len()) as u64;
+                self.spill = Spill::File { file, path, len };
+            }
+            Spill::File { file, len, .. } => {
+                file.seek(SeekFrom::End(0))?;
+                file.write_all(data)?;
+                *len += data.len() as u64;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.spill.len() {
+            let amt = self.spill.read_at(self.pos, buf)?;
+            self.pos += amt as u64;
+            return Ok(amt);
+        }
+
+        let amt = self.inner.read(buf)?;
+        self.record(&buf[..amt])?;
+        self.pos += amt as u64;
+        Ok(amt)
+    }
+}
+
+impl<R> Seek for Tee<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => {
+                let current = self.pos as i64;
+                current.checked_add(delta).filter(|t| *t >= 0).map(|t| t as u64)
+            }
+            SeekFrom::End(_) => None,
+        };
+
+        match target {
+            Some(target) if target <= self.spill.len() => {
+                self.pos = target;
+                Ok(target)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot seek stdin beyond the data read so far",
+            )),
+        }
+    }
+}
+
+impl Spill {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Memory(buf) => buf.len() as u64,
+            Self::File { len, .. } => *len,
+        }
+    }
+
+    fn read_at(&mut self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Memory(data) => (&data[pos as usize..]).read(buf),
+            Self::File { file, len, .. } => {
+                let available = (*len - pos).min(buf.len() as u64) as usize;
+                file.seek(SeekFrom::Start(pos))?;
+                file.read(&mut buf[..available])
+            }
+        }
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        if let Self::File { path, .. } = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn replay_after_spill() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let expected_content = "a stream longer than the memory limit";
+
+        let mut tee = Tee::new(expected_content.as_bytes());
+        tee.memory_limit = 8;
+        tee.spill_dir = tmp_dir.path().to_owned();
+
+        let mut first = [0; 6];
+        tee.read_exact(&mut first)?;
+        assert_eq!(&first, b"a stre");
+        assert!(matches!(tee.spill, Spill::Memory(_)));
+
+        tee.rewind()?;
+        let mut actual_content = String::new();
+        tee.read_to_string(&mut actual_content)?;
+        assert_eq!(actual_content, expected_content);
+        assert!(matches!(tee.spill, Spill::File { .. }));
+
+        tee.seek(SeekFrom::Start(2))?;
+        actual_content.clear();
+        tee.read_to_string(&mut actual_content)?;
+        assert_eq!(actual_content, &expected_content[2..]);
+
+        assert!(tee.seek(SeekFrom::End(0)).is_err());
+
+        drop(tee);
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 0);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
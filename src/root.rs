@@ -0,0 +1,296 @@
+use crate::{instrument, FileOrStdin, FileOrStdout};
+use std::{
+    fs::File,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+/// A directory that relative paths are opened inside of, and never outside of.
+///
+/// Paths that are absolute or contain `..` are refused, and so are symlinks leading out of the
+/// directory, failing with `PermissionDenied`. This makes it safe to open paths from untrusted
+/// archives or users.
+///
+/// On Linux this uses `openat2` with `RESOLVE_BENEATH`, so symlinks staying inside the directory
+/// still work. On older kernels and other unix systems each component is opened with `openat`
+/// and `O_NOFOLLOW`, so any symlink is refused. Elsewhere the resolved path is checked before
+/// opening, which can be raced by someone changing the directory at the same time.
+pub struct Root {
+    path: PathBuf,
+    #[cfg(unix)]
+    dir: File,
+}
+
+impl Root {
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let path = dir.as_ref().to_owned();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+
+            let dir = File::options()
+                .read(true)
+                .custom_flags(libc::O_DIRECTORY)
+                .open(&path)?;
+            Ok(Self { path, dir })
+        }
+        #[cfg(not(unix))]
+        {
+            if !path.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotADirectory,
+                    format!("{} is not a directory", path.display()),
+                ));
+            }
+            Ok(Self { path })
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Open the file at `relative` inside the directory for reading.
+    pub fn input<P: AsRef<Path>>(&self, relative: P) -> io::Result<FileOrStdin> {
+        let relative = relative.as_ref();
+        let full = self.path.join(relative);
+        Ok(instrument::open_file(&full, |_| self.open_beneath(relative, false))?.into())
+    }
+
+    /// Create or truncate the file at `relative` inside the directory for writing.
+    pub fn output<P: AsRef<Path>>(&self, relative: P) -> io::Result<FileOrStdout> {
+        let relative = relative.as_ref();
+        let full = self.path.join(relative);
+        Ok(instrument::open_file(&full, |_| self.open_beneath(relative, true))?.into())
+    }
+
+    fn escapes(&self, relative: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} escapes {}", relative.display(), self.path.display()),
+        )
+    }
+
+    /// Fail unless `relative` is a plain relative path, returning its components.
+    fn components<'p>(&self, relative: &'p Path) -> io::Result<Vec<&'p std::ffi::OsStr>> {
+        let mut components = Vec::new();
+        for component in relative.components() {
+            match component {
+                Component::Normal(name) => components.push(name),
+                Component::CurDir => {}
+                _ => return Err(self.escapes(relative)),
+            }
+        }
+        if components.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty path inside root",
+            ));
+        }
+        Ok(components)
+    }
+
+    #[cfg(unix)]
+    fn open_beneath(&self, relative: &Path, write: bool) -> io::Result<File> {
+        let mut components = self.components(relative)?;
+        let flags = if write {
+            libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC
+        } else {
+            libc::O_RDONLY
+        };
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        match sys::openat2_beneath(&self.dir, relative, flags) {
+            Err(e) if e.raw_os_error() == Some(libc::EXDEV) => return Err(self.escapes(relative)),
+            // Kernels before 5.6, or a sandbox that blocks the syscall.
+            Err(e) if matches!(e.raw_os_error(), Some(libc::ENOSYS) | Some(libc::EPERM)) => {}
+            result => return result,
+        }
+
+        let last = components.pop().expect("paths have at least one component");
+        let mut dir = None;
+        for name in components {
+            let parent = dir.as_ref().unwrap_or(&self.dir);
+            let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW;
+            dir = Some(self.openat(parent, name, flags, relative)?);
+        }
+        let parent = dir.as_ref().unwrap_or(&self.dir);
+        self.openat(parent, last, flags | libc::O_NOFOLLOW, relative)
+    }
+
+    #[cfg(unix)]
+    fn openat(
+        &self,
+        dir: &File,
+        name: &std::ffi::OsStr,
+        flags: libc::c_int,
+        relative: &Path,
+    ) -> io::Result<File> {
+        match sys::openat(dir, name, flags) {
+            Err(e) if e.raw_os_error() == Some(libc::ELOOP) => Err(self.escapes(relative)),
+            // Some systems report a symlink opened with `O_DIRECTORY` as not a directory.
+            Err(e) if e.raw_os_error() == Some(libc::ENOTDIR) && sys::is_symlink_at(dir, name) => {
+                Err(self.escapes(relative))
+            }
+            result => result,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn open_beneath(&self, relative: &Path, write: bool) -> io::Result<File> {
+        let mut components = self.components(relative)?;
+        let last = components.pop().expect("paths have at least one component");
+        let parent = components
+            .iter()
+            .fold(self.path.clone(), |dir, name| dir.join(name));
+        let root = self.path.canonicalize()?;
+        if !parent.canonicalize()?.starts_with(&root) {
+            return Err(self.escapes(relative));
+        }
+        let full = parent.join(last);
+        match full.symlink_metadata() {
+            Ok(meta) if meta.file_type().is_symlink() => return Err(self.escapes(relative)),
+            _ => {}
+        }
+        if write {
+            File::create(full)
+        } else {
+            File::open(full)
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::{
+        ffi::{CString, OsStr},
+        fs::File,
+        io,
+        os::unix::{
+            ffi::OsStrExt,
+            io::{AsRawFd, FromRawFd},
+        },
+    };
+
+    const MODE: libc::c_uint = 0o666;
+
+    fn from_fd(fd: libc::c_long) -> io::Result<File> {
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the fd was just opened and nothing else owns it.
+        Ok(unsafe { File::from_raw_fd(fd as libc::c_int) })
+    }
+
+    pub(super) fn openat(dir: &File, name: &OsStr, flags: libc::c_int) -> io::Result<File> {
+        let name = CString::new(name.as_bytes())?;
+        let flags = flags | libc::O_CLOEXEC;
+        // SAFETY: `name` is a valid C string and `dir` an open directory.
+        let fd = unsafe { libc::openat(dir.as_raw_fd(), name.as_ptr(), flags, MODE) };
+        from_fd(fd.into())
+    }
+
+    pub(super) fn is_symlink_at(dir: &File, name: &OsStr) -> bool {
+        let name = match CString::new(name.as_bytes()) {
+            Ok(name) => name,
+            Err(_) => return false,
+        };
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        // SAFETY: fstatat only writes into the provided `stat`.
+        let ret = unsafe {
+            libc::fstatat(
+                dir.as_raw_fd(),
+                name.as_ptr(),
+                &mut stat,
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        };
+        ret == 0 && stat.st_mode & libc::S_IFMT == libc::S_IFLNK
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(super) fn openat2_beneath(
+        dir: &File,
+        relative: &std::path::Path,
+        flags: libc::c_int,
+    ) -> io::Result<File> {
+        let path = CString::new(relative.as_os_str().as_bytes())?;
+        // SAFETY: open_how is plain data, and all zeroes is its default.
+        let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+        how.flags = (flags | libc::O_CLOEXEC) as u64;
+        if flags & libc::O_CREAT != 0 {
+            how.mode = MODE.into();
+        }
+        how.resolve = libc::RESOLVE_BENEATH | libc::RESOLVE_NO_MAGICLINKS;
+        // SAFETY: `path` is a valid C string and `how` is the size passed.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_openat2,
+                dir.as_raw_fd(),
+                path.as_ptr(),
+                &how as *const libc::open_how,
+                std::mem::size_of::<libc::open_how>(),
+            )
+        };
+        from_fd(fd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        io::{Read, Write},
+    };
+    use tempfile::TempDir;
+
+    #[test]
+    fn open_inside_root() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let inside = tmp_dir.path().join("inside");
+        fs::create_dir_all(inside.join("sub"))?;
+        fs::write(inside.join("sub/data.txt"), "data")?;
+        fs::write(tmp_dir.path().join("secret.txt"), "secret")?;
+
+        let root = Root::open(&inside)?;
+        let mut content = String::new();
+        root.input("sub/./data.txt")?
+            .lock()
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "data");
+
+        let mut output = root.output("sub/new.txt")?;
+        output.lock().write_all(b"new")?;
+        assert_eq!(fs::read_to_string(inside.join("sub/new.txt"))?, "new");
+
+        let secret = tmp_dir.path().join("secret.txt");
+        for path in [
+            Path::new("../secret.txt"),
+            &secret,
+            Path::new("sub/../../secret.txt"),
+        ] {
+            let e = root.input(path).err().expect("escapes are refused");
+            assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("../secret.txt", inside.join("up.txt"))?;
+            std::os::unix::fs::symlink(&secret, inside.join("sub/abs.txt"))?;
+            std::os::unix::fs::symlink(tmp_dir.path(), inside.join("out"))?;
+            for path in ["up.txt", "sub/abs.txt", "out/secret.txt"] {
+                let e = root.input(path).err().expect("symlink escapes are refused");
+                assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+            }
+            assert!(root.output("up.txt").is_err());
+            assert_eq!(fs::read_to_string(&secret)?, "secret");
+        }
+        assert_eq!(
+            root.input("").err().map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidInput)
+        );
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+#[cfg(feature = "compress")]
+use crate::compress::{Codec, CompressWriter};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the log path is checked for having been moved away by someone else.
+const EXTERNAL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+static SIGHUP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Log-style output that rotates its file by size or age.
+///
+/// On rotation `log` becomes `log.1`, `log.1` becomes `log.2` and so on, keeping at most `keep`
+/// old files (optionally compressed, as `log.1.gz` etc.). The file is also reopened when another
+/// program (e.g. logrotate) moves or deletes it, when a [`ReopenHandle`] is triggered, or, after
+/// [`reopen_on_sighup`](RotatingOutput::reopen_on_sighup), on `SIGHUP`. Writes are never split
+/// across files.
+pub struct RotatingOutput {
+    path: PathBuf,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    keep: usize,
+    #[cfg(feature = "compress")]
+    codec: Option<Codec>,
+    file: File,
+    written: u64,
+    opened_at: SystemTime,
+    last_external_check: Instant,
+    reopen: Arc<AtomicBool>,
+    sighup_seen: usize,
+}
+
+/// Handle for asking a [`RotatingOutput`] to reopen its file before the next write.
+///
+/// Setting the flag is async-signal-safe, so it can be used from custom signal handlers.
+#[derive(Clone)]
+pub struct ReopenHandle(Arc<AtomicBool>);
+
+impl ReopenHandle {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+impl RotatingOutput {
+    /// Open `path` for appending. Nothing is rotated until a limit is set.
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+        let (file, written) = open_append(&path)?;
+        Ok(Self {
+            path,
+            max_bytes: None,
+            max_age: None,
+            keep: 5,
+            #[cfg(feature = "compress")]
+            codec: None,
+            file,
+            written,
+            opened_at: SystemTime::now(),
+            last_external_check: Instant::now(),
+            reopen: Arc::new(AtomicBool::new(false)),
+            sighup_seen: SIGHUP_COUNT.load(Ordering::SeqCst),
+        })
+    }
+
+    /// Rotate before a write would make the file larger than `max` bytes.
+    pub fn max_bytes(mut self, max: u64) -> Self {
+        self.max_bytes = Some(max);
+        self
+    }
+
+    /// Rotate once the current file has been open for `max` time.
+    pub fn max_age(mut self, max: Duration) -> Self {
+        self.max_age = Some(max);
+        self
+    }
+
+    /// Keep at most `keep` rotated files. Defaults to 5.
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Compress rotated files.
+    #[cfg(feature = "compress")]
+    pub fn compress(mut self, codec: Codec) -> Self {
+        self.codec = Some(codec);
+        self
+    }
+
+    pub fn reopen_handle(&self) -> ReopenHandle {
+        ReopenHandle(Arc::clone(&self.reopen))
+    }
+
+    /// Install a process-wide `SIGHUP` handler that makes every `RotatingOutput` reopen its file.
+    #[cfg(unix)]
+    pub fn reopen_on_sighup(self) -> io::Result<Self> {
+        extern "C" fn on_sighup(_: libc::c_int) {
+            SIGHUP_COUNT.fetch_add(1, Ordering::SeqCst);
+        }
+        let handler: extern "C" fn(libc::c_int) = on_sighup;
+        // SAFETY: the handler only touches an atomic, which is async-signal-safe.
+        if unsafe { libc::signal(libc::SIGHUP, handler as libc::sighandler_t) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(self)
+    }
+
+    /// Rotate right away.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            self.shift_old_files()?;
+            let first = numbered(&self.path, 1);
+            fs::rename(&self.path, &first)?;
+            #[cfg(feature = "compress")]
+            if let Some(codec) = self.codec {
+                compress_file(&first, codec)?;
+            }
+        }
+        self.reopen_file()
+    }
+
+    fn rotated_name(&self, n: usize) -> PathBuf {
+        let path = numbered(&self.path, n);
+        #[cfg(feature = "compress")]
+        if let Some(codec) = self.codec {
+            return with_extension(&path, codec.extension());
+        }
+        path
+    }
+
+    fn shift_old_files(&self) -> io::Result<()> {
+        let oldest = self.rotated_name(self.keep);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for n in (1..self.keep).rev() {
+            let from = self.rotated_name(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated_name(n + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn reopen_file(&mut self) -> io::Result<()> {
+        let (file, written) = open_append(&self.path)?;
+        self.file = file;
+        self.written = written;
+        self.opened_at = SystemTime::now();
+        Ok(())
+    }
+
+    fn needs_reopen(&mut self) -> bool {
+        let sighups = SIGHUP_COUNT.load(Ordering::SeqCst);
+        let mut reopen = self.reopen.swap(false, Ordering::SeqCst) || sighups != self.sighup_seen;
+        self.sighup_seen = sighups;
+
+        if self.last_external_check.elapsed() >= EXTERNAL_CHECK_INTERVAL {
+            self.last_external_check = Instant::now();
+            reopen |= self.moved_externally();
+        }
+        reopen
+    }
+
+    #[cfg(unix)]
+    fn moved_externally(&self) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        match (fs::metadata(&self.path), self.file.metadata()) {
+            (Ok(on_disk), Ok(open)) => on_disk.dev() != open.dev() || on_disk.ino() != open.ino(),
+            _ => true,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn moved_externally(&self) -> bool {
+        !self.path.exists()
+    }
+
+    fn needs_rotation(&self, len: usize) -> bool {
+        if self.written == 0 {
+            return false;
+        }
+        let too_big = self
+            .max_bytes
+            .is_some_and(|max| self.written + len as u64 > max);
+        let too_old = self
+            .max_age
+            .is_some_and(|max| self.opened_at.elapsed().is_ok_and(|elapsed| elapsed >= max));
+        too_big || too_old
+    }
+}
+
+impl Write for RotatingOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.needs_reopen() {
+            self.reopen_file()?;
+        }
+        if self.needs_rotation(buf.len()) {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<(File, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let len = file.metadata()?.len();
+    Ok((file, len))
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    with_extension(path, &n.to_string())
+}
+
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+#[cfg(feature = "compress")]
+fn compress_file(path: &Path, codec: Codec) -> io::Result<()> {
+    let compressed = with_extension(path, codec.extension());
+    let mut writer = CompressWriter::new(File::create(&compressed)?, codec)?;
+    io::copy(&mut File::open(path)?, &mut writer)?;
+    writer.finish()?.sync_all()?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn rotate_by_size_and_reopen() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let log = tmp_dir.path().join("app.log");
+
+        let mut output = RotatingOutput::new(&log)?.max_bytes(8).keep(2);
+        for line in &["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            output.write_all(line.as_bytes())?;
+        }
+        assert_eq!(fs::read_to_string(&log)?, "five\n");
+        assert_eq!(fs::read_to_string(numbered(&log, 1))?, "four\n");
+        assert_eq!(fs::read_to_string(numbered(&log, 2))?, "three\n");
+        assert!(!numbered(&log, 3).exists());
+
+        // Someone else moves the log away and asks for a reopen.
+        fs::rename(&log, tmp_dir.path().join("moved.log"))?;
+        output.reopen_handle().request();
+        output.write_all(b"six\n")?;
+        assert_eq!(fs::read_to_string(&log)?, "six\n");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
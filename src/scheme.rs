@@ -0,0 +1,148 @@
+#[cfg(feature = "clipboard")]
+use crate::clipboard::{is_clipboard_path, ClipboardReader};
+#[cfg(feature = "git")]
+use crate::git::is_git_spec;
+#[cfg(any(feature = "s3", feature = "gcs"))]
+use crate::object::{is_object_url, ObjectInput};
+#[cfg(feature = "ssh")]
+use crate::sftp::{is_sftp_url, SftpInput};
+#[cfg(any(
+    feature = "clipboard",
+    feature = "s3",
+    feature = "gcs",
+    feature = "ssh"
+))]
+use crate::temp;
+use crate::FileOrStdin;
+use std::{io, path::Path};
+
+/// Open the input at `path` if it's named by a scheme rather than being a local file, or
+/// return `None`. The schemes are `clip:`, `s3://` and `gs://`, `sftp://` and `git:`, each with
+/// its feature.
+///
+/// The content is copied to a temporary file first, so it's read like any other file.
+#[cfg_attr(
+    not(any(
+        feature = "clipboard",
+        feature = "s3",
+        feature = "gcs",
+        feature = "ssh",
+        feature = "git"
+    )),
+    allow(unused_variables)
+)]
+pub(crate) fn open_input(path: &Path) -> Option<io::Result<FileOrStdin>> {
+    #[cfg(feature = "clipboard")]
+    if is_clipboard_path(path) {
+        return Some(ClipboardReader::open().and_then(|mut reader| temp::spool(&mut reader)));
+    }
+    #[cfg(any(feature = "s3", feature = "gcs"))]
+    if is_object_url(path) {
+        let open = ObjectInput::open(&path.to_string_lossy());
+        return Some(open.and_then(|mut reader| temp::spool(&mut reader)));
+    }
+    #[cfg(feature = "ssh")]
+    if is_sftp_url(path) {
+        let open = SftpInput::open(&path.to_string_lossy());
+        return Some(open.and_then(|mut reader| temp::spool(&mut reader)));
+    }
+    #[cfg(feature = "git")]
+    if is_git_spec(path) {
+        return Some(FileOrStdin::from_git_spec(&path.to_string_lossy()));
+    }
+    None
+}
+
+/// Fail with `Unsupported` if the output `path` is named by a scheme, rather than creating a
+/// local file of that name.
+///
+/// Clipboards and remote files are only replaced once their writer is finished, which a
+/// `FileOrStdout` can't be, so they're written with [`ClipboardWriter`](crate::ClipboardWriter),
+/// [`ObjectOutput`](crate::ObjectOutput) and [`SftpOutput`](crate::SftpOutput) instead. `git:`
+/// specs can't be written at all.
+#[cfg_attr(
+    not(any(
+        feature = "clipboard",
+        feature = "s3",
+        feature = "gcs",
+        feature = "ssh",
+        feature = "git"
+    )),
+    allow(unused_variables)
+)]
+pub(crate) fn check_output(path: &Path) -> io::Result<()> {
+    #[cfg(feature = "clipboard")]
+    if is_clipboard_path(path) {
+        return Err(unsupported(path, "ClipboardWriter"));
+    }
+    #[cfg(any(feature = "s3", feature = "gcs"))]
+    if is_object_url(path) {
+        return Err(unsupported(path, "ObjectOutput"));
+    }
+    #[cfg(feature = "ssh")]
+    if is_sftp_url(path) {
+        return Err(unsupported(path, "SftpOutput"));
+    }
+    #[cfg(feature = "git")]
+    if is_git_spec(path) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "`{}` is a git revision, which can't be written",
+                path.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(any(
+    feature = "clipboard",
+    feature = "s3",
+    feature = "gcs",
+    feature = "ssh"
+))]
+fn unsupported(path: &Path, writer: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("`{}` can only be written with `{}`", path.display(), writer),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatch_schemes() {
+        assert!(open_input(Path::new("local.txt")).is_none());
+        assert!(check_output(Path::new("local.txt")).is_ok());
+
+        #[cfg(feature = "git")]
+        {
+            let e = open_input(Path::new("git:HEAD")).unwrap().err().unwrap();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+            assert!(check_output(Path::new("git:HEAD:out.txt")).is_err());
+        }
+        #[cfg(feature = "ssh")]
+        {
+            let e = open_input(Path::new("sftp://host")).unwrap().err().unwrap();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        }
+        #[cfg(all(feature = "s3", not(feature = "gcs")))]
+        {
+            let e = open_input(Path::new("gs://bucket/a"))
+                .unwrap()
+                .err()
+                .unwrap();
+            assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        }
+        #[cfg(feature = "s3")]
+        {
+            let e = check_output(Path::new("s3://bucket/a")).err().unwrap();
+            assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        }
+        #[cfg(feature = "clipboard")]
+        assert!(check_output(Path::new("clip:")).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::FileOrStdin;
+use std::io::{self, BufRead, Read, Write};
+
+impl FileOrStdin {
+    /// Read one line, such as a password, without the trailing newline.
+    ///
+    /// When the input is a terminal, echo is turned off while the line is typed. The terminal
+    /// settings are restored afterwards, even if reading fails or panics. Ctrl-C doesn't raise
+    /// `SIGINT`, which would skip that, but fails the read with `Interrupted`. Files and pipes
+    /// are simply read up to the first newline, so `--password-file -` works identically.
+    pub fn read_secret(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+
+        if self.is_terminal() {
+            let _guard = self.secret_mode()?;
+            let result = read_typed_line(&mut self.lock());
+            // The newline the user typed wasn't echoed either.
+            let _ = writeln!(io::stderr());
+            line = result?;
+        } else {
+            self.lock().read_line(&mut line)?;
+        }
+
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(line)
+    }
+
+    /// Turn off echo, and the terminal's own line editing and signal keys, which
+    /// [`read_typed_line`] handles instead.
+    #[cfg(unix)]
+    fn secret_mode(&self) -> io::Result<crate::term::TermiosGuard> {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = match self {
+            Self::File(file) => file.as_raw_fd(),
+            Self::Stdin(stdin) => stdin.as_raw_fd(),
+        };
+        crate::term::TermiosGuard::modify(fd, |termios| {
+            termios.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+            termios.c_cc[libc::VMIN] = 1;
+            termios.c_cc[libc::VTIME] = 0;
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn secret_mode(&self) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "reading secrets from a terminal is not supported on this platform",
+        ))
+    }
+}
+
+/// Read a line typed at a terminal in [`secret_mode`](FileOrStdin::secret_mode), up to Enter or
+/// Ctrl-D, applying Backspace and Ctrl-U.
+fn read_typed_line<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    let mut byte = [0];
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+        match byte[0] {
+            b'\r' | b'\n' => break,
+            0x03 => return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted")),
+            0x04 if bytes.is_empty() => break,
+            0x7f | 0x08 => {
+                // Remove a whole UTF-8 character.
+                while let Some(b) = bytes.pop() {
+                    if b & 0xc0 != 0x80 {
+                        break;
+                    }
+                }
+            }
+            0x15 => bytes.clear(),
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_secret_from_file() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let password_path = tmp_dir.path().join("password.txt");
+        fs::write(&password_path, "hunter2\r\nsecond line\n")?;
+
+        let secret = FileOrStdin::from_path(&password_path)?.read_secret()?;
+        assert_eq!(secret, "hunter2");
+
+        let typed = read_typed_line(&mut &"h\u{e9}x\x7f\x7funter2\rafter".as_bytes()[..])?;
+        assert_eq!(typed, "hunter2");
+        let e = read_typed_line(&mut &b"hunt\x03er2\r"[..]).expect_err("Ctrl-C was typed");
+        assert_eq!(e.kind(), io::ErrorKind::Interrupted);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    report::Counted, AtomicOutput, Buffering, FileOrStdin, FileOrStdout, IoObserver, Observed,
+    Report, STDIO_FILENAME,
+};
+#[cfg(feature = "compress")]
+use crate::{Codec, CompressOptions, CompressWriter, DecompressReader};
+#[cfg(feature = "compress")]
+use std::io::{BufReader, BufWriter};
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+/// One input and one output, configured once, for tools whose whole job is `input -> output`.
+///
+/// [`run`](Session::run) opens both ends, hands them to a closure, then flushes, finishes
+/// compression and commits an atomic output, so tools don't repeat that setup and teardown.
+/// Bytes read and written add up in [`report`](Session::report) over every successful run, and
+/// an [`observer`](Session::observer) sees both ends.
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// use polymorphio::Session;
+/// use std::io::{self, BufRead, Write};
+///
+/// let mut session = Session::new("in.txt", "out.txt").atomic(true);
+/// session.run(|input, output| {
+///     for line in input.lines() {
+///         writeln!(output, "{}", line?.to_uppercase())?;
+///     }
+///     Ok(())
+/// })?;
+/// eprintln!("{}", session.report());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Session {
+    input: PathBuf,
+    output: PathBuf,
+    buffering: Buffering,
+    atomic: bool,
+    #[cfg(feature = "compress")]
+    compress: Option<CompressOptions>,
+    #[cfg(feature = "compress")]
+    compression_threads: Option<usize>,
+    #[cfg(feature = "compress")]
+    decompress: bool,
+    observer: Option<RefCell<Box<dyn IoObserver>>>,
+    report: Report,
+}
+
+impl Session {
+    /// Either path may be `-` for stdin or stdout.
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Self {
+        Self {
+            input: input.as_ref().to_owned(),
+            output: output.as_ref().to_owned(),
+            buffering: Buffering::Auto,
+            atomic: false,
+            #[cfg(feature = "compress")]
+            compress: None,
+            #[cfg(feature = "compress")]
+            compression_threads: None,
+            #[cfg(feature = "compress")]
+            decompress: false,
+            observer: None,
+            report: Report::new(),
+        }
+    }
+
+    /// How a plain (not atomic or compressed) output is buffered. Defaults to
+    /// [`Buffering::Auto`].
+    pub fn buffering(mut self, buffering: Buffering) -> Self {
+        self.buffering = buffering;
+        self
+    }
+
+    /// Write a file output through an [`AtomicOutput`], so it's only replaced if the closure
+    /// succeeds. Stdout is written directly either way.
+    pub fn atomic(mut self, yes: bool) -> Self {
+        self.atomic = yes;
+        self
+    }
+
+    /// Compress the output with `codec`.
+    #[cfg(feature = "compress")]
+    pub fn compress(self, codec: Codec) -> Self {
+        self.compress_with(CompressOptions::new(codec))
+    }
+
+    /// Compress the output as `options` say, for levels, windows and dictionaries.
+    #[cfg(feature = "compress")]
+    pub fn compress_with(mut self, options: CompressOptions) -> Self {
+        self.compress = Some(options);
+        self
+    }
+
+    /// See [`CompressOptions::compression_threads`]; this overrides the setting of options given
+    /// to [`compress_with`](Session::compress_with).
+    #[cfg(feature = "compress")]
+    pub fn compression_threads(mut self, n: usize) -> Self {
+        self.compression_threads = Some(n);
+        self
+    }
+
+    /// Decompress the input if its extension names a [`Codec`].
+    #[cfg(feature = "compress")]
+    pub fn decompress(mut self, yes: bool) -> Self {
+        self.decompress = yes;
+        self
+    }
+
+    /// Report both ends to `observer`. It sees the uncompressed data, and `on_close` once for
+    /// each end.
+    pub fn observer<O: IoObserver + 'static>(mut self, observer: O) -> Self {
+        self.observer = Some(RefCell::new(Box::new(observer)));
+        self
+    }
+
+    /// Totals over the successful runs so far.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Open the input and output, call `f` with them, and complete the output if it succeeds.
+    ///
+    /// If `f` or completing the output fails, an atomic output is left untouched and the run
+    /// isn't counted.
+    pub fn run<F>(&mut self, f: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut dyn BufRead, &mut dyn Write) -> io::Result<()>,
+    {
+        let shared = Shared(self.observer.as_ref());
+        let mut shared_open = Shared(self.observer.as_ref());
+        let mut input = FileOrStdin::from_path(&self.input)?;
+        shared_open.on_open(&self.input);
+        let mut sink = self.open_sink()?;
+        shared_open.on_open(&self.output);
+
+        let bytes = {
+            let mut input_lock;
+            #[cfg(feature = "compress")]
+            let mut decompressed;
+            let reader: &mut dyn BufRead = match self.input_codec() {
+                #[cfg(feature = "compress")]
+                Some(codec) => {
+                    let owned: Box<dyn io::Read + Send> = match input.into_inner() {
+                        Ok(file) => Box::new(file),
+                        Err(stdin) => Box::new(stdin),
+                    };
+                    decompressed = BufReader::new(DecompressReader::new(owned, codec)?);
+                    &mut decompressed
+                }
+                _ => {
+                    input_lock = input.lock();
+                    &mut input_lock
+                }
+            };
+
+            let mut output_lock;
+            let writer: &mut dyn Write = match &mut sink {
+                Sink::Plain(output) => {
+                    output_lock = output.lock_buffered(self.buffering);
+                    &mut output_lock
+                }
+                Sink::Atomic(output) => output,
+                #[cfg(feature = "compress")]
+                Sink::Compressed(output) => output,
+                #[cfg(feature = "compress")]
+                Sink::CompressedAtomic(output) => output,
+            };
+
+            let mut reader = Observed::new(Counted::new(reader), shared);
+            let mut writer = Observed::new(Counted::new(writer), shared);
+            f(&mut reader, &mut writer)?;
+            writer.flush()?;
+            (reader.get_ref().count, writer.get_ref().count)
+        };
+
+        match sink {
+            Sink::Plain(_) => {}
+            Sink::Atomic(output) => {
+                (*output).commit()?;
+            }
+            #[cfg(feature = "compress")]
+            Sink::Compressed(output) => (*output).finish()?.flush()?,
+            #[cfg(feature = "compress")]
+            Sink::CompressedAtomic(output) => {
+                (*output).finish()?.commit()?;
+            }
+        }
+        self.report.add_processed();
+        self.report.add_bytes(bytes.0, bytes.1);
+        Ok(())
+    }
+
+    #[cfg(feature = "compress")]
+    fn input_codec(&self) -> Option<Codec> {
+        if self.decompress {
+            Codec::from_path(&self.input)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(not(feature = "compress"))]
+    fn input_codec(&self) -> Option<std::convert::Infallible> {
+        None
+    }
+
+    fn open_sink(&self) -> io::Result<Sink> {
+        let atomic = self.atomic && self.output != Path::new(STDIO_FILENAME);
+        let sink = match (atomic, self.output_codec()) {
+            (true, None) => Sink::Atomic(Box::new(AtomicOutput::from_path(&self.output)?)),
+            (false, None) => Sink::Plain(FileOrStdout::from_path(&self.output)?),
+            #[cfg(feature = "compress")]
+            (true, Some(options)) => Sink::CompressedAtomic(Box::new(
+                options.writer(AtomicOutput::from_path(&self.output)?)?,
+            )),
+            #[cfg(feature = "compress")]
+            (false, Some(options)) => {
+                let owned: Box<dyn Write + Send> =
+                    match FileOrStdout::from_path(&self.output)?.into_inner() {
+                        Ok(file) => Box::new(file),
+                        Err(stdout) => Box::new(stdout),
+                    };
+                Sink::Compressed(Box::new(options.writer(BufWriter::new(owned))?))
+            }
+        };
+        Ok(sink)
+    }
+
+    #[cfg(feature = "compress")]
+    fn output_codec(&self) -> Option<CompressOptions> {
+        let options = self.compress.clone()?;
+        Some(match self.compression_threads {
+            Some(n) => options.compression_threads(n),
+            None => options,
+        })
+    }
+
+    #[cfg(not(feature = "compress"))]
+    fn output_codec(&self) -> Option<std::convert::Infallible> {
+        None
+    }
+}
+
+enum Sink {
+    Plain(FileOrStdout),
+    Atomic(Box<AtomicOutput>),
+    #[cfg(feature = "compress")]
+    Compressed(Box<CompressWriter<BufWriter<Box<dyn Write + Send>>>>),
+    #[cfg(feature = "compress")]
+    CompressedAtomic(Box<CompressWriter<AtomicOutput>>),
+}
+
+/// The session's observer, shared by both ends of a run.
+#[derive(Clone, Copy)]
+struct Shared<'a>(Option<&'a RefCell<Box<dyn IoObserver>>>);
+
+impl<'a> Shared<'a> {
+    fn with(&mut self, f: impl FnOnce(&mut dyn IoObserver)) {
+        if let Some(observer) = self.0 {
+            f(&mut **observer.borrow_mut());
+        }
+    }
+}
+
+impl<'a> IoObserver for Shared<'a> {
+    fn on_open(&mut self, path: &Path) {
+        self.with(|o| o.on_open(path))
+    }
+
+    fn on_read(&mut self, data: &[u8]) {
+        self.with(|o| o.on_read(data))
+    }
+
+    fn on_write(&mut self, data: &[u8]) {
+        self.with(|o| o.on_write(data))
+    }
+
+    fn on_close(&mut self) {
+        self.with(|o| o.on_close())
+    }
+
+    fn on_error(&mut self, error: &io::Error) {
+        self.with(|o| o.on_error(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, rc::Rc};
+    use tempfile::TempDir;
+
+    #[derive(Default)]
+    struct Seen(Rc<RefCell<(u64, u64, usize)>>);
+
+    impl IoObserver for Seen {
+        fn on_read(&mut self, data: &[u8]) {
+            self.0.borrow_mut().0 += data.len() as u64;
+        }
+
+        fn on_write(&mut self, data: &[u8]) {
+            self.0.borrow_mut().1 += data.len() as u64;
+        }
+
+        fn on_open(&mut self, _: &Path) {
+            self.0.borrow_mut().2 += 1;
+        }
+    }
+
+    #[test]
+    fn run_session() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let input_path = tmp_dir.path().join("in.txt");
+        let output_path = tmp_dir.path().join("out.txt");
+        fs::write(&input_path, "hello\nworld\n")?;
+        fs::write(&output_path, "old")?;
+
+        let seen = Seen::default();
+        let totals = Rc::clone(&seen.0);
+        let mut session = Session::new(&input_path, &output_path)
+            .atomic(true)
+            .observer(seen);
+        session.run(|input, output| {
+            let mut content = String::new();
+            input.read_to_string(&mut content)?;
+            output.write_all(content.to_uppercase().as_bytes())
+        })?;
+        assert_eq!(fs::read_to_string(&output_path)?, "HELLO\nWORLD\n");
+        assert_eq!(*totals.borrow(), (12, 12, 2));
+
+        let e = session
+            .run(|_, output| {
+                output.write_all(b"partial")?;
+                Err(io::Error::other("failed"))
+            })
+            .expect_err("the closure failed");
+        assert_eq!(e.to_string(), "failed");
+        assert_eq!(fs::read_to_string(&output_path)?, "HELLO\nWORLD\n");
+        assert_eq!(session.report().processed(), 1);
+        assert_eq!(session.report().bytes_in(), 12);
+        assert_eq!(session.report().bytes_out(), 12);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
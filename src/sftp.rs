@@ -0,0 +1,196 @@
+use crate::command::{CommandReader, CommandWriter};
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    process::{self, Command},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Reader for a file on another machine: `sftp://[user@]host[:port]/path`.
+///
+/// Runs the system's `ssh`, so its config, known hosts and `ssh-agent` keys all apply. Paths are
+/// absolute; start them with `/~/` for one relative to the remote home directory.
+pub struct SftpInput {
+    inner: CommandReader,
+}
+
+/// Writer creating or replacing a file on another machine, like [`SftpInput`].
+///
+/// The data goes to a temporary file next to the target, which [`finish`](SftpOutput::finish)
+/// renames over the target once the remote end wrote everything. Dropping the writer instead
+/// kills `ssh` and leaves the target alone, though the temporary file may be left behind.
+pub struct SftpOutput {
+    inner: CommandWriter,
+    /// Renames the temporary file over the target.
+    rename: Command,
+    /// Removes the temporary file after a failed upload.
+    remove: Command,
+}
+
+/// Whether `path` is an `sftp://` URL.
+pub fn is_sftp_url<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().to_string_lossy().starts_with("sftp://")
+}
+
+impl SftpInput {
+    pub fn open(url: &str) -> io::Result<Self> {
+        let remote = Remote::parse(url)?;
+        Ok(Self {
+            inner: CommandReader::new("ssh", remote.command(&format!("cat -- {}", remote.path)))?,
+        })
+    }
+}
+
+impl Read for SftpInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl SftpOutput {
+    pub fn create(url: &str) -> io::Result<Self> {
+        let remote = Remote::parse(url)?;
+        let temp = remote.temp_path();
+        let command = remote.command(&format!("cat > {}", temp));
+        Ok(Self {
+            inner: CommandWriter::new("ssh", command)?,
+            rename: remote.command(&format!("mv -f -- {} {}", temp, remote.path)),
+            remove: remote.command(&format!("rm -f -- {}", temp)),
+        })
+    }
+
+    /// Wait for the remote file to be written, and put it in place.
+    pub fn finish(self) -> io::Result<()> {
+        match self.inner.finish() {
+            Ok(()) => CommandWriter::new("ssh", self.rename)?.finish(),
+            Err(e) => {
+                let _ = CommandWriter::new("ssh", self.remove).and_then(CommandWriter::finish);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Write for SftpOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The parts of an `sftp://` URL, with the path quoted for the remote shell.
+#[derive(Debug, PartialEq, Eq)]
+struct Remote<'a> {
+    destination: String,
+    port: Option<&'a str>,
+    path: String,
+}
+
+impl<'a> Remote<'a> {
+    fn parse(url: &'a str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected sftp://[user@]host[:port]/path, got {}", url),
+            )
+        };
+        let rest = url.strip_prefix("sftp://").ok_or_else(invalid)?;
+        let (authority, path) = rest.split_once('/').ok_or_else(invalid)?;
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (Some(user), host_port),
+            None => (None, authority),
+        };
+        // A port follows the last colon, except inside a bracketed IPv6 address.
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, Some(port)),
+            _ => (host_port, None),
+        };
+        if host.is_empty()
+            || path.is_empty()
+            || port.is_some_and(|port| port.parse::<u16>().is_err())
+        {
+            return Err(invalid());
+        }
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let destination = match user {
+            Some(user) => format!("{}@{}", user, host),
+            None => host.to_owned(),
+        };
+        let path = match path.strip_prefix("~/") {
+            Some(relative) => format!("~/{}", quote(relative)),
+            None => quote(&format!("/{}", path)),
+        };
+        Ok(Self {
+            destination,
+            port,
+            path,
+        })
+    }
+
+    /// A name for uploading to, next to the file and quoted like its path.
+    fn temp_path(&self) -> String {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        format!("{}.{}.{:x}.tmp", self.path, process::id(), nanos)
+    }
+
+    fn command(&self, remote_command: &str) -> Command {
+        let mut command = Command::new("ssh");
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port);
+        }
+        command.arg("--").arg(&self.destination).arg(remote_command);
+        command
+    }
+}
+
+/// Quote `s` for a POSIX shell.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sftp_urls() -> Result<(), io::Error> {
+        assert!(is_sftp_url("sftp://host/file"));
+        assert!(!is_sftp_url("ssh://host/file"));
+
+        assert_eq!(
+            Remote::parse("sftp://deploy@example.com:2222/var/log/app's.log")?,
+            Remote {
+                destination: "deploy@example.com".into(),
+                port: Some("2222"),
+                path: r"'/var/log/app'\''s.log'".into(),
+            }
+        );
+        let remote = Remote::parse("sftp://example.com/~/notes.txt")?;
+        assert_eq!(remote.destination, "example.com");
+        assert_eq!(remote.port, None);
+        assert_eq!(remote.path, "~/'notes.txt'");
+        let temp = remote.temp_path();
+        assert!(temp.starts_with("~/'notes.txt'.") && temp.ends_with(".tmp"));
+        let remote = Remote::parse("sftp://me@[::1]:22/tmp/x")?;
+        assert_eq!(
+            (remote.destination.as_str(), remote.port),
+            ("me@::1", Some("22"))
+        );
+
+        for url in [
+            "sftp://host",
+            "sftp:///path",
+            "sftp://host:port/path",
+            "host/path",
+        ] {
+            let e = Remote::parse(url).expect_err("URL is invalid");
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        }
+        Ok(())
+    }
+}
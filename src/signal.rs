@@ -0,0 +1,291 @@
+use crate::AtomicOutput;
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicI32, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock, Weak,
+    },
+    thread,
+};
+
+/// Write end of the pipe the signal handler wakes the watcher thread through.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+/// How installing the handlers went, the same for every caller after the first.
+static INSTALL: OnceLock<Result<(), io::ErrorKind>> = OnceLock::new();
+static OUTPUTS: Mutex<Vec<Weak<dyn Settle>>> = Mutex::new(Vec::new());
+
+static RESIZES: AtomicU64 = AtomicU64::new(0);
+static INSTALL_RESIZE: OnceLock<Result<(), io::ErrorKind>> = OnceLock::new();
+
+const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/// What a [`SignalFlush`] around an [`AtomicOutput`] does when the process is interrupted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalPolicy {
+    /// Commit what was written so far, replacing the target.
+    Commit,
+    /// Throw away what was written, leaving the target as it was.
+    Abort,
+}
+
+/// Output that is flushed (or, for [`AtomicOutput`], committed or aborted) when the process gets
+/// `SIGINT` or `SIGTERM`.
+///
+/// The first `SignalFlush` installs handlers for both signals. When one arrives, every live
+/// `SignalFlush` is settled and the signal is raised again with its default action, so the
+/// process still dies with the usual status. Writes made after that fail.
+pub struct SignalFlush<W: Send + 'static> {
+    shared: Arc<Shared<W>>,
+}
+
+struct Shared<W> {
+    output: Mutex<Option<W>>,
+    finish: Box<dyn Fn(W) + Send + Sync>,
+}
+
+trait Settle: Send + Sync {
+    fn settle(&self);
+}
+
+impl<W: Send> Settle for Shared<W> {
+    fn settle(&self) {
+        if let Some(output) = lock(&self.output).take() {
+            (self.finish)(output);
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> SignalFlush<W> {
+    pub fn new(output: W) -> io::Result<Self> {
+        Self::register(output, |mut output| {
+            let _ = output.flush();
+        })
+    }
+}
+
+impl SignalFlush<AtomicOutput> {
+    pub fn atomic(output: AtomicOutput, policy: SignalPolicy) -> io::Result<Self> {
+        Self::register(output, move |output| {
+            if policy == SignalPolicy::Commit {
+                let _ = output.commit();
+            }
+        })
+    }
+}
+
+impl<W: Send + 'static> SignalFlush<W> {
+    fn register(output: W, finish: impl Fn(W) + Send + Sync + 'static) -> io::Result<Self> {
+        install()?;
+        let shared = Arc::new(Shared {
+            output: Mutex::new(Some(output)),
+            finish: Box::new(finish),
+        });
+        let settle: Arc<dyn Settle> = shared.clone();
+        let mut outputs = lock(&OUTPUTS);
+        outputs.retain(|output| output.strong_count() > 0);
+        outputs.push(Arc::downgrade(&settle));
+        Ok(Self { shared })
+    }
+
+    /// Stop watching for signals and return the output, e.g. to commit it.
+    ///
+    /// Fails if a signal already settled the output.
+    pub fn into_inner(self) -> io::Result<W> {
+        lock(&self.shared.output).take().ok_or_else(gone)
+    }
+
+    fn with<R>(&mut self, f: impl FnOnce(&mut W) -> io::Result<R>) -> io::Result<R> {
+        match lock(&self.shared.output).as_mut() {
+            Some(output) => f(output),
+            None => Err(gone()),
+        }
+    }
+}
+
+impl<W: Write + Send + 'static> Write for SignalFlush<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with(|output| output.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.with(|output| output.flush())
+    }
+}
+
+/// Notices when the terminal is resized (`SIGWINCH`), e.g. to re-wrap output to a new
+/// [`terminal_size`](crate::FileOrStdout::terminal_size).
+pub struct ResizeWatch {
+    seen: u64,
+}
+
+impl ResizeWatch {
+    /// Start watching, installing a process-wide `SIGWINCH` handler the first time.
+    pub fn new() -> io::Result<Self> {
+        once(&INSTALL_RESIZE, install_resize_handler)?;
+        Ok(Self {
+            seen: RESIZES.load(Ordering::SeqCst),
+        })
+    }
+
+    /// Whether the terminal was resized since the watch started or this last returned `true`.
+    pub fn resized(&mut self) -> bool {
+        let resizes = RESIZES.load(Ordering::SeqCst);
+        let resized = resizes != self.seen;
+        self.seen = resizes;
+        resized
+    }
+}
+
+fn install_resize_handler() -> io::Result<()> {
+    extern "C" fn on_sigwinch(_: libc::c_int) {
+        RESIZES.fetch_add(1, Ordering::SeqCst);
+    }
+    let handler: extern "C" fn(libc::c_int) = on_sigwinch;
+    // SAFETY: the handler only touches an atomic, which is async-signal-safe.
+    if unsafe { libc::signal(libc::SIGWINCH, handler as libc::sighandler_t) } == libc::SIG_ERR {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn gone() -> io::Error {
+    io::Error::other("output was closed by a signal")
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // A panic elsewhere must not stop outputs being saved on the way out.
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Settle every registered output.
+fn settle_all() {
+    let outputs: Vec<_> = lock(&OUTPUTS).drain(..).collect();
+    for output in outputs.iter().filter_map(Weak::upgrade) {
+        output.settle();
+    }
+}
+
+fn install() -> io::Result<()> {
+    once(&INSTALL, install_handlers)
+}
+
+/// Run `install` the first time, and fail every time if it failed.
+fn once(
+    result: &OnceLock<Result<(), io::ErrorKind>>,
+    install: fn() -> io::Result<()>,
+) -> io::Result<()> {
+    (*result.get_or_init(|| install().map_err(|e| e.kind()))).map_err(io::Error::from)
+}
+
+fn install_handlers() -> io::Result<()> {
+    extern "C" fn on_signal(signal: libc::c_int) {
+        let byte = signal as u8;
+        // SAFETY: write(2) is async-signal-safe, and the pipe outlives the process.
+        unsafe { libc::write(PIPE.load(Ordering::SeqCst), (&byte as *const u8).cast(), 1) };
+    }
+
+    let mut fds = [0; 2];
+    // Close-on-exec, so programs the process runs don't hold the pipe open.
+    // SAFETY: `fds` has room for the two descriptors.
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: as above, and `fds` holds the descriptors just opened.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    unsafe {
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for fd in fds {
+            if libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    PIPE.store(fds[1], Ordering::SeqCst);
+
+    let read_fd = fds[0];
+    thread::Builder::new()
+        .name("polymorphio-signal".into())
+        .spawn(move || loop {
+            let mut byte = 0u8;
+            // SAFETY: reading one byte into a local.
+            let amt = unsafe { libc::read(read_fd, (&mut byte as *mut u8).cast(), 1) };
+            if amt == 1 {
+                settle_all();
+                let signal = libc::c_int::from(byte);
+                // SAFETY: restoring the default action and re-raising ends the process as the
+                // signal would have.
+                unsafe {
+                    libc::signal(signal, libc::SIG_DFL);
+                    libc::raise(signal);
+                }
+            } else if amt < 0 && io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                return;
+            }
+        })?;
+
+    let handler: extern "C" fn(libc::c_int) = on_signal;
+    for signal in SIGNALS {
+        // SAFETY: the handler only calls write(2), which is async-signal-safe.
+        if unsafe { libc::signal(signal, handler as libc::sighandler_t) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::BufWriter};
+    use tempfile::TempDir;
+
+    #[test]
+    fn settle_outputs() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let log = tmp_dir.path().join("log.txt");
+        let committed = tmp_dir.path().join("committed.txt");
+        let aborted = tmp_dir.path().join("aborted.txt");
+        fs::write(&aborted, "old")?;
+
+        let mut buffered = SignalFlush::new(BufWriter::new(fs::File::create(&log)?))?;
+        buffered.write_all(b"buffered")?;
+        let mut commit =
+            SignalFlush::atomic(AtomicOutput::from_path(&committed)?, SignalPolicy::Commit)?;
+        commit.write_all(b"partial")?;
+        let mut abort =
+            SignalFlush::atomic(AtomicOutput::from_path(&aborted)?, SignalPolicy::Abort)?;
+        abort.write_all(b"partial")?;
+        let kept = SignalFlush::new(Vec::new())?;
+        let kept = kept.into_inner()?;
+        // SAFETY: only reads the descriptor flags.
+        let flags = unsafe { libc::fcntl(PIPE.load(Ordering::SeqCst), libc::F_GETFD) };
+        assert_ne!(flags & libc::FD_CLOEXEC, 0);
+
+        settle_all();
+        assert_eq!(fs::read_to_string(&log)?, "buffered");
+        assert_eq!(fs::read_to_string(&committed)?, "partial");
+        assert_eq!(fs::read_to_string(&aborted)?, "old");
+        assert!(buffered.write_all(b"more").is_err());
+        assert!(kept.is_empty());
+
+        drop((buffered, commit, abort));
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn watch_resizes() -> Result<(), io::Error> {
+        let mut watch = ResizeWatch::new()?;
+        assert!(!watch.resized());
+        // SAFETY: SIGWINCH is handled by now, and the handler only counts it.
+        unsafe { libc::raise(libc::SIGWINCH) };
+        assert!(watch.resized());
+        assert!(!watch.resized());
+        Ok(())
+    }
+}
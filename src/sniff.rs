@@ -0,0 +1,68 @@
+use crate::FileOrStdinLock;
+use std::{io, str};
+
+/// Number of bytes inspected when classifying an input.
+pub const SNIFF_LEN: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    Text,
+    Binary,
+}
+
+impl ContentKind {
+    /// Classify a chunk of data from the start of a stream.
+    ///
+    /// Data containing a NUL byte, or that is not valid UTF-8, is considered binary. A multi-byte
+    /// character cut off at the end of `bytes` does not count against it.
+    pub fn classify(bytes: &[u8]) -> Self {
+        if bytes.contains(&0) {
+            return Self::Binary;
+        }
+        match str::from_utf8(bytes) {
+            Ok(_) => Self::Text,
+            Err(e) if e.error_len().is_none() => Self::Text,
+            Err(_) => Self::Binary,
+        }
+    }
+}
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Peek at the start of the input and classify it as text or binary.
+    pub fn detect_kind(&mut self) -> io::Result<ContentKind> {
+        Ok(ContentKind::classify(self.peek(SNIFF_LEN)?))
+    }
+
+    /// Return an `InvalidData` error if the input looks like binary data.
+    pub fn require_text(&mut self) -> io::Result<()> {
+        let bytes = self.peek(SNIFF_LEN)?;
+        if let Some(offset) = bytes.iter().position(|b| *b == 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("binary input: NUL byte at offset {}", offset),
+            ));
+        }
+        if ContentKind::classify(bytes) == ContentKind::Binary {
+            let offset = str::from_utf8(bytes).err().map_or(0, |e| e.valid_up_to());
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("binary input: invalid UTF-8 at offset {}", offset),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        assert_eq!(ContentKind::classify(b"plain text\n"), ContentKind::Text);
+        assert_eq!(ContentKind::classify("caf\u{e9}".as_bytes()), ContentKind::Text);
+        assert_eq!(ContentKind::classify(b"caf\xc3"), ContentKind::Text);
+        assert_eq!(ContentKind::classify(b"ab\0cd"), ContentKind::Binary);
+        assert_eq!(ContentKind::classify(b"\xff\xfe"), ContentKind::Binary);
+    }
+}
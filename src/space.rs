@@ -0,0 +1,142 @@
+use crate::Error;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Fail with [`Error::InsufficientSpace`] unless there's room for `needed` bytes at `path`.
+///
+/// The space an existing file at `path` takes counts as free, since writing the output replaces
+/// it. Where free space can't be found out, the check passes.
+pub fn check_free_space<P: AsRef<Path>>(path: P, needed: u64) -> io::Result<()> {
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let available = match free_space(dir)? {
+        Some(free) => free + fs::metadata(path).map_or(0, |meta| meta.len()),
+        None => return Ok(()),
+    };
+    if available < needed {
+        return Err(Error::InsufficientSpace {
+            path: path.to_owned(),
+            needed,
+            available,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Whether `e` means the file system is full, or the user's quota is used up.
+pub(crate) fn is_storage_full(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded
+    )
+}
+
+/// Turn `e` into an [`Error::StorageFull`] if it is one, after `written` bytes to `path`.
+pub(crate) fn storage_full(
+    e: io::Error,
+    path: &Path,
+    written: u64,
+    left_behind: Option<PathBuf>,
+) -> io::Error {
+    if !is_storage_full(&e) || Error::of(&e).is_some() {
+        return e;
+    }
+    Error::StorageFull {
+        path: path.to_owned(),
+        written,
+        left_behind,
+        source: e,
+    }
+    .into()
+}
+
+/// Bytes available to unprivileged users on the file system holding `dir`.
+#[cfg(unix)]
+fn free_space(dir: &Path) -> io::Result<Option<u64>> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let c_dir = CString::new(dir.as_os_str().as_bytes())?;
+    // SAFETY: statvfs only fills in `stat`.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_dir` is a valid C string and `stat` is writable.
+    if unsafe { libc::statvfs(c_dir.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(Some(stat.f_bavail as u64 * stat.f_frsize as u64))
+}
+
+#[cfg(windows)]
+fn free_space(dir: &Path) -> io::Result<Option<u64>> {
+    use std::os::windows::ffi::OsStrExt;
+
+    extern "system" {
+        fn GetDiskFreeSpaceExW(
+            dir: *const u16,
+            available: *mut u64,
+            total: *mut u64,
+            free: *mut u64,
+        ) -> i32;
+    }
+
+    let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0;
+    // SAFETY: `wide` is NUL-terminated and the other totals may be null.
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(available))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn free_space(_: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn free_space_checks() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("out.bin");
+        check_free_space(&path, 1)?;
+
+        let e = check_free_space(&path, u64::MAX).expect_err("no disk is that big");
+        assert_eq!(e.kind(), io::ErrorKind::StorageFull);
+        assert!(matches!(
+            Error::of(&e),
+            Some(Error::InsufficientSpace {
+                needed: u64::MAX,
+                ..
+            })
+        ));
+
+        let full = storage_full(io::ErrorKind::StorageFull.into(), &path, 42, None);
+        assert_eq!(
+            full.to_string(),
+            format!("ran out of space writing {} after 42 bytes", path.display())
+        );
+        let other = storage_full(io::ErrorKind::BrokenPipe.into(), &path, 42, None);
+        assert!(Error::of(&other).is_none());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+#[cfg(feature = "compress")]
+use crate::compress::{Codec, CompressWriter};
+use crate::{
+    template::{self, Segment},
+    STDIO_FILENAME,
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    mem,
+    path::{Path, PathBuf},
+};
+
+const DEFAULT_TEMPLATE: &str = "{path}.{n}";
+const DEFAULT_DIGITS: usize = 4;
+
+/// Writer that splits its output into numbered chunks, like `split(1)`.
+///
+/// A new chunk is started whenever writing more would exceed the byte or line limit. A line limit
+/// alone never cuts a line, but the byte limit does, so a line longer than the room left in a
+/// chunk carries on in the next one. Chunk file names come from a template in which `{path}` is
+/// the base path and `{n}` the chunk number, counting from 1 and zero-padded to four digits
+/// (`{n:6}` pads to six). When the base path is `-`, all chunks go to stdout with a separator
+/// written between them instead.
+pub struct SplitOutput {
+    base: PathBuf,
+    template: String,
+    max_bytes: Option<u64>,
+    max_lines: Option<u64>,
+    separator: Vec<u8>,
+    #[cfg(feature = "compress")]
+    codec: Option<Codec>,
+    chunk: Option<Chunk>,
+    chunk_bytes: u64,
+    chunk_lines: u64,
+    count: usize,
+    paths: Vec<PathBuf>,
+}
+
+enum Chunk {
+    File(BufWriter<File>),
+    #[cfg(feature = "compress")]
+    Compressed(CompressWriter<BufWriter<File>>),
+    Stdout(io::Stdout),
+}
+
+impl SplitOutput {
+    pub fn new<P: AsRef<Path>>(base: P) -> Self {
+        Self {
+            base: base.as_ref().to_owned(),
+            template: DEFAULT_TEMPLATE.to_owned(),
+            max_bytes: None,
+            max_lines: None,
+            separator: Vec::new(),
+            #[cfg(feature = "compress")]
+            codec: None,
+            chunk: None,
+            chunk_bytes: 0,
+            chunk_lines: 0,
+            count: 0,
+            paths: Vec::new(),
+        }
+    }
+
+    /// Limit each chunk to `max` bytes.
+    pub fn max_bytes(mut self, max: u64) -> Self {
+        self.max_bytes = Some(max.max(1));
+        self
+    }
+
+    /// Limit each chunk to `max` lines.
+    pub fn max_lines(mut self, max: u64) -> Self {
+        self.max_lines = Some(max.max(1));
+        self
+    }
+
+    /// Set the chunk file name template. Defaults to `{path}.{n}`.
+    ///
+    /// Fails with `InvalidInput` if the template has no `{n}` field, since every chunk would
+    /// then overwrite the same file.
+    pub fn template<S: Into<String>>(mut self, template: S) -> io::Result<Self> {
+        let template = template.into();
+        let numbered = template::segments(&template).any(|segment| match segment {
+            Segment::Field(field) => {
+                field == "n"
+                    || field
+                        .strip_prefix("n:")
+                        .is_some_and(|w| w.parse::<usize>().is_ok())
+            }
+            Segment::Text(_) => false,
+        });
+        if !numbered {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("split template `{}` has no `{{n}}` field", template),
+            ));
+        }
+        self.template = template;
+        Ok(self)
+    }
+
+    /// Set what is written between chunks when writing to stdout.
+    pub fn separator<B: Into<Vec<u8>>>(mut self, separator: B) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Compress each chunk file, adding the codec's extension to its name.
+    #[cfg(feature = "compress")]
+    pub fn compress(mut self, codec: Codec) -> Self {
+        self.codec = Some(codec);
+        self
+    }
+
+    fn is_stdout(&self) -> bool {
+        self.base.to_string_lossy() == STDIO_FILENAME
+    }
+
+    /// Finish the last chunk and return the paths of all chunk files written.
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.close_chunk()?;
+        Ok(mem::take(&mut self.paths))
+    }
+
+    fn chunk_path(&self, n: usize) -> PathBuf {
+        let name = render_template(&self.template, &self.base.to_string_lossy(), n);
+        #[cfg(feature = "compress")]
+        let name = match self.codec {
+            Some(codec) => format!("{}.{}", name, codec.extension()),
+            None => name,
+        };
+        PathBuf::from(name)
+    }
+
+    fn open_chunk(&mut self) -> io::Result<()> {
+        self.count += 1;
+        self.chunk_bytes = 0;
+        self.chunk_lines = 0;
+
+        if self.is_stdout() {
+            let mut stdout = io::stdout();
+            if self.count > 1 {
+                stdout.write_all(&self.separator)?;
+            }
+            self.chunk = Some(Chunk::Stdout(stdout));
+            return Ok(());
+        }
+
+        let path = self.chunk_path(self.count);
+        let file = BufWriter::new(File::create(&path)?);
+        self.paths.push(path);
+        #[cfg(feature = "compress")]
+        let chunk = match self.codec {
+            Some(codec) => Chunk::Compressed(CompressWriter::new(file, codec)?),
+            None => Chunk::File(file),
+        };
+        #[cfg(not(feature = "compress"))]
+        let chunk = Chunk::File(file);
+        self.chunk = Some(chunk);
+        Ok(())
+    }
+
+    fn close_chunk(&mut self) -> io::Result<()> {
+        match self.chunk.take() {
+            None => Ok(()),
+            Some(Chunk::File(mut file)) => file.flush(),
+            #[cfg(feature = "compress")]
+            Some(Chunk::Compressed(writer)) => writer.finish()?.flush(),
+            Some(Chunk::Stdout(mut stdout)) => stdout.flush(),
+        }
+    }
+
+    /// How much of `buf` fits into the current chunk.
+    fn fits(&self, buf: &[u8]) -> usize {
+        let mut len = buf.len();
+        if let Some(max) = self.max_bytes {
+            len = len.min((max - self.chunk_bytes) as usize);
+        }
+        if let Some(max) = self.max_lines {
+            let mut lines = self.chunk_lines;
+            if let Some(end) = buf[..len].iter().position(|b| {
+                if *b == b'\n' {
+                    lines += 1;
+                }
+                lines == max
+            }) {
+                len = end + 1;
+            }
+        }
+        len
+    }
+
+    fn is_full(&self) -> bool {
+        self.max_bytes.is_some_and(|max| self.chunk_bytes >= max)
+            || self.max_lines.is_some_and(|max| self.chunk_lines >= max)
+    }
+}
+
+impl Write for SplitOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.chunk.is_some() && self.is_full() {
+            self.close_chunk()?;
+        }
+        if self.chunk.is_none() {
+            self.open_chunk()?;
+        }
+
+        let len = self.fits(buf);
+        let data = &buf[..len];
+        match self.chunk.as_mut().unwrap() {
+            Chunk::File(file) => file.write_all(data)?,
+            #[cfg(feature = "compress")]
+            Chunk::Compressed(writer) => writer.write_all(data)?,
+            Chunk::Stdout(stdout) => stdout.write_all(data)?,
+        }
+        self.chunk_bytes += len as u64;
+        self.chunk_lines += data.iter().filter(|b| **b == b'\n').count() as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.chunk {
+            None => Ok(()),
+            Some(Chunk::File(file)) => file.flush(),
+            #[cfg(feature = "compress")]
+            Some(Chunk::Compressed(writer)) => writer.flush(),
+            Some(Chunk::Stdout(stdout)) => stdout.flush(),
+        }
+    }
+}
+
+impl Drop for SplitOutput {
+    fn drop(&mut self) {
+        let _ = self.close_chunk();
+    }
+}
+
+fn render_template(template: &str, path: &str, n: usize) -> String {
+    template::expand(template, |field, out| {
+        let width = match field {
+            "path" => {
+                out.push_str(path);
+                return true;
+            }
+            "n" => DEFAULT_DIGITS,
+            _ => match field.strip_prefix("n:").and_then(|w| w.parse().ok()) {
+                Some(width) => width,
+                None => return false,
+            },
+        };
+        out.push_str(&format!("{:0width$}", n, width = width));
+        true
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn template() {
+        assert_eq!(render_template("{path}.{n}", "out", 7), "out.0007");
+        assert_eq!(render_template("part-{n:2}.txt", "out", 3), "part-03.txt");
+        assert_eq!(render_template("{other}-{n", "out", 1), "{other}-{n");
+
+        assert!(SplitOutput::new("out").template("part-{n:2}.txt").is_ok());
+        let e = SplitOutput::new("out").template("part.txt").err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn split_by_lines_and_bytes() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let base = tmp_dir.path().join("out");
+
+        let mut output = SplitOutput::new(&base).max_lines(2).max_bytes(10);
+        output.write_all(b"a\nb\nc\nthis line is long\n")?;
+        let paths = output.finish()?;
+
+        let chunks = paths
+            .iter()
+            .map(fs::read_to_string)
+            .collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(chunks, vec!["a\nb\n", "c\nthis lin", "e is long\n"]);
+        assert_eq!(paths[0], tmp_dir.path().join("out.0001"));
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{Error, FileOrStdinLock, InputLock};
+use std::{
+    fs::{self, File, Metadata},
+    io,
+    path::Path,
+    time::SystemTime,
+};
+
+/// A file's size, modification time and identity (device and inode, on unix) at some moment,
+/// to find out whether someone else changed or replaced it since.
+///
+/// For in-place editors: stamp the input when opening it, and have the
+/// [`AtomicOutput`](crate::AtomicOutput) replacing it
+/// [`require_unchanged`](crate::AtomicOutput::require_unchanged), so another writer's changes
+/// aren't silently overwritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+    id: Option<(u64, u64)>,
+}
+
+impl FileStamp {
+    /// The stamp of an open file.
+    pub fn of(file: &File) -> io::Result<Self> {
+        Ok(Self::from_metadata(&file.metadata()?))
+    }
+
+    /// The stamp of the file at `path`, following symlinks.
+    pub fn of_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::from_metadata(&fs::metadata(path)?))
+    }
+
+    /// Fail with [`Error::InputChanged`] if the file at `path` no longer has this stamp, or is
+    /// gone.
+    pub fn check<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        match Self::of_path(path) {
+            Ok(now) if now == *self => Ok(()),
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Err(Error::InputChanged {
+                path: Some(path.to_owned()),
+            }
+            .into()),
+        }
+    }
+
+    fn from_metadata(meta: &Metadata) -> Self {
+        #[cfg(unix)]
+        let id = {
+            use std::os::unix::fs::MetadataExt;
+            Some((meta.dev(), meta.ino()))
+        };
+        #[cfg(not(unix))]
+        let id = None;
+        Self {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+            id,
+        }
+    }
+}
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Stamp the input file now, and fail reads at the end of the input with
+    /// [`Error::InputChanged`] if it was written to in the meantime. Stdin isn't checked.
+    ///
+    /// This looks at the open file, so it catches writes to it but not the file being replaced
+    /// by a new one; [`FileStamp::check`] its path for that.
+    pub fn detect_changes(&mut self) -> io::Result<()> {
+        if let InputLock::FileBufReader(reader) = &self.inner {
+            self.stamp = Some(FileStamp::of(reader.get_ref())?);
+        }
+        Ok(())
+    }
+
+    /// Called at the end of the input.
+    pub(crate) fn check_unchanged(&self) -> io::Result<()> {
+        match (&self.stamp, &self.inner) {
+            (Some(stamp), InputLock::FileBufReader(reader))
+                if FileStamp::of(reader.get_ref())? != *stamp =>
+            {
+                Err(Error::InputChanged { path: None }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AtomicOutput, FileOrStdin};
+    use std::io::{Read, Write};
+    use tempfile::TempDir;
+
+    #[test]
+    fn detect_concurrent_changes() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("edited.txt");
+        fs::write(&path, "original")?;
+
+        let stamp = FileStamp::of_path(&path)?;
+        stamp.check(&path)?;
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        lock.detect_changes()?;
+        let mut content = [0; 4];
+        lock.read_exact(&mut content)?;
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b" and more")?;
+        let e = lock
+            .read_to_end(&mut Vec::new())
+            .expect_err("the input grew");
+        assert!(matches!(
+            Error::of(&e),
+            Some(Error::InputChanged { path: None })
+        ));
+        drop(lock);
+
+        let mut output = AtomicOutput::from_path(&path)?.require_unchanged(&path, stamp);
+        output.write_all(b"edited")?;
+        let e = output.commit().expect_err("the target changed");
+        assert!(matches!(
+            Error::of(&e),
+            Some(Error::InputChanged { path: Some(_) })
+        ));
+        assert_eq!(fs::read_to_string(&path)?, "original and more");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    digest::{to_hex, Sha256},
+    temp, FileOrStdin,
+};
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+const EXTENSION: &str = "stdin";
+
+/// A copy of stdin kept on disk under a caller-chosen token, so an invocation that is retried
+/// (by a file watcher, a retry loop, ...) reads the same stdin again after the first attempt
+/// consumed it.
+///
+/// The first [`open`](StdinCache::open) for a token copies all of stdin into the cache and
+/// reads that copy; later ones read the copy without touching stdin. Entries stay until
+/// [`remove`](StdinCache::remove)d, or until they're older than the
+/// [`max_age`](StdinCache::max_age), after which they're replaced by a fresh copy of stdin and
+/// swept by [`remove_expired`](StdinCache::remove_expired).
+///
+/// ```no_run
+/// # use polymorphio::StdinCache;
+/// # use std::{io::Read, time::Duration};
+/// let cache = StdinCache::new("build-42").max_age(Duration::from_secs(3600));
+/// let mut content = String::new();
+/// cache.open()?.lock().read_to_string(&mut content)?;
+/// // ... and once the job succeeded:
+/// cache.remove()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct StdinCache {
+    dir: PathBuf,
+    token: String,
+    max_age: Option<Duration>,
+}
+
+impl StdinCache {
+    /// The cache entry for `token`, in a `polymorphio-stdin` directory of the user's own: under
+    /// `$XDG_RUNTIME_DIR` if it's set, and otherwise `polymorphio-stdin-<uid>` under
+    /// `std::env::temp_dir()` on unix.
+    pub fn new(token: &str) -> Self {
+        Self {
+            dir: default_dir(),
+            token: token.to_owned(),
+            max_age: None,
+        }
+    }
+
+    /// Keep the cache in `dir` instead, created when first needed.
+    ///
+    /// On unix, the cache refuses a `dir` owned by another user or open to anyone else, where
+    /// entries could be read or planted by others.
+    pub fn dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.dir = dir.as_ref().to_owned();
+        self
+    }
+
+    /// Treat entries older than `max_age` as gone. By default they never expire.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// The file the entry is cached in.
+    pub fn path(&self) -> PathBuf {
+        let mut sha = Sha256::new();
+        sha.update(self.token.as_bytes());
+        let name = to_hex(&sha.finish()[..16]);
+        self.dir.join(name).with_extension(EXTENSION)
+    }
+
+    /// Whether stdin has been cached for the token, and hasn't expired.
+    pub fn is_cached(&self) -> bool {
+        fs::metadata(self.path()).is_ok_and(|meta| !self.is_expired(&meta))
+    }
+
+    /// Read the cached stdin, caching it first if needed.
+    pub fn open(&self) -> io::Result<FileOrStdin> {
+        self.open_from(io::stdin().lock())
+    }
+
+    fn open_from<R: Read>(&self, mut stdin: R) -> io::Result<FileOrStdin> {
+        let path = self.path();
+        create_private_dir(&self.dir)?;
+        if self.is_cached() {
+            return Ok(File::open(&path)?.into());
+        }
+        let (mut file, temp_path) = temp::create_unique_with_mode(&self.dir, "stdin", 0o600)?;
+        let copied = io::copy(&mut stdin, &mut file).and_then(|_| file.sync_all());
+        if let Err(e) = copied.and_then(|_| fs::rename(&temp_path, &path)) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e);
+        }
+        file.rewind()?;
+        Ok(file.into())
+    }
+
+    /// Remove the entry, returning whether there was one.
+    pub fn remove(&self) -> io::Result<bool> {
+        match fs::remove_file(self.path()) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Remove every expired entry in the cache directory, whatever its token, returning how
+    /// many there were. Nothing expires without a [`max_age`](StdinCache::max_age).
+    pub fn remove_expired(&self) -> io::Result<usize> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION)
+                && fs::metadata(&path).is_ok_and(|meta| self.is_expired(&meta))
+                && fs::remove_file(&path).is_ok()
+            {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn is_expired(&self, meta: &fs::Metadata) -> bool {
+        let age = meta
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        match (self.max_age, age) {
+            (Some(max_age), Some(age)) => age > max_age,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn default_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime_dir) => Path::new(&runtime_dir).join("polymorphio-stdin"),
+        // SAFETY: `geteuid` has no preconditions and can't fail.
+        None => env::temp_dir().join(format!("polymorphio-stdin-{}", unsafe { libc::geteuid() })),
+    }
+}
+
+#[cfg(not(unix))]
+fn default_dir() -> PathBuf {
+    env::temp_dir().join("polymorphio-stdin")
+}
+
+/// Create `dir`, readable only by the user on unix, since stdin can hold anything, and check
+/// that an existing `dir` is just as private.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let meta = fs::symlink_metadata(dir)?;
+        let refuse = |why: &str| {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("refusing to cache stdin in {}: {}", dir.display(), why),
+            ))
+        };
+        // SAFETY: `geteuid` has no preconditions and can't fail.
+        if meta.uid() != unsafe { libc::geteuid() } {
+            return refuse("it belongs to another user");
+        }
+        if meta.mode() & 0o077 != 0 {
+            return refuse("other users have access to it");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn replay_cached_stdin() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let cache_dir = tmp_dir.path().join("cache");
+        let cache = StdinCache::new("job 1").dir(&cache_dir);
+        let read = |mut input: FileOrStdin| -> io::Result<String> {
+            let mut content = String::new();
+            input.lock().read_to_string(&mut content)?;
+            Ok(content)
+        };
+
+        assert!(!cache.is_cached());
+        assert_eq!(read(cache.open_from(&b"first"[..])?)?, "first");
+        assert!(cache.is_cached());
+        assert_eq!(read(cache.open_from(&b"second"[..])?)?, "first");
+
+        let other = StdinCache::new("job 2").dir(&cache_dir);
+        assert_eq!(read(other.open_from(&b"other"[..])?)?, "other");
+        assert_ne!(cache.path(), other.path());
+
+        assert!(cache.remove()?);
+        assert!(!cache.remove()?);
+        assert_eq!(read(cache.open_from(&b"third"[..])?)?, "third");
+
+        assert_eq!(cache.remove_expired()?, 0);
+        let expiring = cache.clone().max_age(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(!expiring.is_cached());
+        assert_eq!(expiring.remove_expired()?, 2);
+        assert!(!other.is_cached());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let shared = tmp_dir.path().join("shared");
+            fs::create_dir(&shared)?;
+            let cache = StdinCache::new("job 1").dir(&shared);
+            for mode in [0o777, 0o755] {
+                fs::set_permissions(&shared, fs::Permissions::from_mode(mode))?;
+                let e = cache
+                    .open_from(&b"secret"[..])
+                    .err()
+                    .expect("others can get into the directory");
+                assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+                assert!(!cache.is_cached());
+            }
+            fs::set_permissions(&shared, fs::Permissions::from_mode(0o700))?;
+            assert_eq!(read(cache.open_from(&b"secret"[..])?)?, "secret");
+            let mode = fs::metadata(cache.path())?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
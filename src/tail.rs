@@ -0,0 +1,267 @@
+use crate::{FileOrStdinLock, InputLock};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, Read, Seek, SeekFrom},
+    str,
+};
+
+/// Size of the blocks read backwards from the end of a file.
+const BLOCK_LEN: u64 = 8192;
+
+/// Iterator over an input's lines from last to first, from
+/// [`FileOrStdinLock::lines_reversed`].
+pub struct ReverseLines<'a> {
+    source: Source<'a>,
+}
+
+enum Source<'a> {
+    File {
+        file: &'a File,
+        start: u64,
+        pos: u64,
+        /// Data read so far that hasn't been returned, from `pos` on.
+        buf: Vec<u8>,
+        done: bool,
+    },
+    Buffered(Vec<String>),
+}
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Read the rest of the input and return its last `n` lines, without line endings.
+    ///
+    /// Regular files are read backwards from the end, so only the lines returned are read. Other
+    /// inputs are read in full, keeping just the last `n` lines in memory. Invalid UTF-8 in the
+    /// returned lines is an `InvalidData` error.
+    pub fn read_last_lines(&mut self, n: usize) -> io::Result<Vec<String>> {
+        let data = match self.file_range()? {
+            Some((file, start, end)) => {
+                let from = last_lines_start(file, start, end, n)?;
+                let mut data = Vec::with_capacity((end - from) as usize);
+                let mut file = file;
+                file.seek(SeekFrom::Start(from))?;
+                file.take(end - from).read_to_end(&mut data)?;
+                self.skip_to_end()?;
+                data
+            }
+            None => {
+                let mut last = VecDeque::with_capacity(n);
+                let mut line = Vec::new();
+                while self.read_until(b'\n', &mut line)? > 0 {
+                    if n > 0 {
+                        if last.len() == n {
+                            last.pop_front();
+                        }
+                        last.push_back(line.clone());
+                    }
+                    line.clear();
+                }
+                last.into_iter().flatten().collect()
+            }
+        };
+        let text = str::from_utf8(&data).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid UTF-8 in last lines: {}", e),
+            )
+        })?;
+        Ok(text.lines().map(String::from).collect())
+    }
+
+    /// Read the rest of the input's lines from last to first, without line endings.
+    ///
+    /// Regular files are read backwards in blocks as the iterator is advanced. Other inputs are
+    /// read into memory first. The input is left at its end.
+    pub fn lines_reversed(&mut self) -> io::Result<ReverseLines<'a>> {
+        let source = match self.file_range()? {
+            Some((file, start, mut end)) => {
+                let mut last = [0];
+                if end > start {
+                    read_block(file, end - 1, &mut last)?;
+                    if last[0] == b'\n' {
+                        end -= 1;
+                    }
+                }
+                self.skip_to_end()?;
+                Source::File {
+                    file,
+                    start,
+                    pos: end,
+                    buf: Vec::new(),
+                    done: start == end,
+                }
+            }
+            None => Source::Buffered(self.lines().collect::<io::Result<_>>()?),
+        };
+        Ok(ReverseLines { source })
+    }
+
+    /// For a regular file, the file and the byte range from the current read position to its end.
+    fn file_range(&mut self) -> io::Result<Option<(&'a File, u64, u64)>> {
+        let unread_peeked = (self.peeked.len() - self.peeked_pos) as u64;
+        if let InputLock::FileBufReader(reader) = &mut self.inner {
+            let mut file = *reader.get_ref();
+            let meta = file.metadata()?;
+            if meta.is_file() {
+                let buffered = reader.buffer().len() as u64;
+                let pos = file.stream_position()? - buffered - unread_peeked;
+                return Ok(Some((file, pos, meta.len().max(pos))));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Drop anything buffered and move a file-backed input to its end.
+    fn skip_to_end(&mut self) -> io::Result<()> {
+        self.peeked.clear();
+        self.peeked_pos = 0;
+        if let InputLock::FileBufReader(reader) = &mut self.inner {
+            reader.seek(SeekFrom::End(0))?;
+        }
+        Ok(())
+    }
+}
+
+/// Offset in `file` where the last `n` lines between `start` and `end` begin.
+fn last_lines_start(file: &File, start: u64, end: u64, n: usize) -> io::Result<u64> {
+    if n == 0 {
+        return Ok(end);
+    }
+    let mut found = 0;
+    let mut block = vec![0; BLOCK_LEN as usize];
+    let mut pos = end;
+    while pos > start {
+        let len = BLOCK_LEN.min(pos - start);
+        pos -= len;
+        let block = &mut block[..len as usize];
+        read_block(file, pos, block)?;
+        for (i, b) in block.iter().enumerate().rev() {
+            let offset = pos + i as u64;
+            // The final newline ends the last line rather than starting another.
+            if *b == b'\n' && offset + 1 != end {
+                found += 1;
+                if found == n {
+                    return Ok(offset + 1);
+                }
+            }
+        }
+    }
+    Ok(start)
+}
+
+impl<'a> ReverseLines<'a> {
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        let (file, start, pos, buf, done) = match &mut self.source {
+            Source::Buffered(lines) => return Ok(lines.pop()),
+            Source::File {
+                file,
+                start,
+                pos,
+                buf,
+                done,
+            } => (*file, *start, pos, buf, done),
+        };
+        loop {
+            let line = if let Some(i) = buf.iter().rposition(|b| *b == b'\n') {
+                let line = buf.split_off(i + 1);
+                buf.truncate(i);
+                line
+            } else if *pos > start {
+                let len = BLOCK_LEN.min(*pos - start);
+                *pos -= len;
+                let mut block = vec![0; len as usize];
+                read_block(file, *pos, &mut block)?;
+                block.append(buf);
+                *buf = block;
+                continue;
+            } else if !*done {
+                *done = true;
+                std::mem::take(buf)
+            } else {
+                return Ok(None);
+            };
+            let mut line = String::from_utf8(line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid UTF-8 in line: {}", e.utf8_error()),
+                )
+            })?;
+            if line.ends_with('\r') {
+                line.pop();
+            }
+            return Ok(Some(line));
+        }
+    }
+}
+
+impl<'a> Iterator for ReverseLines<'a> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_line().transpose()
+    }
+}
+
+/// Fill `buf` from `file` at `offset`, leaving the file's position where it was.
+fn read_block(mut file: &File, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+    let here = file.stream_position()?;
+    file.seek(SeekFrom::Start(offset))?;
+    let result = file.read_exact(buf);
+    file.seek(SeekFrom::Start(here))?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FileOrStdin;
+    use std::{fs, io};
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_from_the_end() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("log.txt");
+        let lines: Vec<String> = (0..5000).map(|i| format!("line {}", i)).collect();
+        fs::write(&path, lines.join("\n") + "\n")?;
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        assert_eq!(lock.read_last_lines(3)?, lines[4997..]);
+        assert!(lock.read_last_lines(3)?.is_empty());
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        lock.skip_lines(4998)?;
+        assert_eq!(lock.read_last_lines(5)?, lines[4998..]);
+
+        let short = tmp_dir.path().join("short.txt");
+        fs::write(&short, "a\r\nb\nc")?;
+        let mut input = FileOrStdin::from_path(&short)?;
+        assert_eq!(input.lock().read_last_lines(2)?, ["b", "c"]);
+        assert_eq!(
+            FileOrStdin::from_path(&short)?.lock().read_last_lines(9)?,
+            ["a", "b", "c"]
+        );
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let reversed: Vec<String> = input.lock().lines_reversed()?.collect::<Result<_, _>>()?;
+        assert_eq!(reversed.len(), lines.len());
+        assert!(reversed.iter().eq(lines.iter().rev()));
+        let mut input = FileOrStdin::from_path(&short)?;
+        let reversed = input
+            .lock()
+            .lines_reversed()?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(reversed, ["c", "b", "a"]);
+        let empty = tmp_dir.path().join("empty.txt");
+        fs::write(&empty, "")?;
+        assert!(FileOrStdin::from_path(&empty)?
+            .lock()
+            .lines_reversed()?
+            .next()
+            .is_none());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,413 @@
+#[cfg(feature = "compress")]
+use crate::compress::{Codec, DecompressReader};
+use crate::{inputs::path_from_bytes, split_member_spec, STDIO_FILENAME};
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+const BLOCK: usize = 512;
+
+/// Streaming reader for tar archives (ustar, GNU and PAX).
+///
+/// Entries are visited in archive order with [`next_entry`](TarArchive::next_entry), each
+/// readable like a file, without extracting anything to disk.
+pub struct TarArchive<R> {
+    inner: R,
+    /// Data bytes left in the current entry.
+    remaining: u64,
+    /// Padding after the current entry's data.
+    padding: u64,
+    done: bool,
+}
+
+/// An entry of a [`TarArchive`], readable for its content.
+pub struct TarEntry<'a, R> {
+    archive: &'a mut TarArchive<R>,
+    path: PathBuf,
+    size: u64,
+    kind: u8,
+}
+
+/// A single file read out of a tar archive, as opened by [`TarArchive::open_member`].
+pub struct TarMember {
+    archive: TarArchive<Box<dyn Read + Send>>,
+}
+
+impl TarArchive<Box<dyn Read + Send>> {
+    /// Open the archive at `path`, or stdin for `-`.
+    ///
+    /// With the `compress` feature, archives with a compressed extension (`.tar.gz`, `.tgz`, ...)
+    /// are decompressed on the fly.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let reader: Box<dyn Read + Send> = if path.to_string_lossy() == STDIO_FILENAME {
+            Box::new(io::stdin())
+        } else {
+            Box::new(File::open(path)?)
+        };
+        #[cfg(feature = "compress")]
+        let reader: Box<dyn Read + Send> = match Codec::from_path(path) {
+            Some(codec) => Box::new(DecompressReader::new(reader, codec)?),
+            None => reader,
+        };
+        Ok(Self::new(reader))
+    }
+
+    /// Open one member of an archive given as `archive.tar::path/inside.txt`.
+    pub fn open_member(spec: &str) -> io::Result<TarMember> {
+        let (archive, member) = split_member_spec(spec).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("expected `archive::member`, got `{}`", spec),
+            )
+        })?;
+        let mut archive = Self::from_path(archive)?;
+        let wanted = normalize(Path::new(member));
+        while let Some(entry) = archive.next_entry()? {
+            if entry.is_file() && normalize(entry.path()) == wanted {
+                return Ok(TarMember { archive });
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no member `{}` in archive", member),
+        ))
+    }
+}
+
+impl<R: Read> TarArchive<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            padding: 0,
+            done: false,
+        }
+    }
+
+    /// Move on to the next entry, skipping whatever is left of the current one.
+    pub fn next_entry(&mut self) -> io::Result<Option<TarEntry<'_, R>>> {
+        let mut long_path = None;
+        loop {
+            if self.done {
+                return Ok(None);
+            }
+            self.skip_rest()?;
+
+            let mut header = [0; BLOCK];
+            if !self.read_block(&mut header)? || header.iter().all(|b| *b == 0) {
+                self.done = true;
+                return Ok(None);
+            }
+            verify_checksum(&header)?;
+
+            let size = parse_number(&header[124..136])?;
+            let kind = header[156];
+            self.remaining = size;
+            self.padding = padding(size);
+
+            match kind {
+                // GNU long name: the data is the path of the next entry.
+                b'L' => long_path = Some(path_from_bytes(trim_nul(&self.read_data_to_end()?))),
+                // PAX extended header, possibly holding the path of the next entry.
+                b'x' => {
+                    if let Some(path) = pax_path(&self.read_data_to_end()?) {
+                        long_path = Some(path);
+                    }
+                }
+                b'g' => {}
+                _ => {
+                    let path = long_path.take().unwrap_or_else(|| ustar_path(&header));
+                    return Ok(Some(TarEntry {
+                        archive: self,
+                        path,
+                        size,
+                        kind,
+                    }));
+                }
+            }
+        }
+    }
+
+    fn read_block(&mut self, block: &mut [u8; BLOCK]) -> io::Result<bool> {
+        let mut len = 0;
+        while len < BLOCK {
+            match self.inner.read(&mut block[len..]) {
+                // Some writers leave out the end-of-archive blocks.
+                Ok(0) if len == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(amt) => len += amt,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    fn read_data(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.remaining.min(usize::MAX as u64) as usize);
+        if len == 0 {
+            return Ok(0);
+        }
+        let amt = self.inner.read(&mut buf[..len])?;
+        if amt == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= amt as u64;
+        Ok(amt)
+    }
+
+    fn read_data_to_end(&mut self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut buf = [0; BLOCK];
+        loop {
+            let amt = self.read_data(&mut buf)?;
+            if amt == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(&buf[..amt]);
+        }
+    }
+
+    fn skip_rest(&mut self) -> io::Result<()> {
+        let skip = self.remaining + self.padding;
+        let skipped = io::copy(&mut (&mut self.inner).take(skip), &mut io::sink())?;
+        if skipped != skip {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining = 0;
+        self.padding = 0;
+        Ok(())
+    }
+}
+
+impl<'a, R> TarEntry<'a, R> {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_file(&self) -> bool {
+        matches!(self.kind, b'0' | b'\0' | b'7')
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == b'5'
+    }
+}
+
+impl<'a, R: Read> Read for TarEntry<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.archive.read_data(buf)
+    }
+}
+
+impl Read for TarMember {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.archive.read_data(buf)
+    }
+}
+
+/// Headers for a regular file entry: a ustar header, preceded by a PAX header when `name` is too
+/// long for the ustar name field. The length only depends on `name`.
+pub(crate) fn file_headers(name: &str, size: u64, mtime: u64) -> Vec<u8> {
+    let mut headers = Vec::new();
+    if name.len() > 100 {
+        // Each record is `<len> path=<name>\n`, where `<len>` counts its own digits.
+        let body_len = " path=\n".len() + name.len();
+        let mut len = body_len + 1;
+        while len != body_len + len.to_string().len() {
+            len = body_len + len.to_string().len();
+        }
+        let record = format!("{} path={}\n", len, name);
+        headers.extend_from_slice(&ustar_header("PaxHeader", record.len() as u64, mtime, b'x'));
+        headers.extend_from_slice(record.as_bytes());
+        headers.resize(headers.len() + padding(record.len() as u64) as usize, 0);
+    }
+    headers.extend_from_slice(&ustar_header(name, size, mtime, b'0'));
+    headers
+}
+
+/// Zero bytes needed after `size` bytes of data to fill the last block.
+pub(crate) fn padding(size: u64) -> u64 {
+    (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64
+}
+
+/// The two zero blocks marking the end of an archive.
+pub(crate) const END_OF_ARCHIVE: [u8; 2 * BLOCK] = [0; 2 * BLOCK];
+
+fn ustar_header(name: &str, size: u64, mtime: u64, kind: u8) -> [u8; BLOCK] {
+    let mut header = [0; BLOCK];
+    let name = name.as_bytes();
+    let name_len = name.len().min(100);
+    header[..name_len].copy_from_slice(&name[..name_len]);
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    put_number(&mut header[124..136], size);
+    put_number(&mut header[136..148], mtime);
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u64 = header.iter().map(|b| *b as u64).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    header
+}
+
+/// Write a numeric header field as NUL-terminated octal, or base-256 if it doesn't fit.
+fn put_number(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let octal = format!("{:0width$o}", value, width = digits);
+    if octal.len() == digits {
+        field[..digits].copy_from_slice(octal.as_bytes());
+        field[digits] = 0;
+    } else {
+        field.fill(0);
+        let bytes = value.to_be_bytes();
+        let start = field.len() - bytes.len();
+        field[start..].copy_from_slice(&bytes);
+        field[0] |= 0x80;
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, std::path::Component::CurDir))
+        .collect()
+}
+
+fn verify_checksum(header: &[u8; BLOCK]) -> io::Result<()> {
+    let expected = parse_number(&header[148..156])?;
+    let actual: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                *b as u64
+            }
+        })
+        .sum();
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "tar header checksum mismatch",
+        ))
+    }
+}
+
+/// Parse a numeric header field: NUL/space-padded octal, or base-256 for large values.
+fn parse_number(field: &[u8]) -> io::Result<u64> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        return Ok(field[1..]
+            .iter()
+            .fold((field[0] & 0x7f) as u64, |n, b| (n << 8) | *b as u64));
+    }
+    let digits = String::from_utf8_lossy(field);
+    let digits = digits.trim_matches(|c| c == '\0' || c == ' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid number in tar header"))
+}
+
+fn trim_nul(bytes: &[u8]) -> &[u8] {
+    match bytes.iter().position(|b| *b == 0) {
+        Some(end) => &bytes[..end],
+        None => bytes,
+    }
+}
+
+fn ustar_path(header: &[u8; BLOCK]) -> PathBuf {
+    let name = trim_nul(&header[..100]);
+    let prefix = if &header[257..262] == b"ustar" {
+        trim_nul(&header[345..500])
+    } else {
+        &[]
+    };
+    if prefix.is_empty() {
+        path_from_bytes(name)
+    } else {
+        path_from_bytes(&[prefix, b"/", name].concat())
+    }
+}
+
+/// The `path` record of a PAX extended header, made of `<len> <key>=<value>\n` records.
+fn pax_path(mut data: &[u8]) -> Option<PathBuf> {
+    while !data.is_empty() {
+        let space = data.iter().position(|b| *b == b' ')?;
+        let len: usize = std::str::from_utf8(&data[..space]).ok()?.parse().ok()?;
+        let record = data.get(space + 1..len)?;
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(path_from_bytes(path));
+        }
+        data = &data[len..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process::Command};
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_entries_and_members() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let long_dir = "d".repeat(60);
+        let long_name = format!("{}/{}.txt", long_dir, "n".repeat(60));
+        fs::create_dir(tmp_dir.path().join(&long_dir))?;
+        fs::write(tmp_dir.path().join("small.txt"), "small")?;
+        fs::write(tmp_dir.path().join(&long_name), "x".repeat(1000))?;
+
+        let archive = tmp_dir.path().join("test.tar");
+        let status = Command::new("tar")
+            .arg("-cf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(tmp_dir.path())
+            .args(["small.txt", &long_name])
+            .status()?;
+        assert!(status.success());
+
+        let mut tar = TarArchive::from_path(&archive)?;
+        let mut entry = tar.next_entry()?.unwrap();
+        assert_eq!(entry.path(), Path::new("small.txt"));
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+        assert_eq!(content, "small");
+        let entry = tar.next_entry()?.unwrap();
+        assert_eq!(entry.path(), Path::new(&long_name));
+        assert_eq!(entry.size(), 1000);
+        assert!(tar.next_entry()?.is_none());
+
+        let spec = format!("{}::./{}", archive.display(), long_name);
+        let mut content = String::new();
+        TarArchive::open_member(&spec)?.read_to_string(&mut content)?;
+        assert_eq!(content, "x".repeat(1000));
+
+        let missing = format!("{}::missing.txt", archive.display());
+        assert_eq!(
+            TarArchive::open_member(&missing).err().unwrap().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use crate::FileOrStdout;
+
+/// What a [`FileOrStdout`] is connected to, from [`FileOrStdout::target_kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    /// An interactive terminal.
+    Terminal,
+    /// A pipe or socket, usually to another program.
+    Pipe,
+    /// A regular file on disk.
+    RegularFile,
+    /// Anything else, e.g. a device like `/dev/null`, or a type that couldn't be determined.
+    Other,
+}
+
+impl FileOrStdout {
+    /// Classify where the output goes, e.g. to pick column widths for a terminal, flush
+    /// promptly for a pipe, or write compactly to a file.
+    pub fn target_kind(&self) -> TargetKind {
+        if self.is_terminal() {
+            return TargetKind::Terminal;
+        }
+        #[cfg(unix)]
+        match self {
+            Self::File(file) => kind_of(file),
+            Self::Stdout(stdout) => kind_of(stdout),
+        }
+        #[cfg(not(unix))]
+        match self {
+            Self::File(file) => match file.metadata() {
+                Ok(meta) if meta.is_file() => TargetKind::RegularFile,
+                _ => TargetKind::Other,
+            },
+            Self::Stdout(_) => TargetKind::Other,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kind_of<T: std::os::unix::io::AsRawFd>(handle: &T) -> TargetKind {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    // SAFETY: fstat only writes into the provided `stat`, and the fd is borrowed from `handle`.
+    if unsafe { libc::fstat(handle.as_raw_fd(), &mut stat) } != 0 {
+        return TargetKind::Other;
+    }
+    match stat.st_mode & libc::S_IFMT {
+        libc::S_IFREG => TargetKind::RegularFile,
+        libc::S_IFIFO | libc::S_IFSOCK => TargetKind::Pipe,
+        _ => TargetKind::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn classify_targets() -> Result<(), std::io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let output = FileOrStdout::from_path(tmp_dir.path().join("out.txt"))?;
+        assert_eq!(output.target_kind(), TargetKind::RegularFile);
+
+        #[cfg(unix)]
+        {
+            use std::{fs::File, os::unix::io::FromRawFd};
+
+            let null = FileOrStdout::from(File::options().write(true).open("/dev/null")?);
+            assert_eq!(null.target_kind(), TargetKind::Other);
+
+            let mut fds = [0; 2];
+            assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+            // SAFETY: both fds were just created by `pipe` and are owned by these files.
+            let (read_end, write_end) =
+                unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+            assert_eq!(
+                FileOrStdout::from(write_end).target_kind(),
+                TargetKind::Pipe
+            );
+            drop(read_end);
+        }
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use crate::FileOrStdinLock;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// Reader that copies everything read through it into a side writer, like `tee`.
+///
+/// Only data actually consumed is copied, so peeking at a `BufRead` doesn't duplicate anything.
+/// A failure writing the copy fails the read, so a saved copy is never silently incomplete.
+pub struct TeeReader<R, W: Write = BufWriter<File>> {
+    inner: R,
+    side: W,
+    /// A failed copy from `consume`, reported by the next read.
+    error: Option<io::Error>,
+}
+
+impl<R> TeeReader<R> {
+    /// Save a raw copy of everything read to a new file at `path`, e.g. for `--save-raw`.
+    pub fn save_raw<P: AsRef<Path>>(inner: R, path: P) -> io::Result<Self> {
+        Ok(Self::new(inner, BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<R, W: Write> TeeReader<R, W> {
+    pub fn new(inner: R, side: W) -> Self {
+        Self {
+            inner,
+            side,
+            error: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Flush the copy and return both ends.
+    pub fn finish(mut self) -> io::Result<(R, W)> {
+        self.side.flush()?;
+        Ok((self.inner, self.side))
+    }
+}
+
+impl<'a> FileOrStdinLock<'a> {
+    /// Copy everything read from here on into a new file at `path`. See [`TeeReader`].
+    pub fn save_raw<P: AsRef<Path>>(self, path: P) -> io::Result<TeeReader<Self>> {
+        TeeReader::save_raw(self, path)
+    }
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        let amt = self.inner.read(buf)?;
+        self.side.write_all(&buf[..amt])?;
+        Ok(amt)
+    }
+}
+
+impl<R: BufRead, W: Write> BufRead for TeeReader<R, W> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // `consume` can't fail, so a failed copy is reported by the next read instead.
+        if let Ok(buf) = self.inner.fill_buf() {
+            let amt = amt.min(buf.len());
+            if let Err(e) = self.side.write_all(&buf[..amt]) {
+                self.error = Some(e);
+            }
+        }
+        self.inner.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileOrStdin;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn save_raw_copy() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("input.txt");
+        let raw = tmp_dir.path().join("raw.bin");
+        fs::write(&path, b"header\nbody\x00\xff")?;
+
+        let mut input = FileOrStdin::from_path(&path)?;
+        let mut lock = input.lock();
+        assert_eq!(lock.peek(3)?, b"hea");
+        let mut reader = lock.save_raw(&raw)?;
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+        reader.finish()?;
+
+        assert_eq!(body, b"body\x00\xff");
+        assert_eq!(fs::read(&raw)?, fs::read(&path)?);
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{flush, FileOrStdout, STDIO_FILENAME};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAX_ATTEMPTS: usize = 100;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Create a new, uniquely-named file in `dir` opened for reading and writing.
+pub(crate) fn create_unique(dir: &Path, prefix: &str) -> io::Result<(File, PathBuf)> {
+    create_unique_with_mode(dir, prefix, 0o666)
+}
+
+/// Like [`create_unique`], with the permission bits `mode` (before the umask) on unix.
+pub(crate) fn create_unique_with_mode(
+    dir: &Path,
+    prefix: &str,
+    mode: u32,
+) -> io::Result<(File, PathBuf)> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+
+    for _ in 0..MAX_ATTEMPTS {
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!(
+            ".{}.{}.{:x}{:x}.tmp",
+            prefix,
+            process::id(),
+            nanos,
+            count
+        ));
+
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+        #[cfg(not(unix))]
+        let _ = mode;
+        match options.open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "could not find an unused temporary file name",
+    ))
+}
+
+/// Copy everything from `reader` into an anonymous temporary file, rewound for reading.
+#[cfg(any(
+    feature = "clipboard",
+    feature = "s3",
+    feature = "gcs",
+    feature = "ssh",
+    feature = "git"
+))]
+pub(crate) fn spool<R: io::Read + ?Sized>(reader: &mut R) -> io::Result<crate::FileOrStdin> {
+    use std::io::Seek;
+
+    let (mut file, path) = create_unique(&std::env::temp_dir(), "polymorphio-spool")?;
+    // The open file outlives its name, so nothing is left behind once it's closed.
+    let _ = fs::remove_file(&path);
+    io::copy(reader, &mut file)?;
+    file.rewind()?;
+    Ok(file.into())
+}
+
+/// Writer to a uniquely-named temporary file, for handing the output to another program.
+///
+/// The file is deleted on drop unless it is kept with [`keep`](TempOutput::keep) or moved with
+/// [`persist`](TempOutput::persist), both of which return its final path. Created with the
+/// directory `-`, it writes to stdout instead and there is no path to return.
+pub struct TempOutput {
+    target: TempTarget,
+}
+
+enum TempTarget {
+    File {
+        path: PathBuf,
+        /// `None` once closed.
+        writer: Option<BufWriter<File>>,
+        /// Whether the file is kept, or has moved, and is no longer to be removed on drop.
+        kept: bool,
+    },
+    Stdout(io::Stdout),
+}
+
+impl FileOrStdout {
+    /// Write to a new temporary file in `dir`, or to stdout if `dir` is `-`.
+    pub fn temp_in<P: AsRef<Path>>(dir: P) -> io::Result<TempOutput> {
+        let dir = dir.as_ref();
+        let target = if dir.to_string_lossy() == STDIO_FILENAME {
+            TempTarget::Stdout(io::stdout())
+        } else {
+            let (file, path) = create_unique(dir, "tmp")?;
+            TempTarget::File {
+                path,
+                writer: Some(BufWriter::new(file)),
+                kept: false,
+            }
+        };
+        Ok(TempOutput { target })
+    }
+}
+
+impl TempOutput {
+    /// Current path of the temporary file, or `None` for stdout.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.target {
+            TempTarget::File { path, .. } => Some(path),
+            TempTarget::Stdout(_) => None,
+        }
+    }
+
+    /// Flush and keep the file where it is.
+    pub fn keep(mut self) -> io::Result<Option<PathBuf>> {
+        self.close()?;
+        self.set_kept();
+        Ok(self.path().map(Path::to_owned))
+    }
+
+    /// Flush and move the file to `to`, replacing any file already there.
+    pub fn persist<P: AsRef<Path>>(mut self, to: P) -> io::Result<Option<PathBuf>> {
+        self.close()?;
+        let from = match self.path() {
+            Some(from) => from.to_owned(),
+            None => return Ok(None),
+        };
+        let to = to.as_ref();
+        match fs::rename(&from, to) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                fs::copy(&from, to)?;
+                fs::remove_file(&from)?;
+            }
+            Err(e) => return Err(e),
+        }
+        self.set_kept();
+        Ok(Some(to.to_owned()))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        match &mut self.target {
+            TempTarget::File { writer, .. } => {
+                let writer = writer.take().expect("temporary output is only closed once");
+                flush::into_inner(writer)?.sync_all()
+            }
+            TempTarget::Stdout(stdout) => stdout.flush(),
+        }
+    }
+
+    fn set_kept(&mut self) {
+        if let TempTarget::File { kept, .. } = &mut self.target {
+            *kept = true;
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.target {
+            TempTarget::File { writer, .. } => {
+                writer.as_mut().expect("temporary output not closed yet")
+            }
+            TempTarget::Stdout(stdout) => stdout,
+        }
+    }
+}
+
+impl Write for TempOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+impl Drop for TempOutput {
+    fn drop(&mut self) {
+        if let TempTarget::File {
+            path, kept: false, ..
+        } = &self.target
+        {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn keep_persist_and_discard() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+
+        let mut output = FileOrStdout::temp_in(tmp_dir.path())?;
+        output.write_all(b"kept")?;
+        let kept = output.keep()?.unwrap();
+        assert_eq!(kept.parent(), Some(tmp_dir.path()));
+        assert_eq!(fs::read_to_string(&kept)?, "kept");
+
+        let mut output = FileOrStdout::temp_in(tmp_dir.path())?;
+        output.write_all(b"persisted")?;
+        let to = tmp_dir.path().join("final");
+        assert_eq!(output.persist(&to)?, Some(to.clone()));
+        assert_eq!(fs::read_to_string(&to)?, "persisted");
+
+        let mut output = FileOrStdout::temp_in(tmp_dir.path())?;
+        output.write_all(b"discarded")?;
+        let discarded = output.path().unwrap().to_owned();
+        drop(output);
+        assert!(!discarded.exists());
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 2);
+
+        let output = FileOrStdout::temp_in(tmp_dir.path())?;
+        let failed = output.path().unwrap().to_owned();
+        output
+            .persist(tmp_dir.path().join("no/such/dir"))
+            .expect_err("the destination directory is missing");
+        assert!(!failed.exists());
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
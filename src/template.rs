@@ -0,0 +1,186 @@
+use crate::STDIO_FILENAME;
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+/// Derives output paths from input paths, for tools converting many files at once.
+///
+/// The template can use these fields of the input path:
+///
+/// - `{path}`: the whole path
+/// - `{dir}`: the parent directory (`.` if there is none)
+/// - `{name}`: the file name
+/// - `{stem}`: the file name without its extension
+/// - `{ext}`: the extension, without the dot
+///
+/// A `.` right before an empty `{ext}` is dropped, so `{stem}.out.{ext}` maps `README` to
+/// `README.out`. The input `-` (stdin) always maps to `-` (stdout).
+#[derive(Debug, Clone)]
+pub struct OutputTemplate {
+    template: String,
+    output_dir: Option<PathBuf>,
+    base: Option<PathBuf>,
+}
+
+impl OutputTemplate {
+    pub fn new<S: Into<String>>(template: S) -> Self {
+        Self {
+            template: template.into(),
+            output_dir: None,
+            base: None,
+        }
+    }
+
+    /// Put outputs in `dir` instead of next to their inputs, i.e. what `{dir}` expands to.
+    pub fn output_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.output_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// With [`output_dir`](OutputTemplate::output_dir), keep the directory structure of inputs
+    /// below `base`, so `base/a/b.txt` goes to `<output_dir>/a/`. Inputs outside `base` go straight
+    /// into the output directory.
+    pub fn relative_to<P: AsRef<Path>>(mut self, base: P) -> Self {
+        self.base = Some(base.as_ref().to_owned());
+        self
+    }
+
+    /// The output path for `input`.
+    pub fn render<P: AsRef<Path>>(&self, input: P) -> PathBuf {
+        let input = input.as_ref();
+        if input.to_string_lossy() == STDIO_FILENAME {
+            return PathBuf::from(STDIO_FILENAME);
+        }
+
+        let dir = self.dir(input);
+        let lossy = |s: Option<&OsStr>| {
+            s.map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let name = lossy(input.file_name());
+        let stem = lossy(input.file_stem());
+        let ext = lossy(input.extension());
+
+        let out = expand(&self.template, |field, out| {
+            match field {
+                "path" => out.push_str(&input.to_string_lossy()),
+                "dir" => out.push_str(&dir.to_string_lossy()),
+                "name" => out.push_str(&name),
+                "stem" => out.push_str(&stem),
+                "ext" if ext.is_empty() => {
+                    if out.ends_with('.') {
+                        out.pop();
+                    }
+                }
+                "ext" => out.push_str(&ext),
+                _ => return false,
+            }
+            true
+        });
+        PathBuf::from(out)
+    }
+
+    fn dir(&self, input: &Path) -> PathBuf {
+        let parent = match input.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let output_dir = match &self.output_dir {
+            Some(output_dir) => output_dir,
+            None => return parent.to_owned(),
+        };
+        match self
+            .base
+            .as_ref()
+            .and_then(|base| parent.strip_prefix(base).ok())
+        {
+            Some(relative) if !relative.as_os_str().is_empty() => output_dir.join(relative),
+            _ => output_dir.clone(),
+        }
+    }
+}
+
+/// A piece of a template: literal text, or the name of a `{field}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Segment<'a> {
+    Text(&'a str),
+    Field(&'a str),
+}
+
+/// The segments of `template`. A `{` without a closing `}` is literal text.
+pub(crate) fn segments(template: &str) -> impl Iterator<Item = Segment<'_>> {
+    let mut rest = template;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let field = rest
+            .strip_prefix('{')
+            .and_then(|after| Some(&after[..after.find('}')?]));
+        let segment = match field {
+            Some(field) => {
+                rest = &rest[field.len() + 2..];
+                Segment::Field(field)
+            }
+            None => {
+                // Up to the next `{` that starts a field, skipping the one at the start.
+                let end = rest[1..].find('{').map_or(rest.len(), |i| i + 1);
+                let (text, after) = rest.split_at(end);
+                rest = after;
+                Segment::Text(text)
+            }
+        };
+        Some(segment)
+    })
+}
+
+/// Render `template`, with `field` appending each field's value to the output so far. Fields
+/// it returns `false` for are unknown and kept as they are.
+pub(crate) fn expand<F>(template: &str, mut field: F) -> String
+where
+    F: FnMut(&str, &mut String) -> bool,
+{
+    let mut out = String::with_capacity(template.len());
+    for segment in segments(template) {
+        match segment {
+            Segment::Text(text) => out.push_str(text),
+            Segment::Field(name) => {
+                if !field(name, &mut out) {
+                    out.push('{');
+                    out.push_str(name);
+                    out.push('}');
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_paths() {
+        let template = OutputTemplate::new("{dir}/{stem}.out.{ext}");
+        assert_eq!(template.render("data/in.txt"), Path::new("data/in.out.txt"));
+        assert_eq!(template.render("README"), Path::new("./README.out"));
+        assert_eq!(template.render("-"), Path::new("-"));
+
+        let rebased = OutputTemplate::new("{dir}/{name}.gz")
+            .output_dir("build")
+            .relative_to("src");
+        assert_eq!(rebased.render("src/a/b.txt"), Path::new("build/a/b.txt.gz"));
+        assert_eq!(rebased.render("other/c.txt"), Path::new("build/c.txt.gz"));
+
+        assert_eq!(
+            segments("a{b}{c").collect::<Vec<_>>(),
+            [Segment::Text("a"), Segment::Field("b"), Segment::Text("{c")]
+        );
+        assert_eq!(
+            OutputTemplate::new("{x}-{name").render("in"),
+            Path::new("{x}-{name")
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{FileOrStdout, FileOrStdoutLock};
+use std::{env, io};
+
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLUMNS: u16 = 80;
+
+impl FileOrStdout {
+    /// Size of the terminal the output is connected to as `(columns, rows)`, or `None` if it
+    /// isn't a terminal.
+    ///
+    /// With the `signal` feature, `ResizeWatch` tells when to ask again.
+    pub fn terminal_size(&self) -> Option<(u16, u16)> {
+        match self {
+            Self::File(file) => handle_size(file),
+            Self::Stdout(stdout) => handle_size(stdout),
+        }
+    }
+}
+
+impl<'a> FileOrStdoutLock<'a> {
+    /// See [`FileOrStdout::terminal_size`].
+    pub fn terminal_size(&self) -> Option<(u16, u16)> {
+        match self.file() {
+            Some(file) => handle_size(file),
+            None => handle_size(&io::stdout()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn handle_size<T: std::os::unix::io::AsRawFd>(handle: &T) -> Option<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    // SAFETY: TIOCGWINSZ only writes into the provided `winsize`.
+    let ret = unsafe { libc::ioctl(handle.as_raw_fd(), libc::TIOCGWINSZ, &mut size) };
+    if ret == 0 && size.ws_col > 0 && size.ws_row > 0 {
+        Some((size.ws_col, size.ws_row))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn handle_size<T>(_: &T) -> Option<(u16, u16)> {
+    None
+}
+
+fn env_dimension(name: &str) -> Option<u16> {
+    env::var(name).ok()?.trim().parse().ok().filter(|n| *n > 0)
+}
+
+/// Best-effort terminal size, falling back to `COLUMNS`/`LINES` and then 80x24.
+pub(crate) fn size_or_default() -> (u16, u16) {
+    handle_size(&io::stdout()).unwrap_or_else(|| {
+        (
+            env_dimension("COLUMNS").unwrap_or(DEFAULT_COLUMNS),
+            env_dimension("LINES").unwrap_or(DEFAULT_ROWS),
+        )
+    })
+}
+
+/// Restores a terminal's original settings when dropped, including during a panic unwind.
+#[cfg(unix)]
+pub(crate) struct TermiosGuard {
+    fd: libc::c_int,
+    original: libc::termios,
+}
+
+#[cfg(unix)]
+impl TermiosGuard {
+    /// Apply `f` to the terminal settings of `fd`, returning a guard that undoes the change.
+    pub(crate) fn modify<F>(fd: libc::c_int, f: F) -> io::Result<Self>
+    where
+        F: FnOnce(&mut libc::termios),
+    {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        // SAFETY: `original` is a valid termios for tcgetattr to fill in.
+        if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut modified = original;
+        f(&mut modified);
+        // SAFETY: `modified` is a fully initialized termios.
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &modified) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd, original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for TermiosGuard {
+    fn drop(&mut self) {
+        // SAFETY: restoring settings previously returned by tcgetattr.
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn no_size_for_files() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let mut output = FileOrStdout::from_path(tmp_dir.path().join("out.txt"))?;
+        assert_eq!(output.terminal_size(), None);
+        assert_eq!(output.lock().terminal_size(), None);
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use crate::FileOrStdoutLock;
+use std::{
+    io::{self, Write},
+    mem,
+};
+
+/// Line ending written by a [`TextWriter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Newline {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// `\r\n` on Windows and `\n` everywhere else.
+    #[default]
+    Native,
+}
+
+/// What a [`TextWriter`] does about a newline at the very end of its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FinalNewline {
+    /// Leave the end as written.
+    #[default]
+    Keep,
+    /// End non-empty output with a newline, adding one if it's missing.
+    Ensure,
+    /// Drop one line ending from the end of the output, if there is one.
+    Strip,
+}
+
+/// Writer for text output with a consistent line ending.
+///
+/// The [`FinalNewline`] policy is applied by [`finish`](TextWriter::finish). Dropping the writer
+/// applies it too, but ignores any errors.
+pub struct TextWriter<W: Write> {
+    inner: Option<W>,
+    newline: Newline,
+    final_newline: FinalNewline,
+    last: Option<u8>,
+    /// A trailing line ending held back by [`FinalNewline::Strip`] until more is written.
+    held: Vec<u8>,
+}
+
+impl Newline {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::Native if cfg!(windows) => "\r\n",
+            Self::Native => "\n",
+        }
+    }
+}
+
+impl<W: Write> TextWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            newline: Newline::default(),
+            final_newline: FinalNewline::default(),
+            last: None,
+            held: Vec::new(),
+        }
+    }
+
+    /// Line ending for [`write_line`](TextWriter::write_line). Defaults to [`Newline::Native`].
+    pub fn newline(mut self, newline: Newline) -> Self {
+        self.newline = newline;
+        self
+    }
+
+    /// Defaults to [`FinalNewline::Keep`].
+    pub fn final_newline(mut self, policy: FinalNewline) -> Self {
+        self.final_newline = policy;
+        self
+    }
+
+    /// Write `line` followed by the line ending.
+    pub fn write_line<S: AsRef<str>>(&mut self, line: S) -> io::Result<()> {
+        self.write_all(line.as_ref().as_bytes())?;
+        self.write_all(self.newline.as_str().as_bytes())
+    }
+
+    /// Write each of `lines` followed by the line ending, then flush.
+    pub fn write_lines<I>(&mut self, lines: I) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for line in lines {
+            self.write_line(line)?;
+        }
+        self.flush()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("writer not finished yet")
+    }
+
+    /// Apply the final newline policy, flush, and return the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_final()?;
+        let mut inner = self.inner.take().expect("writer is only finished once");
+        inner.flush()?;
+        Ok(inner)
+    }
+
+    fn inner(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer not finished yet")
+    }
+
+    fn write_final(&mut self) -> io::Result<()> {
+        match self.final_newline {
+            FinalNewline::Ensure if self.last.is_some_and(|b| b != b'\n') => {
+                let newline = self.newline.as_str().as_bytes();
+                self.inner().write_all(newline)
+            }
+            // A lone `\r` wasn't a line ending after all.
+            FinalNewline::Strip if self.held == b"\r" => {
+                let held = mem::take(&mut self.held);
+                self.inner().write_all(&held)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> FileOrStdoutLock<'a> {
+    /// Write text lines ending in `newline`. See [`TextWriter`].
+    pub fn text(self, newline: Newline) -> TextWriter<Self> {
+        TextWriter::new(self).newline(newline)
+    }
+}
+
+impl<W: Write> Write for TextWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.final_newline != FinalNewline::Strip {
+            let amt = self.inner().write(buf)?;
+            if amt > 0 {
+                self.last = Some(buf[amt - 1]);
+            }
+            return Ok(amt);
+        }
+
+        let mut data = mem::take(&mut self.held);
+        data.extend_from_slice(buf);
+        let keep = if data.ends_with(b"\r\n") {
+            2
+        } else if data.ends_with(b"\n") || data.ends_with(b"\r") {
+            1
+        } else {
+            0
+        };
+        let (out, held) = data.split_at(data.len() - keep);
+        self.inner().write_all(out)?;
+        self.held = held.to_vec();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner().flush()
+    }
+}
+
+impl<W: Write> Drop for TextWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_final();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_lines_with_newline() -> Result<(), io::Error> {
+        let mut writer = TextWriter::new(Vec::new()).newline(Newline::CrLf);
+        writer.write_line("header")?;
+        writer.write_lines(["a", "b"])?;
+        writer.write_lines(vec![String::from("c")])?;
+        assert_eq!(writer.finish()?, b"header\r\na\r\nb\r\nc\r\n");
+
+        let mut writer = TextWriter::new(Vec::new());
+        writer.write_line("x")?;
+        let expected: &[u8] = if cfg!(windows) { b"x\r\n" } else { b"x\n" };
+        assert_eq!(writer.get_ref(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn final_newline_policy() -> Result<(), io::Error> {
+        let finish = |policy, chunks: &[&str]| -> io::Result<String> {
+            let mut writer = TextWriter::new(Vec::new())
+                .newline(Newline::Lf)
+                .final_newline(policy);
+            for chunk in chunks {
+                writer.write_all(chunk.as_bytes())?;
+            }
+            Ok(String::from_utf8(writer.finish()?).unwrap())
+        };
+        assert_eq!(finish(FinalNewline::Ensure, &["a\nb"])?, "a\nb\n");
+        assert_eq!(finish(FinalNewline::Ensure, &["a\n"])?, "a\n");
+        assert_eq!(finish(FinalNewline::Ensure, &[])?, "");
+        assert_eq!(finish(FinalNewline::Strip, &["a\n", "b\r", "\n"])?, "a\nb");
+        assert_eq!(finish(FinalNewline::Strip, &["a\n\n"])?, "a\n");
+        assert_eq!(finish(FinalNewline::Strip, &["a\r"])?, "a\r");
+        assert_eq!(finish(FinalNewline::Keep, &["a"])?, "a");
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{Base64Reader, Base64Writer};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+const HEADER: &str = "# polymorphio trace v1";
+
+/// A trace file that [`Traced`] streams record their reads and writes to.
+///
+/// Each line is `<microseconds> <label> <read|write> <length> [<base64 data>]`, timed from when
+/// the log was created, or `<microseconds> <label> <read|write> error <kind>` for failures. Data
+/// is only recorded with [`record_data`](TraceLog::record_data), which [`Replay`] needs. Clones
+/// share the same file, so several streams can be traced together.
+#[derive(Clone)]
+pub struct TraceLog {
+    sink: Arc<Mutex<Sink>>,
+    data: bool,
+}
+
+struct Sink {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+}
+
+/// A reader or writer whose every read or write is recorded in a [`TraceLog`].
+pub struct Traced<T> {
+    inner: T,
+    label: String,
+    log: TraceLog,
+}
+
+/// A reader feeding back the data of a stream recorded in a trace, such as a user's stdin.
+///
+/// Reads return the recorded chunks as they were originally read, waiting until each one's
+/// original time unless timing is turned off.
+pub struct Replay {
+    events: Vec<(Duration, Vec<u8>)>,
+    next: usize,
+    pos: usize,
+    start: Option<Instant>,
+    timing: bool,
+}
+
+impl TraceLog {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+
+    pub fn new<W: Write + Send + 'static>(out: W) -> Self {
+        let mut out: Box<dyn Write + Send> = Box::new(out);
+        let _ = writeln!(out, "{}", HEADER);
+        Self {
+            sink: Arc::new(Mutex::new(Sink {
+                out,
+                start: Instant::now(),
+            })),
+            data: false,
+        }
+    }
+
+    /// Record the bytes read and written, not just their sizes. Defaults to false.
+    pub fn record_data(mut self, data: bool) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Wrap `inner` so its reads and writes are recorded under `label`, e.g. `stdin`.
+    pub fn trace<T>(&self, label: &str, inner: T) -> Traced<T> {
+        let label = label.replace(char::is_whitespace, "_");
+        Traced {
+            inner,
+            label: if label.is_empty() { "_".into() } else { label },
+            log: self.clone(),
+        }
+    }
+
+    fn record(&self, label: &str, op: &str, result: Result<&[u8], io::ErrorKind>) {
+        let mut sink = match self.sink.lock() {
+            Ok(sink) => sink,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let micros = sink.start.elapsed().as_micros();
+        let mut line = format!("{} {} {} ", micros, label, op);
+        match result {
+            Ok(data) => {
+                line.push_str(&data.len().to_string());
+                if self.data && !data.is_empty() {
+                    line.push(' ');
+                    line.push_str(&encode(data));
+                }
+            }
+            Err(kind) => line.push_str(&format!("error {:?}", kind)),
+        }
+        line.push('\n');
+        // Tracing is diagnostic only, so it must not make the traced I/O fail.
+        let _ = sink.out.write_all(line.as_bytes());
+        let _ = sink.out.flush();
+    }
+}
+
+impl<T> Traced<T> {
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Read> Read for Traced<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = self.inner.read(buf);
+        let recorded = result
+            .as_ref()
+            .map(|amt| &buf[..*amt])
+            .map_err(|e| e.kind());
+        self.log.record(&self.label, "read", recorded);
+        result
+    }
+}
+
+impl<T: Write> Write for Traced<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        let recorded = result
+            .as_ref()
+            .map(|amt| &buf[..*amt])
+            .map_err(|e| e.kind());
+        self.log.record(&self.label, "write", recorded);
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Replay {
+    /// Load the reads recorded under `label` in the trace at `path`.
+    ///
+    /// Fails with `InvalidData` if the trace was recorded without data.
+    pub fn open<P: AsRef<Path>>(path: P, label: &str) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?), label)
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R, label: &str) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid("not a polymorphio trace".into()));
+        }
+
+        let mut events = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let fields: Vec<&str> = line.split(' ').collect();
+            if fields.len() < 4 {
+                return Err(invalid(format!("malformed trace line {}", i + 2)));
+            }
+            if fields[1] != label || fields[2] != "read" || fields[3] == "error" {
+                continue;
+            }
+            let micros: u64 = fields[0]
+                .parse()
+                .map_err(|_| invalid(format!("bad time on trace line {}", i + 2)))?;
+            let len: usize = fields[3]
+                .parse()
+                .map_err(|_| invalid(format!("bad length on trace line {}", i + 2)))?;
+            let data = match fields.get(4) {
+                Some(encoded) => decode(encoded)?,
+                None if len == 0 => Vec::new(),
+                None => return Err(invalid("trace was recorded without data".into())),
+            };
+            events.push((Duration::from_micros(micros), data));
+        }
+
+        Ok(Self {
+            events,
+            next: 0,
+            pos: 0,
+            start: None,
+            timing: true,
+        })
+    }
+
+    /// Whether to wait for each chunk's original time. Defaults to true.
+    pub fn timing(mut self, timing: bool) -> Self {
+        self.timing = timing;
+        self
+    }
+}
+
+impl Read for Replay {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        // Skip recorded end-of-file markers and anything already fully returned.
+        while let Some((time, data)) = self.events.get(self.next) {
+            if self.pos < data.len() {
+                if self.pos == 0 && self.timing {
+                    if let Some(wait) = time.checked_sub(start.elapsed()) {
+                        thread::sleep(wait);
+                    }
+                }
+                let amt = buf.len().min(data.len() - self.pos);
+                buf[..amt].copy_from_slice(&data[self.pos..self.pos + amt]);
+                self.pos += amt;
+                return Ok(amt);
+            }
+            self.next += 1;
+            self.pos = 0;
+        }
+        Ok(0)
+    }
+}
+
+fn encode(data: &[u8]) -> String {
+    let mut writer = Base64Writer::new(Vec::new());
+    let encoded = writer
+        .write_all(data)
+        .and_then(|_| writer.finish())
+        .expect("writing to a Vec can't fail");
+    String::from_utf8(encoded).expect("base64 is ASCII")
+}
+
+fn decode(encoded: &str) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    Base64Reader::new(encoded.as_bytes()).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn record_and_replay() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let path = tmp_dir.path().join("trace.log");
+
+        let log = TraceLog::create(&path)?.record_data(true);
+        let mut input = log.trace("stdin", &b"typed input\n"[..]);
+        let mut buf = [0; 6];
+        input.read_exact(&mut buf)?;
+        io::copy(&mut input, &mut io::sink())?;
+        let mut output = log.trace("stdout", Vec::new());
+        output.write_all(b"echo")?;
+        drop(log);
+        drop(input);
+        drop(output);
+
+        let trace = fs::read_to_string(&path)?;
+        let lines: Vec<Vec<&str>> = trace
+            .lines()
+            .skip(1)
+            .map(|l| l.split(' ').collect())
+            .collect();
+        assert_eq!(lines[0][1..], ["stdin", "read", "6", "dHlwZWQg"]);
+        assert_eq!(
+            lines.last().unwrap()[1..],
+            ["stdout", "write", "4", "ZWNobw=="]
+        );
+
+        let mut replayed = String::new();
+        Replay::open(&path, "stdin")?
+            .timing(false)
+            .read_to_string(&mut replayed)?;
+        assert_eq!(replayed, "typed input\n");
+
+        let trace =
+            Replay::from_reader(format!("{}\n5 stdin read 3\n", HEADER).as_bytes(), "stdin");
+        assert_eq!(trace.err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
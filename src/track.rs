@@ -0,0 +1,153 @@
+use std::{
+    fmt,
+    io::{self, BufRead, Read},
+    path::{Path, PathBuf},
+};
+
+/// Where a [`Tracked`] reader is in its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Bytes consumed so far.
+    pub offset: u64,
+    /// Line number, starting from 1.
+    pub line: u64,
+    /// Column in characters, starting from 1. Invalid UTF-8 counts one column per byte.
+    pub column: u64,
+}
+
+/// Reader that keeps track of the byte offset, line and column of the data consumed from it.
+///
+/// Data counts as consumed once returned from `read` or passed to `consume`, so a parser
+/// reading through it always knows where it is, e.g. to report errors as `file.txt:37:12`.
+pub struct Tracked<R> {
+    inner: R,
+    path: Option<PathBuf>,
+    position: Position,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl Position {
+    fn advance(&mut self, data: &[u8]) {
+        self.offset += data.len() as u64;
+        for b in data {
+            if *b == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else if *b & 0xC0 != 0x80 {
+                // UTF-8 continuation bytes belong to the previous character's column.
+                self.column += 1;
+            }
+        }
+    }
+}
+
+impl<R> Tracked<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            path: None,
+            position: Position::default(),
+        }
+    }
+
+    /// Name the input in [`location`](Tracked::location).
+    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().to_owned());
+        self
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// The current position as `path:line:column`, or `line:column` without a path.
+    pub fn location(&self) -> String {
+        match &self.path {
+            Some(path) => format!("{}:{}", path.display(), self.position),
+            None => self.position.to_string(),
+        }
+    }
+
+    /// An error of `kind` whose message is prefixed with the current location.
+    pub fn error<M: fmt::Display>(&self, kind: io::ErrorKind, msg: M) -> io::Error {
+        io::Error::new(kind, format!("{}: {}", self.location(), msg))
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.inner.read(buf)?;
+        self.position.advance(&buf[..amt]);
+        Ok(amt)
+    }
+}
+
+impl<R: BufRead> BufRead for Tracked<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The consumed bytes are still in the inner buffer, so filling it again is free.
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.position.advance(&buf[..amt.min(buf.len())]);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_line_and_column() -> Result<(), io::Error> {
+        let mut reader =
+            Tracked::new(&b"first\nsecond \xc3\xa9\xc3\xa9 line\nthird"[..]).path("file.txt");
+        assert_eq!(reader.location(), "file.txt:1:1");
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        assert_eq!(reader.location(), "file.txt:2:1");
+
+        let mut word = [0; 11];
+        reader.read_exact(&mut word)?;
+        assert_eq!(
+            reader.position(),
+            Position {
+                offset: 17,
+                line: 2,
+                column: 10
+            }
+        );
+        let e = reader.error(io::ErrorKind::InvalidData, "unexpected 'line'");
+        assert_eq!(e.to_string(), "file.txt:2:10: unexpected 'line'");
+
+        io::copy(&mut reader, &mut io::sink())?;
+        assert_eq!(reader.position().to_string(), "3:6");
+        Ok(())
+    }
+}
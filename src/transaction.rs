@@ -0,0 +1,251 @@
+use crate::{atomic::Replaced, temp, AtomicOutput, Commit};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A set of [`AtomicOutput`]s that are committed together or not at all.
+///
+/// Nothing is moved into place until every output has been flushed and synced successfully. If
+/// replacing one of the targets still fails, the targets already replaced are restored from
+/// backups. Dropping the transaction without committing discards all outputs.
+#[derive(Default)]
+pub struct OutputTransaction {
+    outputs: Vec<AtomicOutput>,
+}
+
+/// A target that has been replaced, and where its old content was kept.
+///
+/// The backup is a hard link to the old file if it was renamed over, or a copy of it if it was
+/// rewritten in place.
+struct Installed {
+    path: PathBuf,
+    in_place: bool,
+    backup: Option<PathBuf>,
+}
+
+impl OutputTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new output for `path` as part of this transaction.
+    pub fn create<P: AsRef<Path>>(&mut self, path: P) -> io::Result<&mut AtomicOutput> {
+        let output = AtomicOutput::from_path(path)?;
+        Ok(self.add(output))
+    }
+
+    /// Add an already-created output to this transaction.
+    pub fn add(&mut self, output: AtomicOutput) -> &mut AtomicOutput {
+        self.outputs.push(output);
+        self.outputs.last_mut().unwrap()
+    }
+
+    /// Move every output into place, returning what was done to each, in the order they were
+    /// added.
+    pub fn commit(mut self) -> io::Result<Vec<Commit>> {
+        let mut changed = Vec::with_capacity(self.outputs.len());
+        for output in &mut self.outputs {
+            changed.push(output.prepare()?);
+        }
+
+        let mut installed = Vec::new();
+        for (output, _) in self.outputs.iter_mut().zip(&changed).filter(|(_, c)| **c) {
+            if let Err(e) = install(output, &mut installed) {
+                rollback(installed);
+                return Err(e);
+            }
+        }
+        for backup in installed.into_iter().filter_map(|i| i.backup) {
+            let _ = fs::remove_file(backup);
+        }
+
+        Ok(changed
+            .into_iter()
+            .map(|changed| {
+                if changed {
+                    Commit::Written
+                } else {
+                    Commit::Unchanged
+                }
+            })
+            .collect())
+    }
+
+    /// Discard all outputs, leaving every target untouched.
+    pub fn rollback(self) {}
+}
+
+fn install(output: &mut AtomicOutput, installed: &mut Vec<Installed>) -> io::Result<()> {
+    let (path, in_place) = match output.replaced()? {
+        Replaced::Nothing => return output.install(),
+        Replaced::Entry(path) => (path, false),
+        Replaced::InPlace(path) => (path, true),
+    };
+    let backup = if fs::symlink_metadata(&path).is_ok() {
+        Some(back_up(&path, in_place)?)
+    } else {
+        None
+    };
+
+    let result = output.install();
+    installed.push(Installed {
+        path,
+        in_place,
+        backup,
+    });
+    result
+}
+
+/// Keep the file at `path` under a new name next to it, without moving it, so the target never
+/// goes missing and is still there for the link policy to see.
+fn back_up(path: &Path, in_place: bool) -> io::Result<PathBuf> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (_, backup) = temp::create_unique(dir, &name)?;
+    let result = if in_place {
+        fs::copy(path, &backup).map(drop)
+    } else {
+        fs::remove_file(&backup).and_then(|()| match fs::hard_link(path, &backup) {
+            // Not every file system has hard links.
+            Err(_) => copy_entry(path, &backup),
+            linked => linked,
+        })
+    };
+    if let Err(e) = result {
+        let _ = fs::remove_file(&backup);
+        return Err(e);
+    }
+    Ok(backup)
+}
+
+/// Copy the file at `from` to `to`, or for a symlink, make `to` a symlink to the same place.
+fn copy_entry(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    if fs::symlink_metadata(from)?.file_type().is_symlink() {
+        return std::os::unix::fs::symlink(fs::read_link(from)?, to);
+    }
+    fs::copy(from, to).map(drop)
+}
+
+/// Put back the old content of every replaced target, most recent first.
+fn rollback(installed: Vec<Installed>) {
+    for Installed {
+        path,
+        in_place,
+        backup,
+    } in installed.into_iter().rev()
+    {
+        match backup {
+            Some(backup) if in_place => {
+                let _ = fs::copy(&backup, path);
+                let _ = fs::remove_file(backup);
+            }
+            Some(backup) => {
+                let _ = fs::rename(backup, path);
+            }
+            None => {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LinkPolicy;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn all_or_nothing() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let a = tmp_dir.path().join("a");
+        let b = tmp_dir.path().join("b");
+        fs::write(&a, "old a")?;
+
+        let mut transaction = OutputTransaction::new();
+        transaction.create(&a)?.write_all(b"new a")?;
+        transaction.create(&b)?.write_all(b"new b")?;
+        drop(transaction);
+        assert_eq!(fs::read_to_string(&a)?, "old a");
+        assert!(!b.exists());
+
+        // `b` can't be replaced because it is a directory, so `a` must be restored.
+        fs::create_dir(&b)?;
+        fs::write(b.join("keep"), "")?;
+        let mut transaction = OutputTransaction::new();
+        transaction.create(&a)?.write_all(b"new a")?;
+        transaction.create(&b)?.write_all(b"new b")?;
+        assert!(transaction.commit().is_err());
+        assert_eq!(fs::read_to_string(&a)?, "old a");
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 2);
+
+        fs::remove_dir_all(&b)?;
+        let mut transaction = OutputTransaction::new();
+        transaction.create(&a)?.write_all(b"new a")?;
+        transaction.create(&b)?.write_all(b"new b")?;
+        assert_eq!(transaction.commit()?, vec![Commit::Written; 2]);
+        assert_eq!(fs::read_to_string(&a)?, "new a");
+        assert_eq!(fs::read_to_string(&b)?, "new b");
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 2);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_targets() -> Result<(), io::Error> {
+        use std::os::unix::fs::symlink;
+
+        let tmp_dir = TempDir::new()?;
+        let real = tmp_dir.path().join("real");
+        let link = tmp_dir.path().join("link");
+        let dir = tmp_dir.path().join("dir");
+        fs::write(&real, "old")?;
+        symlink(&real, &link)?;
+        fs::create_dir(&dir)?;
+        fs::write(dir.join("keep"), "")?;
+
+        for policy in [LinkPolicy::FollowAndReplaceTarget, LinkPolicy::WriteThrough] {
+            // The directory can't be replaced, so the symlinked target must be restored.
+            let mut transaction = OutputTransaction::new();
+            transaction
+                .add(AtomicOutput::from_path(&link)?.link_policy(policy))
+                .write_all(b"new")?;
+            transaction.create(&dir)?.write_all(b"dir")?;
+            assert!(transaction.commit().is_err());
+            assert!(fs::symlink_metadata(&link)?.is_symlink());
+            assert_eq!(fs::read_to_string(&real)?, "old");
+            assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 3);
+
+            let mut transaction = OutputTransaction::new();
+            transaction
+                .add(AtomicOutput::from_path(&link)?.link_policy(policy))
+                .write_all(b"new")?;
+            transaction.commit()?;
+            assert!(fs::symlink_metadata(&link)?.is_symlink());
+            assert_eq!(fs::read_to_string(&real)?, "new");
+            assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 3);
+            fs::write(&real, "old")?;
+        }
+
+        let mut transaction = OutputTransaction::new();
+        transaction.create(&link)?.write_all(b"new")?;
+        transaction.create(&dir)?.write_all(b"dir")?;
+        assert!(transaction.commit().is_err());
+        assert!(fs::symlink_metadata(&link)?.is_symlink());
+        assert_eq!(fs::read_dir(tmp_dir.path())?.count(), 3);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    error, fmt,
+    io::{self, BufRead, Read},
+    str,
+};
+
+const REPLACEMENT: &[u8] = "\u{fffd}".as_bytes();
+
+/// Reader adapter that validates UTF-8 as it streams.
+///
+/// By default invalid data produces an `InvalidData` error carrying a [`Utf8Error`] with the
+/// position of the first bad byte. In lossy mode invalid sequences are replaced with U+FFFD.
+pub struct Utf8Reader<R> {
+    inner: R,
+    lossy: bool,
+    pending: Vec<u8>,
+    out: Vec<u8>,
+    out_pos: usize,
+    error: Option<Utf8Error>,
+    offset: u64,
+    line: u64,
+}
+
+/// Position of invalid UTF-8 data in a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Utf8Error {
+    offset: u64,
+    line: u64,
+}
+
+impl Utf8Error {
+    /// Byte offset of the invalid sequence from the start of the stream.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// One-based line number the invalid sequence is on.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+}
+
+impl fmt::Display for Utf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid UTF-8 on line {} (byte offset {})",
+            self.line, self.offset
+        )
+    }
+}
+
+impl error::Error for Utf8Error {}
+
+impl<R: BufRead> Utf8Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            lossy: false,
+            pending: Vec::new(),
+            out: Vec::new(),
+            out_pos: 0,
+            error: None,
+            offset: 0,
+            line: 1,
+        }
+    }
+
+    /// Replace invalid sequences with U+FFFD instead of returning an error.
+    pub fn lossy(mut self, lossy: bool) -> Self {
+        self.lossy = lossy;
+        self
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn emit_valid(&mut self, start: usize, end: usize) {
+        let valid = &self.pending[start..end];
+        self.line += valid.iter().filter(|b| **b == b'\n').count() as u64;
+        self.offset += valid.len() as u64;
+        self.out.extend_from_slice(valid);
+    }
+
+    /// Handle `len` invalid bytes. Returns `false` if reading must stop with an error.
+    fn emit_invalid(&mut self, len: usize) -> bool {
+        if !self.lossy {
+            self.error = Some(Utf8Error {
+                offset: self.offset,
+                line: self.line,
+            });
+            return false;
+        }
+        self.offset += len as u64;
+        self.out.extend_from_slice(REPLACEMENT);
+        true
+    }
+
+    fn fill_out(&mut self) -> io::Result<()> {
+        self.out.clear();
+        self.out_pos = 0;
+
+        while self.out.is_empty() && self.error.is_none() {
+            let buf = self.inner.fill_buf()?;
+            let eof = buf.is_empty();
+            let amt = buf.len();
+            self.pending.extend_from_slice(buf);
+            self.inner.consume(amt);
+
+            let mut start = 0;
+            while start < self.pending.len() {
+                let (valid_up_to, error_len) = match str::from_utf8(&self.pending[start..]) {
+                    Ok(_) => (self.pending.len() - start, None),
+                    Err(e) => (e.valid_up_to(), Some(e.error_len())),
+                };
+                self.emit_valid(start, start + valid_up_to);
+                start += valid_up_to;
+
+                let invalid_len = match error_len {
+                    None => break,
+                    Some(Some(len)) => len,
+                    // Truncated sequence: wait for more data unless the stream has ended.
+                    Some(None) if eof => self.pending.len() - start,
+                    Some(None) => break,
+                };
+                if !self.emit_invalid(invalid_len) {
+                    break;
+                }
+                start += invalid_len;
+            }
+            self.pending.drain(..start);
+
+            if eof {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Utf8Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = self.fill_buf()?.read(buf)?;
+        self.consume(amt);
+        Ok(amt)
+    }
+}
+
+impl<R: BufRead> BufRead for Utf8Reader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.out_pos == self.out.len() {
+            if let Some(e) = self.error {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+            self.fill_out()?;
+        }
+        Ok(&self.out[self.out_pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.out_pos = (self.out_pos + amt).min(self.out.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_reports_position() {
+        let mut reader = Utf8Reader::new(&b"ok\nstill ok\nbad \xff here"[..]);
+        let mut content = String::new();
+        let err = reader.read_to_string(&mut content).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let utf8_err = err.get_ref().unwrap().downcast_ref::<Utf8Error>().unwrap();
+        assert_eq!(utf8_err.line(), 3);
+        assert_eq!(utf8_err.offset(), 16);
+    }
+
+    #[test]
+    fn lossy_replaces_split_and_truncated_sequences() -> Result<(), io::Error> {
+        // Split the input so a valid multi-byte character straddles two reads.
+        let input = io::BufReader::with_capacity(4, &b"caf\xc3\xa9 \xff!\xe2\x82"[..]);
+        let mut content = String::new();
+        Utf8Reader::new(input)
+            .lossy(true)
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "caf\u{e9} \u{fffd}!\u{fffd}");
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::{FileOrStdin, STDIO_FILENAME};
+use std::{
+    fs, io,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Re-run a callback with a fresh input whenever one of the watched files changes.
+///
+/// The callback is invoked once for every path up front, then again for each path whose
+/// modification time or length changes. Stdin (`-`) is only ever passed to the callback once.
+/// Return `ControlFlow::Break(())` from the callback to stop watching.
+pub struct Watch {
+    paths: Vec<PathBuf>,
+    interval: Duration,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl Watch {
+    pub fn new<I, P>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        Self {
+            paths: paths.into_iter().map(|p| p.as_ref().to_owned()).collect(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Set how often the watched files are polled for changes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn run<F>(self, mut callback: F) -> io::Result<()>
+    where
+        F: FnMut(&Path, FileOrStdin) -> io::Result<ControlFlow<()>>,
+    {
+        let mut stamps = Vec::with_capacity(self.paths.len());
+
+        for path in &self.paths {
+            let stamp = stamp(path);
+            if (is_stdin(path) || stamp.is_some())
+                && callback(path, FileOrStdin::from_path(path)?)?.is_break()
+            {
+                return Ok(());
+            }
+            stamps.push(stamp);
+        }
+
+        if self.paths.iter().all(|p| is_stdin(p)) {
+            return Ok(());
+        }
+
+        loop {
+            thread::sleep(self.interval);
+
+            for (path, last) in self.paths.iter().zip(stamps.iter_mut()) {
+                if is_stdin(path) {
+                    continue;
+                }
+
+                let current = stamp(path);
+                if current == *last {
+                    continue;
+                }
+                *last = current;
+
+                // A file that disappeared will be picked up again once it is recreated.
+                let input = match FileOrStdin::from_path(path) {
+                    Ok(input) => input,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
+                };
+
+                if callback(path, input)?.is_break() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Watch `paths` and call `callback` with a fresh input each time one of them changes.
+///
+/// See [`Watch`] to configure the polling interval.
+pub fn watch<I, P, F>(paths: I, callback: F) -> io::Result<()>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    F: FnMut(&Path, FileOrStdin) -> io::Result<ControlFlow<()>>,
+{
+    Watch::new(paths).run(callback)
+}
+
+fn is_stdin(path: &Path) -> bool {
+    path.to_string_lossy() == STDIO_FILENAME
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = fs::metadata(path).ok()?;
+    Some(Stamp {
+        modified: meta.modified().ok(),
+        len: meta.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    #[test]
+    fn reruns_on_change() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let test_file_path = tmp_dir.path().join("watched.txt");
+        fs::write(&test_file_path, "first")?;
+
+        let writer_path = test_file_path.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            fs::write(writer_path, "second, longer").unwrap();
+        });
+
+        let mut seen = Vec::new();
+        Watch::new([&test_file_path])
+            .interval(Duration::from_millis(10))
+            .run(|_, mut input| {
+                let mut content = String::new();
+                input.lock().read_to_string(&mut content)?;
+                seen.push(content);
+                Ok(if seen.len() == 2 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                })
+            })?;
+
+        writer.join().unwrap();
+        assert_eq!(seen, vec!["first", "second, longer"]);
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+//! Copying extended attributes between files.
+//!
+//! ACLs (`system.posix_acl_*` on Linux) and macOS resource forks (`com.apple.ResourceFork`) are
+//! extended attributes too, so they come along.
+
+use std::{fs::File, io, path::Path};
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub(crate) fn copy(from: &Path, to: &File) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, os::unix::io::AsRawFd};
+
+    let from = CString::new(from.as_os_str().as_bytes())?;
+    let names = match read_value(|buf, len| sys::list(&from, buf, len)) {
+        Ok(names) => names,
+        // The file system has no extended attributes to copy.
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for name in names.split(|b| *b == 0).filter(|name| !name.is_empty()) {
+        let name = CString::new(name)?;
+        let value = read_value(|buf, len| sys::get(&from, &name, buf, len))?;
+        if sys::set(to.as_raw_fd(), &name, &value) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Copy the SELinux security context (`security.selinux`) of `from` to `to`, if it has one.
+#[cfg(all(feature = "selinux", any(target_os = "linux", target_os = "android")))]
+pub(crate) fn copy_selinux_context(from: &Path, to: &File) -> io::Result<()> {
+    use std::{ffi::CString, os::unix::io::AsRawFd};
+
+    let value = match selinux_context(from)? {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    let name = CString::new("security.selinux")?;
+    if sys::set(to.as_raw_fd(), &name, &value) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The SELinux security context of `path`, or `None` if SELinux is disabled or the file system
+/// doesn't label files.
+#[cfg(all(feature = "selinux", any(target_os = "linux", target_os = "android")))]
+pub(crate) fn selinux_context(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new("security.selinux")?;
+    match read_value(|buf, len| sys::get(&path, &name, buf, len)) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if matches!(e.raw_os_error(), Some(libc::ENODATA) | Some(libc::ENOTSUP)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(all(
+    feature = "selinux",
+    not(any(target_os = "linux", target_os = "android"))
+))]
+pub(crate) fn copy_selinux_context(_: &Path, _: &File) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+pub(crate) fn copy(_: &Path, _: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Call a size-then-fill style function until the value fits, in case it grows in between.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn read_value(f: impl Fn(*mut u8, usize) -> isize) -> io::Result<Vec<u8>> {
+    loop {
+        let len = f(std::ptr::null_mut(), 0);
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut buf = vec![0; len as usize];
+        let len = f(buf.as_mut_ptr(), buf.len());
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ERANGE) {
+            return Err(e);
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys {
+    use std::ffi::CStr;
+
+    pub(super) fn list(path: &CStr, buf: *mut u8, len: usize) -> isize {
+        // SAFETY: `buf` is null with a zero `len`, or points to `len` writable bytes.
+        unsafe { libc::listxattr(path.as_ptr(), buf.cast(), len) }
+    }
+
+    pub(super) fn get(path: &CStr, name: &CStr, buf: *mut u8, len: usize) -> isize {
+        // SAFETY: as for `list`.
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf.cast(), len) }
+    }
+
+    pub(super) fn set(fd: libc::c_int, name: &CStr, value: &[u8]) -> libc::c_int {
+        // SAFETY: `value` is a valid slice for the duration of the call.
+        unsafe { libc::fsetxattr(fd, name.as_ptr(), value.as_ptr().cast(), value.len(), 0) }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use std::ffi::CStr;
+
+    pub(super) fn list(path: &CStr, buf: *mut u8, len: usize) -> isize {
+        // SAFETY: `buf` is null with a zero `len`, or points to `len` writable bytes.
+        unsafe { libc::listxattr(path.as_ptr(), buf.cast(), len, 0) }
+    }
+
+    pub(super) fn get(path: &CStr, name: &CStr, buf: *mut u8, len: usize) -> isize {
+        // SAFETY: as for `list`.
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf.cast(), len, 0, 0) }
+    }
+
+    pub(super) fn set(fd: libc::c_int, name: &CStr, value: &[u8]) -> libc::c_int {
+        // SAFETY: `value` is a valid slice for the duration of the call.
+        unsafe { libc::fsetxattr(fd, name.as_ptr(), value.as_ptr().cast(), value.len(), 0, 0) }
+    }
+}
@@ -0,0 +1,620 @@
+use crate::{
+    deterministic, digest::Crc32, inflate::Inflate, split_member_spec, Rewindable, STDIO_FILENAME,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const END_OF_DIRECTORY_LEN: usize = 22;
+const MAX_COMMENT_LEN: usize = 0xffff;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+const ENCRYPTED_FLAG: u16 = 0x0001;
+const UTF8_FLAG: u16 = 0x0800;
+
+/// Reader for the entries of a zip archive, located through its central directory.
+///
+/// Stored and deflated entries are supported; encrypted and Zip64 archives are not.
+pub struct ZipArchive<R> {
+    inner: R,
+    entries: Vec<Entry>,
+}
+
+/// The content of one entry of a [`ZipArchive`], checked against its CRC once fully read.
+pub struct ZipEntry<R> {
+    data: EntryData<R>,
+    crc: Crc32,
+    expected_crc: u32,
+    verified: bool,
+}
+
+enum EntryData<R> {
+    Stored(io::Take<R>),
+    Deflated(Box<Inflate<io::Take<R>>>),
+}
+
+/// An entry being added to a zip archive, stored uncompressed.
+///
+/// The archive is only valid again once [`finish`](ZipEntryWriter::finish) is called. Dropping
+/// the writer instead puts the archive back the way it was.
+pub struct ZipEntryWriter {
+    writer: Option<BufWriter<File>>,
+    /// Set when the archive did not exist before, so an unfinished one can be removed.
+    created: Option<PathBuf>,
+    name: String,
+    header_offset: u64,
+    directory: Directory,
+    crc: Crc32,
+    size: u64,
+    modified: (u16, u16),
+}
+
+struct Entry {
+    name: String,
+    method: u16,
+    flags: u16,
+    crc: u32,
+    compressed_size: u64,
+    header_offset: u64,
+}
+
+/// Header fields of an uncompressed entry.
+pub(crate) struct StoredEntry<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) crc: u32,
+    pub(crate) size: u32,
+    pub(crate) modified: (u16, u16),
+    pub(crate) header_offset: u32,
+}
+
+/// The central directory of an archive, as found at its end.
+#[derive(Default)]
+struct Directory {
+    entries: Vec<Entry>,
+    raw: Vec<u8>,
+    offset: u64,
+    comment: Vec<u8>,
+}
+
+impl ZipArchive<Rewindable> {
+    /// Open the archive at `path`, or stdin for `-`.
+    ///
+    /// Stdin is buffered as it is read, since the central directory is at the end of the archive.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(Rewindable::from_path(path)?)
+    }
+
+    /// Open one entry of an archive given as `archive.zip::path/inside.txt`.
+    pub fn open_member(spec: &str) -> io::Result<ZipEntry<Rewindable>> {
+        let (archive, member) = parse_spec(spec)?;
+        Self::from_path(archive)?.into_entry(member)
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let entries = read_directory(&mut inner)?.entries;
+        Ok(Self { inner, entries })
+    }
+
+    /// Names of the archive's entries, in central directory order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// Open the entry called `name`.
+    pub fn by_name(&mut self, name: &str) -> io::Result<ZipEntry<&mut R>> {
+        let entry = find(&self.entries, name)?;
+        open_entry(&mut self.inner, entry)
+    }
+
+    /// Open the entry called `name`, consuming the archive.
+    pub fn into_entry(self, name: &str) -> io::Result<ZipEntry<R>> {
+        let entry = find(&self.entries, name)?;
+        open_entry(self.inner, entry)
+    }
+}
+
+impl<R: Read> Read for ZipEntry<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let amt = match &mut self.data {
+            EntryData::Stored(data) => data.read(buf)?,
+            EntryData::Deflated(data) => data.read(buf)?,
+        };
+        self.crc.update(&buf[..amt]);
+        if amt == 0 && !buf.is_empty() && !self.verified {
+            self.verified = true;
+            if self.crc.finish() != self.expected_crc {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "zip entry CRC mismatch",
+                ));
+            }
+        }
+        Ok(amt)
+    }
+}
+
+impl ZipEntryWriter {
+    /// Add an entry called `name` to the archive at `path`, creating the archive if needed.
+    ///
+    /// Fails with `AlreadyExists` if the archive already has an entry of that name.
+    pub fn create<P: AsRef<Path>>(path: P, name: &str) -> io::Result<Self> {
+        let path = path.as_ref();
+        if path.to_string_lossy() == STDIO_FILENAME {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot add zip entries to stdout",
+            ));
+        }
+        let name = name.strip_prefix("./").unwrap_or(name).to_owned();
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let is_new = file.metadata()?.len() == 0;
+        let directory = if is_new {
+            Directory::default()
+        } else {
+            read_directory(&mut file)?
+        };
+        if directory.entries.iter().any(|entry| entry.name == name) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("zip archive already has an entry `{}`", name),
+            ));
+        }
+        if directory.entries.len() >= 0xffff {
+            return Err(unsupported("zip archives with 65535 or more entries"));
+        }
+
+        // The new entry goes where the central directory was; it is rewritten after it.
+        let header_offset = directory.offset;
+        file.seek(SeekFrom::Start(header_offset))?;
+        let mut writer = Self {
+            writer: Some(BufWriter::new(file)),
+            created: if is_new { Some(path.to_owned()) } else { None },
+            name,
+            header_offset,
+            directory,
+            crc: Crc32::new(),
+            size: 0,
+            modified: dos_time(SystemTime::now()),
+        };
+        let header = writer.stored_entry().local_header();
+        writer.writer.as_mut().unwrap().write_all(&header)?;
+        Ok(writer)
+    }
+
+    /// Date the entry `SOURCE_DATE_EPOCH` if it's set, or else 1980, rather than the time it was
+    /// written, for reproducible builds.
+    pub fn deterministic(mut self, yes: bool) -> Self {
+        self.modified = dos_time(deterministic::timestamp(yes));
+        self
+    }
+
+    /// Add an entry to an archive given as `archive.zip::path/inside.txt`.
+    pub fn from_spec(spec: &str) -> io::Result<Self> {
+        let (archive, member) = parse_spec(spec)?;
+        Self::create(archive, member)
+    }
+
+    /// Complete the entry and write the archive's new central directory.
+    ///
+    /// On failure the archive is put back the way it was, as when the writer is dropped.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.size > u32::MAX as u64 {
+            return Err(unsupported("zip entries of 4 GiB or more"));
+        }
+        let entry = self.stored_entry();
+        let header = entry.local_header();
+        let offset = self.header_offset + header.len() as u64 + self.size;
+        let mut directory = self.directory.raw.clone();
+        entry.put_central_header(&mut directory);
+        let end = end_of_directory(
+            directory.len(),
+            self.directory.entries.len() + 1,
+            offset,
+            &self.directory.comment,
+        );
+
+        let writer = self.writer.as_mut().expect("entry is only finished once");
+        writer.flush()?;
+        let file = writer.get_mut();
+        // Rewrite the local header now that the CRC and size are known.
+        file.seek(SeekFrom::Start(self.header_offset))?;
+        file.write_all(&header)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&directory)?;
+        file.write_all(&end)?;
+        file.set_len(offset + (directory.len() + end.len()) as u64)?;
+        file.sync_all()?;
+        self.writer = None;
+        Ok(())
+    }
+
+    fn stored_entry(&self) -> StoredEntry<'_> {
+        StoredEntry {
+            name: &self.name,
+            crc: self.crc.finish(),
+            size: self.size as u32,
+            modified: self.modified,
+            header_offset: self.header_offset as u32,
+        }
+    }
+}
+
+impl Write for ZipEntryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let amt = self.writer.as_mut().unwrap().write(buf)?;
+        self.crc.update(&buf[..amt]);
+        self.size += amt as u64;
+        Ok(amt)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.as_mut().unwrap().flush()
+    }
+}
+
+impl Drop for ZipEntryWriter {
+    fn drop(&mut self) {
+        let writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => return,
+        };
+        if let Some(path) = &self.created {
+            drop(writer);
+            let _ = fs::remove_file(path);
+            return;
+        }
+        // Put the old central directory back in place of the partial entry.
+        let (mut file, _) = writer.into_parts();
+        let end = end_of_directory(
+            self.directory.raw.len(),
+            self.directory.entries.len(),
+            self.header_offset,
+            &self.directory.comment,
+        );
+        let _ = file
+            .seek(SeekFrom::Start(self.header_offset))
+            .and_then(|_| file.write_all(&self.directory.raw))
+            .and_then(|_| file.write_all(&end))
+            .and_then(|_| {
+                file.set_len(self.header_offset + (self.directory.raw.len() + end.len()) as u64)
+            });
+    }
+}
+
+fn parse_spec(spec: &str) -> io::Result<(&str, &str)> {
+    split_member_spec(spec).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("expected `archive::member`, got `{}`", spec),
+        )
+    })
+}
+
+fn unsupported(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} are not supported", what),
+    )
+}
+
+fn invalid_archive(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid zip archive: {}", msg),
+    )
+}
+
+fn find<'a>(entries: &'a [Entry], name: &str) -> io::Result<&'a Entry> {
+    let name = name.strip_prefix("./").unwrap_or(name);
+    entries
+        .iter()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no member `{}` in archive", name),
+            )
+        })
+}
+
+fn open_entry<R: Read + Seek>(mut inner: R, entry: &Entry) -> io::Result<ZipEntry<R>> {
+    if entry.flags & ENCRYPTED_FLAG != 0 {
+        return Err(unsupported("encrypted zip entries"));
+    }
+    inner.seek(SeekFrom::Start(entry.header_offset))?;
+    let mut header = [0; LOCAL_HEADER_LEN];
+    inner.read_exact(&mut header)?;
+    if u32_at(&header, 0) != LOCAL_HEADER_SIGNATURE {
+        return Err(invalid_archive("bad local header"));
+    }
+    let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+    inner.seek(SeekFrom::Current(skip))?;
+
+    let data = inner.take(entry.compressed_size);
+    let data = match entry.method {
+        STORED => EntryData::Stored(data),
+        DEFLATED => EntryData::Deflated(Box::new(Inflate::new(data))),
+        method => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported zip compression method {}", method),
+            ))
+        }
+    };
+    Ok(ZipEntry {
+        data,
+        crc: Crc32::new(),
+        expected_crc: entry.crc,
+        verified: false,
+    })
+}
+
+fn read_directory<R: Read + Seek>(inner: &mut R) -> io::Result<Directory> {
+    // Stdin can't seek to its end, so read it all to find the length.
+    let len = match inner.seek(SeekFrom::End(0)) {
+        Ok(len) => len,
+        Err(_) => {
+            io::copy(inner, &mut io::sink())?;
+            inner.stream_position()?
+        }
+    };
+
+    let tail_len = len.min((END_OF_DIRECTORY_LEN + MAX_COMMENT_LEN) as u64);
+    inner.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![0; tail_len as usize];
+    inner.read_exact(&mut tail)?;
+    let end = (0..(tail.len() + 1).saturating_sub(END_OF_DIRECTORY_LEN))
+        .rev()
+        .find(|i| u32_at(&tail, *i) == END_OF_DIRECTORY_SIGNATURE)
+        .ok_or_else(|| invalid_archive("no end of central directory record"))?;
+    let record = &tail[end..];
+
+    let count = u16_at(record, 10);
+    let size = u32_at(record, 12);
+    let offset = u32_at(record, 16);
+    if count == 0xffff || size == u32::MAX || offset == u32::MAX {
+        return Err(unsupported("Zip64 archives"));
+    }
+    let comment_len = (u16_at(record, 20) as usize).min(record.len() - END_OF_DIRECTORY_LEN);
+    let comment = record[END_OF_DIRECTORY_LEN..END_OF_DIRECTORY_LEN + comment_len].to_vec();
+
+    inner.seek(SeekFrom::Start(offset as u64))?;
+    let mut raw = vec![0; size as usize];
+    inner.read_exact(&mut raw)?;
+
+    let mut entries = Vec::with_capacity(count as usize);
+    let mut pos = 0;
+    for _ in 0..count {
+        let header = raw
+            .get(pos..pos + CENTRAL_HEADER_LEN)
+            .filter(|header| u32_at(header, 0) == CENTRAL_HEADER_SIGNATURE)
+            .ok_or_else(|| invalid_archive("bad central directory header"))?;
+        let flags = u16_at(header, 8);
+        let name_len = u16_at(header, 28) as usize;
+        let variable_len = name_len + u16_at(header, 30) as usize + u16_at(header, 32) as usize;
+        let name = raw
+            .get(pos + CENTRAL_HEADER_LEN..pos + CENTRAL_HEADER_LEN + name_len)
+            .ok_or_else(|| invalid_archive("truncated central directory"))?;
+        entries.push(Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: u16_at(header, 10),
+            flags,
+            crc: u32_at(header, 16),
+            compressed_size: u32_at(header, 20) as u64,
+            header_offset: u32_at(header, 42) as u64,
+        });
+        pos += CENTRAL_HEADER_LEN + variable_len;
+    }
+
+    Ok(Directory {
+        entries,
+        raw,
+        offset: offset as u64,
+        comment,
+    })
+}
+
+impl StoredEntry<'_> {
+    pub(crate) fn local_header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(LOCAL_HEADER_LEN + self.name.len());
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, 10); // version needed
+        put_u16(&mut header, self.flags());
+        put_u16(&mut header, STORED);
+        put_u16(&mut header, self.modified.0);
+        put_u16(&mut header, self.modified.1);
+        put_u32(&mut header, self.crc);
+        put_u32(&mut header, self.size);
+        put_u32(&mut header, self.size);
+        put_u16(&mut header, self.name.len() as u16);
+        put_u16(&mut header, 0); // extra field length
+        header.extend_from_slice(self.name.as_bytes());
+        header
+    }
+
+    pub(crate) fn put_central_header(&self, out: &mut Vec<u8>) {
+        put_u32(out, CENTRAL_HEADER_SIGNATURE);
+        put_u16(out, 20); // version made by
+        put_u16(out, 10); // version needed
+        put_u16(out, self.flags());
+        put_u16(out, STORED);
+        put_u16(out, self.modified.0);
+        put_u16(out, self.modified.1);
+        put_u32(out, self.crc);
+        put_u32(out, self.size);
+        put_u32(out, self.size);
+        put_u16(out, self.name.len() as u16);
+        out.extend_from_slice(&[0; 12]); // extra and comment lengths, disk, attributes
+        put_u32(out, self.header_offset);
+        out.extend_from_slice(self.name.as_bytes());
+    }
+
+    fn flags(&self) -> u16 {
+        if self.name.is_ascii() {
+            0
+        } else {
+            UTF8_FLAG
+        }
+    }
+}
+
+/// The end of central directory record for a directory of `len` bytes at `offset`.
+pub(crate) fn end_of_directory(len: usize, count: usize, offset: u64, comment: &[u8]) -> Vec<u8> {
+    let mut end = Vec::with_capacity(END_OF_DIRECTORY_LEN + comment.len());
+    put_u32(&mut end, END_OF_DIRECTORY_SIGNATURE);
+    put_u16(&mut end, 0); // this disk
+    put_u16(&mut end, 0); // disk with the directory
+    put_u16(&mut end, count as u16);
+    put_u16(&mut end, count as u16);
+    put_u32(&mut end, len as u32);
+    put_u32(&mut end, offset as u32);
+    put_u16(&mut end, comment.len() as u16);
+    end.extend_from_slice(comment);
+    end
+}
+
+/// MS-DOS `(time, date)` fields for `time`, in UTC.
+pub(crate) fn dos_time(time: SystemTime) -> (u16, u16) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    if year < 1980 {
+        return (0, 0x21); // 1980-01-01
+    }
+
+    let time = ((secs / 3600) << 11) | ((secs % 3600 / 60) << 5) | ((secs % 60) / 2);
+    let date = ((year - 1980).min(127) << 9) | (month << 5) | day;
+    (time as u16, date as u16)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    #[test]
+    fn read_and_add_entries() -> Result<(), io::Error> {
+        let tmp_dir = TempDir::new()?;
+        let text = "some repetitive content\n".repeat(200);
+        fs::create_dir(tmp_dir.path().join("doc"))?;
+        fs::write(tmp_dir.path().join("doc/content.xml"), &text)?;
+        fs::write(tmp_dir.path().join("mimetype"), "text/plain")?;
+
+        let archive = tmp_dir.path().join("test.zip");
+        let status = Command::new("zip")
+            .arg("-q")
+            .arg(&archive)
+            .args(["-0", "mimetype"])
+            .current_dir(tmp_dir.path())
+            .status()?;
+        assert!(status.success());
+        let status = Command::new("zip")
+            .arg("-q")
+            .arg(&archive)
+            .args(["-9", "doc/content.xml"])
+            .current_dir(tmp_dir.path())
+            .status()?;
+        assert!(status.success());
+
+        let mut zip = ZipArchive::from_path(&archive)?;
+        assert_eq!(
+            zip.names().collect::<Vec<_>>(),
+            ["mimetype", "doc/content.xml"]
+        );
+        let mut content = String::new();
+        zip.by_name("mimetype")?.read_to_string(&mut content)?;
+        assert_eq!(content, "text/plain");
+
+        let spec = format!("{}::doc/content.xml", archive.display());
+        let mut content = String::new();
+        ZipArchive::open_member(&spec)?.read_to_string(&mut content)?;
+        assert_eq!(content, text);
+
+        let mut writer = ZipEntryWriter::from_spec(&format!("{}::added.txt", archive.display()))?;
+        writer.write_all(b"added")?;
+        writer.finish()?;
+        assert_eq!(
+            ZipEntryWriter::create(&archive, "added.txt")
+                .err()
+                .unwrap()
+                .kind(),
+            io::ErrorKind::AlreadyExists
+        );
+
+        // A dropped writer leaves the archive as it was.
+        let mut writer = ZipEntryWriter::create(&archive, "dropped.txt")?;
+        writer.write_all(b"dropped")?;
+        drop(writer);
+
+        let output = Command::new("unzip")
+            .arg("-p")
+            .arg(&archive)
+            .arg("added.txt")
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"added");
+        let status = Command::new("unzip").arg("-tq").arg(&archive).status()?;
+        assert!(status.success());
+        assert_eq!(ZipArchive::from_path(&archive)?.names().count(), 3);
+
+        let created = tmp_dir.path().join("new.zip");
+        let mut writer = ZipEntryWriter::create(&created, "only.txt")?;
+        writer.write_all(b"only")?;
+        writer.finish()?;
+        let mut content = String::new();
+        ZipArchive::from_path(&created)?
+            .into_entry("only.txt")?
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "only");
+
+        tmp_dir.close()?;
+        Ok(())
+    }
+}